@@ -0,0 +1,139 @@
+// Frame-time benchmark for a large world: 50k nodes in a lattice joined by 50k edges, so
+// 100k elements. Run with `cargo bench --bench spatial`.
+// Times whole editor frames (the egui update, culling through the spatial index, drawing and
+// tessellating what is visible) next to hit testing, box selection and dragging, against the
+// 16.7ms a frame has at 60fps. Below zoom 0.4 the canvas draws no labels, and below 0.25
+// it draws an overview
+use eframe::egui::{self, pos2, vec2, Color32, Pos2, Rect};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use node_simulator::editor::GraphEditor;
+use node_simulator::graph::ID;
+use node_simulator::state::{Camera, GraphState};
+
+const SIDE: usize = 224;
+const SPACING: f32 = 120.0;
+const FRAME_BUDGET: Duration = Duration::from_micros(16_667);
+
+fn world() -> (GraphState, Vec<ID>) {
+    let mut state = GraphState::new();
+    let knows = state.relations.add("knows", Color32::LIGHT_BLUE);
+    let mut nodes = Vec::with_capacity(SIDE * SIDE);
+    for y in 0..SIDE {
+        for x in 0..SIDE {
+            let id = state.add_node_at(pos2(x as f32 * SPACING, y as f32 * SPACING));
+            state.graph.set_name(id, format!("Node {x},{y}")).unwrap();
+            nodes.push(id);
+        }
+    }
+    // Right and down neighbours, until there are as many edges as nodes
+    let mut edges = 0;
+    for y in 0..SIDE {
+        for x in 0..SIDE {
+            let here = nodes[y * SIDE + x];
+            let right = (x + 1 < SIDE).then(|| nodes[y * SIDE + x + 1]);
+            let down = (y + 1 < SIDE).then(|| nodes[(y + 1) * SIDE + x]);
+            for there in right.into_iter().chain(down) {
+                if edges < nodes.len() {
+                    state.add_edge_between(here, there, Some(knows)).unwrap();
+                    edges += 1;
+                }
+            }
+        }
+    }
+    (state, nodes)
+}
+
+// Average time of `f` over `runs` calls
+fn time<T>(runs: u32, mut f: impl FnMut(u32) -> T) -> Duration {
+    let start = Instant::now();
+    for i in 0..runs {
+        black_box(f(i));
+    }
+    start.elapsed() / runs
+}
+
+fn report(name: &str, took: Duration) {
+    let note = if took <= FRAME_BUDGET { "" } else { "  (over a 60fps frame)" };
+    println!("{name:<44} {:>10.3} ms{note}", took.as_secs_f64() * 1000.0);
+}
+
+fn main() {
+    let start = Instant::now();
+    let (mut state, nodes) = world();
+    println!("{} elements, built in {:.2?}\n", state.positions.len(), start.elapsed());
+
+    let canvas = vec2(1600.0, 900.0);
+    // Wander over the world so no query is answered from warm cells alone
+    let center = |i: u32| state.positions[nodes[(i as usize * 7919) % nodes.len()]];
+    let camera_at = |at: Pos2, zoom: f32| Camera { offset: (canvas * 0.5) / zoom - at.to_vec2(), zoom };
+
+    report(
+        "visible elements at zoom 1",
+        time(200, |i| state.elements_touching(Rect::from_center_size(center(i), canvas))),
+    );
+    report(
+        "visible elements at zoom 0.1",
+        time(50, |i| state.elements_touching(Rect::from_center_size(center(i), canvas / 0.1))),
+    );
+
+    // One editor frame as eframe runs it, minus handing the triangles to the GPU. The pointer
+    // rests on the canvas, so hovering is part of the frame
+    let ctx = egui::Context::default();
+    let mut editor = GraphEditor::default();
+    editor.state = state.clone();
+    let run_frame = |editor: &mut GraphEditor, camera: Camera| {
+        editor.state.camera = camera;
+        let input = egui::RawInput {
+            screen_rect: Some(Rect::from_min_size(Pos2::ZERO, canvas)),
+            events: vec![egui::Event::PointerMoved((canvas * 0.5).to_pos2())],
+            ..Default::default()
+        };
+        let output = ctx.run(input, |ctx| editor.show(ctx));
+        ctx.tessellate(output.shapes, output.pixels_per_point)
+    };
+    // The first frame lays out fonts and the panels
+    run_frame(&mut editor, Camera::default());
+    for zoom in [1.0, 0.5, 0.1] {
+        report(
+            &format!("editor frame at zoom {zoom}"),
+            time(20, |i| run_frame(&mut editor, camera_at(center(i), zoom))),
+        );
+    }
+    // With a node selected the inspector is drawn as well
+    editor.select_element(nodes[nodes.len() / 2]);
+    for zoom in [1.0, 0.1] {
+        report(
+            &format!("editor frame at zoom {zoom}, one selected"),
+            time(20, |i| run_frame(&mut editor, camera_at(center(i), zoom))),
+        );
+    }
+
+    report(
+        "hit test (find_element_at)",
+        time(10_000, |i| state.find_element_at(center(i) + vec2(3.0, -2.0), 20.0)),
+    );
+    report(
+        "box select 1000x1000",
+        time(1000, |i| state.elements_in_rect(Rect::from_center_size(center(i), vec2(1000.0, 1000.0)))),
+    );
+    report(
+        "linear scan of every position, for comparison",
+        time(200, |i| {
+            let view = Rect::from_center_size(center(i), canvas);
+            state.positions.iter().filter(|(_, p)| view.contains(**p)).count()
+        }),
+    );
+
+    let mut i = 0;
+    report(
+        "drag one node (move_elements)",
+        time(10_000, |_| {
+            i += 1;
+            let id = nodes[(i * 7919) % nodes.len()];
+            let to = state.positions[id] + vec2(1.5, -0.5);
+            state.move_elements(&[(id, to)])
+        }),
+    );
+}
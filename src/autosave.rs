@@ -0,0 +1,200 @@
+// autosave.rs
+//
+// Periodic recovery snapshots. While the editor runs, a lock file sits next to the snapshot,
+// held with an OS lock that goes away with the process; a clean exit removes both. So finding
+// the lock file unlocked at startup means the last session crashed and its snapshot is worth
+// offering back, and finding it locked means another editor is running and owns the folder.
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::thread::{self, JoinHandle};
+
+use crate::file_format::{self, Format};
+use crate::state::GraphState;
+
+const SNAPSHOT_FILE: &str = "recovery.bin";
+const LOCK_FILE: &str = "session.lock";
+// Editors running at the same time take numbered folders inside the first one
+const MAX_INSTANCES: usize = 64;
+
+// Where recovery files live when nobody asks for somewhere else
+pub fn default_dir() -> PathBuf {
+    eframe::storage_dir("node_simulator")
+        .unwrap_or_else(|| std::env::temp_dir().join("node_simulator"))
+        .join("recovery")
+}
+
+pub struct Autosave {
+    dir: PathBuf,
+    // Open and locked for as long as this session owns `dir`
+    lock: File,
+    // History generation of the document in the last snapshot, to skip rewriting it unchanged
+    written: Option<u64>,
+    // Snapshot still being encoded and written off the UI thread
+    writing: Option<JoinHandle<io::Result<()>>>,
+}
+
+impl Autosave {
+    // Claim `dir` for this session, or a folder of its own inside it while another running
+    // editor has `dir`. Returns the snapshot path if the previous session there did not shut
+    // down cleanly
+    pub fn start(dir: PathBuf) -> io::Result<(Self, Option<PathBuf>)> {
+        for instance in 0..MAX_INSTANCES {
+            let dir = match instance {
+                0 => dir.clone(),
+                n => dir.join(format!("instance-{n}")),
+            };
+            if let Some(claimed) = Self::claim(dir)? {
+                return Ok(claimed);
+            }
+        }
+        Err(io::Error::other("too many editors are running"))
+    }
+
+    // None when another running editor holds the lock on `dir`
+    fn claim(dir: PathBuf) -> io::Result<Option<(Self, Option<PathBuf>)>> {
+        fs::create_dir_all(&dir)?;
+        let lock_path = dir.join(LOCK_FILE);
+        let crashed = lock_path.exists();
+        let mut lock = OpenOptions::new().write(true).create(true).truncate(false).open(&lock_path)?;
+        match lock.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => return Ok(None),
+            Err(TryLockError::Error(error)) => return Err(error),
+        }
+        // The PID is only there for whoever looks at the folder
+        lock.set_len(0)?;
+        write!(lock, "{}", std::process::id())?;
+
+        let autosave = Self { dir, lock, written: None, writing: None };
+        let recovery = Some(autosave.snapshot_path()).filter(|path| crashed && path.exists());
+        Ok(Some((autosave, recovery)))
+    }
+
+    pub fn snapshot_path(&self) -> PathBuf {
+        self.dir.join(SNAPSHOT_FILE)
+    }
+
+    fn lock_path(&self) -> PathBuf {
+        self.dir.join(LOCK_FILE)
+    }
+
+    // Start writing a snapshot of `state` in the background, unless the last one already holds
+    // this generation of it. Returns whether it started; an error is from the previous write
+    pub fn write(&mut self, state: &GraphState, generation: u64) -> io::Result<bool> {
+        self.wait()?;
+        if self.written == Some(generation) {
+            return Ok(false);
+        }
+        let state = state.clone();
+        let path = self.snapshot_path();
+        self.writing = Some(thread::spawn(move || write_snapshot(&state, &path)));
+        self.written = Some(generation);
+        Ok(true)
+    }
+
+    // Block until the snapshot being written, if any, is on disk
+    pub fn wait(&mut self) -> io::Result<()> {
+        let Some(writing) = self.writing.take() else {
+            return Ok(());
+        };
+        let result = writing
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::other("autosave thread panicked")));
+        if result.is_err() {
+            // Try again on the next write
+            self.written = None;
+        }
+        result
+    }
+
+    // Drop the snapshot, e.g. once the document it holds has been saved or thrown away
+    pub fn discard(&mut self) -> io::Result<()> {
+        // A write still running would bring the snapshot back
+        let _ = self.wait();
+        self.written = None;
+        remove_if_exists(&self.snapshot_path())
+    }
+
+    // Clean shutdown: nothing is left to recover
+    pub fn finish(mut self) -> io::Result<()> {
+        self.discard()?;
+        let lock_path = self.lock_path();
+        drop(self.lock);
+        remove_if_exists(&lock_path)
+    }
+}
+
+// Write beside the snapshot and rename, so a crash mid-write keeps the previous one
+fn write_snapshot(state: &GraphState, path: &Path) -> io::Result<()> {
+    let partial = path.with_extension("partial");
+    fs::write(&partial, file_format::encode(state, Format::Binary)?)?;
+    fs::rename(&partial, path)
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eframe::egui::Pos2;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("node_sim_autosave_{name}_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_recovery_after_crash() {
+        let dir = scratch_dir("crash");
+        let mut state = GraphState::new();
+        let a = state.add_node_at(Pos2::new(3.0, 4.0));
+        state.graph.set_name(a, "Lighthouse").unwrap();
+
+        let (mut autosave, recovery) = Autosave::start(dir.clone()).unwrap();
+        assert_eq!(recovery, None);
+        assert!(autosave.write(&state, 1).unwrap());
+        assert!(!autosave.write(&state, 1).unwrap());
+        autosave.wait().unwrap();
+        // Simulate a crash by never calling finish
+        drop(autosave);
+
+        let (autosave, recovery) = Autosave::start(dir.clone()).unwrap();
+        let recovered = GraphState::load_from_file(&recovery.unwrap()).unwrap();
+        assert_eq!(recovered.graph.node_data(a), state.graph.node_data(a));
+        assert_eq!(recovered.positions.get(a), state.positions.get(a));
+
+        autosave.finish().unwrap();
+        let (autosave, recovery) = Autosave::start(dir.clone()).unwrap();
+        assert_eq!(recovery, None);
+        autosave.finish().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_running_editors_get_their_own_folder() {
+        let dir = scratch_dir("instances");
+        let (first, _) = Autosave::start(dir.clone()).unwrap();
+        let (mut second, recovery) = Autosave::start(dir.clone()).unwrap();
+        // The first editor is still running, so its folder holds nothing to recover
+        assert_eq!(recovery, None);
+        assert_ne!(second.snapshot_path(), first.snapshot_path());
+
+        // A crash of the second editor is offered to the next one that takes its folder
+        second.write(&GraphState::new(), 1).unwrap();
+        second.wait().unwrap();
+        drop(second);
+        let (third, recovery) = Autosave::start(dir.clone()).unwrap();
+        assert_eq!(recovery, Some(third.snapshot_path()));
+
+        third.finish().unwrap();
+        first.finish().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,31 +1,120 @@
 use egui::UiBuilder;
-use slotmap::Key as SlotKey;
 use eframe::{egui, App, Frame};
 use egui::{
     Color32, Key, PointerButton, Pos2, Rect, Sense, Stroke, StrokeKind, Vec2,
 };
 use rfd::FileDialog;
-use std::io;
 
 use crate::state::GraphState;
-use crate::graph::ID;
+use crate::graph::{GraphError, NodeData, RelationTypeId, ID};
+use crate::file_format::Format;
+use crate::history::History;
+use crate::layout::force::ForceLayout;
+use crate::layout::layered::LayeredLayout;
+use crate::layout::radial::{CircularLayout, RadialLayout};
+use crate::relations::{LineStyle, RelationType};
+use crate::render;
+use crate::style::{NodeShape, ResolvedStyle};
+
+mod auto_layout;
+mod csv_wizard;
+mod image_export;
+mod import_export;
+mod inspector;
+mod labels;
+mod schema_window;
+mod selection;
+mod session;
+mod style_ui;
+mod toasts;
+
+use auto_layout::LayoutAnimation;
+use csv_wizard::CsvWizard;
+use image_export::ImageExport;
+use inspector::InspectorState;
+use labels::LabelSettings;
+use selection::Selection;
+use session::{PendingAction, Session};
+use toasts::Toasts;
+
+// Below this zoom the canvas draws an overview: edges as single lines without arrowheads,
+// dashes or anchor rings, nodes as filled squares, and no handles in the middle of edges
+// unless they are selected
+const OVERVIEW_ZOOM: f32 = 0.25;
+// Labels are hidden in an overview, so only small node shapes reach past their position
+const OVERVIEW_CULL_MARGIN: f32 = 50.0;
 
 pub struct GraphEditor {
     pub state: GraphState,
-    selected: Option<ID>,
+    history: History,
+    selection: Selection,
+    // Where the dragged elements started, recorded as one undo step when the drag ends
+    drag_start: Vec<(ID, Pos2)>,
+    // World position where a rubber-band selection started
+    box_select_start: Option<Pos2>,
+    tag_buffer: String,
+    show_inspector: bool,
+    inspector: InspectorState,
+    // Screen rect of the canvas last frame, used to center the view on elements
+    canvas_rect: Rect,
+    labels: LabelSettings,
     // edge_mode: Option<ID>,
     show_help: bool,
     highlight: bool,
+    // Relation type given to newly created edges
+    current_relation: Option<RelationTypeId>,
+    show_relations: bool,
+    new_relation_name: String,
+    new_relation_color: Color32,
+    show_schema: bool,
+    new_kind_name: String,
+    schema_property_buffer: String,
+    // Last "Validate document" result, as (element, description)
+    validation: Option<Vec<(ID, String)>>,
+    csv_wizard: CsvWizard,
+    image_export: ImageExport,
+    toasts: Toasts,
+    session: Session,
+    force_layout: ForceLayout,
+    layered_layout: LayeredLayout,
+    radial_layout: RadialLayout,
+    circular_layout: CircularLayout,
+    layout_animation: Option<LayoutAnimation>,
 }
 
 impl Default for GraphEditor {
     fn default() -> Self {
         Self {
             state: GraphState::default(),
-            selected: None,
+            history: History::new(),
+            selection: Selection::default(),
+            drag_start: Vec::new(),
+            box_select_start: None,
+            tag_buffer: String::new(),
+            show_inspector: true,
+            inspector: InspectorState::default(),
+            canvas_rect: Rect::NOTHING,
+            labels: LabelSettings::default(),
             // edge_mode: None,
             show_help: false,
             highlight:true,
+            current_relation: None,
+            show_relations: false,
+            new_relation_name: String::new(),
+            new_relation_color: Color32::LIGHT_BLUE,
+            show_schema: false,
+            new_kind_name: String::new(),
+            schema_property_buffer: String::new(),
+            validation: None,
+            csv_wizard: CsvWizard::default(),
+            image_export: ImageExport::default(),
+            toasts: Toasts::default(),
+            session: Session::default(),
+            force_layout: ForceLayout::default(),
+            layered_layout: LayeredLayout::default(),
+            radial_layout: RadialLayout::default(),
+            circular_layout: CircularLayout::default(),
+            layout_animation: None,
         }
     }
 }
@@ -53,61 +142,225 @@ impl GraphEditor {
         self.state.camera.offset += delta / self.state.camera.zoom;
     }
 
+    // Move `id` to `new_pos`, carrying the rest of the selection along if it is part of it
     fn move_node(&mut self, id: ID, new_pos: Pos2) {
-        self.state.positions.insert(id, new_pos);
-        self.state.update_positions_recursive(id);
+        let Some(&old_pos) = self.state.positions.get(id) else {
+            return;
+        };
+        let delta = new_pos - old_pos;
+        let moved: Vec<ID> = if self.selection.contains(id) {
+            self.selection.to_vec()
+        } else {
+            vec![id]
+        };
+        // All at once, so edges moved along with their ends keep their shape
+        let moves: Vec<(ID, Pos2)> = moved
+            .into_iter()
+            .filter_map(|moved_id| Some((moved_id, *self.state.positions.get(moved_id)? + delta)))
+            .collect();
+        self.state.move_elements(&moves);
     }
 
-    fn select_element(&mut self, id: ID) {
-        self.selected = Some(id);
+    pub fn select_element(&mut self, id: ID) {
+        self.selection.set(id);
         // We no longer set "dragging = true" here because we handle dragging in process_node_input
     }
 
     fn handle_edge_creation(&mut self, id: ID) {
-        if let Some(src) = self.selected {
-            self.state.add_edge_between(src, id);
+        if let Some(src) = self.selection.primary() {
+            if let Err(error) = self.history.add_edge(&mut self.state, src, id, self.current_relation) {
+                self.report_error(error);
+            }
         } else {
-            self.selected = Some(id);
+            self.selection.set(id);
         }
     }
 
     fn create_node(&mut self, pos: Pos2) {
-        self.selected = Some(
-            self.state.add_node_at(pos));
+        self.selection.set(
+            self.history.add_node(&mut self.state, pos, NodeData::default()));
     }
 
     fn delete_element(&mut self, id: ID) {
-        self.state.remove_element(id);
+        self.delete_elements(&[id]);
+    }
 
-        //not needed for now but keep it in for good measure
-        if self.selected == Some(id) {
-            self.selected = None;
+    // Delete several elements as one undo step
+    fn delete_elements(&mut self, ids: &[ID]) {
+        if ids.is_empty() {
+            return;
+        }
+        self.stop_layout_animation();
+        if let Err(error) = self.history.remove(&mut self.state, ids) {
+            self.report_error(error);
         }
+        self.selection.retain_existing(&self.state.graph);
     }
 
-    fn save_graph(&self) -> io::Result<()> {
-        if let Some(path) = FileDialog::new()
-            .set_title("Save Graph")
-            .set_file_name("graph_save.bin")
-            .save_file()
-        {
-            self.state.save_to_file(&path)?;
+    fn delete_selection(&mut self) {
+        let ids = self.selection.to_vec();
+        self.delete_elements(&ids);
+    }
+
+    fn select_all(&mut self) {
+        let ids: Vec<ID> = self.state.graph.nodes_iter().map(|(id, _)| id).collect();
+        self.selection.extend(ids);
+    }
+
+    // Add or remove a tag on every selected element as one undo step
+    fn tag_selection(&mut self, tag: &str, add: bool) {
+        let tag = tag.trim();
+        if tag.is_empty() {
+            return;
         }
-        Ok(())
+        let edits: Vec<_> = self
+            .selection
+            .iter()
+            .filter_map(|id| {
+                let mut data = self.state.graph.node_data(id)?.clone();
+                let changed = if add {
+                    data.tags.insert(tag.to_owned())
+                } else {
+                    data.tags.remove(tag)
+                };
+                changed.then_some((id, data))
+            })
+            .collect();
+        if edits.is_empty() {
+            return;
+        }
+        if let Err(error) = self.history.edit_nodes(&mut self.state, edits) {
+            self.report_error(error);
+        }
+    }
+
+    // File dialog offering every world format; the chosen file's extension decides the format
+    fn world_dialog(title: &str) -> FileDialog {
+        Format::ALL
+            .into_iter()
+            .fold(FileDialog::new().set_title(title), |dialog, format| {
+                dialog.add_filter(format.name(), format.extensions())
+            })
     }
 
-    fn load_graph(&mut self) -> io::Result<()> {
-        if let Some(path) = FileDialog::new().set_title("Load Graph").pick_file() {
-            self.state = GraphState::load_from_file(&path)?;
-            // Reset
-            self.selected = None;
+    // Returns whether a file was written; false if the user cancelled or the write failed
+    fn save_graph(&mut self) -> bool {
+        let dialog = Self::world_dialog("Save Graph");
+        // Start from the file the document came from, if any
+        let dialog = match self.session.file().and_then(|path| Some((path.parent()?, path.file_name()?))) {
+            Some((dir, name)) => dialog.set_directory(dir).set_file_name(name.to_string_lossy()),
+            None => dialog.set_file_name("graph_save.bin"),
+        };
+        let Some(path) = dialog.save_file() else {
+            return false;
+        };
+        self.history.compact(&mut self.state);
+        match self.state.save_to_file(&path) {
+            Ok(()) => {
+                self.toasts.info(format!("Saved {}", path.display()));
+                self.mark_saved(Some(path));
+                true
+            }
+            Err(error) => {
+                self.toasts.error(format!("Could not save {}: {error}", path.display()));
+                false
+            }
+        }
+    }
+
+    fn load_graph(&mut self) {
+        let Some(path) = Self::world_dialog("Load Graph").add_filter("All files", &["*"]).pick_file() else {
+            return;
+        };
+        match GraphState::load_from_file(&path) {
+            Ok(state) => {
+                self.replace_document(state);
+                self.mark_saved(Some(path));
+            }
+            Err(error) => self.toasts.error(format!("Could not load {}: {error}", path.display())),
         }
-        Ok(())
     }
 
     fn new_graph(&mut self) {
-        self.state = GraphState::default();
-        self.selected = None;
+        self.replace_document(GraphState::default());
+        self.mark_saved(None);
+    }
+
+    // Swap in another document and reset everything that referred to the old one
+    fn replace_document(&mut self, state: GraphState) {
+        self.layout_animation = None;
+        self.state = state;
+        self.history.clear(&mut self.state);
+        self.selection.clear();
+        self.current_relation = None;
+        self.validation = None;
+    }
+
+    fn add_relation_type(&mut self) {
+        let name = self.new_relation_name.trim();
+        if name.is_empty() {
+            return;
+        }
+        let id = self.state.relations.add(name, self.new_relation_color);
+        self.history.touch();
+        self.current_relation = Some(id);
+        self.new_relation_name.clear();
+    }
+
+    fn remove_relation_type(&mut self, relation: RelationTypeId) {
+        if let Err(error) = self.history.remove_relation_type(&mut self.state, relation) {
+            self.report_error(error);
+        }
+        if self.current_relation == Some(relation) {
+            self.current_relation = None;
+        }
+    }
+
+    // Show a refused edit as a toast, naming the elements involved for schema violations
+    fn report_error(&mut self, error: GraphError) {
+        let text = match (&error, &self.state.schema) {
+            (GraphError::SchemaViolation(violation), Some(schema)) => {
+                schema.describe(violation, &self.state.graph, &self.state.relations)
+            }
+            _ => error.to_string(),
+        };
+        self.toasts.error(text);
+    }
+
+    fn relation_name(&self, relation: Option<RelationTypeId>) -> &str {
+        relation
+            .and_then(|r| self.state.relations.get(r))
+            .map_or("(untyped)", |r| r.name.as_str())
+    }
+
+    fn undo(&mut self) {
+        self.stop_layout_animation();
+        if let Err(error) = self.history.undo(&mut self.state) {
+            self.report_error(error);
+        }
+        self.forget_missing_selection();
+    }
+
+    fn redo(&mut self) {
+        self.stop_layout_animation();
+        if let Err(error) = self.history.redo(&mut self.state) {
+            self.report_error(error);
+        }
+        self.forget_missing_selection();
+    }
+
+    // Undo/redo can remove selected elements out from under us
+    fn forget_missing_selection(&mut self) {
+        self.selection.retain_existing(&self.state.graph);
+    }
+
+    // Pan so the element sits in the middle of the canvas
+    fn focus_on(&mut self, id: ID) {
+        let Some(&pos) = self.state.positions.get(id) else {
+            return;
+        };
+        let half_size = self.canvas_rect.size() * 0.5;
+        self.state.camera.offset = half_size / self.state.camera.zoom - pos.to_vec2();
     }
 
     fn reset_camera(&mut self) {
@@ -133,13 +386,37 @@ impl GraphEditor {
 
         // ── KEYBOARD SHORTCUTS ────────────────────────────────────
         if input.key_pressed(Key::S) && input.modifiers.ctrl {
-            let _ = self.save_graph();
+            self.save_graph();
             return;
         } else if input.key_pressed(Key::O) && input.modifiers.ctrl {
-            let _ = self.load_graph();
+            self.request(PendingAction::Load);
             return;
         } else if input.key_pressed(Key::N) && input.modifiers.ctrl {
-            self.new_graph();
+            self.request(PendingAction::New);
+            return;
+        } else if input.key_pressed(Key::Z)
+            && input.modifiers.ctrl
+            && !ctx.wants_keyboard_input()
+        {
+            // Text fields keep their own Ctrl+Z
+            if input.modifiers.shift {
+                self.redo();
+            } else {
+                self.undo();
+            }
+            return;
+        } else if input.key_pressed(Key::Y) && input.modifiers.ctrl && !ctx.wants_keyboard_input() {
+            self.redo();
+            return;
+        } else if input.key_pressed(Key::A) && input.modifiers.ctrl && !ctx.wants_keyboard_input() {
+            self.select_all();
+            return;
+        } else if input.key_pressed(Key::Delete) && !ctx.wants_keyboard_input() {
+            self.delete_selection();
+            return;
+        } else if input.key_pressed(Key::Escape) {
+            self.selection.clear();
+            self.box_select_start = None;
             return;
         } else if input.key_pressed(Key::Home) {
             self.reset_camera();
@@ -148,10 +425,27 @@ impl GraphEditor {
 
         //__ Deselect on any delete like op  ─────────────────────────
         if input.pointer.button_pressed(PointerButton::Secondary){
-            self.selected = None;
+            self.selection.clear();
 
         }
 
+        // ── RUBBER-BAND SELECTION (left drag on empty space) ───────
+        // Handled before the hover check so releasing outside the canvas still finishes it
+        if let Some(start) = self.box_select_start {
+            if !input.pointer.primary_down() {
+                self.box_select_start = None;
+                if let Some(end) = input.pointer.latest_pos() {
+                    let end = self.to_world(end, screen_origin);
+                    let ids = self.state.elements_in_rect(Rect::from_two_pos(start, end));
+                    if !input.modifiers.ctrl {
+                        self.selection.clear();
+                    }
+                    self.selection.extend(ids);
+                }
+            }
+            return;
+        }
+
         // If the mouse is not inside the main drawing area, skip
         if !response.hovered() {
             return;
@@ -178,18 +472,26 @@ impl GraphEditor {
             }
             return;
         }
+        if response.drag_started_by(PointerButton::Primary) && !input.modifiers.alt {
+            if let Some(press) = input.pointer.press_origin() {
+                self.box_select_start = Some(self.to_world(press, screen_origin));
+            }
+            return;
+        }
+
         // ── LEFT-CLICK EMPTY SPACE => CREATE NODE ──────────────────
-        if input.pointer.button_pressed(PointerButton::Primary)
+        // (on click rather than press, so a press can still turn into a box selection)
+        if response.clicked_by(PointerButton::Primary)
             && !input.modifiers.alt
             && !input.modifiers.shift
         {
-            // If no other widget used the click, we create a node
-            if !ctx.is_pointer_over_area() {
-                if let Some(cursor_pos) = input.pointer.hover_pos() {
-                    let world_pos = self.to_world(cursor_pos, screen_origin);
-                    self.create_node(world_pos);
-                    return;
-                }
+            if input.modifiers.ctrl {
+                // Ctrl-click on nothing keeps the selection
+                return;
+            }
+            if let Some(cursor_pos) = input.pointer.hover_pos() {
+                let world_pos = self.to_world(cursor_pos, screen_origin);
+                self.create_node(world_pos);
             }
 
             return;
@@ -199,7 +501,8 @@ impl GraphEditor {
     }
 
     fn process_node_input(&mut self, node_id: ID, response: &egui::Response, screen_origin: Pos2) {
-        let input = response.ctx.input(|i| i.clone());
+        // Every visible node comes through here each frame, so only copy what is needed
+        let (modifiers, hover_pos) = response.ctx.input(|i| (i.modifiers, i.pointer.hover_pos()));
 
         // Right-click => delete node
         if response.clicked_by(PointerButton::Secondary) {
@@ -208,13 +511,19 @@ impl GraphEditor {
         }
 
         // Shift+left-click => edge creation
-        if response.clicked_by(PointerButton::Primary) && input.modifiers.shift {
+        if response.clicked_by(PointerButton::Primary) && modifiers.shift {
             self.handle_edge_creation(node_id);
             return;
         }
 
+        // Ctrl+left-click => toggle in selection
+        if response.clicked_by(PointerButton::Primary) && modifiers.ctrl {
+            self.selection.toggle(node_id);
+            return;
+        }
+
         // Regular left-click => select node
-        if response.clicked_by(PointerButton::Primary) && !input.modifiers.shift {
+        if response.clicked_by(PointerButton::Primary) && !modifiers.shift {
             self.select_element(node_id);
         }
 
@@ -222,25 +531,43 @@ impl GraphEditor {
         // We'll only move if it's the selected node and the user is dragging
         // This means you can drag multiple nodes if you click them in the same frame,
         // but typically you won't. This is one approach. 
+        if response.drag_started() {
+            // Grabbing a node ends a running layout so the drag is its own undo step
+            self.stop_layout_animation();
+            // Dragging an unselected node grabs just that node
+            if !self.selection.contains(node_id) {
+                self.select_element(node_id);
+            }
+            self.drag_start = self
+                .selection
+                .iter()
+                .filter_map(|id| self.state.positions.get(id).map(|&pos| (id, pos)))
+                .collect();
+        }
+
         if response.dragged() {
             // self.selected = Some(node_id);
             // self.selected = None;
 
-            if let Some(pointer_pos) = input.pointer.hover_pos() {
+            if let Some(pointer_pos) = hover_pos {
                 let new_world_pos = self.to_world(pointer_pos, screen_origin);
                 self.move_node(node_id, new_world_pos);
             }
         }
+
+        if response.drag_stopped() {
+            self.history.record_move(std::mem::take(&mut self.drag_start));
+        }
     }
 
     fn process_edge_segment_input(&mut self, edge_id: ID, response: &egui::Response) {
-        let input = response.ctx.input(|i| i.clone());
+        let modifiers = response.ctx.input(|i| i.modifiers);
 
         if response.clicked_by(PointerButton::Secondary) {
             self.delete_element(edge_id);
         }
 
-        if response.clicked_by(PointerButton::Primary) && input.modifiers.shift {
+        if response.clicked_by(PointerButton::Primary) && modifiers.shift {
             self.handle_edge_creation(edge_id);
         }
 
@@ -257,13 +584,21 @@ impl GraphEditor {
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             ui.horizontal(|ui| {
                 if ui.button("💾 Save").clicked() {
-                    let _ = self.save_graph();
+                    self.save_graph();
                 }
                 if ui.button("📂 Load").clicked() {
-                    let _ = self.load_graph();
+                    self.request(PendingAction::Load);
                 }
                 if ui.button("✚ New").clicked() {
-                    self.new_graph();
+                    self.request(PendingAction::New);
+                }
+                ui.menu_button("📥 Import", |ui| self.draw_import_menu(ui));
+                ui.menu_button("📤 Export", |ui| self.draw_export_menu(ui));
+                if ui.add_enabled(self.history.can_undo(), egui::Button::new("↶ Undo")).clicked() {
+                    self.undo();
+                }
+                if ui.add_enabled(self.history.can_redo(), egui::Button::new("↷ Redo")).clicked() {
+                    self.redo();
                 }
                 if ui.button("🏠 Reset Camera").clicked() {
                     self.reset_camera();
@@ -274,6 +609,33 @@ impl GraphEditor {
                 }
 
                 ui.label(format!("Zoom: {:.1}x", self.state.camera.zoom));
+                ui.separator();
+                self.draw_file_status(ui);
+
+                ui.separator();
+                ui.label("New edges:");
+                egui::ComboBox::from_id_salt("current_relation")
+                    .selected_text(self.relation_name(self.current_relation).to_owned())
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.current_relation, None, "(untyped)");
+                        for (id, relation) in self.state.relations.iter() {
+                            ui.selectable_value(
+                                &mut self.current_relation,
+                                Some(id),
+                                &relation.name,
+                            );
+                        }
+                    });
+                if ui.button("🔗 Relation Types").clicked() {
+                    self.show_relations = !self.show_relations;
+                }
+                if ui.button("📐 Schema").clicked() {
+                    self.show_schema = !self.show_schema;
+                }
+                ui.toggle_value(&mut self.show_inspector, "🔍 Inspector");
+                ui.menu_button("👁 Labels", |ui| self.labels.ui(ui));
+                ui.menu_button("🎨 Theme", |ui| self.draw_theme_menu(ui));
+                ui.menu_button("🧭 Layout", |ui| self.draw_layout_menu(ui));
 
                 // Right-justified help toggle
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
@@ -286,6 +648,39 @@ impl GraphEditor {
         });
     }
 
+    // Group operations on the current selection
+    fn draw_selection_bar(&mut self, ctx: &egui::Context) {
+        if self.selection.len() < 2 {
+            return;
+        }
+        egui::TopBottomPanel::bottom("selection_bar").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(format!("{} selected", self.selection.len()));
+                ui.separator();
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.tag_buffer)
+                        .hint_text("tag")
+                        .desired_width(120.0),
+                );
+                if ui.button("🏷 Add tag").clicked() {
+                    let tag = std::mem::take(&mut self.tag_buffer);
+                    self.tag_selection(&tag, true);
+                }
+                if ui.button("Remove tag").clicked() {
+                    let tag = std::mem::take(&mut self.tag_buffer);
+                    self.tag_selection(&tag, false);
+                }
+                ui.separator();
+                if ui.button("🗑 Delete").clicked() {
+                    self.delete_selection();
+                }
+                if ui.button("Clear selection").clicked() {
+                    self.selection.clear();
+                }
+            });
+        });
+    }
+
     fn draw_help_overlay(&self, ctx: &egui::Context) {
         if !self.show_help {
             return;
@@ -309,37 +704,113 @@ impl GraphEditor {
                     ui.set_max_width(300.0);
                     ui.label("Left-click empty space: add node");
                     ui.label("Shift + click two nodes: connect with edge");
+                    ui.label("New edges get the relation type picked in the top bar");
                     ui.label("Right-click: delete node/edge");
-                    ui.label("Drag node: move with edge updates");
+                    ui.label("Drag node: move with edge updates (moves the whole selection)");
+                    ui.label("Drag an edge's middle node: bend it, drop it back on the middle to straighten");
+                    ui.label("Drag empty space: box select (Ctrl adds to selection)");
+                    ui.label("Ctrl + click: toggle selection, Ctrl+A: select all");
+                    ui.label("Delete: delete selection, Esc: clear selection");
                     ui.label("Middle-click drag or Alt+Left drag: pan view");
                     ui.label("Mouse wheel: zoom in/out");
                     ui.label("Home key or Reset Camera button: reset view");
                     ui.label("Ctrl+S: save, Ctrl+O: load, Ctrl+N: new");
+                    ui.label("Ctrl+Z: undo, Ctrl+Shift+Z or Ctrl+Y: redo");
+                    ui.label("Layout menu: arrange the selection or world, pin nodes in place");
                     ui.label("❓ button: toggle this help overlay");
                 });
         });
     }
 
+    fn draw_relations_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_relations;
+        let mut to_remove = None;
+
+        egui::Window::new("Relation Types")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let ids: Vec<_> = self.state.relations.iter().map(|(id, _)| id).collect();
+                for id in ids {
+                    let count = self.state.graph.edges_of_type(id).len();
+                    let Some(original) = self.state.relations.get(id) else {
+                        continue;
+                    };
+                    let mut relation = original.clone();
+                    ui.horizontal(|ui| {
+                        ui.color_edit_button_srgba(&mut relation.color);
+                        ui.text_edit_singleline(&mut relation.name);
+                        ui.add(
+                            egui::DragValue::new(&mut relation.width)
+                                .range(0.5..=8.0)
+                                .speed(0.1)
+                                .suffix("px"),
+                        );
+                        egui::ComboBox::from_id_salt(("line_style", id))
+                            .selected_text(relation.line.name())
+                            .width(70.0)
+                            .show_ui(ui, |ui| {
+                                for line in LineStyle::ALL {
+                                    ui.selectable_value(&mut relation.line, line, line.name());
+                                }
+                            });
+                        ui.label(format!("{count} edges"));
+                        if ui.button("🗑").on_hover_text("Delete (edges become untyped)").clicked() {
+                            to_remove = Some(id);
+                        }
+                    });
+                    if self.state.relations.get(id) != Some(&relation) {
+                        if let Err(error) = self.history.edit_relation_merging(&mut self.state, id, relation) {
+                            self.report_error(error);
+                        }
+                    }
+                }
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.color_edit_button_srgba(&mut self.new_relation_color);
+                    let response = ui.text_edit_singleline(&mut self.new_relation_name);
+                    let submitted =
+                        response.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter));
+                    if ui.button("➕ Add").clicked() || submitted {
+                        self.add_relation_type();
+                    }
+                });
+            });
+
+        if let Some(id) = to_remove {
+            self.remove_relation_type(id);
+        }
+        // Like the inspector, fold edits into one step until nothing is typed into or dragged
+        let idle = ctx.memory(|m| m.focused().is_none()) && !ctx.input(|i| i.pointer.any_down());
+        if idle {
+            self.history.end_merge();
+        }
+        self.show_relations = open;
+    }
+
+// One half of an edge, `points` running from `start` (an end or the control point) to `end`
 fn draw_edge_segment(
     &mut self,
     edge_id: ID,
-    start: Pos2,
-    end: Pos2,
+    mut points: Vec<Pos2>,
     ui: &mut egui::Ui,
     extra: &'static str,
 ) {
+    let (Some(&start), Some(&end)) = (points.first(), points.last()) else {
+        return;
+    };
     let thickness = 11.0 * self.state.camera.zoom;
-    let id = ui.id().with("edge").with(edge_id).with(extra);
 
-    let pointer = ui.input(|i| i.pointer.clone());
-    let pointer_pos = pointer.hover_pos();
+    let pointer_pos = ui.input(|i| i.pointer.hover_pos());
 
     let mut hovered = false;
 
     if let Some(pos) = pointer_pos {
-        if distance_to_segment(pos, start, end) <= thickness {
+        if distance_to_polyline(pos, &points) <= thickness {
             // Only now do we register a UI element
-            let rect = Rect::from_two_pos(start, end).expand(thickness);
+            let rect = Rect::from_points(&points).expand(thickness);
+            let id = ui.id().with("edge").with(edge_id).with(extra);
             let response = ui.interact(rect, id, Sense::click());
 
             hovered = true;
@@ -347,13 +818,63 @@ fn draw_edge_segment(
         }
     }
 
+    let zoom = self.state.camera.zoom;
+    let overview = zoom < OVERVIEW_ZOOM;
+    let edge = self.state.graph.get_edge(edge_id);
+    let relation = edge
+        .and_then(|e| e.relation)
+        .and_then(|r| self.state.relations.get(r));
+
+    let (width, line) = match relation {
+        Some(r) if !overview => (r.width, r.line),
+        Some(r) => (r.width, LineStyle::Solid),
+        None => (RelationType::DEFAULT_WIDTH, LineStyle::Solid),
+    };
     let stroke = if self.highlight && hovered {
-        Stroke::new(2.0, Color32::YELLOW)
+        Stroke::new(width + 0.5, Color32::YELLOW)
     } else {
-        Stroke::new(1.5, Color32::LIGHT_BLUE)
+        Stroke::new(width, relation.map_or(Color32::LIGHT_BLUE, |r| r.color))
+    };
+    if overview {
+        draw_styled_line(ui.painter(), &points, stroke, line, zoom);
+        return;
+    }
+
+    // The end attached to this segment, if it is the source or target of the edge
+    let endpoint = match extra {
+        "src" => edge.map(|e| e.source),
+        "tgt" => edge.map(|e| e.target),
+        _ => None,
     };
+    let attached_to_edge = endpoint.is_some_and(|id| self.state.graph.get_edge(id).is_some());
+
+    // Stop the target segment at the border of the target shape so the arrow stays visible
+    if let (Some(target), "tgt") = (endpoint, extra) {
+        let (target_rect, shape) = self.node_outline(ui.painter(), target, end);
+        render::clip_to_border(&mut points, shape, target_rect);
+    }
 
-    ui.painter().line_segment([start, end], stroke);
+    draw_styled_line(ui.painter(), &points, stroke, line, zoom);
+    if extra == "tgt" {
+        draw_arrowhead(ui.painter(), &points, stroke.color, render::arrow_size(width, zoom));
+    }
+    // Relations about other relations are anchored with a ring on the edge they point at
+    if let Some(anchor_id) = endpoint.filter(|_| attached_to_edge) {
+        let anchor = if extra == "src" { start } else { end };
+        let (anchor_rect, _) = self.node_outline(ui.painter(), anchor_id, anchor);
+        let radius = anchor_rect.size().max_elem() * 0.5 + 3.0 * zoom;
+        ui.painter().circle_stroke(anchor, radius, Stroke::new(width, stroke.color));
+    }
+
+    // The label goes along the first half so the edge node in the middle doesn't cover it
+    if extra != "src" || !self.labels.shows_edge_labels(zoom) {
+        return;
+    }
+    let Some(label) = render::edge_label(relation, edge.and_then(|e| e.label.as_deref())) else {
+        return;
+    };
+    let font = self.labels.edge_font(zoom);
+    draw_text_along(ui.painter(), start, end, label, font, stroke.color);
 }
 
 
@@ -365,10 +886,19 @@ fn draw_graph(
     ctx: &egui::Context,
     ui: &mut egui::Ui,
 ) {
+    // Only what can show up on the canvas is drawn or gets an interactive region. The margin
+    // covers shapes and labels reaching past their element's position
+    let clip = painter.clip_rect();
+    let view = Rect::from_min_max(self.to_world(clip.min, screen_origin), self.to_world(clip.max, screen_origin));
+    let zoom = self.state.camera.zoom;
+    let overview = zoom < OVERVIEW_ZOOM;
+    let margin = if overview { OVERVIEW_CULL_MARGIN } else { render::CULL_MARGIN };
+    let visible = self.state.elements_touching(view.expand(margin / zoom));
+
     // Allocate one UI element for the entire drawing area
-    ui.allocate_new_ui(UiBuilder::new().max_rect(painter.clip_rect()), |ui| {
+    ui.allocate_new_ui(UiBuilder::new().max_rect(clip), |ui| {
         // 1) Draw edges
-        let edges: Vec<_> = self.state.graph.edges_iter().cloned().collect();
+        let edges: Vec<_> = visible.iter().filter_map(|&id| self.state.graph.get_edge(id).cloned()).collect();
         for edge in edges {
             if let (Some(src), Some(tgt), Some(mid)) = (
                 self.state.positions.get(edge.source),
@@ -379,84 +909,156 @@ fn draw_graph(
                 let screen_mid = self.to_screen(*mid, screen_origin);
                 let screen_tgt = self.to_screen(*tgt, screen_origin);
 
-                // Draw each segment of the edge with interactive hitboxes
-                for (start, end, seg_label) in [
-                    (screen_src, screen_mid, "src"),
-                    (screen_mid, screen_tgt, "tgt"),
-                ] {
-                    self.draw_edge_segment(edge.id, start, end, ui, seg_label);
+                // Draw each half of the edge with interactive hitboxes, curved through
+                // the control point when it was dragged off the midpoint
+                let [first, second] = render::edge_curve(screen_src, screen_mid, screen_tgt);
+                if overview {
+                    // Nothing is drawn at the control point, so a straight edge is one segment
+                    let line = if first.len() == 2 && second.len() == 2 {
+                        vec![screen_src, screen_tgt]
+                    } else {
+                        first.into_iter().chain(second.into_iter().skip(1)).collect()
+                    };
+                    self.draw_edge_segment(edge.id, line, ui, "line");
+                    continue;
+                }
+                for (points, seg_label) in [(first, "src"), (second, "tgt")] {
+                    self.draw_edge_segment(edge.id, points, ui, seg_label);
                 }
-
-                // self.draw_edge_segment(edge.id, screen_src, screen_tgt, ui, "line");
             }
         }
 
         // 2) Draw all nodes (including edge nodes)
-        for (id, pos) in self.state.positions.clone() {
-            let is_edge = self.state.graph.get_edge(id).is_some();
-            let base_color = if is_edge {
-                Color32::LIGHT_BLUE
-            } else {
-                Color32::LIGHT_GREEN
+        let pointer = ui.input(|i| i.pointer.hover_pos());
+        let reach = ui.style().interaction.interact_radius;
+        let mut selected_rects = Vec::new();
+        // Overview squares go into one mesh, painted where the first node would be
+        let mut squares = egui::Mesh::default();
+        let squares_at = ui.painter().add(egui::Shape::Noop);
+        for &id in &visible {
+            let Some(&pos) = self.state.positions.get(id) else {
+                continue;
             };
-
-            let node_size = egui::vec2(20.0, 20.0) * self.state.camera.zoom;
+            if overview && self.state.graph.get_edge(id).is_some() && !self.selection.contains(id) {
+                continue;
+            }
+            let style = self.state.style_of(id);
             let screen_pos = self.to_screen(pos, screen_origin);
-            let rect = Rect::from_center_size(screen_pos, node_size);
-            let corner_radius = 5.0 * self.state.camera.zoom;
+            let (name, secondary) = self.node_label_galleys(ui.painter(), id, &style);
+            let rect = node_rect_for(screen_pos, zoom, &style, name.as_deref(), secondary.as_deref());
+            let corner_radius = 5.0 * zoom;
 
-            // Interactable region
-            let response = ui.interact(rect, ui.id().with("node").with(id), Sense::all());
+            // Like edges, only the node under the pointer (or one being dragged) gets an interactive
+            // region, so a frame doesn't register thousands of them
+            let widget = ui.id().with("node").with(id);
+            let under_pointer = pointer.is_some_and(|p| rect.expand(reach).contains(p));
+            let held = ctx.dragged_id() == Some(widget) || ctx.drag_stopped_id() == Some(widget);
+            let response = (under_pointer || held).then(|| ui.interact(rect, widget, Sense::all()));
 
             // Only turn fill yellow if self.highlight is true AND hovered
-            let fill_color = if self.highlight && response.hovered() {
+            let fill_color = if self.highlight && response.as_ref().is_some_and(|r| r.hovered()) {
                 Color32::YELLOW
             } else {
-                base_color
+                style.fill
             };
 
-            // Always use a consistent black border
-            let stroke = Stroke::new(1.0, Color32::BLACK);
-            ui.painter().rect(rect, corner_radius, fill_color, stroke, StrokeKind::Middle);
+            if overview {
+                // A few pixels across, a filled square reads the same as the full shape
+                squares.add_colored_rect(rect, fill_color);
+            } else {
+                // Always use a consistent black border
+                let stroke = Stroke::new(1.0, Color32::BLACK);
+                paint_node_shape(ui.painter(), style.shape, rect, corner_radius, fill_color, stroke);
+            }
 
             // Process node input (drag, delete, etc.)
-            self.process_node_input(id, &response, screen_origin);
+            if let Some(response) = &response {
+                self.process_node_input(id, response, screen_origin);
+            }
 
-            // Optionally show text if zoomed in enough
-            if self.state.camera.zoom > 0.4 {
-                let text_style = if self.state.camera.zoom < 0.7 {
-                    egui::TextStyle::Small
-                } else {
-                    egui::TextStyle::Body
-                };
-                ui.painter().text(
-                    rect.center(),
-                    egui::Align2::CENTER_CENTER,
-                    format!("{:?}", id.data().as_ffi() as u32),
-                    text_style.resolve(&ctx.style()),
-                    Color32::BLACK,
-                );
+            // Name, with the kind/subtitle line underneath
+            if let Some(name) = name {
+                let secondary_height = secondary.as_ref().map_or(0.0, |g| g.size().y);
+                let name_pos = rect.center() - egui::vec2(name.size().x, name.size().y + secondary_height) * 0.5;
+                let below = name_pos.y + name.size().y;
+                ui.painter().galley(name_pos, name, Color32::BLACK);
+                if let Some(secondary) = secondary {
+                    let pos = egui::pos2(rect.center().x - secondary.size().x * 0.5, below);
+                    ui.painter().galley(pos, secondary, Color32::DARK_GRAY);
+                }
             }
-        }
 
-        // 3) Draw a red highlight for the selected node, only if self.highlight is true
-        if let Some(selected_id) = self.selected {
-            if let Some(pos) = self.state.positions.get(selected_id) {
-                let screen_pos = self.to_screen(*pos, screen_origin) + self.state.camera.zoom*Vec2{x:0.3,y:0.1};
-                let node_radius = 10.0 * self.state.camera.zoom; // node is 20x20
-                let highlight_radius = node_radius + 5.0 * self.state.camera.zoom;
+            if self.state.is_pinned(id) {
+                let font = egui::FontId::proportional(12.0 * zoom.max(0.5));
+                ui.painter().text(rect.right_top(), egui::Align2::CENTER_CENTER, "📌", font, Color32::WHITE);
+            }
 
-                ui.painter().circle_stroke(
-                    screen_pos,
-                    highlight_radius,
-                    Stroke::new(2.3, Color32::RED),
-                );
+            if self.selection.contains(id) {
+                selected_rects.push((id, rect, style.shape));
             }
         }
+
+        ui.painter().set(squares_at, squares);
+
+        // 3) Draw a red highlight around the selected nodes, the primary one slightly heavier
+        for (selected_id, rect, shape) in selected_rects {
+            let width = if self.selection.primary() == Some(selected_id) { 2.3 } else { 1.5 };
+            paint_node_shape(
+                ui.painter(),
+                shape,
+                rect.expand(4.0 * zoom),
+                8.0 * zoom,
+                Color32::TRANSPARENT,
+                Stroke::new(width, Color32::RED),
+            );
+        }
+
+        // 4) Rubber band
+        if let (Some(start), Some(pointer)) = (self.box_select_start, ctx.pointer_latest_pos()) {
+            let rect = Rect::from_two_pos(self.to_screen(start, screen_origin), pointer);
+            ui.painter().rect(
+                rect,
+                0.0,
+                Color32::from_rgba_unmultiplied(100, 150, 255, 30),
+                Stroke::new(1.0, Color32::LIGHT_BLUE),
+                StrokeKind::Inside,
+            );
+        }
     });
 }
 
 
+    // Screen rect and shape of a node drawn at `screen_pos`, sized to its label
+    fn node_outline(&self, painter: &egui::Painter, id: ID, screen_pos: Pos2) -> (Rect, NodeShape) {
+        let style = self.state.style_of(id);
+        let (name, secondary) = self.node_label_galleys(painter, id, &style);
+        let rect = node_rect_for(screen_pos, self.state.camera.zoom, &style, name.as_deref(), secondary.as_deref());
+        (rect, style.shape)
+    }
+
+    // Galleys for a node's name (after its icon) and second line, or None when the zoom hides them.
+    // Nodes with neither name nor icon stay plain shapes
+    fn node_label_galleys(
+        &self,
+        painter: &egui::Painter,
+        id: ID,
+        style: &ResolvedStyle,
+    ) -> (Option<std::sync::Arc<egui::Galley>>, Option<std::sync::Arc<egui::Galley>>) {
+        let zoom = self.state.camera.zoom;
+        if !self.labels.shows_names(zoom) {
+            return (None, None);
+        }
+        let Some((text, secondary_text)) = render::node_label(&self.state, id, style) else {
+            return (None, None);
+        };
+        let name = painter.layout_no_wrap(text, self.labels.name_font(zoom), Color32::BLACK);
+
+        let secondary = secondary_text
+            .filter(|_| self.labels.shows_secondary(zoom))
+            .map(|text| painter.layout_no_wrap(text, self.labels.secondary_font(zoom), Color32::DARK_GRAY));
+        (Some(name), secondary)
+    }
+
     fn to_screen(&self, pos: Pos2, screen_origin: Pos2) -> Pos2 {
         self.state.camera.world_to_screen(pos, screen_origin)
     }
@@ -470,27 +1072,148 @@ fn draw_graph(
 //  4) eframe::App Implementation
 // ─────────────────────────────────────────────────────────────────
 
-impl App for GraphEditor {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut Frame) {
+impl GraphEditor {
+    // One whole editor frame. It needs nothing from eframe, so it can also run without a window
+    pub fn show(&mut self, ctx: &egui::Context) {
+        self.handle_close_request(ctx);
+        self.step_layout_animation(ctx);
+        self.tick_autosave(ctx);
         self.draw_top_panel(ctx);
+        self.draw_selection_bar(ctx);
+        self.draw_inspector(ctx);
 
-        egui::CentralPanel::default().show(ctx, |ui| {
+        let mut canvas_frame = egui::Frame::central_panel(&ctx.style());
+        if let Some(background) = self.state.theme.background {
+            canvas_frame = canvas_frame.fill(background);
+        }
+        egui::CentralPanel::default().frame(canvas_frame).show(ctx, |ui| {
             // We use Sense::click_and_drag() here so we can do e.g. "drag from empty space"
             // if you ever want that. But it's mostly for capturing pointer input.
             let (response, painter) = ui.allocate_painter(ui.available_size(), Sense::click_and_drag());
             let screen_origin = response.rect.left_top();
+            self.canvas_rect = response.rect;
 
             // 1) Global input (zoom, pan, new node in empty space):
             self.process_global_input(ctx, &response, screen_origin);
 
-            // 2) Cleanup orphaned positions, then draw the graph
-            self.state.cleanup_positions();
+            // 2) Draw the graph
             self.draw_graph(&painter, screen_origin, ctx, ui);
         });
 
+        self.draw_relations_window(ctx);
+        self.draw_schema_window(ctx);
+        self.draw_csv_wizard(ctx);
+        self.draw_image_export(ctx);
         self.draw_help_overlay(ctx);
+        self.draw_unsaved_prompt(ctx);
+        self.draw_recovery_prompt(ctx);
+        self.toasts.show(ctx);
+    }
+}
+
+impl App for GraphEditor {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut Frame) {
+        self.show(ctx);
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.finish_session();
+    }
+}
+
+// render::node_rect for labels laid out by the painter
+fn node_rect_for(
+    center: Pos2,
+    zoom: f32,
+    style: &ResolvedStyle,
+    name: Option<&egui::Galley>,
+    secondary: Option<&egui::Galley>,
+) -> Rect {
+    render::node_rect(center, zoom, style, name.map(|g| g.size()), secondary.map(|g| g.size()))
+}
+
+fn paint_node_shape(
+    painter: &egui::Painter,
+    shape: NodeShape,
+    rect: Rect,
+    corner_radius: f32,
+    fill: Color32,
+    stroke: Stroke,
+) {
+    match shape {
+        NodeShape::Rectangle => {
+            painter.rect(rect, corner_radius, fill, stroke, StrokeKind::Middle);
+        }
+        NodeShape::Circle => {
+            painter.circle(rect.center(), rect.width() * 0.5, fill, stroke);
+        }
+        NodeShape::Diamond | NodeShape::Hexagon => {
+            let points = shape.polygon(rect).unwrap_or_default();
+            painter.add(egui::Shape::convex_polygon(points, fill, stroke));
+        }
+    }
+}
+
+fn draw_styled_line(painter: &egui::Painter, points: &[Pos2], stroke: Stroke, line: LineStyle, zoom: f32) {
+    match (line, render::dash_pattern(line, zoom)) {
+        (_, None) => match points {
+            [a, b] => {
+                painter.line_segment([*a, *b], stroke);
+            }
+            _ => {
+                painter.line(points.to_vec(), stroke);
+            }
+        },
+        (LineStyle::Dotted, Some([_, gap])) => {
+            painter.extend(egui::Shape::dotted_line(points, stroke.color, gap, stroke.width * 0.75));
+        }
+        (_, Some([dash, gap])) => {
+            painter.extend(egui::Shape::dashed_line(points, stroke, dash, gap));
+        }
     }
 }
+
+// Filled triangle at the end of the line, pointing along its last stretch
+fn draw_arrowhead(painter: &egui::Painter, line: &[Pos2], color: Color32, size: f32) {
+    if let Some(points) = render::arrowhead_on(line, size) {
+        painter.add(egui::Shape::convex_polygon(points.to_vec(), color, Stroke::NONE));
+    }
+}
+
+// Draw `text` centered above the segment a-b, rotated to follow it but never upside down
+fn draw_text_along(
+    painter: &egui::Painter,
+    a: Pos2,
+    b: Pos2,
+    text: String,
+    font: egui::FontId,
+    color: Color32,
+) {
+    let dir = b - a;
+    if dir.length_sq() < 1.0 {
+        return;
+    }
+    let angle = render::upright_angle(dir);
+
+    let galley = painter.layout_no_wrap(text, font, color);
+    // Don't squeeze a label onto a segment shorter than itself
+    if galley.size().x > dir.length() {
+        return;
+    }
+    // The text shape rotates around its top-left corner
+    let rot = egui::emath::Rot2::from_angle(angle);
+    let offset = rot * egui::vec2(-galley.size().x * 0.5, -galley.size().y - 2.0);
+    let center = a + dir * 0.5;
+    painter.add(egui::epaint::TextShape::new(center + offset, galley, color).with_angle(angle));
+}
+
+fn distance_to_polyline(p: Pos2, points: &[Pos2]) -> f32 {
+    points
+        .windows(2)
+        .map(|pair| distance_to_segment(p, pair[0], pair[1]))
+        .fold(f32::INFINITY, f32::min)
+}
+
 fn distance_to_segment(p: Pos2, a: Pos2, b: Pos2) -> f32 {
     let ab = b - a;
     let ap = p - a;
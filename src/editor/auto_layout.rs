@@ -0,0 +1,249 @@
+// Layout menu: automatic layouts, pinning and straightening bent edges. Layouts play out on the canvas: force layouts
+// step by step, the others gliding from the old positions to the new ones
+use eframe::egui;
+use egui::Pos2;
+use std::collections::BTreeSet;
+
+use super::GraphEditor;
+use crate::graph::ID;
+use crate::layout;
+use crate::layout::force::{self, ForceLayout, ForceSimulation};
+use crate::layout::layered::{self, LayerDirection, LayeredLayout};
+use crate::layout::radial::{self, CircularLayout, RadialLayout};
+
+// Simulation steps per frame while animating
+const STEPS_PER_FRAME: usize = 2;
+// Most nodes a force layout is animated for; larger ones would stall every frame
+const MAX_ANIMATED_NODES: usize = 2000;
+// How long nodes take to glide into a computed layout, in seconds
+const TRANSITION_TIME: f64 = 0.6;
+
+enum Motion {
+    Force(ForceSimulation),
+    // Each node from one position to another; the clock starts on the first frame
+    Glide { paths: Vec<(ID, Pos2, Pos2)>, started: Option<f64> },
+}
+
+// A layout playing out on the canvas
+pub(super) struct LayoutAnimation {
+    motion: Motion,
+    // Where the moving nodes started, recorded as one undo step when the animation ends
+    start: Vec<(ID, Pos2)>,
+}
+
+impl GraphEditor {
+    // Lay out the selection when there is one, otherwise the whole world
+    fn layout_scope(&self) -> Option<BTreeSet<ID>> {
+        (!self.selection.is_empty()).then(|| self.selection.iter().collect())
+    }
+
+    fn start_positions(&self, ids: &[ID]) -> Vec<(ID, Pos2)> {
+        ids.iter()
+            .filter_map(|&id| self.state.positions.get(id).map(|&pos| (id, pos)))
+            .collect()
+    }
+
+    pub(super) fn draw_layout_menu(&mut self, ui: &mut egui::Ui) {
+        let scope = self.layout_scope();
+        ui.label(if scope.is_some() { "Arranges the selection" } else { "Arranges the whole world" });
+        ui.label("Pinned nodes stay where they are");
+        ui.separator();
+
+        if ui.button("Force-directed").clicked() {
+            ui.close_menu();
+            self.stop_layout_animation();
+            let movable = layout::movable_nodes(&self.state, scope.as_ref());
+            let start = self.start_positions(&movable);
+            force::run(&mut self.state, self.force_layout, scope.as_ref());
+            self.history.record_move(start);
+        }
+        let simulating = matches!(self.layout_animation, Some(LayoutAnimation { motion: Motion::Force(_), .. }));
+        let too_many = !simulating && layout::movable_nodes(&self.state, scope.as_ref()).len() > MAX_ANIMATED_NODES;
+        let animated = ui
+            .add_enabled(!too_many, egui::SelectableLabel::new(simulating, "Force-directed (animated)"))
+            .on_disabled_hover_text(format!("Only animated for up to {MAX_ANIMATED_NODES} nodes"));
+        if animated.clicked() {
+            ui.close_menu();
+            self.stop_layout_animation();
+            if !simulating {
+                let simulation = ForceSimulation::new(&self.state, self.force_layout, scope.as_ref());
+                let start = self.start_positions(simulation.movable());
+                self.layout_animation = Some(LayoutAnimation { motion: Motion::Force(simulation), start });
+            }
+        }
+        ui.menu_button("Force settings", |ui| force_settings(ui, &mut self.force_layout));
+
+        if ui.button("Layered (hierarchy)").clicked() {
+            ui.close_menu();
+            let moves = layered::layout(&self.state, &self.layered_layout, scope.as_ref());
+            self.glide_to(moves);
+        }
+        ui.menu_button("Layered settings", |ui| layered_settings(ui, &mut self.layered_layout));
+
+        // The root is the node picked last; a lone selected root spreads out over the whole world
+        let root = self.selection.primary().filter(|&id| self.state.graph.get_edge(id).is_none());
+        let radial_scope = scope.as_ref().filter(|scope| scope.len() > 1);
+        let clicked = ui
+            .add_enabled(root.is_some(), egui::Button::new("Radial around selected"))
+            .on_disabled_hover_text("Select a node to center on")
+            .clicked();
+        if let Some(root) = root.filter(|_| clicked) {
+            ui.close_menu();
+            let moves = radial::radial(&self.state, root, &self.radial_layout, radial_scope);
+            self.glide_to(moves);
+        }
+        if ui.button("Circular").clicked() {
+            ui.close_menu();
+            let moves = radial::circular(&self.state, &self.circular_layout, scope.as_ref());
+            self.glide_to(moves);
+        }
+        ui.menu_button("Radial/circular settings", |ui| {
+            ring_settings(ui, &mut self.radial_layout, &mut self.circular_layout);
+        });
+
+        ui.separator();
+        let selected = self.selection.to_vec();
+        for (label, pinned) in [("📌 Pin selection", true), ("Unpin selection", false)] {
+            if ui.add_enabled(!selected.is_empty(), egui::Button::new(label)).clicked() {
+                ui.close_menu();
+                if let Err(error) = self.history.set_pinned(&mut self.state, &selected, pinned) {
+                    self.report_error(error);
+                }
+            }
+        }
+        // Bent edges in the selection, or all of them when nothing is selected
+        let bent: Vec<ID> = match scope.as_ref() {
+            Some(scope) => scope.iter().copied().filter(|&id| self.state.is_detached(id)).collect(),
+            None => self.state.edge_offsets.keys().collect(),
+        };
+        if ui.add_enabled(!bent.is_empty(), egui::Button::new("Straighten edges")).clicked() {
+            ui.close_menu();
+            if let Err(error) = self.history.reattach_edges(&mut self.state, &bent) {
+                self.report_error(error);
+            }
+        }
+    }
+
+    // Animate the nodes from where they are to `moves`
+    fn glide_to(&mut self, to: Vec<(ID, Pos2)>) {
+        self.stop_layout_animation();
+        let paths: Vec<_> = to
+            .into_iter()
+            .filter_map(|(id, end)| Some((id, *self.state.positions.get(id)?, end)))
+            .collect();
+        let start = paths.iter().map(|&(id, from, _)| (id, from)).collect();
+        self.layout_animation = Some(LayoutAnimation { motion: Motion::Glide { paths, started: None }, start });
+    }
+
+    // Advance a running layout by one frame
+    pub(super) fn step_layout_animation(&mut self, ctx: &egui::Context) {
+        let Some(animation) = &mut self.layout_animation else {
+            return;
+        };
+        let finished = match &mut animation.motion {
+            Motion::Force(simulation) => {
+                for _ in 0..STEPS_PER_FRAME {
+                    simulation.step(&mut self.state);
+                }
+                simulation.is_settled()
+            }
+            Motion::Glide { paths, started } => {
+                let now = ctx.input(|i| i.time);
+                let t = ((now - *started.get_or_insert(now)) / TRANSITION_TIME).min(1.0) as f32;
+                // Smoothstep, so nodes ease in and out
+                let eased = t * t * (3.0 - 2.0 * t);
+                let frame = paths.iter().map(|&(id, from, to)| (id, from.lerp(to, eased))).collect();
+                layout::apply(&mut self.state, frame);
+                t >= 1.0
+            }
+        };
+        if finished {
+            self.stop_layout_animation();
+        } else {
+            ctx.request_repaint();
+        }
+    }
+
+    // End a running animation as a single undo step. A force layout stops where it is,
+    // a glide jumps to where it was going
+    pub(super) fn stop_layout_animation(&mut self) {
+        let Some(animation) = self.layout_animation.take() else {
+            return;
+        };
+        if let Motion::Glide { paths, .. } = animation.motion {
+            let end = paths
+                .into_iter()
+                .filter(|&(id, _, _)| self.state.graph.contains(id))
+                .map(|(id, _, to)| (id, to))
+                .collect();
+            layout::apply(&mut self.state, end);
+        }
+        let start = animation
+            .start
+            .into_iter()
+            .filter(|&(id, _)| self.state.graph.contains(id))
+            .collect();
+        self.history.record_move(start);
+    }
+}
+
+fn ring_settings(ui: &mut egui::Ui, radial: &mut RadialLayout, circular: &mut CircularLayout) {
+    egui::Grid::new("ring_settings").num_columns(2).show(ui, |ui| {
+        ui.label("Ring spacing");
+        ui.add(egui::Slider::new(&mut radial.ring_gap, 40.0..=400.0));
+        ui.end_row();
+        ui.label("Spacing on rings");
+        ui.add(egui::Slider::new(&mut radial.node_gap, 30.0..=300.0));
+        ui.end_row();
+        ui.label("Spacing on circle");
+        ui.add(egui::Slider::new(&mut circular.node_gap, 30.0..=300.0));
+        ui.end_row();
+    });
+    if ui.button("Reset").clicked() {
+        *radial = RadialLayout::default();
+        *circular = CircularLayout::default();
+    }
+}
+
+fn layered_settings(ui: &mut egui::Ui, layout: &mut LayeredLayout) {
+    egui::Grid::new("layered_settings").num_columns(2).show(ui, |ui| {
+        ui.label("Direction");
+        egui::ComboBox::from_id_salt("layer_direction")
+            .selected_text(layout.direction.name())
+            .show_ui(ui, |ui| {
+                for direction in LayerDirection::ALL {
+                    ui.selectable_value(&mut layout.direction, direction, direction.name());
+                }
+            });
+        ui.end_row();
+        ui.label("Layer spacing");
+        ui.add(egui::Slider::new(&mut layout.layer_gap, 40.0..=400.0));
+        ui.end_row();
+        ui.label("Node spacing");
+        ui.add(egui::Slider::new(&mut layout.node_gap, 40.0..=400.0));
+        ui.end_row();
+    });
+    if ui.button("Reset").clicked() {
+        *layout = LayeredLayout::default();
+    }
+}
+
+fn force_settings(ui: &mut egui::Ui, layout: &mut ForceLayout) {
+    egui::Grid::new("force_settings").num_columns(2).show(ui, |ui| {
+        ui.label("Edge length");
+        ui.add(egui::Slider::new(&mut layout.spring_length, 30.0..=400.0));
+        ui.end_row();
+        ui.label("Spring strength");
+        ui.add(egui::Slider::new(&mut layout.spring_strength, 0.1..=5.0));
+        ui.end_row();
+        ui.label("Repulsion");
+        ui.add(egui::Slider::new(&mut layout.repulsion, 0.1..=5.0));
+        ui.end_row();
+        ui.label("Gravity");
+        ui.add(egui::Slider::new(&mut layout.gravity, 0.0..=0.2));
+        ui.end_row();
+    });
+    if ui.button("Reset").clicked() {
+        *layout = ForceLayout::default();
+    }
+}
@@ -0,0 +1,168 @@
+// CSV import wizard: pick a nodes table and optionally an edges table, say what each column
+// means, then import
+use eframe::egui;
+use rfd::FileDialog;
+use std::path::{Path, PathBuf};
+
+use super::GraphEditor;
+use crate::interchange::csv::{self, EdgeColumn, NodeColumn, Table};
+use crate::interchange::ValueType;
+
+// How many values of each column the mapping grid previews
+const SAMPLES: usize = 3;
+
+struct LoadedTable<C> {
+    path: PathBuf,
+    table: Table,
+    columns: Vec<C>,
+}
+
+#[derive(Default)]
+pub(super) struct CsvWizard {
+    pub(super) open: bool,
+    nodes: Option<LoadedTable<NodeColumn>>,
+    edges: Option<LoadedTable<EdgeColumn>>,
+}
+
+fn pick_table(title: &str) -> Option<(PathBuf, Result<Table, String>)> {
+    let path = FileDialog::new()
+        .set_title(title)
+        .add_filter("CSV", &["csv", "txt"])
+        .pick_file()?;
+    let table = std::fs::read_to_string(&path)
+        .map_err(|error| error.to_string())
+        .and_then(|text| Table::parse(&text).map_err(|error| error.to_string()));
+    Some((path, table))
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().into_owned())
+}
+
+// One row per column: its header, what it means, and a few of its values
+fn mapping_grid<C: PartialEq + Clone>(
+    ui: &mut egui::Ui,
+    id: &str,
+    loaded: &mut LoadedTable<C>,
+    options: &[C],
+    name: fn(&C) -> String,
+) {
+    egui::Grid::new(id).num_columns(3).striped(true).show(ui, |ui| {
+        ui.strong("Column");
+        ui.strong("Meaning");
+        ui.strong("Values");
+        ui.end_row();
+        for (i, header) in loaded.table.headers.iter().enumerate() {
+            ui.label(header);
+            let current = &mut loaded.columns[i];
+            egui::ComboBox::from_id_salt((id, i))
+                .selected_text(name(current))
+                .show_ui(ui, |ui| {
+                    for option in options {
+                        ui.selectable_value(current, option.clone(), name(option));
+                    }
+                });
+            ui.label(loaded.table.sample(i, SAMPLES).join(", "));
+            ui.end_row();
+        }
+    });
+}
+
+impl GraphEditor {
+    pub(super) fn draw_csv_wizard(&mut self, ctx: &egui::Context) {
+        let mut open = self.csv_wizard.open;
+        let mut import = false;
+        egui::Window::new("Import CSV")
+            .open(&mut open)
+            .default_width(460.0)
+            .show(ctx, |ui| {
+                let node_options: Vec<NodeColumn> = NodeColumn::FIELDS
+                    .into_iter()
+                    .chain(ValueType::ALL.map(NodeColumn::Property))
+                    .collect();
+                let edge_options: Vec<EdgeColumn> = EdgeColumn::FIELDS
+                    .into_iter()
+                    .chain(ValueType::ALL.map(EdgeColumn::Property))
+                    .collect();
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    ui.heading("Nodes");
+                    ui.horizontal(|ui| {
+                        match &self.csv_wizard.nodes {
+                            Some(loaded) => ui.label(file_name(&loaded.path)),
+                            None => ui.weak("No table chosen"),
+                        };
+                        if ui.button("Choose…").clicked() {
+                            if let Some((path, table)) = pick_table("Nodes table") {
+                                match table {
+                                    Ok(table) => {
+                                        let columns = csv::guess_node_columns(&table);
+                                        self.csv_wizard.nodes = Some(LoadedTable { path, table, columns });
+                                    }
+                                    Err(error) => self.toasts.error(format!("Could not read {}: {error}", path.display())),
+                                }
+                            }
+                        }
+                    });
+                    if let Some(loaded) = &mut self.csv_wizard.nodes {
+                        ui.label(format!("{} rows", loaded.table.rows.len()));
+                        mapping_grid(ui, "csv_node_columns", loaded, &node_options, NodeColumn::name);
+                    }
+
+                    ui.separator();
+                    ui.heading("Edges (optional)");
+                    ui.horizontal(|ui| {
+                        match &self.csv_wizard.edges {
+                            Some(loaded) => ui.label(file_name(&loaded.path)),
+                            None => ui.weak("No table chosen"),
+                        };
+                        if ui.button("Choose…").clicked() {
+                            if let Some((path, table)) = pick_table("Edges table") {
+                                match table {
+                                    Ok(table) => {
+                                        let columns = csv::guess_edge_columns(&table);
+                                        self.csv_wizard.edges = Some(LoadedTable { path, table, columns });
+                                    }
+                                    Err(error) => self.toasts.error(format!("Could not read {}: {error}", path.display())),
+                                }
+                            }
+                        }
+                        if self.csv_wizard.edges.is_some() && ui.button("Clear").clicked() {
+                            self.csv_wizard.edges = None;
+                        }
+                    });
+                    if let Some(loaded) = &mut self.csv_wizard.edges {
+                        ui.label(format!("{} rows", loaded.table.rows.len()));
+                        mapping_grid(ui, "csv_edge_columns", loaded, &edge_options, EdgeColumn::name);
+                    }
+                });
+
+                ui.separator();
+                ui.weak("Edges refer to nodes (or other edges) by id. Nodes without x/y are laid out automatically.");
+                let ready = self.csv_wizard.nodes.is_some();
+                if ui.add_enabled(ready, egui::Button::new("📥 Import")).clicked() {
+                    import = true;
+                }
+            });
+        self.csv_wizard.open = open;
+
+        if import {
+            self.import_csv_tables();
+        }
+    }
+
+    fn import_csv_tables(&mut self) {
+        let Some(nodes) = &self.csv_wizard.nodes else {
+            return;
+        };
+        let edges = self.csv_wizard.edges.as_ref().map(|e| (&e.table, e.columns.as_slice()));
+        match csv::import(&mut self.state, &nodes.table, &nodes.columns, edges) {
+            Ok(report) => {
+                self.finish_import(report);
+                self.csv_wizard = CsvWizard::default();
+            }
+            // Nothing was added, so the wizard stays open to fix the mapping
+            Err(error) => self.toasts.error(format!("Could not import: {error}")),
+        }
+    }
+}
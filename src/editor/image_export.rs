@@ -0,0 +1,139 @@
+// Export window for pictures of the graph: SVG with real text, or PNG at a chosen resolution
+use eframe::egui::{self, Vec2};
+use rfd::FileDialog;
+
+use super::GraphEditor;
+use crate::render::{self, png, svg, Area, Fonts, RenderOptions};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Svg,
+    Png,
+}
+
+pub(super) struct ImageExport {
+    pub(super) open: bool,
+    // Whole world, or just what the canvas shows right now
+    whole_world: bool,
+    format: Format,
+    // PNG pixels per world unit (or per screen pixel for the current view)
+    scale: f32,
+    transparent: bool,
+    // Loaded the first time the window opens
+    fonts: Option<Fonts>,
+    // Size of the whole-world picture at scale 1, with the document generation and font size
+    // it was built for, so the window doesn't lay out the world every frame
+    world_size: Option<(u64, f32, Vec2)>,
+}
+
+impl Default for ImageExport {
+    fn default() -> Self {
+        Self {
+            open: false,
+            whole_world: true,
+            format: Format::Png,
+            scale: 2.0,
+            transparent: false,
+            fonts: None,
+            world_size: None,
+        }
+    }
+}
+
+impl GraphEditor {
+    pub(super) fn draw_image_export(&mut self, ctx: &egui::Context) {
+        let mut open = self.image_export.open;
+        let mut export = false;
+        egui::Window::new("Export image").open(&mut open).resizable(false).show(ctx, |ui| {
+            let settings = &mut self.image_export;
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut settings.whole_world, true, "Whole world");
+                ui.radio_value(&mut settings.whole_world, false, "Current view");
+            });
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut settings.format, Format::Png, "PNG");
+                ui.radio_value(&mut settings.format, Format::Svg, "SVG");
+            });
+            ui.checkbox(&mut settings.transparent, "Transparent background");
+            if settings.format == Format::Png {
+                ui.add(egui::Slider::new(&mut settings.scale, 0.5..=8.0).text("Resolution").suffix("×"));
+                let (width, height) = png::pixel_size(self.image_size(), self.image_export.scale);
+                let size = format!("{width} × {height} px");
+                if width > png::MAX_SIDE || height > png::MAX_SIDE {
+                    ui.colored_label(egui::Color32::RED, format!("{size} is too large"));
+                } else {
+                    ui.weak(size);
+                }
+            }
+            ui.separator();
+            if ui.button("📤 Export…").clicked() {
+                export = true;
+            }
+        });
+        self.image_export.open = open;
+
+        if export {
+            self.export_image();
+        }
+    }
+
+    // The picture's size at scale 1. A view is as big as the canvas, the world is laid out again
+    // only once the document or the label size changes
+    fn image_size(&mut self) -> Vec2 {
+        if !self.image_export.whole_world {
+            return self.canvas_rect.size();
+        }
+        let key = (self.history.generation(), self.labels.font_size);
+        match self.image_export.world_size {
+            Some((generation, font_size, size)) if (generation, font_size) == key => size,
+            _ => {
+                let size = self.image_scene().size;
+                self.image_export.world_size = Some((key.0, key.1, size));
+                size
+            }
+        }
+    }
+
+    fn image_scene(&mut self) -> render::Scene {
+        let area = if self.image_export.whole_world {
+            Area::World
+        } else {
+            Area::View { camera: self.state.camera, size: self.canvas_rect.size() }
+        };
+        let options = RenderOptions {
+            area,
+            font_size: self.labels.font_size,
+            transparent: self.image_export.transparent,
+        };
+        let fonts = self.image_export.fonts.get_or_insert_with(Fonts::default);
+        render::build_scene(&self.state, &options, fonts)
+    }
+
+    fn export_image(&mut self) {
+        let (name, extension) = match self.image_export.format {
+            Format::Svg => ("SVG", "svg"),
+            Format::Png => ("PNG", "png"),
+        };
+        let Some(path) = FileDialog::new()
+            .set_title(format!("Export {name}"))
+            .set_file_name(format!("world.{extension}"))
+            .add_filter(name, &[extension])
+            .save_file()
+        else {
+            return;
+        };
+        let scene = self.image_scene();
+        let bytes = match self.image_export.format {
+            Format::Svg => Ok(svg::render(&scene).into_bytes()),
+            Format::Png => {
+                let fonts = self.image_export.fonts.get_or_insert_with(Fonts::default);
+                png::render(&scene, fonts, self.image_export.scale)
+            }
+        };
+        let written = bytes.and_then(|bytes| std::fs::write(&path, bytes).map_err(|error| error.to_string()));
+        match written {
+            Ok(()) => self.toasts.info(format!("Wrote {}", path.display())),
+            Err(error) => self.toasts.error(format!("Could not export {}: {error}", path.display())),
+        }
+    }
+}
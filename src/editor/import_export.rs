@@ -0,0 +1,169 @@
+// Import and export menus for other tools' formats
+use eframe::egui;
+use rfd::FileDialog;
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use super::GraphEditor;
+use crate::interchange::{csv, dot, gexf, graphml, markdown, ImportError, ImportReport};
+
+// How many import warnings get a toast of their own before the rest are summarised
+const SHOWN_WARNINGS: usize = 3;
+
+impl GraphEditor {
+    pub(super) fn draw_import_menu(&mut self, ui: &mut egui::Ui) {
+        if ui.button("Graphviz DOT…").clicked() {
+            ui.close_menu();
+            self.import_with("Import DOT", &["dot", "gv"], |state, path| {
+                dot::import(state, &std::fs::read_to_string(path)?)
+            });
+        }
+        if ui.button("CSV tables…").clicked() {
+            ui.close_menu();
+            self.csv_wizard.open = true;
+        }
+        if ui.button("GraphML…").clicked() {
+            ui.close_menu();
+            self.import_with("Import GraphML", &["graphml", "xml"], |state, path| {
+                graphml::import(state, &std::fs::read_to_string(path)?)
+            });
+        }
+        if ui.button("GEXF (Gephi)…").clicked() {
+            ui.close_menu();
+            self.import_with("Import GEXF", &["gexf"], |state, path| {
+                gexf::import(state, &std::fs::read_to_string(path)?)
+            });
+        }
+        if ui.button("Markdown / Obsidian vault…").clicked() {
+            ui.close_menu();
+            if let Some(dir) = FileDialog::new().set_title("Import vault").pick_folder() {
+                match markdown::read_vault(&dir) {
+                    Ok(notes) => {
+                        let report = markdown::import(&mut self.state, &notes);
+                        self.finish_import(report);
+                    }
+                    Err(error) => self.toasts.error(format!("Could not read {}: {error}", dir.display())),
+                }
+            }
+        }
+    }
+
+    pub(super) fn draw_export_menu(&mut self, ui: &mut egui::Ui) {
+        let selection: BTreeSet<_> = self.selection.iter().collect();
+        let only = (!selection.is_empty()).then_some(&selection);
+        let scope = if only.is_some() { "selection" } else { "world" };
+
+        if ui.button("Image (PNG / SVG)…").clicked() {
+            ui.close_menu();
+            self.image_export.open = true;
+        }
+        if ui.button(format!("Graphviz DOT ({scope})…")).clicked() {
+            ui.close_menu();
+            let text = dot::export(&self.state, only);
+            self.export_text("Export DOT", "world.dot", &["dot", "gv"], text);
+        }
+        if ui.button(format!("CSV tables ({scope})…")).clicked() {
+            ui.close_menu();
+            let (nodes, edges) = csv::export(&self.state, only);
+            self.export_csv(nodes, edges);
+        }
+        if ui.button(format!("GraphML ({scope})…")).clicked() {
+            ui.close_menu();
+            let text = graphml::export(&self.state, only);
+            self.export_text("Export GraphML", "world.graphml", &["graphml"], text);
+        }
+        if ui.button(format!("GEXF ({scope})…")).clicked() {
+            ui.close_menu();
+            let text = gexf::export(&self.state, only);
+            self.export_text("Export GEXF", "world.gexf", &["gexf"], text);
+        }
+        if ui.button(format!("Markdown wiki ({scope})…")).clicked() {
+            ui.close_menu();
+            let notes = markdown::export(&self.state, only);
+            if let Some(dir) = FileDialog::new().set_title("Export wiki into folder").pick_folder() {
+                match markdown::write_vault(&dir, &notes) {
+                    Ok(()) => self.toasts.info(format!("Wrote {} notes to {}", notes.len(), dir.display())),
+                    Err(error) => self.toasts.error(format!("Could not write {}: {error}", dir.display())),
+                }
+            }
+        }
+    }
+
+    // Ask for a file and add its contents to the document as one undo step
+    fn import_with(
+        &mut self,
+        title: &str,
+        extensions: &[&str],
+        import: impl FnOnce(&mut crate::state::GraphState, &Path) -> Result<ImportReport, ImportError>,
+    ) {
+        let Some(path) = FileDialog::new()
+            .set_title(title)
+            .add_filter(title.trim_start_matches("Import "), extensions)
+            .pick_file()
+        else {
+            return;
+        };
+        match import(&mut self.state, &path) {
+            Ok(report) => self.finish_import(report),
+            Err(error) => self
+                .toasts
+                .error(format!("Could not import {}: {error}", path.display())),
+        }
+    }
+
+    pub(super) fn finish_import(&mut self, report: ImportReport) {
+        self.toasts.info(format!("Imported {} elements", report.added.len()));
+        for warning in report.warnings.iter().take(SHOWN_WARNINGS) {
+            self.toasts.error(warning.clone());
+        }
+        if report.warnings.len() > SHOWN_WARNINGS {
+            self.toasts
+                .error(format!("…and {} more warnings", report.warnings.len() - SHOWN_WARNINGS));
+        }
+        self.selection.clear();
+        self.selection.extend(report.nodes(&self.state.graph));
+        self.history.record_insert(report.added);
+        // Relation types and kinds an import adds are not part of the undo step
+        self.history.touch();
+    }
+
+    fn export_text(&mut self, title: &str, file_name: &str, extensions: &[&str], text: String) {
+        let Some(path) = FileDialog::new()
+            .set_title(title)
+            .set_file_name(file_name)
+            .add_filter(title.trim_start_matches("Export "), extensions)
+            .save_file()
+        else {
+            return;
+        };
+        if let Err(error) = std::fs::write(&path, text) {
+            self.toasts
+                .error(format!("Could not write {}: {error}", path.display()));
+        }
+    }
+
+    // The nodes table goes where the user says, the edges table next to it
+    fn export_csv(&mut self, nodes: String, edges: String) {
+        let Some(path) = FileDialog::new()
+            .set_title("Export CSV (nodes table)")
+            .set_file_name("world_nodes.csv")
+            .add_filter("CSV", &["csv"])
+            .save_file()
+        else {
+            return;
+        };
+        let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        let edges_stem = match stem.strip_suffix("_nodes") {
+            Some(base) => format!("{base}_edges"),
+            None => format!("{stem}_edges"),
+        };
+        let edges_path = path.with_file_name(format!("{edges_stem}.csv"));
+        for (path, text) in [(&path, nodes), (&edges_path, edges)] {
+            if let Err(error) = std::fs::write(path, text) {
+                self.toasts.error(format!("Could not write {}: {error}", path.display()));
+                return;
+            }
+        }
+        self.toasts.info(format!("Wrote {} and {}", path.display(), edges_path.display()));
+    }
+}
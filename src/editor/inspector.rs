@@ -0,0 +1,391 @@
+// Side panel showing and editing the primary selected element
+use eframe::egui;
+use egui::{Color32, RichText};
+use slotmap::Key as SlotKey;
+
+use super::style_ui::node_style_editor;
+use super::GraphEditor;
+use crate::graph::{Date, NodeData, PropertyValue, ID};
+
+// Value type picked when adding a new property
+#[derive(Clone, Copy, PartialEq, Default)]
+pub(super) enum PropertyKind {
+    #[default]
+    Text,
+    Number,
+    Bool,
+    Date,
+    Reference,
+}
+
+impl PropertyKind {
+    const ALL: [PropertyKind; 5] = [
+        PropertyKind::Text,
+        PropertyKind::Number,
+        PropertyKind::Bool,
+        PropertyKind::Date,
+        PropertyKind::Reference,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            PropertyKind::Text => "Text",
+            PropertyKind::Number => "Number",
+            PropertyKind::Bool => "Yes/No",
+            PropertyKind::Date => "Date",
+            PropertyKind::Reference => "Reference",
+        }
+    }
+
+    fn default_value(self, this: ID) -> PropertyValue {
+        match self {
+            PropertyKind::Text => PropertyValue::Text(String::new()),
+            PropertyKind::Number => PropertyValue::Number(0.0),
+            PropertyKind::Bool => PropertyValue::Bool(false),
+            PropertyKind::Date => PropertyValue::Date(Date::new(1, 1, 1)),
+            PropertyKind::Reference => PropertyValue::Reference(this),
+        }
+    }
+}
+
+// Inspector text buffers that outlive a single frame
+#[derive(Default)]
+pub(super) struct InspectorState {
+    new_tag: String,
+    new_property: String,
+    new_property_kind: PropertyKind,
+}
+
+impl GraphEditor {
+    pub(super) fn draw_inspector(&mut self, ctx: &egui::Context) {
+        if !self.show_inspector {
+            return;
+        }
+        egui::SidePanel::right("inspector")
+            .default_width(280.0)
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    match self.selection.primary().filter(|&id| self.state.graph.contains(id)) {
+                        Some(id) => self.draw_element_inspector(ui, id),
+                        None => {
+                            ui.heading("Inspector");
+                            ui.label("Nothing selected");
+                        }
+                    }
+                });
+            });
+
+        // A continuous edit ends once nothing is being typed into or dragged
+        let idle = ctx.memory(|m| m.focused().is_none()) && !ctx.input(|i| i.pointer.any_down());
+        if idle {
+            self.history.end_merge();
+        }
+    }
+
+    // Name of an element for display: its own name, or a fallback built from its index
+    pub(super) fn element_name(&self, id: ID) -> String {
+        match self.state.graph.node_data(id) {
+            Some(data) if !data.name.is_empty() => data.name.clone(),
+            _ if self.state.graph.get_edge(id).is_some() => {
+                format!("Edge #{}", id.data().as_ffi() as u32)
+            }
+            _ => format!("#{}", id.data().as_ffi() as u32),
+        }
+    }
+
+    fn draw_element_inspector(&mut self, ui: &mut egui::Ui, id: ID) {
+        let Some(original) = self.state.graph.node_data(id).cloned() else {
+            return;
+        };
+        let mut data = original.clone();
+        let is_edge = self.state.graph.get_edge(id).is_some();
+
+        ui.horizontal(|ui| {
+            ui.heading(if is_edge { "Edge" } else { "Node" });
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.button("🗑").on_hover_text("Delete element").clicked() {
+                    self.delete_element(id);
+                }
+                if ui.button("🎯").on_hover_text("Center view").clicked() {
+                    self.focus_on(id);
+                }
+            });
+        });
+        if !self.state.graph.contains(id) {
+            return;
+        }
+
+        if let Some(schema) = &self.state.schema {
+            let current = data.kind.and_then(|k| schema.kinds.get(k)).map(|k| k.name.as_str());
+            ui.horizontal(|ui| {
+                ui.label("Kind");
+                egui::ComboBox::from_id_salt("inspector_kind")
+                    .selected_text(current.unwrap_or("(no kind)"))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut data.kind, None, "(no kind)");
+                        for (kind_id, kind) in schema.kinds.iter() {
+                            ui.selectable_value(&mut data.kind, Some(kind_id), &kind.name);
+                        }
+                    });
+            });
+        }
+
+        ui.label("Name");
+        ui.text_edit_singleline(&mut data.name);
+        ui.label("Subtitle");
+        ui.text_edit_singleline(&mut data.subtitle);
+        ui.label("Description");
+        ui.add(egui::TextEdit::multiline(&mut data.description).desired_rows(3));
+
+        if is_edge {
+            self.draw_edge_fields(ui, id);
+        }
+
+        if !is_edge || !data.style.is_empty() {
+            egui::CollapsingHeader::new("Style").id_salt("inspector_style").show(ui, |ui| {
+                node_style_editor(ui, &mut data.style);
+            });
+        }
+
+        ui.separator();
+        self.draw_tags(ui, &mut data);
+        ui.separator();
+        self.draw_properties(ui, id, &mut data);
+
+        if data != original {
+            if let Err(error) = self.history.edit_node_merging(&mut self.state, id, data) {
+                self.report_error(error);
+            }
+        }
+
+        self.draw_violations(ui, id);
+        ui.separator();
+        self.draw_connections(ui, id);
+    }
+
+    fn draw_edge_fields(&mut self, ui: &mut egui::Ui, id: ID) {
+        let Some(edge) = self.state.graph.get_edge(id).cloned() else {
+            return;
+        };
+        let mut relation = edge.relation;
+        let mut label = edge.label.clone().unwrap_or_default();
+
+        ui.horizontal(|ui| {
+            ui.label("Relation");
+            egui::ComboBox::from_id_salt("inspector_relation")
+                .selected_text(self.relation_name(relation).to_owned())
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut relation, None, "(untyped)");
+                    for (relation_id, r) in self.state.relations.iter() {
+                        ui.selectable_value(&mut relation, Some(relation_id), &r.name);
+                    }
+                });
+        });
+        ui.horizontal(|ui| {
+            ui.label("Label");
+            ui.text_edit_singleline(&mut label);
+        });
+        ui.horizontal(|ui| {
+            ui.label("From");
+            self.element_link(ui, edge.source);
+            ui.label("to");
+            self.element_link(ui, edge.target);
+        });
+        if self.state.is_detached(id) {
+            ui.horizontal(|ui| {
+                ui.label("Bent by hand");
+                if ui.button("Re-attach to midpoint").clicked() {
+                    if let Err(error) = self.history.reattach_edges(&mut self.state, &[id]) {
+                        self.report_error(error);
+                    }
+                }
+            });
+        }
+
+        let label = (!label.is_empty()).then_some(label);
+        if relation != edge.relation || label != edge.label {
+            if let Err(error) = self.history.edit_edge_merging(&mut self.state, id, relation, label) {
+                self.report_error(error);
+            }
+        }
+    }
+
+    fn draw_tags(&mut self, ui: &mut egui::Ui, data: &mut NodeData) {
+        ui.label(RichText::new("Tags").strong());
+        ui.horizontal_wrapped(|ui| {
+            let mut dropped = None;
+            for tag in &data.tags {
+                if ui.small_button(format!("{tag} ✖")).on_hover_text("Remove tag").clicked() {
+                    dropped = Some(tag.clone());
+                }
+            }
+            if let Some(tag) = dropped {
+                data.tags.remove(&tag);
+            }
+        });
+        ui.horizontal(|ui| {
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.inspector.new_tag)
+                    .hint_text("new tag")
+                    .desired_width(140.0),
+            );
+            let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if (ui.button("➕").clicked() || submitted) && !self.inspector.new_tag.trim().is_empty() {
+                data.tags.insert(self.inspector.new_tag.trim().to_owned());
+                self.inspector.new_tag.clear();
+            }
+        });
+    }
+
+    fn draw_properties(&mut self, ui: &mut egui::Ui, id: ID, data: &mut NodeData) {
+        ui.label(RichText::new("Properties").strong());
+
+        let is_plain_node = |other: ID| self.state.graph.contains(other) && self.state.graph.get_edge(other).is_none();
+        let mut dropped = None;
+        let mut jump = None;
+        egui::Grid::new("inspector_properties").num_columns(3).show(ui, |ui| {
+            for (key, value) in data.properties.iter_mut() {
+                ui.label(key);
+                ui.push_id(key, |ui| match value {
+                    PropertyValue::Text(text) => {
+                        ui.text_edit_singleline(text);
+                    }
+                    PropertyValue::Number(number) => {
+                        ui.add(egui::DragValue::new(number).speed(0.1));
+                    }
+                    PropertyValue::Bool(flag) => {
+                        ui.checkbox(flag, "");
+                    }
+                    PropertyValue::Date(date) => {
+                        ui.horizontal(|ui| {
+                            ui.add(egui::DragValue::new(&mut date.year));
+                            ui.add(egui::DragValue::new(&mut date.month).range(1..=12));
+                            ui.add(egui::DragValue::new(&mut date.day).range(1..=31));
+                        });
+                    }
+                    PropertyValue::Reference(target) => {
+                        ui.horizontal(|ui| {
+                            let current = if is_plain_node(*target) {
+                                self.element_name(*target)
+                            } else {
+                                "(missing)".to_owned()
+                            };
+                            // Reference targets: every plain node, by name. Only listed while open
+                            egui::ComboBox::from_id_salt("reference")
+                                .selected_text(current)
+                                .show_ui(ui, |ui| {
+                                    for (node, _) in self.state.graph.nodes_iter() {
+                                        if is_plain_node(node) {
+                                            ui.selectable_value(target, node, self.element_name(node));
+                                        }
+                                    }
+                                });
+                            if ui.small_button("→").on_hover_text("Go to").clicked() {
+                                jump = Some(*target);
+                            }
+                        });
+                    }
+                });
+                if ui.small_button("✖").on_hover_text("Remove property").clicked() {
+                    dropped = Some(key.clone());
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(key) = dropped {
+            data.properties.remove(&key);
+        }
+        if let Some(target) = jump.filter(|&t| self.state.graph.contains(t)) {
+            self.select_element(target);
+            self.focus_on(target);
+        }
+
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.inspector.new_property)
+                    .hint_text("new property")
+                    .desired_width(110.0),
+            );
+            egui::ComboBox::from_id_salt("new_property_kind")
+                .selected_text(self.inspector.new_property_kind.name())
+                .show_ui(ui, |ui| {
+                    for kind in PropertyKind::ALL {
+                        ui.selectable_value(&mut self.inspector.new_property_kind, kind, kind.name());
+                    }
+                });
+            let key = self.inspector.new_property.trim().to_owned();
+            let addable = !key.is_empty() && !data.properties.contains_key(&key);
+            if ui.add_enabled(addable, egui::Button::new("➕")).clicked() {
+                data.properties.insert(key, self.inspector.new_property_kind.default_value(id));
+                self.inspector.new_property.clear();
+            }
+        });
+    }
+
+    fn draw_violations(&mut self, ui: &mut egui::Ui, id: ID) {
+        let Some(schema) = &self.state.schema else {
+            return;
+        };
+        let violations = schema.validate_element(&self.state.graph, id);
+        if violations.is_empty() {
+            return;
+        }
+        ui.separator();
+        for violation in violations {
+            let text = schema.describe(&violation, &self.state.graph, &self.state.relations);
+            ui.colored_label(Color32::LIGHT_RED, format!("⚠ {text}"));
+        }
+    }
+
+    // Incoming and outgoing relations as links to the edge and the element on the other end
+    fn draw_connections(&mut self, ui: &mut egui::Ui, id: ID) {
+        let outgoing = self.state.graph.get_outgoing_edges(id);
+        let incoming = self.state.graph.get_incoming_edges(id);
+
+        ui.label(RichText::new(format!("Outgoing ({})", outgoing.len())).strong());
+        for edge_id in outgoing {
+            let Some(target) = self.state.graph.get_edge(edge_id).map(|e| e.target) else {
+                continue;
+            };
+            ui.horizontal(|ui| {
+                self.relation_link(ui, edge_id);
+                ui.label("→");
+                self.element_link(ui, target);
+            });
+        }
+
+        ui.label(RichText::new(format!("Incoming ({})", incoming.len())).strong());
+        for edge_id in incoming {
+            let Some(source) = self.state.graph.get_edge(edge_id).map(|e| e.source) else {
+                continue;
+            };
+            ui.horizontal(|ui| {
+                self.element_link(ui, source);
+                ui.label("→");
+                self.relation_link(ui, edge_id);
+            });
+        }
+    }
+
+    fn relation_link(&mut self, ui: &mut egui::Ui, edge_id: ID) {
+        let relation = self.state.graph.get_edge(edge_id).and_then(|e| e.relation);
+        let text = match self.state.graph.get_edge(edge_id).and_then(|e| e.label.as_deref()) {
+            Some(label) => format!("{} ({label})", self.relation_name(relation)),
+            None => self.relation_name(relation).to_owned(),
+        };
+        let color = relation
+            .and_then(|r| self.state.relations.get(r))
+            .map_or(Color32::LIGHT_BLUE, |r| r.color);
+        if ui.link(RichText::new(text).color(color)).clicked() {
+            self.select_element(edge_id);
+            self.focus_on(edge_id);
+        }
+    }
+
+    fn element_link(&mut self, ui: &mut egui::Ui, id: ID) {
+        if ui.link(self.element_name(id)).clicked() {
+            self.select_element(id);
+            self.focus_on(id);
+        }
+    }
+}
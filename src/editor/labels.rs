@@ -0,0 +1,84 @@
+// When and how big node names, secondary lines and edge labels are drawn
+use eframe::egui;
+
+pub(super) struct LabelSettings {
+    // Below this zoom nodes are drawn as plain boxes
+    pub(super) node_min_zoom: f32,
+    // Below this zoom only the name is shown, without the kind/subtitle line
+    pub(super) secondary_min_zoom: f32,
+    // Below this zoom edges are drawn without their labels
+    pub(super) edge_min_zoom: f32,
+    // Font size of node names at zoom 1.0; text scales with the zoom
+    pub(super) font_size: f32,
+}
+
+impl Default for LabelSettings {
+    fn default() -> Self {
+        Self {
+            node_min_zoom: 0.4,
+            secondary_min_zoom: 0.7,
+            edge_min_zoom: 0.5,
+            font_size: 14.0,
+        }
+    }
+}
+
+impl LabelSettings {
+    pub(super) fn shows_names(&self, zoom: f32) -> bool {
+        zoom >= self.node_min_zoom
+    }
+
+    // The kind/subtitle line only ever comes with the name above it
+    pub(super) fn shows_secondary(&self, zoom: f32) -> bool {
+        self.shows_names(zoom) && zoom >= self.secondary_min_zoom
+    }
+
+    pub(super) fn shows_edge_labels(&self, zoom: f32) -> bool {
+        zoom >= self.edge_min_zoom
+    }
+
+    pub(super) fn name_font(&self, zoom: f32) -> egui::FontId {
+        egui::FontId::proportional(self.font_size * zoom)
+    }
+
+    pub(super) fn secondary_font(&self, zoom: f32) -> egui::FontId {
+        egui::FontId::proportional(self.font_size * 0.8 * zoom)
+    }
+
+    pub(super) fn edge_font(&self, zoom: f32) -> egui::FontId {
+        egui::FontId::proportional(self.font_size * 0.8 * zoom)
+    }
+
+    pub(super) fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.add(egui::Slider::new(&mut self.node_min_zoom, 0.1..=5.0).text("Names from zoom"));
+        ui.add(egui::Slider::new(&mut self.secondary_min_zoom, 0.1..=5.0).text("Kind/subtitle from zoom"));
+        ui.add(egui::Slider::new(&mut self.edge_min_zoom, 0.1..=5.0).text("Edge labels from zoom"));
+        ui.add(egui::Slider::new(&mut self.font_size, 6.0..=32.0).text("Font size"));
+        if ui.button("Reset").clicked() {
+            *self = Self::default();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_labels_appear_as_you_zoom_in() {
+        let labels = LabelSettings::default();
+        let shown = |zoom| (labels.shows_names(zoom), labels.shows_secondary(zoom), labels.shows_edge_labels(zoom));
+        assert_eq!(shown(0.3), (false, false, false));
+        assert_eq!(shown(0.4), (true, false, false));
+        assert_eq!(shown(0.5), (true, false, true));
+        assert_eq!(shown(0.7), (true, true, true));
+        assert_eq!(shown(3.0), (true, true, true));
+    }
+
+    #[test]
+    fn test_secondary_line_needs_the_name() {
+        let labels = LabelSettings { secondary_min_zoom: 0.2, ..LabelSettings::default() };
+        assert!(!labels.shows_secondary(0.3));
+        assert!(labels.shows_secondary(0.4));
+    }
+}
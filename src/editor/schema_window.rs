@@ -0,0 +1,255 @@
+// Schema editing window: node kinds, relation rules and document validation
+use eframe::egui;
+use egui::{Color32, RichText};
+
+use super::style_ui::node_style_editor;
+use super::GraphEditor;
+use crate::graph::RelationTypeId;
+use crate::schema::{Endpoint, NodeKind, RelationRule, Schema};
+
+impl GraphEditor {
+    pub(super) fn draw_schema_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_schema;
+        // Schema edits are not undo steps, but they still change the document
+        let before = self.show_schema.then(|| self.state.schema.clone());
+        egui::Window::new("Schema")
+            .open(&mut open)
+            .default_width(380.0)
+            .show(ctx, |ui| {
+                if self.state.schema.is_none() {
+                    ui.label("This document has no schema: any elements may be connected.");
+                    if ui.button("➕ Create schema").clicked() {
+                        self.state.schema = Some(Schema::new());
+                    }
+                    return;
+                }
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    self.draw_schema_kinds(ui);
+                    ui.separator();
+                    self.draw_schema_rules(ui);
+                    ui.separator();
+                    self.draw_schema_validation(ui);
+                    ui.separator();
+                    if ui.button("🗑 Remove schema").clicked() {
+                        self.state.schema = None;
+                        self.validation = None;
+                    }
+                });
+            });
+        if before.is_some_and(|before| before != self.state.schema) {
+            self.history.touch();
+        }
+        self.show_schema = open;
+    }
+
+    fn draw_schema_kinds(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Node kinds");
+        let Some(schema) = &mut self.state.schema else {
+            return;
+        };
+
+        let mut to_remove = None;
+        let kind_ids: Vec<_> = schema.kinds.keys().collect();
+        for kind_id in kind_ids {
+            let kind = &mut schema.kinds[kind_id];
+            ui.push_id(kind_id, |ui| {
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut kind.name);
+                    if ui.button("🗑").on_hover_text("Delete kind").clicked() {
+                        to_remove = Some(kind_id);
+                    }
+                });
+                ui.horizontal_wrapped(|ui| {
+                    ui.label("Required:");
+                    let mut dropped = None;
+                    for property in &kind.required_properties {
+                        if ui.small_button(format!("{property} ✖")).clicked() {
+                            dropped = Some(property.clone());
+                        }
+                    }
+                    if let Some(property) = dropped {
+                        kind.required_properties.remove(&property);
+                    }
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.schema_property_buffer)
+                            .hint_text("property")
+                            .desired_width(80.0),
+                    );
+                    if ui.small_button("➕").clicked() {
+                        let property = self.schema_property_buffer.trim();
+                        if !property.is_empty() {
+                            kind.required_properties.insert(property.to_owned());
+                            self.schema_property_buffer.clear();
+                        }
+                    }
+                });
+                egui::CollapsingHeader::new("Style").show(ui, |ui| {
+                    node_style_editor(ui, &mut kind.style);
+                });
+            });
+        }
+        if let Some(kind_id) = to_remove {
+            if let Err(error) = self.history.remove_node_kind(&mut self.state, kind_id) {
+                self.report_error(error);
+            }
+        }
+
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.new_kind_name).hint_text("new kind"));
+            if ui.button("➕ Add kind").clicked() && !self.new_kind_name.trim().is_empty() {
+                if let Some(schema) = &mut self.state.schema {
+                    schema.add_kind(NodeKind::new(self.new_kind_name.trim()));
+                }
+                self.new_kind_name.clear();
+            }
+        });
+    }
+
+    fn draw_schema_rules(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Relation rules");
+        let relation_names: Vec<(RelationTypeId, String)> = self
+            .state
+            .relations
+            .iter()
+            .map(|(id, r)| (id, r.name.clone()))
+            .collect();
+        let Some(schema) = &mut self.state.schema else {
+            return;
+        };
+        ui.checkbox(&mut schema.strict, "Strict: reject relation types without a rule");
+
+        // Every endpoint a rule could mention
+        let mut endpoints = vec![(Endpoint::AnyNode, "any node".to_owned())];
+        endpoints.extend(schema.kinds.iter().map(|(id, k)| (Endpoint::Kind(id), k.name.clone())));
+        endpoints.push((Endpoint::AnyEdge, "any edge".to_owned()));
+        endpoints.extend(
+            relation_names
+                .iter()
+                .map(|(id, name)| (Endpoint::EdgeOfType(*id), format!("\"{name}\" edge"))),
+        );
+
+        let name_of = |relation: Option<RelationTypeId>| {
+            relation
+                .and_then(|r| relation_names.iter().find(|(id, _)| *id == r))
+                .map_or("(untyped)", |(_, name)| name.as_str())
+                .to_owned()
+        };
+
+        let mut to_remove = None;
+        for (index, rule) in schema.rules.iter_mut().enumerate() {
+            ui.push_id(index, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new(name_of(rule.relation)).strong());
+                    if ui.small_button("🗑").clicked() {
+                        to_remove = Some(rule.relation);
+                    }
+                });
+                ui.horizontal(|ui| {
+                    endpoint_menu(ui, "From", &mut rule.sources, &endpoints);
+                    endpoint_menu(ui, "To", &mut rule.targets, &endpoints);
+                });
+                ui.horizontal(|ui| {
+                    limit_editor(ui, "max out", &mut rule.max_outgoing);
+                    limit_editor(ui, "max in", &mut rule.max_incoming);
+                });
+            });
+        }
+        if let Some(relation) = to_remove {
+            schema.remove_rule(relation);
+        }
+
+        let unruled: Vec<Option<RelationTypeId>> = std::iter::once(None)
+            .chain(relation_names.iter().map(|(id, _)| Some(*id)))
+            .filter(|&r| schema.rule_for(r).is_none())
+            .collect();
+        if !unruled.is_empty() {
+            ui.menu_button("➕ Add rule", |ui| {
+                for relation in unruled {
+                    if ui.button(name_of(relation)).clicked() {
+                        schema.set_rule(RelationRule::new(relation));
+                        ui.close_menu();
+                    }
+                }
+            });
+        }
+    }
+
+    fn draw_schema_validation(&mut self, ui: &mut egui::Ui) {
+        if ui.button("✔ Validate document").clicked() {
+            if let Some(schema) = &self.state.schema {
+                let report = schema.validate(&self.state.graph);
+                self.validation = Some(
+                    report
+                        .violations
+                        .iter()
+                        .map(|(id, v)| (*id, schema.describe(v, &self.state.graph, &self.state.relations)))
+                        .collect(),
+                );
+            }
+        }
+
+        let Some(validation) = &self.validation else {
+            return;
+        };
+        if validation.is_empty() {
+            ui.colored_label(Color32::LIGHT_GREEN, "No violations");
+            return;
+        }
+        ui.colored_label(Color32::LIGHT_RED, format!("{} violations", validation.len()));
+        let mut clicked = None;
+        for (id, text) in validation {
+            if ui.link(text).on_hover_text("Select element").clicked() {
+                clicked = Some(*id);
+            }
+        }
+        if let Some(id) = clicked {
+            if self.state.graph.get_node(id).is_some() {
+                self.select_element(id);
+            }
+        }
+    }
+}
+
+fn endpoint_menu(ui: &mut egui::Ui, title: &str, chosen: &mut Vec<Endpoint>, options: &[(Endpoint, String)]) {
+    let names: Vec<&str> = options
+        .iter()
+        .filter(|(e, _)| chosen.contains(e))
+        .map(|(_, name)| name.as_str())
+        .collect();
+    // Endpoints of removed kinds or relation types stay where they were all a side allowed
+    let summary = match (chosen.is_empty(), names.is_empty()) {
+        (true, _) => RichText::new(format!("{title}: anything")),
+        (false, true) => RichText::new(format!("{title}: nothing")).color(Color32::LIGHT_RED),
+        (false, false) => RichText::new(format!("{title}: {}", names.join(", "))),
+    };
+    let dead_end = !chosen.is_empty() && names.is_empty();
+    let response = ui.menu_button(summary, |ui| {
+        for (endpoint, name) in options {
+            let mut on = chosen.contains(endpoint);
+            if ui.checkbox(&mut on, name).changed() {
+                if on {
+                    chosen.retain(|e| options.iter().any(|(option, _)| option == e));
+                    chosen.push(*endpoint);
+                } else {
+                    chosen.retain(|e| e != endpoint);
+                }
+            }
+        }
+    });
+    if dead_end {
+        response
+            .response
+            .on_hover_text("Everything this side allowed was removed, so the rule rejects every edge");
+    }
+}
+
+fn limit_editor(ui: &mut egui::Ui, label: &str, limit: &mut Option<u32>) {
+    let mut enabled = limit.is_some();
+    if ui.checkbox(&mut enabled, label).changed() {
+        *limit = enabled.then_some(1);
+    }
+    if let Some(max) = limit {
+        ui.add(egui::DragValue::new(max).range(0..=1000));
+    }
+}
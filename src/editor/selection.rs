@@ -0,0 +1,78 @@
+// The set of selected elements, plus the one the user touched last
+use std::collections::BTreeSet;
+
+use crate::graph::{Graph, ID};
+
+#[derive(Default)]
+pub(super) struct Selection {
+    ids: BTreeSet<ID>,
+    // Most recently selected element; source for shift-click edges
+    primary: Option<ID>,
+}
+
+impl Selection {
+    pub(super) fn primary(&self) -> Option<ID> {
+        self.primary
+    }
+
+    pub(super) fn contains(&self, id: ID) -> bool {
+        self.ids.contains(&id)
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    pub(super) fn iter(&self) -> impl Iterator<Item = ID> + '_ {
+        self.ids.iter().copied()
+    }
+
+    pub(super) fn to_vec(&self) -> Vec<ID> {
+        self.iter().collect()
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.ids.clear();
+        self.primary = None;
+    }
+
+    // Select only this element
+    pub(super) fn set(&mut self, id: ID) {
+        self.clear();
+        self.add(id);
+    }
+
+    pub(super) fn add(&mut self, id: ID) {
+        self.ids.insert(id);
+        self.primary = Some(id);
+    }
+
+    // Ctrl-click behaviour
+    pub(super) fn toggle(&mut self, id: ID) {
+        if self.ids.remove(&id) {
+            if self.primary == Some(id) {
+                self.primary = self.ids.iter().next_back().copied();
+            }
+        } else {
+            self.add(id);
+        }
+    }
+
+    pub(super) fn extend(&mut self, ids: impl IntoIterator<Item = ID>) {
+        for id in ids {
+            self.add(id);
+        }
+    }
+
+    // Drop elements that no longer exist (after deletes, undo, ...)
+    pub(super) fn retain_existing(&mut self, graph: &Graph) {
+        self.ids.retain(|&id| graph.contains(id));
+        if self.primary.is_some_and(|id| !self.ids.contains(&id)) {
+            self.primary = self.ids.iter().next_back().copied();
+        }
+    }
+}
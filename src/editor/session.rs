@@ -0,0 +1,253 @@
+// Unsaved-changes tracking, autosave and crash recovery for the open document
+use eframe::egui;
+use std::path::{Path, PathBuf};
+
+use super::GraphEditor;
+use crate::autosave::{self, Autosave};
+use crate::state::GraphState;
+
+// Seconds between recovery snapshots while there are unsaved changes
+const AUTOSAVE_INTERVAL: f64 = 30.0;
+
+// Something that throws the current document away, held back until the user decides
+#[derive(Clone, Copy, PartialEq)]
+pub(super) enum PendingAction {
+    New,
+    Load,
+    Quit,
+}
+
+pub(super) struct Session {
+    autosave: Option<Autosave>,
+    // History generation of the document as it was last saved, loaded or created
+    saved: u64,
+    // Where the document was last saved to or loaded from; None for a new world
+    file: Option<PathBuf>,
+    dirty: bool,
+    last_autosave: f64,
+    pending: Option<PendingAction>,
+    // Snapshot left behind by a session that did not close cleanly
+    recovery: Option<PathBuf>,
+    // Set once the user agreed to quit, so the next close request goes through
+    closing: bool,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self {
+            autosave: None,
+            saved: 0,
+            file: None,
+            dirty: false,
+            last_autosave: 0.0,
+            pending: None,
+            recovery: None,
+            closing: false,
+        }
+    }
+}
+
+impl Session {
+    // Autosave into `dir`, remembering a snapshot the previous session left there
+    pub(super) fn with_autosave(dir: PathBuf) -> std::io::Result<Self> {
+        let (autosave, recovery) = Autosave::start(dir)?;
+        Ok(Self {
+            autosave: Some(autosave),
+            recovery,
+            ..Self::default()
+        })
+    }
+
+    pub(super) fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+}
+
+impl GraphEditor {
+    // Editor that autosaves to the standard recovery folder and offers back a crashed session
+    pub fn new() -> Self {
+        let mut editor = Self::default();
+        match Session::with_autosave(autosave::default_dir()) {
+            Ok(session) => editor.session = session,
+            Err(error) => editor.toasts.error(format!("Autosave is off: {error}")),
+        }
+        editor
+    }
+
+    // The current document now matches `file` on disk, or is a new world if there is none
+    pub(super) fn mark_saved(&mut self, file: Option<PathBuf>) {
+        self.session.saved = self.history.generation();
+        self.session.file = file;
+        self.session.dirty = false;
+        if let Some(autosave) = &mut self.session.autosave {
+            let _ = autosave.discard();
+        }
+    }
+
+    fn refresh_dirty(&mut self) {
+        self.session.dirty = self.history.generation() != self.session.saved;
+    }
+
+    // Run `action` now, or ask first if it would throw away unsaved changes
+    pub(super) fn request(&mut self, action: PendingAction) {
+        if self.session.pending.is_some() || self.session.recovery.is_some() {
+            return;
+        }
+        self.refresh_dirty();
+        if self.session.dirty {
+            self.session.pending = Some(action);
+        } else {
+            self.perform(action);
+        }
+    }
+
+    fn perform(&mut self, action: PendingAction) {
+        match action {
+            PendingAction::New => self.new_graph(),
+            PendingAction::Load => self.load_graph(),
+            PendingAction::Quit => {
+                if let Some(autosave) = self.session.autosave.take() {
+                    let _ = autosave.finish();
+                }
+                self.session.closing = true;
+            }
+        }
+    }
+
+    // Window close button: hold the close back while there are unsaved changes
+    pub(super) fn handle_close_request(&mut self, ctx: &egui::Context) {
+        if !ctx.input(|i| i.viewport().close_requested()) {
+            // The user agreed to quit in the prompt since the last close request
+            if self.session.closing {
+                ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+            }
+            return;
+        }
+        if !self.session.closing {
+            self.request(PendingAction::Quit);
+        }
+        if !self.session.closing {
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+        }
+    }
+
+    // Keep the dirty flag current and snapshot unsaved work every AUTOSAVE_INTERVAL
+    pub(super) fn tick_autosave(&mut self, ctx: &egui::Context) {
+        let now = ctx.input(|i| i.time);
+        self.refresh_dirty();
+        // Don't overwrite the snapshot the user hasn't decided about yet
+        if !self.session.dirty || self.session.recovery.is_some() {
+            return;
+        }
+        let Some(autosave) = &mut self.session.autosave else {
+            return;
+        };
+        let due = self.session.last_autosave + AUTOSAVE_INTERVAL;
+        if now < due {
+            // Make sure a frame comes around to write it even if the user goes idle
+            ctx.request_repaint_after(std::time::Duration::from_secs_f64(due - now));
+            return;
+        }
+        self.session.last_autosave = now;
+        if let Err(error) = autosave.write(&self.state, self.history.generation()) {
+            self.toasts.error(format!("Autosave failed: {error}"));
+        }
+    }
+
+    // Current file name in the top bar, with a dot while there are unsaved changes
+    pub(super) fn draw_file_status(&self, ui: &mut egui::Ui) {
+        let name = self
+            .session
+            .file
+            .as_ref()
+            .and_then(|path| path.file_name())
+            .map_or("Untitled".into(), |name| name.to_string_lossy());
+        let (text, hint) = if self.session.dirty {
+            (format!("📄 {name} ●"), "Unsaved changes, autosaved for crash recovery. Ctrl+S to save")
+        } else {
+            (format!("📄 {name}"), "All changes saved")
+        };
+        let location = self
+            .session
+            .file
+            .as_ref()
+            .map_or("Not saved to a file yet".to_owned(), |path| path.display().to_string());
+        ui.label(text).on_hover_text(format!("{location}\n{hint}"));
+    }
+
+    pub(super) fn draw_unsaved_prompt(&mut self, ctx: &egui::Context) {
+        let Some(action) = self.session.pending else {
+            return;
+        };
+        let mut choice = None;
+        egui::Modal::new(egui::Id::new("unsaved_prompt")).show(ctx, |ui| {
+            ui.heading("Unsaved changes");
+            let verb = match action {
+                PendingAction::New => "starting a new world",
+                PendingAction::Load => "loading another world",
+                PendingAction::Quit => "quitting",
+            };
+            ui.label(format!("Save the current world before {verb}?"));
+            ui.horizontal(|ui| {
+                if ui.button("💾 Save").clicked() {
+                    choice = Some(true);
+                }
+                if ui.button("Discard").clicked() {
+                    choice = Some(false);
+                }
+                if ui.button("Cancel").clicked() {
+                    self.session.pending = None;
+                }
+            });
+        });
+        let Some(save) = choice else {
+            return;
+        };
+        // A cancelled or failed save keeps the prompt open
+        if save && !self.save_graph() {
+            return;
+        }
+        self.session.pending = None;
+        self.perform(action);
+        ctx.request_repaint();
+    }
+
+    pub(super) fn draw_recovery_prompt(&mut self, ctx: &egui::Context) {
+        let Some(path) = self.session.recovery.clone() else {
+            return;
+        };
+        let mut restore = None;
+        egui::Modal::new(egui::Id::new("recovery_prompt")).show(ctx, |ui| {
+            ui.heading("Recover unsaved work?");
+            ui.label("The last session did not close cleanly. Restore its autosaved world?");
+            ui.horizontal(|ui| {
+                if ui.button("Restore").clicked() {
+                    restore = Some(true);
+                }
+                if ui.button("Discard").clicked() {
+                    restore = Some(false);
+                }
+            });
+        });
+        let Some(restore) = restore else {
+            return;
+        };
+        self.session.recovery = None;
+        if restore {
+            match GraphState::load_from_file(&path) {
+                // Recovered work has never been saved, so it stays dirty against an empty world
+                Ok(state) => self.replace_document(state),
+                Err(error) => self.toasts.error(format!("Could not restore {}: {error}", path.display())),
+            }
+        } else if let Some(autosave) = &mut self.session.autosave {
+            let _ = autosave.discard();
+        }
+    }
+
+    // Clean shutdown: the snapshot is only needed after a crash
+    pub(super) fn finish_session(&mut self) {
+        if let Some(autosave) = self.session.autosave.take() {
+            let _ = autosave.finish();
+        }
+    }
+}
@@ -0,0 +1,97 @@
+// Widgets for node styles and the document theme
+use eframe::egui;
+
+use super::GraphEditor;
+use crate::style::{NodeShape, NodeStyle, Theme};
+
+// Editor for a style's overrides; fields left unset fall back to the kind or theme
+pub(super) fn node_style_editor(ui: &mut egui::Ui, style: &mut NodeStyle) {
+    egui::Grid::new("node_style").num_columns(2).show(ui, |ui| {
+        let mut has_fill = style.fill.is_some();
+        ui.checkbox(&mut has_fill, "Fill");
+        if has_fill {
+            let fill = style.fill.get_or_insert(egui::Color32::LIGHT_GRAY);
+            ui.color_edit_button_srgba(fill);
+        } else {
+            style.fill = None;
+        }
+        ui.end_row();
+
+        ui.label("Shape");
+        egui::ComboBox::from_id_salt("style_shape")
+            .selected_text(style.shape.map_or("(default)", NodeShape::name))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut style.shape, None, "(default)");
+                for shape in NodeShape::ALL {
+                    ui.selectable_value(&mut style.shape, Some(shape), shape.name());
+                }
+            });
+        ui.end_row();
+
+        let mut has_size = style.size.is_some();
+        ui.checkbox(&mut has_size, "Size");
+        if has_size {
+            let size = style.size.get_or_insert(20.0);
+            ui.add(egui::DragValue::new(size).range(4.0..=200.0));
+        } else {
+            style.size = None;
+        }
+        ui.end_row();
+
+        ui.label("Icon");
+        let mut icon = style.icon.clone().unwrap_or_default();
+        ui.add(egui::TextEdit::singleline(&mut icon).hint_text("emoji").desired_width(60.0));
+        style.icon = (!icon.trim().is_empty()).then(|| icon.trim().to_owned());
+        ui.end_row();
+    });
+}
+
+impl GraphEditor {
+    pub(super) fn draw_theme_menu(&mut self, ui: &mut egui::Ui) {
+        let before = self.state.theme.clone();
+        let theme = &mut self.state.theme;
+        egui::Grid::new("theme").num_columns(2).show(ui, |ui| {
+            ui.label("Node fill");
+            ui.color_edit_button_srgba(&mut theme.node_fill);
+            ui.end_row();
+
+            ui.label("Edge fill");
+            ui.color_edit_button_srgba(&mut theme.edge_fill);
+            ui.end_row();
+
+            ui.label("Shape");
+            egui::ComboBox::from_id_salt("theme_shape")
+                .selected_text(theme.node_shape.name())
+                .show_ui(ui, |ui| {
+                    for shape in NodeShape::ALL {
+                        ui.selectable_value(&mut theme.node_shape, shape, shape.name());
+                    }
+                });
+            ui.end_row();
+
+            ui.label("Node size");
+            ui.add(egui::DragValue::new(&mut theme.node_size).range(4.0..=200.0));
+            ui.end_row();
+
+            ui.label("Edge size");
+            ui.add(egui::DragValue::new(&mut theme.edge_size).range(4.0..=200.0));
+            ui.end_row();
+
+            let mut has_background = theme.background.is_some();
+            ui.checkbox(&mut has_background, "Background");
+            if has_background {
+                let background = theme.background.get_or_insert(egui::Color32::from_gray(30));
+                ui.color_edit_button_srgba(background);
+            } else {
+                theme.background = None;
+            }
+            ui.end_row();
+        });
+        if ui.button("Reset").clicked() {
+            *theme = Theme::default();
+        }
+        if self.state.theme != before {
+            self.history.touch();
+        }
+    }
+}
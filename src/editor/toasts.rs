@@ -0,0 +1,76 @@
+// Transient notifications stacked in the bottom-right corner of the window
+use eframe::egui;
+use egui::{Color32, Stroke};
+
+// How long a toast stays on screen, in seconds
+const TOAST_LIFETIME: f64 = 4.0;
+
+#[derive(Clone, Copy, PartialEq)]
+enum ToastKind {
+    Info,
+    Error,
+}
+
+struct Toast {
+    kind: ToastKind,
+    text: String,
+    // Set the first frame the toast is drawn
+    shown_at: Option<f64>,
+}
+
+#[derive(Default)]
+pub(super) struct Toasts {
+    items: Vec<Toast>,
+}
+
+impl Toasts {
+    pub(super) fn info(&mut self, text: impl Into<String>) {
+        self.push(ToastKind::Info, text.into());
+    }
+
+    pub(super) fn error(&mut self, text: impl Into<String>) {
+        self.push(ToastKind::Error, text.into());
+    }
+
+    fn push(&mut self, kind: ToastKind, text: String) {
+        self.items.push(Toast {
+            kind,
+            text,
+            shown_at: None,
+        });
+    }
+
+    pub(super) fn show(&mut self, ctx: &egui::Context) {
+        let now = ctx.input(|i| i.time);
+        self.items
+            .retain(|toast| toast.shown_at.is_none_or(|t| now - t < TOAST_LIFETIME));
+        if self.items.is_empty() {
+            return;
+        }
+
+        egui::Area::new(egui::Id::new("toasts"))
+            .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-10.0, -10.0))
+            .interactable(false)
+            .show(ctx, |ui| {
+                for toast in &mut self.items {
+                    toast.shown_at.get_or_insert(now);
+                    let accent = match toast.kind {
+                        ToastKind::Info => Color32::LIGHT_BLUE,
+                        ToastKind::Error => Color32::LIGHT_RED,
+                    };
+                    egui::Frame::NONE
+                        .fill(Color32::from_rgba_premultiplied(0, 0, 0, 200))
+                        .corner_radius(5.0)
+                        .stroke(Stroke::new(1.0, accent))
+                        .inner_margin(8.0)
+                        .show(ui, |ui| {
+                            ui.set_max_width(320.0);
+                            ui.colored_label(accent, &toast.text);
+                        });
+                }
+            });
+
+        // Keep repainting so toasts disappear even when nothing else happens
+        ctx.request_repaint_after(std::time::Duration::from_millis(250));
+    }
+}
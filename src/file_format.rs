@@ -0,0 +1,635 @@
+// file_format.rs
+//
+// On-disk layout of a world file:
+//   MAGIC, then a bincode `Header`, then the bincode body of that format version.
+// Files from before the header existed start straight with the body and are treated as version 0.
+//
+// Whenever a serialized type changes, bump FORMAT_VERSION, copy the old definitions into a
+// `vN` module below and give it an `upgrade` to the next version, then add a fixture file.
+use bincode::Options;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::io;
+use std::path::Path;
+
+use crate::state::GraphState;
+
+pub const MAGIC: [u8; 8] = *b"NSIMWRLD";
+pub const FORMAT_VERSION: u32 = 4;
+
+// Never change this struct: it is how readers find out which layout follows
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Header {
+    pub format_version: u32,
+    // Version of node_simulator that wrote the file, for error messages
+    pub app_version: String,
+}
+
+impl Header {
+    pub fn current() -> Self {
+        Self {
+            format_version: FORMAT_VERSION,
+            app_version: env!("CARGO_PKG_VERSION").to_owned(),
+        }
+    }
+}
+
+// How a world is written to disk. The text formats carry exactly the same data as the binary
+// one, IDs included, so a world can go binary -> JSON -> binary without changing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Binary,
+    Json,
+    Ron,
+}
+
+impl Format {
+    pub const ALL: [Format; 3] = [Format::Binary, Format::Json, Format::Ron];
+
+    // Picked by extension; anything unknown is binary, like files from before text formats existed
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("json") => Format::Json,
+            Some("ron") => Format::Ron,
+            _ => Format::Binary,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Binary => "World (binary)",
+            Format::Json => "World (JSON)",
+            Format::Ron => "World (RON)",
+        }
+    }
+
+    pub fn extensions(self) -> &'static [&'static str] {
+        match self {
+            Format::Binary => &["bin", "world"],
+            Format::Json => &["json"],
+            Format::Ron => &["ron"],
+        }
+    }
+}
+
+// The text formats wrap the world with the same information the binary header holds.
+// Field order here is the order in the file, so the header reads first
+#[derive(Serialize, Deserialize)]
+struct TextFile<T> {
+    magic: String,
+    format_version: u32,
+    app_version: String,
+    world: T,
+}
+
+#[derive(Deserialize)]
+struct TextHeader {
+    magic: String,
+    format_version: u32,
+    app_version: String,
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn corrupt(error: impl std::fmt::Display) -> io::Error {
+    invalid_data(format!("corrupt world file: {error}"))
+}
+
+// Same encoding as `bincode::serialize`, but a body with bytes left over is rejected
+// instead of silently read as a shorter older layout
+fn decode_body<T: DeserializeOwned>(bytes: &[u8]) -> io::Result<T> {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .reject_trailing_bytes()
+        .deserialize(bytes)
+        .map_err(corrupt)
+}
+
+pub fn encode(state: &GraphState, format: Format) -> io::Result<Vec<u8>> {
+    let header = Header::current();
+    let text = |state| TextFile {
+        magic: String::from_utf8_lossy(&MAGIC).into_owned(),
+        format_version: header.format_version,
+        app_version: header.app_version.clone(),
+        world: state,
+    };
+    match format {
+        Format::Binary => {
+            let mut bytes = MAGIC.to_vec();
+            bincode::serialize_into(&mut bytes, &header).map_err(io::Error::other)?;
+            bincode::serialize_into(&mut bytes, state).map_err(io::Error::other)?;
+            Ok(bytes)
+        }
+        Format::Json => serde_json::to_vec_pretty(&text(state)).map_err(io::Error::other),
+        Format::Ron => {
+            let config = ron::ser::PrettyConfig::default();
+            let text = ron::ser::to_string_pretty(&text(state), config).map_err(io::Error::other)?;
+            Ok(text.into_bytes())
+        }
+    }
+}
+
+// Where the body of a file of some version comes from; lets one migration chain serve every format
+trait Body {
+    fn read<T: DeserializeOwned>(&self) -> io::Result<T>;
+
+    // Self-describing bodies skip fields a type doesn't know, so dropped fields need no legacy type
+    fn self_describing(&self) -> bool;
+}
+
+struct BinaryBody<'a>(&'a [u8]);
+
+impl Body for BinaryBody<'_> {
+    fn read<T: DeserializeOwned>(&self) -> io::Result<T> {
+        decode_body(self.0)
+    }
+
+    fn self_describing(&self) -> bool {
+        false
+    }
+}
+
+struct TextBody<'a>(&'a str, Format);
+
+impl Body for TextBody<'_> {
+    fn read<T: DeserializeOwned>(&self) -> io::Result<T> {
+        let file: TextFile<T> = match self.1 {
+            Format::Ron => ron::from_str(self.0).map_err(corrupt)?,
+            _ => serde_json::from_str(self.0).map_err(corrupt)?,
+        };
+        Ok(file.world)
+    }
+
+    fn self_describing(&self) -> bool {
+        true
+    }
+}
+
+// Reads a world file of any known version, upgrading it to the current layout
+pub fn decode(bytes: &[u8], format: Format) -> io::Result<GraphState> {
+    match format {
+        Format::Binary => {
+            let Some(mut rest) = bytes.strip_prefix(&MAGIC) else {
+                let mut state = decode_body::<v0::GraphState>(bytes)?.upgrade();
+                state.cleanup_positions();
+                state.settle_all_edges();
+                return Ok(state);
+            };
+            let header: Header = bincode::deserialize_from(&mut rest)
+                .map_err(|error| invalid_data(format!("corrupt world file header: {error}")))?;
+            upgrade(header, BinaryBody(rest))
+        }
+        Format::Json | Format::Ron => {
+            let text = std::str::from_utf8(bytes).map_err(corrupt)?;
+            let header: TextHeader = match format {
+                Format::Ron => ron::from_str(text).map_err(corrupt)?,
+                _ => serde_json::from_str(text).map_err(corrupt)?,
+            };
+            if header.magic.as_bytes() != MAGIC {
+                return Err(invalid_data("not a node_simulator world file"));
+            }
+            let header = Header {
+                format_version: header.format_version,
+                app_version: header.app_version,
+            };
+            upgrade(header, TextBody(text, format))
+        }
+    }
+}
+
+fn upgrade(header: Header, body: impl Body) -> io::Result<GraphState> {
+    let mut state: GraphState = match header.format_version {
+        1 if body.self_describing() => body.read()?,
+        1 => body.read::<v1::GraphState>()?.upgrade(),
+        2 if body.self_describing() => body.read()?,
+        2 => body.read::<v2::GraphState>()?.upgrade(),
+        3 if body.self_describing() => body.read()?,
+        3 => body.read::<v3::GraphState>()?.upgrade(),
+        FORMAT_VERSION => body.read()?,
+        newer => {
+            return Err(invalid_data(format!(
+                "world file uses format {newer} (written by node_simulator {}), \
+                 this version only reads up to format {FORMAT_VERSION}",
+                header.app_version
+            )))
+        }
+    };
+    // Older versions kept positions of removed elements around until the editor drew them
+    state.cleanup_positions();
+    // Older versions drew parallel edges on top of each other, and edges are only filed in the
+    // spatial index by the whole curve they draw once they are placed
+    state.settle_all_edges();
+    Ok(state)
+}
+
+// The original headerless layout: nodes carried no data and edges no relation
+mod v0 {
+    use eframe::egui::{Pos2, Vec2};
+    use serde::{Deserialize, Serialize};
+    use slotmap::{DenseSlotMap, SecondaryMap, SparseSecondaryMap};
+    use std::collections::{HashMap, HashSet};
+
+    use crate::graph::{NodeData, ID};
+    use crate::state::Camera;
+
+    #[derive(Serialize, Deserialize)]
+    pub struct NodeDataV0 {}
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Node {
+        id: ID,
+        data: NodeDataV0,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Edge {
+        id: ID,
+        source: ID,
+        target: ID,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Graph {
+        nodes: DenseSlotMap<ID, Node>,
+        edges: SecondaryMap<ID, Edge>,
+        source_to_edges: SparseSecondaryMap<ID, HashSet<ID>>,
+        target_to_edges: SparseSecondaryMap<ID, HashSet<ID>>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct CameraV0 {
+        offset: Vec2,
+        zoom: f32,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct GraphState {
+        graph: Graph,
+        positions: SecondaryMap<ID, Pos2>,
+        camera: CameraV0,
+    }
+
+    impl GraphState {
+        // v0 IDs can't be carried over (the node map changed type), so elements are re-added
+        // in their original order and positions follow them to their new IDs.
+        // Nothing in a v0 file refers to an ID from inside node data, so remapping is safe
+        pub fn upgrade(self) -> super::GraphState {
+            let mut state = super::GraphState::new();
+            let mut new_ids: HashMap<ID, ID> = HashMap::new();
+
+            for (old, _) in self.graph.nodes.iter() {
+                if !self.graph.edges.contains_key(old) {
+                    new_ids.insert(old, state.graph.add_node(NodeData::default()));
+                }
+            }
+            // Edges may hang off other edges, so keep adding whichever are ready
+            let mut pending: Vec<&Edge> = self.graph.edges.values().collect();
+            loop {
+                let before = pending.len();
+                pending.retain(|edge| {
+                    let (Some(&source), Some(&target)) = (new_ids.get(&edge.source), new_ids.get(&edge.target)) else {
+                        return true;
+                    };
+                    // A broken edge in an old file is dropped rather than failing the load
+                    if let Ok(id) = state.graph.add_edge(source, target) {
+                        new_ids.insert(edge.id, id);
+                    }
+                    false
+                });
+                if pending.is_empty() || pending.len() == before {
+                    break;
+                }
+            }
+
+            for (old, pos) in self.positions.iter() {
+                if let Some(&new) = new_ids.get(&old) {
+                    state.positions.insert(new, *pos);
+                }
+            }
+            state.camera = Camera {
+                offset: self.camera.offset,
+                zoom: self.camera.zoom,
+            };
+            state
+        }
+    }
+}
+
+// Graphs stored their adjacency indices after the edges. They are read only to be thrown away:
+// the current Graph rebuilds them from the edges, so a damaged index gets repaired
+mod v1 {
+    use eframe::egui::Pos2;
+    use serde::{Deserialize, Serialize};
+    use slotmap::{SecondaryMap, SparseSecondaryMap};
+    use std::collections::BTreeSet;
+
+    use crate::graph::ID;
+    use crate::relations::RelationRegistry;
+    use crate::schema::Schema;
+    use crate::state::Camera;
+    use crate::style::Theme;
+
+    // Bincode lays structs out back to back, so the current graph (nodes, edges)
+    // followed by the two indices is exactly the old graph
+    #[derive(Serialize, Deserialize)]
+    pub struct Graph {
+        pub(super) graph: crate::graph::Graph,
+        pub(super) source_to_edges: SparseSecondaryMap<ID, BTreeSet<ID>>,
+        pub(super) target_to_edges: SparseSecondaryMap<ID, BTreeSet<ID>>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct GraphState {
+        pub(super) graph: Graph,
+        pub(super) positions: SecondaryMap<ID, Pos2>,
+        pub(super) camera: Camera,
+        pub(super) relations: RelationRegistry,
+        pub(super) schema: Option<Schema>,
+        pub(super) theme: Theme,
+    }
+
+    impl GraphState {
+        pub fn upgrade(self) -> super::GraphState {
+            super::GraphState {
+                graph: self.graph.graph,
+                positions: self.positions.into(),
+                camera: self.camera,
+                relations: self.relations,
+                schema: self.schema,
+                theme: self.theme,
+                pinned: BTreeSet::new(),
+                edge_offsets: SecondaryMap::new(),
+            }
+        }
+    }
+}
+
+// Worlds had no pinned nodes
+mod v2 {
+    use eframe::egui::Pos2;
+    use serde::{Deserialize, Serialize};
+    use slotmap::SecondaryMap;
+    use std::collections::BTreeSet;
+
+    use crate::graph::{Graph, ID};
+    use crate::relations::RelationRegistry;
+    use crate::schema::Schema;
+    use crate::state::Camera;
+    use crate::style::Theme;
+
+    #[derive(Serialize, Deserialize)]
+    pub struct GraphState {
+        pub(super) graph: Graph,
+        pub(super) positions: SecondaryMap<ID, Pos2>,
+        pub(super) camera: Camera,
+        pub(super) relations: RelationRegistry,
+        pub(super) schema: Option<Schema>,
+        pub(super) theme: Theme,
+    }
+
+    impl GraphState {
+        pub fn upgrade(self) -> super::GraphState {
+            super::GraphState {
+                graph: self.graph,
+                positions: self.positions.into(),
+                camera: self.camera,
+                relations: self.relations,
+                schema: self.schema,
+                theme: self.theme,
+                pinned: BTreeSet::new(),
+                edge_offsets: SecondaryMap::new(),
+            }
+        }
+    }
+}
+
+// Before edge control points could be dragged off the midpoint
+mod v3 {
+    use eframe::egui::Pos2;
+    use serde::{Deserialize, Serialize};
+    use slotmap::SecondaryMap;
+    use std::collections::BTreeSet;
+
+    use crate::graph::{Graph, ID};
+    use crate::relations::RelationRegistry;
+    use crate::schema::Schema;
+    use crate::state::Camera;
+    use crate::style::Theme;
+
+    #[derive(Serialize, Deserialize)]
+    pub struct GraphState {
+        pub(super) graph: Graph,
+        pub(super) positions: SecondaryMap<ID, Pos2>,
+        pub(super) camera: Camera,
+        pub(super) relations: RelationRegistry,
+        pub(super) schema: Option<Schema>,
+        pub(super) theme: Theme,
+        pub(super) pinned: BTreeSet<ID>,
+    }
+
+    impl GraphState {
+        pub fn upgrade(self) -> super::GraphState {
+            super::GraphState {
+                graph: self.graph,
+                positions: self.positions.into(),
+                camera: self.camera,
+                relations: self.relations,
+                schema: self.schema,
+                theme: self.theme,
+                pinned: self.pinned,
+                edge_offsets: SecondaryMap::new(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{NodeData, PropertyValue};
+    use crate::style::NodeShape;
+    use eframe::egui::{Pos2, Vec2};
+    use slotmap::SparseSecondaryMap;
+    use std::collections::BTreeSet;
+
+    const V0_FIXTURE: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/world_v0.bin"));
+    const V1_FIXTURE: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/world_v1.bin"));
+    const V2_FIXTURE: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/world_v2.bin"));
+    const V3_FIXTURE: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/world_v3.bin"));
+    const V4_FIXTURE: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/world_v4.bin"));
+
+    fn named(state: &GraphState, name: &str) -> crate::graph::ID {
+        state
+            .graph
+            .nodes_iter()
+            .find(|(_, node)| node.data().name == name)
+            .map(|(id, _)| id)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_v0_fixture_upgrades() {
+        // Three nodes at (0,0), (100,0), (0,100); an edge from the first to the second,
+        // and an edge from the third to that edge
+        let state = decode(V0_FIXTURE, Format::Binary).unwrap();
+        assert_eq!(state.graph.node_count(), 5);
+        assert_eq!(state.graph.edges_iter().count(), 2);
+        assert_eq!(state.camera.offset, Vec2::new(5.0, -5.0));
+        assert_eq!(state.camera.zoom, 1.5);
+
+        let at = |pos: Pos2| state.positions.iter().find(|(_, p)| **p == pos).map(|(id, _)| id).unwrap();
+        let (a, b, c) = (at(Pos2::new(0.0, 0.0)), at(Pos2::new(100.0, 0.0)), at(Pos2::new(0.0, 100.0)));
+        let ab = state.graph.get_outgoing_edges(a)[0];
+        assert_eq!(state.graph.get_edge(ab).unwrap().target, b);
+        assert_eq!(state.positions[ab], Pos2::new(50.0, 0.0));
+        let c_ab = state.graph.get_outgoing_edges(c)[0];
+        assert_eq!(state.graph.get_edge(c_ab).unwrap().target, ab);
+        assert!(state.positions.contains_key(c_ab));
+    }
+
+    // The v1 to v4 fixtures hold the same small world
+    fn check_queen_fixture(state: &GraphState) {
+        let queen = named(state, "Queen Ilsa");
+        let city = named(state, "Varenholm");
+        let data = state.graph.node_data(queen).unwrap();
+        assert_eq!(data.subtitle, "of House Varen");
+        assert!(data.tags.contains("royalty"));
+        assert_eq!(data.properties.get("age"), Some(&PropertyValue::Number(42.0)));
+        assert_eq!(data.style.shape, Some(NodeShape::Circle));
+
+        let rules = state.relations.find_by_name("rules over").unwrap();
+        assert_eq!(state.graph.edges_of_type(rules).len(), 1);
+        let edge = state.graph.get_outgoing_edges(queen)[0];
+        assert_eq!(state.graph.get_edge(edge).unwrap().target, city);
+        assert_eq!(state.graph.get_incoming_edges(city), vec![edge]);
+
+        let schema = state.schema.as_ref().unwrap();
+        let character = schema.find_kind("Character").unwrap();
+        assert_eq!(data.kind, Some(character));
+    }
+
+    #[test]
+    fn test_v1_and_v2_fixtures_load() {
+        check_queen_fixture(&decode(V1_FIXTURE, Format::Binary).unwrap());
+        let v2 = decode(V2_FIXTURE, Format::Binary).unwrap();
+        check_queen_fixture(&v2);
+        assert!(v2.pinned.is_empty());
+    }
+
+    #[test]
+    fn test_v3_fixture_keeps_pins() {
+        let state = decode(V3_FIXTURE, Format::Binary).unwrap();
+        check_queen_fixture(&state);
+        assert_eq!(state.pinned, BTreeSet::from([named(&state, "Queen Ilsa")]));
+        assert!(state.edge_offsets.is_empty());
+    }
+
+    #[test]
+    fn test_v4_fixture_keeps_control_points() {
+        let state = decode(V4_FIXTURE, Format::Binary).unwrap();
+        check_queen_fixture(&state);
+        let edge = state.graph.get_outgoing_edges(named(&state, "Queen Ilsa"))[0];
+        assert_eq!(state.edge_offsets.get(edge), Some(&Vec2::new(0.0, -40.0)));
+        assert_eq!(state.positions[edge], state.midpoint(edge).unwrap() + Vec2::new(0.0, -40.0));
+    }
+
+    #[test]
+    fn test_tampered_v1_index_is_repaired() {
+        let state = decode(V1_FIXTURE, Format::Binary).unwrap();
+        let queen = named(&state, "Queen Ilsa");
+        let city = named(&state, "Varenholm");
+        let edge = state.graph.get_outgoing_edges(queen)[0];
+
+        // Indices claiming the city has an outgoing edge to itself and nothing comes in anywhere
+        let mut source_to_edges = SparseSecondaryMap::new();
+        source_to_edges.insert(city, BTreeSet::from([city]));
+        let tampered = v1::GraphState {
+            graph: v1::Graph {
+                graph: state.graph,
+                source_to_edges,
+                target_to_edges: SparseSecondaryMap::new(),
+            },
+            positions: state.positions.into(),
+            camera: state.camera,
+            relations: state.relations,
+            schema: state.schema,
+            theme: state.theme,
+        };
+        let mut bytes = MAGIC.to_vec();
+        let header = Header {
+            format_version: 1,
+            app_version: "0.1.0".into(),
+        };
+        bincode::serialize_into(&mut bytes, &header).unwrap();
+        bincode::serialize_into(&mut bytes, &tampered).unwrap();
+
+        let repaired = decode(&bytes, Format::Binary).unwrap();
+        assert_eq!(repaired.graph.get_outgoing_edges(queen), vec![edge]);
+        assert_eq!(repaired.graph.get_incoming_edges(city), vec![edge]);
+        assert!(repaired.graph.get_outgoing_edges(city).is_empty());
+    }
+
+    #[test]
+    fn test_round_trip_and_rejections() {
+        let mut state = GraphState::new();
+        let a = state.graph.add_node(NodeData::named("a"));
+        state.positions.insert(a, Pos2::new(1.0, 2.0));
+        let bytes = encode(&state, Format::Binary).unwrap();
+        assert!(bytes.starts_with(&MAGIC));
+        let loaded = decode(&bytes, Format::Binary).unwrap();
+        assert_eq!(loaded.graph.node_data(a).unwrap().name, "a");
+        assert_eq!(loaded.positions[a], Pos2::new(1.0, 2.0));
+
+        // A file from the future is refused with a readable message
+        let mut future = MAGIC.to_vec();
+        let header = Header {
+            format_version: FORMAT_VERSION + 1,
+            app_version: "9.9.9".into(),
+        };
+        bincode::serialize_into(&mut future, &header).unwrap();
+        let Err(error) = decode(&future, Format::Binary) else {
+            panic!("newer format accepted");
+        };
+        assert!(error.to_string().contains("9.9.9"));
+
+        // Truncated or garbage data is an error, not a panic
+        assert!(decode(&bytes[..bytes.len() - 3], Format::Binary).is_err());
+        assert!(decode(b"not a world", Format::Binary).is_err());
+    }
+
+    #[test]
+    fn test_text_formats_round_trip_exactly() {
+        let mut state = decode(V1_FIXTURE, Format::Binary).unwrap();
+        // A tombstone and an edge-to-edge relation, so IDs with gaps survive the trip
+        let gone = state.add_node_at(Pos2::new(3.0, 4.0));
+        state.remove_element(gone).unwrap();
+        let ids: Vec<_> = state.graph.nodes_iter().map(|(id, _)| id).collect();
+        let extra = state.add_node_at(Pos2::new(0.1, -7.25));
+        let edge = state.graph.edges_iter().next().unwrap().id;
+        state.add_edge_between(extra, edge, None).unwrap();
+        let binary = encode(&state, Format::Binary).unwrap();
+
+        for format in [Format::Json, Format::Ron] {
+            let text = encode(&state, format).unwrap();
+            // Stable output: saving the same world twice gives the same file
+            assert_eq!(text, encode(&state, format).unwrap());
+            let loaded = decode(&text, format).unwrap();
+            assert_eq!(encode(&loaded, Format::Binary).unwrap(), binary, "{format:?}");
+            for id in &ids {
+                assert!(loaded.graph.contains(*id));
+            }
+            assert!(!loaded.graph.contains(gone));
+        }
+        assert!(decode(br#"{"magic":"SOMETHING","format_version":1,"app_version":"0","world":null}"#, Format::Json).is_err());
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(Format::from_path(Path::new("world.JSON")), Format::Json);
+        assert_eq!(Format::from_path(Path::new("dir.v2/world.ron")), Format::Ron);
+        assert_eq!(Format::from_path(Path::new("graph_save.bin")), Format::Binary);
+        assert_eq!(Format::from_path(Path::new("no_extension")), Format::Binary);
+    }
+}
@@ -1,14 +1,134 @@
 use serde::{Deserialize, Serialize};
-use slotmap::{new_key_type, DenseSlotMap,SecondaryMap,SparseSecondaryMap};
-use std::collections::{HashSet};
+use slotmap::{new_key_type, SlotMap,SecondaryMap,SparseSecondaryMap};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt;
+
+use crate::schema::SchemaViolation;
+use crate::style::NodeStyle;
 
 new_key_type! {
     pub struct ID;
+    // Index into the document's RelationRegistry
+    pub struct RelationTypeId;
+    // Index into the kinds of the document's Schema
+    pub struct NodeKindId;
+}
+
+// Calendar date for properties; deliberately naive so worlds can use their own calendars
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Date {
+    pub year: i32,
+    pub month: u8,
+    pub day: u8,
+}
+
+impl Date {
+    pub fn new(year: i32, month: u8, day: u8) -> Self {
+        Self { year, month, day }
+    }
+
+    // Parse "YYYY-MM-DD" (the year may be negative). Imports guess dates with this, so a month
+    // or day out of range means the text is not a date
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        let (sign, rest) = match text.strip_prefix('-') {
+            Some(rest) => (-1, rest),
+            None => (1, text),
+        };
+        let mut parts = rest.splitn(3, '-');
+        let year: i32 = parts.next()?.parse().ok()?;
+        let month: u8 = parts.next()?.parse().ok()?;
+        let day: u8 = parts.next()?.parse().ok()?;
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            return None;
+        }
+        Some(Self::new(sign * year, month, day))
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+// Value of a custom property on a node
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum PropertyValue {
+    Text(String),
+    Number(f64),
+    Bool(bool),
+    Date(Date),
+    Reference(ID),
 }
 
 #[derive(Default,Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct NodeData{
-	//some sort of visual indicator should go here
+    pub name: String,
+    // Short second line drawn under the name, e.g. "Queen of Varenholm"
+    pub subtitle: String,
+    pub description: String,
+    pub tags: BTreeSet<String>,
+    // BTreeMap so properties show up (and save) in a stable order
+    pub properties: BTreeMap<String, PropertyValue>,
+    // Only meaningful when the document has a schema
+    pub kind: Option<NodeKindId>,
+    // Overrides on top of the kind's style and the document theme
+    pub style: NodeStyle,
+}
+
+impl NodeData {
+    pub fn named(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..Self::default()
+        }
+    }
+}
+
+// Why a graph or document mutation was refused
+#[derive(Debug, Clone, PartialEq)]
+pub enum GraphError {
+    // An edge can't connect an element to itself
+    SelfLoop(ID),
+    MissingSource(ID),
+    MissingTarget(ID),
+    MissingNode(ID),
+    // The ID doesn't exist or isn't an edge
+    MissingEdge(ID),
+    UnknownRelation(RelationTypeId),
+    UnknownKind(NodeKindId),
+    // Kind operations on a document without a schema
+    NoSchema,
+    // Restoring an element whose ID is in use or was never handed out by this graph
+    CannotRestore(ID),
+    SchemaViolation(SchemaViolation),
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphError::SelfLoop(_) => write!(f, "an edge can't connect an element to itself"),
+            GraphError::MissingSource(_) => write!(f, "the source element doesn't exist"),
+            GraphError::MissingTarget(_) => write!(f, "the target element doesn't exist"),
+            GraphError::MissingNode(_) => write!(f, "the element doesn't exist"),
+            GraphError::MissingEdge(_) => write!(f, "the element is not an edge"),
+            GraphError::UnknownRelation(_) => write!(f, "unknown relation type"),
+            GraphError::UnknownKind(_) => write!(f, "unknown node kind"),
+            GraphError::NoSchema => write!(f, "the document has no schema"),
+            GraphError::CannotRestore(_) => write!(f, "the element can't be restored"),
+            GraphError::SchemaViolation(violation) => write!(f, "schema violation: {violation}"),
+        }
+    }
+}
+
+impl std::error::Error for GraphError {}
+
+impl From<SchemaViolation> for GraphError {
+    fn from(violation: SchemaViolation) -> Self {
+        GraphError::SchemaViolation(violation)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -17,39 +137,201 @@ pub struct Node {
     data: NodeData,
 }
 
+impl Node {
+    pub fn id(&self) -> ID {
+        self.id
+    }
+
+    pub fn data(&self) -> &NodeData {
+        &self.data
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Edge {
     pub id: ID,           // Graph element ID (can be connected to like a node)
     pub source: ID,
     pub target: ID,
+    pub relation: Option<RelationTypeId>,
+    pub label: Option<String>,
+}
+
+// Elements taken out of a graph together (an element plus everything that cascaded with it)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Subgraph {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+impl Subgraph {
+    pub fn ids(&self) -> impl Iterator<Item = ID> + '_ {
+        self.nodes.iter().map(|node| node.id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+// One slot of the node map: a live element, or None for the tombstone of a removed one
+#[derive(Debug, Clone, PartialEq)]
+struct NodeSlot(Option<Node>);
+
+// Text formats can't tell Some(None) from None, which slotmap needs to tell a tombstone from
+// a free slot, so they spell tombstones out. Binary stays a plain Option.
+// Graphs are saved without their tombstones now, but older files may still have them
+#[derive(Serialize)]
+enum TextSlotRef<'a> {
+    Live(&'a Node),
+    Removed,
+}
+
+#[derive(Deserialize)]
+enum TextSlot {
+    Live(Node),
+    Removed,
+}
+
+impl Serialize for NodeSlot {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if !serializer.is_human_readable() {
+            return self.0.serialize(serializer);
+        }
+        match &self.0 {
+            Some(node) => TextSlotRef::Live(node),
+            None => TextSlotRef::Removed,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for NodeSlot {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if !deserializer.is_human_readable() {
+            return Option::deserialize(deserializer).map(NodeSlot);
+        }
+        Ok(NodeSlot(match TextSlot::deserialize(deserializer)? {
+            TextSlot::Live(node) => Some(node),
+            TextSlot::Removed => None,
+        }))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct Graph {
-    nodes: DenseSlotMap<ID, Node>,
+    // Removed elements leave a tombstone (None) instead of freeing their slot,
+    // so undo can bring them back under the very same ID
+    nodes: SlotMap<ID, NodeSlot>,
+    // How many slots of `nodes` are tombstones
+    tombstones: usize,
     edges: SecondaryMap<ID, Edge>,
     
     // Maps node/edge ID to its outgoing edges
-    source_to_edges: SparseSecondaryMap<ID, HashSet<ID>>,
+    source_to_edges: SparseSecondaryMap<ID, BTreeSet<ID>>,
     
     // Maps node/edge ID to its incoming edges
-    target_to_edges: SparseSecondaryMap<ID, HashSet<ID>>,
+    target_to_edges: SparseSecondaryMap<ID, BTreeSet<ID>>,
     
 }
 
+// On disk a graph is only its live elements. The adjacency maps are derived from the edges on
+// load, so a damaged or hand-edited file can't carry indices that disagree with them
+#[derive(Serialize)]
+struct StoredGraphRef<'a> {
+    nodes: &'a SlotMap<ID, NodeSlot>,
+    edges: &'a SecondaryMap<ID, Edge>,
+}
+
+#[derive(Deserialize)]
+struct StoredGraph {
+    nodes: SlotMap<ID, NodeSlot>,
+    edges: SecondaryMap<ID, Edge>,
+}
+
+impl Serialize for Graph {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // Tombstones only matter to the undo history of this session
+        let nodes = if self.tombstones == 0 {
+            Cow::Borrowed(&self.nodes)
+        } else {
+            let mut nodes = self.nodes.clone();
+            nodes.retain(|_, slot| slot.0.is_some());
+            Cow::Owned(nodes)
+        };
+        StoredGraphRef {
+            nodes: &nodes,
+            edges: &self.edges,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Graph {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let stored = StoredGraph::deserialize(deserializer)?;
+        Graph::from_elements(stored.nodes, stored.edges)
+            .map_err(|error| serde::de::Error::custom(format!("invalid graph: {error}")))
+    }
+}
+
 impl Graph {
     pub fn new() -> Self {
         Self::default()
     }
+
+    // Builds the adjacency maps from the edge list, refusing edges that don't fit the nodes
+    fn from_elements(mut nodes: SlotMap<ID, NodeSlot>, edges: SecondaryMap<ID, Edge>) -> Result<Self, GraphError> {
+        // Nothing can restore the tombstones of an older file, so their slots are freed
+        nodes.retain(|_, slot| slot.0.is_some());
+        let mut graph = Graph {
+            nodes,
+            ..Graph::default()
+        };
+        for (key, edge) in edges {
+            if key != edge.id || !graph.contains(key) {
+                return Err(GraphError::MissingEdge(key));
+            }
+            if edge.source == edge.target {
+                return Err(GraphError::SelfLoop(edge.source));
+            }
+            if !graph.contains(edge.source) {
+                return Err(GraphError::MissingSource(edge.source));
+            }
+            if !graph.contains(edge.target) {
+                return Err(GraphError::MissingTarget(edge.target));
+            }
+            graph.link_edge(edge);
+        }
+        Ok(graph)
+    }
     
     pub fn add_node(&mut self, data: NodeData) -> ID {
-        self.nodes.insert_with_key(|k| Node { id: k, data })
+        self.nodes.insert_with_key(|k| NodeSlot(Some(Node { id: k, data })))
+    }
+
+    pub fn contains(&self, id: ID) -> bool {
+        self.get_node(id).is_some()
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len() - self.tombstones
+    }
+
+    // Free the slots of removed elements, except those `keep` holds on to for a later restore
+    pub fn compact(&mut self, keep: impl Fn(ID) -> bool) {
+        self.nodes.retain(|id, slot| slot.0.is_some() || keep(id));
+        self.tombstones = self.nodes.values().filter(|slot| slot.0.is_none()).count();
     }
     
-    pub fn remove_node(&mut self, id: ID) -> Option<Node> {
+    pub fn remove_node(&mut self, id: ID) -> Result<Node, GraphError> {
         // Can't remove a node that doesn't exist
-        if !self.nodes.contains_key(id) {
-            return None;
+        if !self.contains(id) {
+            return Err(GraphError::MissingNode(id));
+        }
+
+        // An edge's node can't outlive the edge itself
+        if let Some(edge) = self.edges.remove(id) {
+            self.unlink_edge(&edge);
         }
         
         // Collect all edges to remove
@@ -65,48 +347,70 @@ impl Graph {
             edges_to_remove.extend(incoming.iter().copied());
         }
         
-        // Remove all connected edges
+        // Remove all connected edges.
+        // An earlier cascade may already have taken some of them, so misses are expected here
         for edge_id in edges_to_remove {
-            self.remove_edge(edge_id);
+            let _ = self.remove_edge(edge_id);
         }
         
         // Remove the node from the lookup maps
         self.source_to_edges.remove(id);
         self.target_to_edges.remove(id);
         
-        // Remove the node
-        self.nodes.remove(id)
+        // Remove the node, keeping its slot reserved
+        let node = self.nodes
+            .get_mut(id)
+            .and_then(|slot| slot.0.take())
+            .ok_or(GraphError::MissingNode(id))?;
+        self.tombstones += 1;
+        Ok(node)
     }
     
-    pub fn add_edge(&mut self, source: ID, target: ID) -> Option<ID> {
+    pub fn add_edge(&mut self, source: ID, target: ID) -> Result<ID, GraphError> {
+        self.add_typed_edge(source, target, None)
+    }
+
+    pub fn add_typed_edge(
+        &mut self,
+        source: ID,
+        target: ID,
+        relation: Option<RelationTypeId>,
+    ) -> Result<ID, GraphError> {
         if source==target {
-        	return None;
+        	return Err(GraphError::SelfLoop(source));
         }
 
         // Check if both endpoints exist (either as nodes or as edges)
-        let source_exists = self.nodes.contains_key(source);
-        let target_exists = self.nodes.contains_key(target);
-        
-        if !source_exists || !target_exists {
-            return None;
+        if !self.contains(source) {
+            return Err(GraphError::MissingSource(source));
+        }
+        if !self.contains(target) {
+            return Err(GraphError::MissingTarget(target));
         }
 
         
         // Create a temporary ID for the graph element
-        let id = self.nodes.insert_with_key(|k| Node { 
+        let id = self.nodes.insert_with_key(|k| NodeSlot(Some(Node { 
             id: k, 
             data: NodeData::default() 
-        });
+        })));
         
         
-        self.edges.insert(id,Edge {
+        self.link_edge(Edge {
             id,
             source,
             target,
+            relation,
+            label: None,
         });
-
-
         
+        Ok(id)
+    }
+
+    // Store an edge and add it to the source/target lookup maps
+    fn link_edge(&mut self, edge: Edge) {
+        let (id, source, target) = (edge.id, edge.source, edge.target);
+        self.edges.insert(id, edge);
         
         // Update source_to_edges map
         self.source_to_edges
@@ -119,64 +423,238 @@ impl Graph {
             .entry(target).unwrap()
             .or_default()
             .insert(id);
-        
-        Some(id)
     }
-    
-    pub fn remove_edge(&mut self, edge_id: ID) -> Option<Edge> {
-        //first simple cleanup before we ruin the meta data
-        self.remove_node(edge_id);
-
-
-        // Get the edge
-        let edge = self.edges.get(edge_id)?;
-        let graph_id = edge.id;
-        let source = edge.source;
-        let target = edge.target;
-        
-        // Collect all child edges that need to be removed
-        let mut child_edges = Vec::new();
-        if let Some(outgoing) = self.source_to_edges.get(graph_id) {
-            for &child_edge_id in outgoing {
-                if let Some(edge) = self.edges.get(child_edge_id) {
-                    child_edges.push(edge.id);
-                }
+
+    // Everything removing `id` would take with it: the element and all edges attached to it, recursively
+    pub fn cascade_of(&self, id: ID) -> Subgraph {
+        let mut subgraph = Subgraph::default();
+        let mut seen = HashSet::new();
+        let mut stack = vec![id];
+
+        while let Some(curr) = stack.pop() {
+            if !seen.insert(curr) {
+                continue;
             }
+            let Some(node) = self.get_node(curr) else {
+                continue;
+            };
+            subgraph.nodes.push(node.clone());
+            if let Some(edge) = self.edges.get(curr) {
+                subgraph.edges.push(edge.clone());
+            }
+            stack.extend(self.get_outgoing_edges(curr));
+            stack.extend(self.get_incoming_edges(curr));
         }
-        
-        // Remove all child edges
-        for child_id in child_edges {
-            self.remove_edge(child_id);
+        subgraph
+    }
+
+    // Remove an element and return everything that went with it, ready for `restore`
+    pub fn detach(&mut self, id: ID) -> Result<Subgraph, GraphError> {
+        let subgraph = self.cascade_of(id);
+        self.remove_node(id)?;
+        Ok(subgraph)
+    }
+
+    // Put previously removed elements back under their original IDs
+    pub fn restore(&mut self, subgraph: Subgraph) -> Result<(), GraphError> {
+        // Check everything first so a failed restore leaves the graph untouched
+        for node in &subgraph.nodes {
+            if !matches!(self.nodes.get(node.id), Some(NodeSlot(None))) {
+                return Err(GraphError::CannotRestore(node.id));
+            }
         }
-        
-        // Remove the edge from source_to_edges
-        if let Some(edges) = self.source_to_edges.get_mut(source) {
-            edges.remove(&edge_id);
+        let restored: HashSet<ID> = subgraph.ids().collect();
+        for edge in &subgraph.edges {
+            if !restored.contains(&edge.id) {
+                return Err(GraphError::CannotRestore(edge.id));
+            }
+            if !self.contains(edge.source) && !restored.contains(&edge.source) {
+                return Err(GraphError::MissingSource(edge.source));
+            }
+            if !self.contains(edge.target) && !restored.contains(&edge.target) {
+                return Err(GraphError::MissingTarget(edge.target));
+            }
+        }
+
+        for node in subgraph.nodes {
+            let id = node.id;
+            self.nodes[id] = NodeSlot(Some(node));
+            self.tombstones -= 1;
+        }
+        for edge in subgraph.edges {
+            self.link_edge(edge);
+        }
+        Ok(())
+    }
+    
+    pub fn remove_edge(&mut self, edge_id: ID) -> Result<Edge, GraphError> {
+        let edge = self.edges.remove(edge_id).ok_or(GraphError::MissingEdge(edge_id))?;
+        self.unlink_edge(&edge);
+
+        // Removing the edge's node cascades to every edge attached to this edge
+        self.remove_node(edge_id)?;
+        Ok(edge)
+    }
+
+    // Drop an edge from the source/target lookup maps
+    fn unlink_edge(&mut self, edge: &Edge) {
+        if let Some(edges) = self.source_to_edges.get_mut(edge.source) {
+            edges.remove(&edge.id);
             if edges.is_empty() {
-                self.source_to_edges.remove(source);
+                self.source_to_edges.remove(edge.source);
             }
         }
         
-        // Remove the edge from target_to_edges
-        if let Some(edges) = self.target_to_edges.get_mut(target) {
-            edges.remove(&edge_id);
+        if let Some(edges) = self.target_to_edges.get_mut(edge.target) {
+            edges.remove(&edge.id);
             if edges.is_empty() {
-                self.target_to_edges.remove(target);
+                self.target_to_edges.remove(edge.target);
             }
         }
-        
-        
-        // Remove and return the edge
-        self.edges.remove(edge_id)
     }
     
     pub fn get_node(&self, id: ID) -> Option<&Node> {
-        self.nodes.get(id)
+        self.nodes.get(id)?.0.as_ref()
     }
     
     pub fn get_edge(&self, id: ID) -> Option<&Edge> {
         self.edges.get(id)
     }
+
+    // ── Relations ────────────────────────────────────────────────
+
+    // Returns the previous relation type
+    pub fn set_edge_relation(
+        &mut self,
+        id: ID,
+        relation: Option<RelationTypeId>,
+    ) -> Result<Option<RelationTypeId>, GraphError> {
+        let edge = self.edges.get_mut(id).ok_or(GraphError::MissingEdge(id))?;
+        Ok(std::mem::replace(&mut edge.relation, relation))
+    }
+
+    // Returns the previous label
+    pub fn set_edge_label(&mut self, id: ID, label: Option<String>) -> Result<Option<String>, GraphError> {
+        let edge = self.edges.get_mut(id).ok_or(GraphError::MissingEdge(id))?;
+        Ok(std::mem::replace(&mut edge.label, label))
+    }
+
+    pub fn edges_of_type(&self, relation: RelationTypeId) -> Vec<ID> {
+        self.edges
+            .values()
+            .filter(|edge| edge.relation == Some(relation))
+            .map(|edge| edge.id)
+            .collect()
+    }
+
+    pub fn untyped_edges(&self) -> Vec<ID> {
+        self.edges
+            .values()
+            .filter(|edge| edge.relation.is_none())
+            .map(|edge| edge.id)
+            .collect()
+    }
+
+    // Outgoing edges of `id` that carry the given relation type
+    pub fn get_outgoing_edges_of_type(&self, id: ID, relation: RelationTypeId) -> Vec<ID> {
+        self.get_outgoing_edges(id)
+            .into_iter()
+            .filter(|&edge_id| self.edges[edge_id].relation == Some(relation))
+            .collect()
+    }
+
+    // Incoming edges of `id` that carry the given relation type
+    pub fn get_incoming_edges_of_type(&self, id: ID, relation: RelationTypeId) -> Vec<ID> {
+        self.get_incoming_edges(id)
+            .into_iter()
+            .filter(|&edge_id| self.edges[edge_id].relation == Some(relation))
+            .collect()
+    }
+
+    // Turn every edge of this type back into an untyped edge, returning the affected edges
+    pub fn clear_relation_type(&mut self, relation: RelationTypeId) -> Vec<ID> {
+        let affected = self.edges_of_type(relation);
+        for &edge_id in &affected {
+            self.edges[edge_id].relation = None;
+        }
+        affected
+    }
+
+    // ── Node data ────────────────────────────────────────────────
+    // Edges are stored as nodes too, so all of these work on edge IDs as well
+
+    pub fn node_data(&self, id: ID) -> Option<&NodeData> {
+        self.get_node(id).map(|node| &node.data)
+    }
+
+    pub fn node_data_mut(&mut self, id: ID) -> Option<&mut NodeData> {
+        self.nodes.get_mut(id)?.0.as_mut().map(|node| &mut node.data)
+    }
+
+    // Like node_data_mut, but reporting a missing node as an error
+    fn data_or_err(&mut self, id: ID) -> Result<&mut NodeData, GraphError> {
+        self.node_data_mut(id).ok_or(GraphError::MissingNode(id))
+    }
+
+    // Replace the whole data block, returning the previous one
+    pub fn set_node_data(&mut self, id: ID, data: NodeData) -> Result<NodeData, GraphError> {
+        Ok(std::mem::replace(self.data_or_err(id)?, data))
+    }
+
+    pub fn set_name(&mut self, id: ID, name: impl Into<String>) -> Result<String, GraphError> {
+        Ok(std::mem::replace(&mut self.data_or_err(id)?.name, name.into()))
+    }
+
+    pub fn set_description(&mut self, id: ID, description: impl Into<String>) -> Result<String, GraphError> {
+        Ok(std::mem::replace(&mut self.data_or_err(id)?.description, description.into()))
+    }
+
+    // Returns whether the tag was newly added
+    pub fn add_tag(&mut self, id: ID, tag: impl Into<String>) -> Result<bool, GraphError> {
+        Ok(self.data_or_err(id)?.tags.insert(tag.into()))
+    }
+
+    // Returns whether the tag was present
+    pub fn remove_tag(&mut self, id: ID, tag: &str) -> Result<bool, GraphError> {
+        Ok(self.data_or_err(id)?.tags.remove(tag))
+    }
+
+    // Returns the previous value, if any
+    pub fn set_property(
+        &mut self,
+        id: ID,
+        key: impl Into<String>,
+        value: PropertyValue,
+    ) -> Result<Option<PropertyValue>, GraphError> {
+        Ok(self.data_or_err(id)?.properties.insert(key.into(), value))
+    }
+
+    // Returns the removed value, if there was one
+    pub fn remove_property(&mut self, id: ID, key: &str) -> Result<Option<PropertyValue>, GraphError> {
+        Ok(self.data_or_err(id)?.properties.remove(key))
+    }
+
+    pub fn get_property(&self, id: ID, key: &str) -> Option<&PropertyValue> {
+        self.node_data(id)?.properties.get(key)
+    }
+
+    // Returns the previous kind
+    pub fn set_kind(&mut self, id: ID, kind: Option<NodeKindId>) -> Result<Option<NodeKindId>, GraphError> {
+        Ok(std::mem::replace(&mut self.data_or_err(id)?.kind, kind))
+    }
+
+    pub fn nodes_of_kind(&self, kind: NodeKindId) -> Vec<ID> {
+        self.nodes_iter()
+            .filter(|(_, node)| node.data.kind == Some(kind))
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    pub fn nodes_with_tag<'a>(&'a self, tag: &'a str) -> impl Iterator<Item = ID> + 'a {
+        self.nodes_iter()
+            .filter(move |(_, node)| node.data.tags.contains(tag))
+            .map(|(id, _)| id)
+    }
     
     pub fn get_outgoing_edges(&self, id: ID) -> Vec<ID> {
         match self.source_to_edges.get(id) {
@@ -204,8 +682,10 @@ impl Graph {
         }
     }
     
-    pub fn nodes_iter(&self) -> slotmap::dense::Iter<'_, ID, Node> {
-        self.nodes.iter()
+    pub fn nodes_iter(&self) -> impl Iterator<Item = (ID, &Node)> {
+        self.nodes
+            .iter()
+            .filter_map(|(id, slot)| slot.0.as_ref().map(|node| (id, node)))
     }
     
     pub fn edges_iter(&self) -> impl Iterator<Item = &Edge> {
@@ -227,6 +707,7 @@ impl Graph {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use slotmap::SlotMap;
 
     #[test]
     fn test_add_remove_nodes() {
@@ -239,7 +720,8 @@ mod tests {
         assert!(graph.get_node(node2).is_some());
         
         let removed = graph.remove_node(node1);
-        assert!(removed.is_some());
+        assert!(removed.is_ok());
+        assert_eq!(graph.remove_node(node1), Err(GraphError::MissingNode(node1)));
         assert!(graph.get_node(node1).is_none());
         assert!(graph.get_node(node2).is_some());
     }
@@ -264,26 +746,40 @@ mod tests {
         
         // Test adding an edge with non-existent source
         let non_existent_id = ID::default(); // This ID doesn't exist in the graph
-        assert!(graph.add_edge(non_existent_id, node2).is_none());
+        assert_eq!(graph.add_edge(non_existent_id, node2), Err(GraphError::MissingSource(non_existent_id)));
         
         // Test adding an edge with non-existent target
-        assert!(graph.add_edge(node1, non_existent_id).is_none());
+        assert_eq!(graph.add_edge(node1, non_existent_id), Err(GraphError::MissingTarget(non_existent_id)));
+
+        // Test adding a self-loop
+        assert_eq!(graph.add_edge(node1, node1), Err(GraphError::SelfLoop(node1)));
         
         // Test removing edge
         let removed_edge = graph.remove_edge(edge_id);
-        assert!(removed_edge.is_some());
+        assert!(removed_edge.is_ok());
         assert!(graph.get_edge(edge_id).is_none());
         
-        // Test removing the same edge twice (should return None)
+        // Test removing the same edge twice (should fail)
         let removed_again = graph.remove_edge(edge_id);
-        assert!(removed_again.is_none());
+        assert_eq!(removed_again, Err(GraphError::MissingEdge(edge_id)));
+
+        // Plain nodes are not edges
+        assert_eq!(graph.remove_edge(node2), Err(GraphError::MissingEdge(node2)));
         
         // Add edge again
         let edge_id = graph.add_edge(node1, node2).unwrap();
         
         // Remove node - should cascade remove the edge
-        graph.remove_node(node1);
+        graph.remove_node(node1).unwrap();
         assert!(graph.get_edge(edge_id).is_none());
+
+        // Removing an edge through its node removes the edge record too
+        let node3 = graph.add_node(NodeData::default());
+        let edge_id = graph.add_edge(node2, node3).unwrap();
+        graph.remove_node(edge_id).unwrap();
+        assert!(graph.get_edge(edge_id).is_none());
+        assert!(graph.get_outgoing_edges(node2).is_empty());
+        assert!(graph.get_incoming_edges(node3).is_empty());
     }
     
     #[test]
@@ -318,7 +814,7 @@ mod tests {
         
         // Test removing the middle edge (edge2)
         // This should also remove edge3 due to cascade
-        graph.remove_edge(edge2);
+        graph.remove_edge(edge2).unwrap();
         assert!(graph.get_edge(edge2).is_none());
         assert!(graph.get_edge(edge3).is_none());
         assert!(graph.get_edge(edge1).is_some()); // edge1 should still exist
@@ -334,7 +830,7 @@ mod tests {
         assert_eq!(graph.get_edge(edge5).unwrap().target, edge1);
         
         // Removing edge1 should remove both edge4 and edge5 due to cascade
-        graph.remove_edge(edge1);
+        graph.remove_edge(edge1).unwrap();
         assert!(graph.get_edge(edge1).is_none());
         assert!(graph.get_edge(edge4).is_none());
         assert!(graph.get_edge(edge5).is_none());
@@ -358,7 +854,7 @@ mod tests {
         let edge4 = graph.add_edge(edge2, node3).unwrap();
         
         // Remove edge2 - should cascade to edge3 and edge4
-        graph.remove_edge(edge2);
+        graph.remove_edge(edge2).unwrap();
         assert!(graph.get_edge(edge2).is_none());
         assert!(graph.get_edge(edge3).is_none());
         assert!(graph.get_edge(edge4).is_none());
@@ -370,7 +866,7 @@ mod tests {
         let edge7 = graph.add_edge(edge5, edge6).unwrap();
         
         // Remove node2 - should cascade to all connected edges
-        graph.remove_node(node2);
+        graph.remove_node(node2).unwrap();
         assert!(graph.get_edge(edge5).is_none());
         assert!(graph.get_edge(edge6).is_none());
         assert!(graph.get_edge(edge7).is_none());
@@ -406,7 +902,7 @@ mod tests {
         let node1 = graph.add_node(NodeData::default());
         let node2 = graph.add_node(NodeData::default());
         
-        graph.add_edge(node1, node2);
+        let edge = graph.add_edge(node1, node2).unwrap();
         
         // Test binary serialization
         let binary = bincode::serialize(&graph).unwrap();
@@ -414,6 +910,183 @@ mod tests {
         
         assert!(deserialized.get_node(node1).is_some());
         assert!(deserialized.get_node(node2).is_some());
+        // Indices aren't stored but rebuilt from the edges
+        assert_eq!(deserialized.get_outgoing_edges(node1), vec![edge]);
+        assert_eq!(deserialized.get_incoming_edges(node2), vec![edge]);
+
+        // An edge pointing at an element that isn't there is refused
+        graph.nodes[node2].0 = None;
+        let binary = bincode::serialize(&graph).unwrap();
+        assert!(bincode::deserialize::<Graph>(&binary).is_err());
+
+    }
+
+    #[test]
+    fn test_node_data() {
+        let mut graph = Graph::new();
+
+        let king = graph.add_node(NodeData::named("Aldric"));
+        let city = graph.add_node(NodeData::named("Varenholm"));
+
+        assert_eq!(graph.node_data(king).unwrap().name, "Aldric");
+        assert_eq!(graph.set_name(king, "Aldric II").as_deref(), Ok("Aldric"));
+        graph.set_description(king, "Second of his name").unwrap();
+
+        assert_eq!(graph.add_tag(king, "royalty"), Ok(true));
+        assert_eq!(graph.add_tag(king, "royalty"), Ok(false));
+        assert_eq!(graph.nodes_with_tag("royalty").collect::<Vec<_>>(), vec![king]);
+
+        graph.set_property(king, "age", PropertyValue::Number(54.0)).unwrap();
+        graph.set_property(king, "alive", PropertyValue::Bool(true)).unwrap();
+        graph.set_property(king, "crowned", PropertyValue::Date(Date::new(-212, 3, 1))).unwrap();
+        graph.set_property(king, "seat", PropertyValue::Reference(city)).unwrap();
+        assert_eq!(graph.get_property(king, "seat"), Some(&PropertyValue::Reference(city)));
+
+        assert_eq!(graph.remove_property(king, "alive"), Ok(Some(PropertyValue::Bool(true))));
+        assert!(graph.get_property(king, "alive").is_none());
+        assert_eq!(graph.remove_tag(king, "royalty"), Ok(true));
+
+        // Missing nodes are reported rather than silently created
+        let missing = ID::default();
+        assert_eq!(graph.set_name(missing, "nobody"), Err(GraphError::MissingNode(missing)));
+        assert!(graph.set_property(missing, "x", PropertyValue::Bool(false)).is_err());
+
+        let binary = bincode::serialize(&graph).unwrap();
+        let deserialized: Graph = bincode::deserialize(&binary).unwrap();
+        assert_eq!(deserialized.node_data(king), graph.node_data(king));
+        assert_eq!(deserialized.node_data(king).unwrap().description, "Second of his name");
+    }
+
+    #[test]
+    fn test_mutations_on_removed_elements() {
+        let mut graph = Graph::new();
+        let a = graph.add_node(NodeData::named("a"));
+        let b = graph.add_node(NodeData::named("b"));
+        let ab = graph.add_edge(a, b).unwrap();
+        graph.remove_node(b).unwrap();
+
+        // The cascade took the edge with it, and neither comes back through a setter
+        assert_eq!(graph.set_name(b, "b"), Err(GraphError::MissingNode(b)));
+        assert_eq!(graph.add_tag(ab, "x"), Err(GraphError::MissingNode(ab)));
+        assert_eq!(graph.set_kind(b, None), Err(GraphError::MissingNode(b)));
+        assert_eq!(graph.remove_edge(ab), Err(GraphError::MissingEdge(ab)));
+        assert_eq!(graph.add_edge(a, b), Err(GraphError::MissingTarget(b)));
+        assert!(!graph.contains(b) && !graph.contains(ab));
+
+        // Edge-only changes on a node
+        assert_eq!(graph.set_edge_label(a, Some("x".into())), Err(GraphError::MissingEdge(a)));
+        assert_eq!(graph.remove_edge(a), Err(GraphError::MissingEdge(a)));
+        assert_eq!(GraphError::MissingEdge(a).to_string(), "the element is not an edge");
+        assert_eq!(graph.node_data(a).unwrap().name, "a");
+    }
+
+    #[test]
+    fn test_detach_and_restore() {
+        let mut graph = Graph::new();
+        let a = graph.add_node(NodeData::named("a"));
+        let b = graph.add_node(NodeData::named("b"));
+        let c = graph.add_node(NodeData::named("c"));
+        let ab = graph.add_edge(a, b).unwrap();
+        let bc = graph.add_edge(b, c).unwrap();
+        let on_edge = graph.add_edge(ab, c).unwrap();
+        graph.set_property(c, "friend", PropertyValue::Reference(b)).unwrap();
+
+        let removed = graph.detach(b).unwrap();
+        let mut ids: Vec<_> = removed.ids().collect();
+        ids.sort();
+        let mut expected = vec![b, ab, bc, on_edge];
+        expected.sort();
+        assert_eq!(ids, expected);
+        assert!(!graph.contains(b));
+        assert!(graph.get_edge(on_edge).is_none());
+        assert_eq!(graph.node_count(), 2);
+
+        // New elements never reuse a removed ID
+        let d = graph.add_node(NodeData::default());
+        assert!(!expected.contains(&d));
+
+        graph.restore(removed.clone()).unwrap();
+        assert_eq!(graph.node_data(b).unwrap().name, "b");
+        assert_eq!(graph.get_edge(on_edge).unwrap().source, ab);
+        assert!(graph.get_outgoing_edges(a).contains(&ab));
+        assert!(graph.get_incoming_edges(c).contains(&on_edge));
+        assert_eq!(graph.get_property(c, "friend"), Some(&PropertyValue::Reference(b)));
+
+        // IDs that are alive can't be restored over
+        assert_eq!(graph.restore(removed), Err(GraphError::CannotRestore(b)));
+    }
+
+    #[test]
+    fn test_tombstones_are_freed() {
+        let mut graph = Graph::new();
+        let a = graph.add_node(NodeData::named("a"));
+        let b = graph.add_node(NodeData::named("b"));
+        let c = graph.add_node(NodeData::named("c"));
+        let removed_b = graph.detach(b).unwrap();
+        let removed_c = graph.detach(c).unwrap();
+
+        // A saved graph has no tombstones, so nothing removed can come back into it
+        let binary = bincode::serialize(&graph).unwrap();
+        let mut loaded: Graph = bincode::deserialize(&binary).unwrap();
+        assert_eq!(loaded.nodes.len(), 1);
+        assert_eq!(loaded.restore(removed_b.clone()), Err(GraphError::CannotRestore(b)));
+        assert_eq!(loaded.node_data(a).unwrap().name, "a");
 
+        graph.compact(|id| id == c);
+        assert_eq!(graph.nodes.len(), 2);
+        assert_eq!(graph.node_count(), 1);
+        assert_eq!(graph.restore(removed_b), Err(GraphError::CannotRestore(b)));
+        graph.restore(removed_c).unwrap();
+        assert_eq!(graph.node_count(), 2);
+    }
+
+    #[test]
+    fn test_edge_relations() {
+        let mut graph = Graph::new();
+        let mut relation_ids: SlotMap<RelationTypeId, ()> = SlotMap::with_key();
+        let father_of = relation_ids.insert(());
+        let rules_over = relation_ids.insert(());
+
+        let king = graph.add_node(NodeData::named("Aldric"));
+        let prince = graph.add_node(NodeData::named("Corwin"));
+        let city = graph.add_node(NodeData::named("Varenholm"));
+
+        let fatherhood = graph.add_typed_edge(king, prince, Some(father_of)).unwrap();
+        let rule = graph.add_typed_edge(king, city, Some(rules_over)).unwrap();
+        let plain = graph.add_edge(prince, city).unwrap();
+
+        assert_eq!(graph.edges_of_type(father_of), vec![fatherhood]);
+        assert_eq!(graph.untyped_edges(), vec![plain]);
+        assert_eq!(graph.get_outgoing_edges_of_type(king, rules_over), vec![rule]);
+        assert_eq!(graph.get_incoming_edges_of_type(city, rules_over), vec![rule]);
+
+        assert_eq!(graph.set_edge_label(rule, Some("since the war".into())), Ok(None));
+        assert_eq!(graph.get_edge(rule).unwrap().label.as_deref(), Some("since the war"));
+        assert_eq!(graph.set_edge_relation(plain, Some(rules_over)), Ok(None));
+        assert_eq!(graph.edges_of_type(rules_over).len(), 2);
+
+        // Labels and types only exist on edges
+        assert_eq!(graph.set_edge_label(king, None), Err(GraphError::MissingEdge(king)));
+
+        let mut cleared = graph.clear_relation_type(rules_over);
+        cleared.sort();
+        let mut expected = vec![rule, plain];
+        expected.sort();
+        assert_eq!(cleared, expected);
+        assert!(graph.edges_of_type(rules_over).is_empty());
+        assert_eq!(graph.get_edge(rule).unwrap().label.as_deref(), Some("since the war"));
+    }
+
+    #[test]
+    fn test_date_parse() {
+        let date = Date::new(-212, 3, 1);
+        assert_eq!(Date::parse(&date.to_string()), Some(date));
+        assert_eq!(Date::parse("1066-10-14"), Some(Date::new(1066, 10, 14)));
+        assert!(Date::parse("not a date").is_none());
+        assert!(Date::parse("2024-13-01").is_none());
+        assert!(Date::parse("2024-12-32").is_none());
+        assert!(Date::parse("2024-00-10").is_none());
+        assert!(Date::parse("2024-01-00").is_none());
+        assert_eq!(Date::parse("2024-12-31"), Some(Date::new(2024, 12, 31)));
     }
 }
\ No newline at end of file
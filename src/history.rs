@@ -0,0 +1,799 @@
+// history.rs
+use eframe::egui::{Pos2, Vec2};
+use std::collections::HashSet;
+
+use crate::graph::{GraphError, NodeData, NodeKindId, RelationTypeId, ID};
+use crate::relations::RelationType;
+use crate::schema::DroppedRules;
+use crate::state::{Detached, GraphState};
+
+// How many steps are kept before the oldest ones are dropped
+const HISTORY_LIMIT: usize = 500;
+
+// A reversible change to a GraphState. Applying a command returns its inverse
+#[derive(Debug, Clone)]
+enum Command {
+    // Put removed elements back (applied in order)
+    Insert(Vec<Detached>),
+    // Remove elements with everything attached to them
+    Remove(Vec<ID>),
+    // Set element positions, edge control points follow
+    Move(Vec<(ID, Pos2)>),
+    // Hold edge control points off their midpoint, or re-attach them with None
+    EdgeOffsets(Vec<(ID, Option<Vec2>)>),
+    EditNode { id: ID, data: NodeData },
+    EditEdge { id: ID, relation: Option<RelationTypeId>, label: Option<String> },
+    // Pin or unpin elements for automatic layouts
+    Pin(Vec<(ID, bool)>),
+    // Remove a relation type, leaving its edges untyped
+    RemoveRelation(RelationTypeId),
+    // Bring a removed relation type back onto its edges, with the rule entries it dropped
+    RestoreRelation { id: RelationTypeId, edges: Vec<ID>, rules: DroppedRules },
+    EditRelation { id: RelationTypeId, relation: RelationType },
+    // Remove a node kind from the schema, leaving its nodes without a kind
+    RemoveKind(NodeKindId),
+    RestoreKind { id: NodeKindId, nodes: Vec<ID>, rules: DroppedRules },
+    // Several commands applied as one undo step
+    Batch(Vec<Command>),
+}
+
+// Removed things a command can bring back, whose tombstones must stay until it is dropped
+#[derive(Default)]
+struct Restorable {
+    elements: HashSet<ID>,
+    relations: HashSet<RelationTypeId>,
+    kinds: HashSet<NodeKindId>,
+}
+
+impl Command {
+    fn collect_restorable(&self, into: &mut Restorable) {
+        match self {
+            Command::Insert(detached) => {
+                into.elements.extend(detached.iter().flat_map(|d| d.subgraph.ids()));
+            }
+            Command::RestoreRelation { id, .. } => {
+                into.relations.insert(*id);
+            }
+            Command::RestoreKind { id, .. } => {
+                into.kinds.insert(*id);
+            }
+            Command::Batch(commands) => {
+                for command in commands {
+                    command.collect_restorable(into);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn apply(self, state: &mut GraphState) -> Result<Command, GraphError> {
+        match self {
+            Command::Insert(detached) => {
+                let mut ids = Vec::new();
+                for d in detached {
+                    let id = d.subgraph.nodes.first().map(|n| n.id());
+                    if let Err(error) = state.restore(d) {
+                        // Take back what was already restored so the step stays atomic
+                        for &id in ids.iter().rev() {
+                            let _ = state.remove_element(id);
+                        }
+                        return Err(error);
+                    }
+                    ids.extend(id);
+                }
+                Ok(Command::Remove(ids))
+            }
+            Command::Remove(ids) => {
+                // Every element must exist up front; the ones that disappear along the way
+                // went with the cascade of an earlier one
+                if let Some(&missing) = ids.iter().find(|&&id| !state.graph.contains(id)) {
+                    return Err(GraphError::MissingNode(missing));
+                }
+                let mut removed = Vec::new();
+                for &id in &ids {
+                    if !state.graph.contains(id) {
+                        continue;
+                    }
+                    match state.remove_element(id) {
+                        Ok(detached) => removed.push(detached),
+                        Err(error) => {
+                            for detached in removed.into_iter().rev() {
+                                let _ = state.restore(detached);
+                            }
+                            return Err(error);
+                        }
+                    }
+                }
+                // Restore in reverse so cascaded edges find their endpoints
+                removed.reverse();
+                Ok(Command::Insert(removed))
+            }
+            Command::Move(moves) => Ok(Command::Move(state.move_elements(&moves))),
+            Command::EdgeOffsets(offsets) => {
+                let mut previous = Vec::with_capacity(offsets.len());
+                for (id, offset) in offsets {
+                    previous.push((id, state.set_edge_offset(id, offset)?));
+                }
+                Ok(Command::EdgeOffsets(previous))
+            }
+            Command::EditNode { id, data } => {
+                let previous = state.graph.set_node_data(id, data)?;
+                Ok(Command::EditNode { id, data: previous })
+            }
+            Command::EditEdge { id, relation, label } => {
+                if let Some(r) = relation.filter(|&r| !state.relations.contains(r)) {
+                    return Err(GraphError::UnknownRelation(r));
+                }
+                let previous_relation = state.graph.set_edge_relation(id, relation)?;
+                let previous_label = state.graph.set_edge_label(id, label)?;
+                Ok(Command::EditEdge { id, relation: previous_relation, label: previous_label })
+            }
+            Command::Pin(pins) => {
+                let mut previous = Vec::with_capacity(pins.len());
+                for (id, pinned) in pins {
+                    previous.push((id, state.set_pinned(id, pinned)?));
+                }
+                Ok(Command::Pin(previous))
+            }
+            Command::RemoveRelation(id) => {
+                let (edges, rules) = state.remove_relation_type(id)?;
+                Ok(Command::RestoreRelation { id, edges, rules })
+            }
+            Command::RestoreRelation { id, edges, rules } => {
+                state.restore_relation_type(id, &edges, rules)?;
+                Ok(Command::RemoveRelation(id))
+            }
+            Command::EditRelation { id, relation } => {
+                let current = state.relations.get_mut(id).ok_or(GraphError::UnknownRelation(id))?;
+                let previous = std::mem::replace(current, relation);
+                Ok(Command::EditRelation { id, relation: previous })
+            }
+            Command::RemoveKind(id) => {
+                let (nodes, rules) = state.remove_node_kind(id)?;
+                Ok(Command::RestoreKind { id, nodes, rules })
+            }
+            Command::RestoreKind { id, nodes, rules } => {
+                state.restore_node_kind(id, &nodes, rules)?;
+                Ok(Command::RemoveKind(id))
+            }
+            Command::Batch(commands) => {
+                let mut inverses = Vec::with_capacity(commands.len());
+                for command in commands {
+                    match command.apply(state) {
+                        Ok(inverse) => inverses.push(inverse),
+                        Err(error) => {
+                            // Roll back what was already done so the batch stays atomic
+                            for inverse in inverses.into_iter().rev() {
+                                let _ = inverse.apply(state);
+                            }
+                            return Err(error);
+                        }
+                    }
+                }
+                inverses.reverse();
+                Ok(Command::Batch(inverses))
+            }
+        }
+    }
+}
+
+// Undo/redo stacks around a GraphState.
+// Every edit that should be undoable goes through one of the methods here
+#[derive(Default)]
+pub struct History {
+    // Inverses of the applied commands, most recent last
+    undo: Vec<Command>,
+    redo: Vec<Command>,
+    // What a continuous edit (typing, dragging a value) is being folded into one step for
+    merging: Option<Merging>,
+    // Bumped on every change to the document, so comparing two readings tells whether it changed
+    generation: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Merging {
+    Node(ID),
+    Relation(RelationTypeId),
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_node(&mut self, state: &mut GraphState, position: Pos2, data: NodeData) -> ID {
+        let id = state.add_node_at(position);
+        // A fresh node can't be missing
+        let _ = state.graph.set_node_data(id, data);
+        self.record(Command::Remove(vec![id]));
+        id
+    }
+
+    pub fn add_edge(
+        &mut self,
+        state: &mut GraphState,
+        source: ID,
+        target: ID,
+        relation: Option<RelationTypeId>,
+    ) -> Result<ID, GraphError> {
+        let id = state.add_edge_between(source, target, relation)?;
+        self.record(Command::Remove(vec![id]));
+        Ok(id)
+    }
+
+    // Remove elements (and their cascades) as one undo step
+    pub fn remove(&mut self, state: &mut GraphState, ids: &[ID]) -> Result<(), GraphError> {
+        self.execute(state, Command::Remove(ids.to_vec()))
+    }
+
+    // Record elements that were already added (e.g. by an import) as one undo step
+    pub fn record_insert(&mut self, ids: Vec<ID>) {
+        if !ids.is_empty() {
+            self.record(Command::Remove(ids));
+        }
+    }
+
+    pub fn move_elements(&mut self, state: &mut GraphState, moves: Vec<(ID, Pos2)>) {
+        if let Ok(inverse) = Command::Move(moves).apply(state) {
+            self.record(inverse);
+        }
+    }
+
+    // Record a move that was already done live (e.g. while dragging) given the starting positions
+    pub fn record_move(&mut self, start: Vec<(ID, Pos2)>) {
+        if !start.is_empty() {
+            self.record(Command::Move(start));
+        }
+    }
+
+    // Put the control points of dragged-off edges back on their midpoints as one undo step;
+    // edges already attached are left out
+    pub fn reattach_edges(&mut self, state: &mut GraphState, ids: &[ID]) -> Result<(), GraphError> {
+        let offsets: Vec<_> = ids
+            .iter()
+            .filter(|&&id| state.is_detached(id))
+            .map(|&id| (id, None))
+            .collect();
+        if offsets.is_empty() {
+            return Ok(());
+        }
+        self.execute(state, Command::EdgeOffsets(offsets))
+    }
+
+    // Pin or unpin elements as one undo step; elements already in that state are left out
+    pub fn set_pinned(&mut self, state: &mut GraphState, ids: &[ID], pinned: bool) -> Result<(), GraphError> {
+        let pins: Vec<_> = ids
+            .iter()
+            .filter(|&&id| state.is_pinned(id) != pinned)
+            .map(|&id| (id, pinned))
+            .collect();
+        if pins.is_empty() {
+            return Ok(());
+        }
+        self.execute(state, Command::Pin(pins))
+    }
+
+    pub fn edit_node(&mut self, state: &mut GraphState, id: ID, data: NodeData) -> Result<(), GraphError> {
+        self.execute(state, Command::EditNode { id, data })
+    }
+
+    // Replace the data of several elements as one undo step
+    pub fn edit_nodes(&mut self, state: &mut GraphState, edits: Vec<(ID, NodeData)>) -> Result<(), GraphError> {
+        let commands = edits
+            .into_iter()
+            .map(|(id, data)| Command::EditNode { id, data })
+            .collect();
+        self.execute(state, Command::Batch(commands))
+    }
+
+    // Like edit_node, but consecutive calls for the same node become a single undo step
+    // until end_merge is called. Meant for edits that arrive every frame, such as typing
+    pub fn edit_node_merging(&mut self, state: &mut GraphState, id: ID, data: NodeData) -> Result<(), GraphError> {
+        let continuing = self.merging == Some(Merging::Node(id))
+            && matches!(self.undo.last(), Some(Command::EditNode { id: last, .. }) if *last == id);
+        if !continuing {
+            self.edit_node(state, id, data)?;
+            self.merging = Some(Merging::Node(id));
+            return Ok(());
+        }
+        // The step already on the stack holds the state from before the edit started
+        state.graph.set_node_data(id, data)?;
+        self.redo.clear();
+        self.generation += 1;
+        Ok(())
+    }
+
+    // edit_edge counterpart of edit_node_merging
+    pub fn edit_edge_merging(
+        &mut self,
+        state: &mut GraphState,
+        id: ID,
+        relation: Option<RelationTypeId>,
+        label: Option<String>,
+    ) -> Result<(), GraphError> {
+        let continuing = self.merging == Some(Merging::Node(id))
+            && matches!(self.undo.last(), Some(Command::EditEdge { id: last, .. }) if *last == id)
+            && state.graph.get_edge(id).is_some_and(|e| e.relation == relation);
+        if !continuing {
+            self.edit_edge(state, id, relation, label)?;
+            self.merging = Some(Merging::Node(id));
+            return Ok(());
+        }
+        state.graph.set_edge_label(id, label)?;
+        self.redo.clear();
+        self.generation += 1;
+        Ok(())
+    }
+
+    // Remove a relation type as one undo step; its edges become untyped
+    pub fn remove_relation_type(&mut self, state: &mut GraphState, id: RelationTypeId) -> Result<(), GraphError> {
+        self.execute(state, Command::RemoveRelation(id))
+    }
+
+    // Change a relation type's name or look. Consecutive calls for the same type become a
+    // single undo step until end_merge is called, like edit_node_merging
+    pub fn edit_relation_merging(
+        &mut self,
+        state: &mut GraphState,
+        id: RelationTypeId,
+        relation: RelationType,
+    ) -> Result<(), GraphError> {
+        let continuing = self.merging == Some(Merging::Relation(id))
+            && matches!(self.undo.last(), Some(Command::EditRelation { id: last, .. }) if *last == id);
+        if !continuing {
+            self.execute(state, Command::EditRelation { id, relation })?;
+            self.merging = Some(Merging::Relation(id));
+            return Ok(());
+        }
+        *state.relations.get_mut(id).ok_or(GraphError::UnknownRelation(id))? = relation;
+        self.redo.clear();
+        self.generation += 1;
+        Ok(())
+    }
+
+    // Remove a node kind from the schema as one undo step; its nodes lose their kind
+    pub fn remove_node_kind(&mut self, state: &mut GraphState, id: NodeKindId) -> Result<(), GraphError> {
+        self.execute(state, Command::RemoveKind(id))
+    }
+
+    pub fn end_merge(&mut self) {
+        self.merging = None;
+    }
+
+    // Change an edge's relation type and label. A new relation type is checked against the schema
+    pub fn edit_edge(
+        &mut self,
+        state: &mut GraphState,
+        id: ID,
+        relation: Option<RelationTypeId>,
+        label: Option<String>,
+    ) -> Result<(), GraphError> {
+        let edge = state.graph.get_edge(id).ok_or(GraphError::MissingEdge(id))?;
+        if let (Some(schema), true) = (&state.schema, edge.relation != relation) {
+            schema.check_edge(&state.graph, edge.source, edge.target, relation)?;
+        }
+        self.execute(state, Command::EditEdge { id, relation, label })
+    }
+
+    // Returns false when there was nothing to undo. A step that can't be undone stays on
+    // the stack, the document unchanged
+    pub fn undo(&mut self, state: &mut GraphState) -> Result<bool, GraphError> {
+        let Some(command) = self.undo.last() else {
+            return Ok(false);
+        };
+        let inverse = command.clone().apply(state)?;
+        self.undo.pop();
+        self.redo.push(inverse);
+        self.merging = None;
+        self.generation += 1;
+        Ok(true)
+    }
+
+    // Returns false when there was nothing to redo. A step that can't be redone stays on the stack
+    pub fn redo(&mut self, state: &mut GraphState) -> Result<bool, GraphError> {
+        let Some(command) = self.redo.last() else {
+            return Ok(false);
+        };
+        let inverse = command.clone().apply(state)?;
+        self.redo.pop();
+        self.undo.push(inverse);
+        self.merging = None;
+        self.generation += 1;
+        Ok(true)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    // Forget every step, e.g. because another document replaced the old one. Nothing can
+    // bring removed elements back after that, so their tombstones go too
+    pub fn clear(&mut self, state: &mut GraphState) {
+        self.undo.clear();
+        self.redo.clear();
+        self.merging = None;
+        self.generation += 1;
+        self.compact(state);
+    }
+
+    // Changes since an earlier reading show up as a different number
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    // Count a change made outside the undo steps (theme, schema, new relation types), so the
+    // document still shows as changed
+    pub fn touch(&mut self) {
+        self.generation += 1;
+    }
+
+    // Free the tombstones of removed elements, relation types and kinds that no step can restore
+    pub fn compact(&self, state: &mut GraphState) {
+        let mut held = Restorable::default();
+        for command in self.undo.iter().chain(&self.redo) {
+            command.collect_restorable(&mut held);
+        }
+        state.graph.compact(|id| held.elements.contains(&id));
+        state.relations.purge_removed(|id| held.relations.contains(&id));
+        if let Some(schema) = &mut state.schema {
+            schema.purge_removed(|id| held.kinds.contains(&id));
+        }
+    }
+
+    fn execute(&mut self, state: &mut GraphState, command: Command) -> Result<(), GraphError> {
+        let inverse = command.apply(state)?;
+        self.record(inverse);
+        Ok(())
+    }
+
+    fn record(&mut self, inverse: Command) {
+        self.merging = None;
+        self.generation += 1;
+        self.undo.push(inverse);
+        if self.undo.len() > HISTORY_LIMIT {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::PropertyValue;
+
+    #[test]
+    fn test_undo_remove_restores_ids() {
+        let mut state = GraphState::new();
+        let mut history = History::new();
+
+        let a = history.add_node(&mut state, Pos2::new(0.0, 0.0), NodeData::named("a"));
+        let b = history.add_node(&mut state, Pos2::new(10.0, 0.0), NodeData::named("b"));
+        let ab = history.add_edge(&mut state, a, b, None).unwrap();
+        let c = history.add_node(&mut state, Pos2::new(5.0, 5.0), NodeData::named("c"));
+        let on_edge = history.add_edge(&mut state, c, ab, None).unwrap();
+        state.graph.set_property(c, "knows", PropertyValue::Reference(a)).unwrap();
+
+        history.remove(&mut state, &[a]).unwrap();
+        assert!(!state.graph.contains(a));
+        assert!(!state.graph.contains(on_edge));
+        assert!(state.positions.get(ab).is_none());
+
+        assert!(history.undo(&mut state).unwrap());
+        assert_eq!(state.graph.node_data(a).unwrap().name, "a");
+        assert_eq!(state.graph.get_edge(on_edge).unwrap().target, ab);
+        assert_eq!(state.positions.get(ab), Some(&Pos2::new(5.0, 0.0)));
+        assert_eq!(state.graph.get_property(c, "knows"), Some(&PropertyValue::Reference(a)));
+
+        assert!(history.redo(&mut state).unwrap());
+        assert!(!state.graph.contains(ab));
+
+        // Undo everything back to an empty document
+        while history.undo(&mut state).unwrap() {}
+        assert_eq!(state.graph.node_count(), 0);
+        while history.redo(&mut state).unwrap() {}
+        assert!(!state.graph.contains(a));
+        assert!(state.graph.contains(c));
+    }
+
+    #[test]
+    fn test_failed_undo_keeps_the_step() {
+        let mut state = GraphState::new();
+        let mut history = History::new();
+        let a = history.add_node(&mut state, Pos2::new(0.0, 0.0), NodeData::default());
+        let b = history.add_node(&mut state, Pos2::new(10.0, 0.0), NodeData::default());
+        let ab = history.add_edge(&mut state, a, b, None).unwrap();
+        let rules = state.relations.add("rules over", eframe::egui::Color32::GOLD);
+        history.edit_edge(&mut state, ab, Some(rules), None).unwrap();
+        history.edit_edge(&mut state, ab, None, Some("label".into())).unwrap();
+
+        // Undoing the label puts back a relation type that no longer exists
+        state.relations.remove(rules);
+        assert_eq!(history.undo(&mut state), Err(GraphError::UnknownRelation(rules)));
+        assert!(history.can_undo() && !history.can_redo());
+        assert_eq!(state.graph.get_edge(ab).unwrap().label.as_deref(), Some("label"));
+        assert_eq!(history.undo(&mut state), Err(GraphError::UnknownRelation(rules)));
+    }
+
+    #[test]
+    fn test_remove_is_all_or_nothing() {
+        let mut state = GraphState::new();
+        let a = state.add_node_at(Pos2::new(0.0, 0.0));
+        let b = state.add_node_at(Pos2::new(10.0, 0.0));
+        let ab = state.add_edge_between(a, b, None).unwrap();
+        let gone = state.add_node_at(Pos2::ZERO);
+        state.remove_element(gone).unwrap();
+
+        let result = Command::Remove(vec![a, gone]).apply(&mut state);
+        assert!(matches!(result, Err(GraphError::MissingNode(id)) if id == gone));
+        assert!(state.graph.contains(a) && state.graph.contains(ab));
+
+        // The edge went with `a`, whichever order they come in
+        for ids in [vec![a, ab, b], vec![ab, a, b]] {
+            let Ok(inverse) = Command::Remove(ids).apply(&mut state) else {
+                panic!("remove failed");
+            };
+            assert_eq!(state.graph.node_count(), 0);
+            inverse.apply(&mut state).unwrap();
+            assert!(state.graph.contains(a) && state.graph.contains(ab) && state.graph.contains(b));
+        }
+    }
+
+    #[test]
+    fn test_relation_and_kind_removal_undo() {
+        use crate::schema::{Endpoint, NodeKind, RelationRule, Schema};
+        use eframe::egui::Color32;
+
+        let mut state = GraphState::new();
+        let mut history = History::new();
+        let mut schema = Schema::new();
+        let person = schema.add_kind(NodeKind::new("Person"));
+        state.schema = Some(schema);
+        let rules = state.relations.add("rules over", Color32::GOLD);
+        let a = history.add_node(&mut state, Pos2::new(0.0, 0.0), NodeData::default());
+        let b = history.add_node(&mut state, Pos2::new(10.0, 0.0), NodeData::default());
+        state.set_node_kind(a, Some(person)).unwrap();
+        let ab = history.add_edge(&mut state, a, b, Some(rules)).unwrap();
+        let rule = RelationRule::new(Some(rules)).from(Endpoint::Kind(person)).to(Endpoint::AnyNode);
+        let untyped = RelationRule::new(None).to(Endpoint::EdgeOfType(rules));
+        let schema = state.schema.as_mut().unwrap();
+        schema.set_rule(rule.clone());
+        schema.set_rule(untyped.clone());
+
+        history.remove_relation_type(&mut state, rules).unwrap();
+        assert!(!state.relations.contains(rules));
+        assert_eq!(state.graph.get_edge(ab).unwrap().relation, None);
+        assert_eq!(state.schema.as_ref().unwrap().rules.len(), 1);
+
+        history.remove_node_kind(&mut state, person).unwrap();
+        assert_eq!(state.graph.node_data(a).unwrap().kind, None);
+
+        history.undo(&mut state).unwrap();
+        assert_eq!(state.graph.node_data(a).unwrap().kind, Some(person));
+        history.undo(&mut state).unwrap();
+        assert_eq!(state.relations.get(rules).unwrap().name, "rules over");
+        assert_eq!(state.graph.get_edge(ab).unwrap().relation, Some(rules));
+        assert_eq!(state.schema.as_ref().unwrap().rules, vec![rule, untyped]);
+
+        // Renaming while typing folds into one step
+        for name in ["r", "ru", "rul"] {
+            let mut edited = state.relations.get(rules).unwrap().clone();
+            edited.name = name.to_owned();
+            history.edit_relation_merging(&mut state, rules, edited).unwrap();
+        }
+        history.end_merge();
+        history.undo(&mut state).unwrap();
+        assert_eq!(state.relations.get(rules).unwrap().name, "rules over");
+        history.redo(&mut state).unwrap();
+        assert_eq!(state.relations.get(rules).unwrap().name, "rul");
+    }
+
+    #[test]
+    fn test_kind_removal_undo_keeps_later_rule_edits() {
+        use crate::schema::{Endpoint, NodeKind, RelationRule, Schema};
+
+        let mut state = GraphState::new();
+        let mut history = History::new();
+        let mut schema = Schema::new();
+        let person = schema.add_kind(NodeKind::new("Person"));
+        let place = schema.add_kind(NodeKind::new("Place"));
+        let lives = state.relations.add("lives in", eframe::egui::Color32::GREEN);
+        schema.set_rule(
+            RelationRule::new(Some(lives))
+                .from(Endpoint::Kind(person))
+                .from(Endpoint::Kind(place))
+                .to(Endpoint::Kind(place)),
+        );
+        state.schema = Some(schema);
+
+        history.remove_node_kind(&mut state, person).unwrap();
+        let schema = state.schema.as_mut().unwrap();
+        assert_eq!(schema.rules[0].sources, vec![Endpoint::Kind(place)]);
+        // Schema window edits are not undo steps of their own
+        let untyped = RelationRule::new(None).to(Endpoint::AnyNode);
+        schema.set_rule(untyped.clone());
+        history.touch();
+
+        history.undo(&mut state).unwrap();
+        let schema = state.schema.as_ref().unwrap();
+        assert_eq!(schema.rules[0].sources, vec![Endpoint::Kind(person), Endpoint::Kind(place)]);
+        assert_eq!(schema.rule_for(None), Some(&untyped));
+    }
+
+    #[test]
+    fn test_compact_keeps_what_undo_can_restore() {
+        let mut state = GraphState::new();
+        let mut history = History::new();
+        let a = history.add_node(&mut state, Pos2::new(0.0, 0.0), NodeData::default());
+        let b = history.add_node(&mut state, Pos2::new(10.0, 0.0), NodeData::default());
+        let c = history.add_node(&mut state, Pos2::new(20.0, 0.0), NodeData::default());
+        history.remove(&mut state, &[a]).unwrap();
+        history.undo(&mut state).unwrap();
+        history.undo(&mut state).unwrap();
+        assert!(!state.graph.contains(c));
+        // Only the redo stack could bring `c` back, and a new step drops it
+        history.remove(&mut state, &[b]).unwrap();
+
+        history.compact(&mut state);
+        history.undo(&mut state).unwrap();
+        assert!(state.graph.contains(b));
+
+        // Once history is cleared nothing can restore a removed element
+        let detached = state.remove_element(b).unwrap();
+        history.clear(&mut state);
+        assert_eq!(state.restore(detached), Err(GraphError::CannotRestore(b)));
+        assert_eq!(state.graph.node_count(), 1);
+    }
+
+    #[test]
+    fn test_generation_moves_with_every_change() {
+        let mut state = GraphState::new();
+        let mut history = History::new();
+        let start = history.generation();
+        let a = history.add_node(&mut state, Pos2::new(0.0, 0.0), NodeData::default());
+        let added = history.generation();
+        assert_ne!(added, start);
+
+        for name in ["x", "xy"] {
+            history.edit_node_merging(&mut state, a, NodeData::named(name)).unwrap();
+        }
+        let typed = history.generation();
+        assert_ne!(typed, added);
+        history.undo(&mut state).unwrap();
+        assert_ne!(history.generation(), typed);
+
+        // Edits that leave the document as it is don't count
+        let before = history.generation();
+        history.set_pinned(&mut state, &[a], false).unwrap();
+        assert_eq!(history.generation(), before);
+        history.redo(&mut state).unwrap();
+        assert_ne!(history.generation(), before);
+    }
+
+    #[test]
+    fn test_pins_undo_and_survive_removal() {
+        let mut state = GraphState::new();
+        let mut history = History::new();
+        let a = history.add_node(&mut state, Pos2::new(0.0, 0.0), NodeData::default());
+        let b = history.add_node(&mut state, Pos2::new(10.0, 0.0), NodeData::default());
+
+        history.set_pinned(&mut state, &[a, b], true).unwrap();
+        history.set_pinned(&mut state, &[a], false).unwrap();
+        assert!(!state.is_pinned(a) && state.is_pinned(b));
+        history.undo(&mut state).unwrap();
+        assert!(state.is_pinned(a));
+
+        history.remove(&mut state, &[b]).unwrap();
+        assert!(!state.is_pinned(b));
+        history.undo(&mut state).unwrap();
+        assert!(state.is_pinned(b));
+        history.undo(&mut state).unwrap();
+        assert!(state.pinned.is_empty());
+    }
+
+    #[test]
+    fn test_control_points_undo_and_reattach() {
+        let mut state = GraphState::new();
+        let mut history = History::new();
+        let a = history.add_node(&mut state, Pos2::new(0.0, 0.0), NodeData::default());
+        let b = history.add_node(&mut state, Pos2::new(100.0, 0.0), NodeData::default());
+        let ab = history.add_edge(&mut state, a, b, None).unwrap();
+
+        // Dragged off, the control point keeps its offset as the ends move
+        history.move_elements(&mut state, vec![(ab, Pos2::new(50.0, 40.0))]);
+        assert!(state.is_detached(ab));
+        history.move_elements(&mut state, vec![(b, Pos2::new(100.0, 100.0))]);
+        assert_eq!(state.positions.get(ab), Some(&Pos2::new(50.0, 90.0)));
+
+        history.reattach_edges(&mut state, &[ab]).unwrap();
+        assert!(!state.is_detached(ab));
+        assert_eq!(state.positions.get(ab), Some(&Pos2::new(50.0, 50.0)));
+        history.undo(&mut state).unwrap();
+        assert_eq!(state.positions.get(ab), Some(&Pos2::new(50.0, 90.0)));
+
+        // Dropped next to the midpoint, it snaps back on
+        history.move_elements(&mut state, vec![(ab, Pos2::new(52.0, 51.0))]);
+        assert!(!state.is_detached(ab));
+        history.undo(&mut state).unwrap();
+        assert!(state.is_detached(ab));
+    }
+
+    #[test]
+    fn test_edge_label_typing_merges_until_the_relation_changes() {
+        let mut state = GraphState::new();
+        let mut history = History::new();
+        let a = history.add_node(&mut state, Pos2::new(0.0, 0.0), NodeData::default());
+        let b = history.add_node(&mut state, Pos2::new(10.0, 0.0), NodeData::default());
+        let ab = history.add_edge(&mut state, a, b, None).unwrap();
+        let rules = state.relations.add("rules over", eframe::egui::Color32::GOLD);
+
+        for label in ["s", "si", "since"] {
+            history.edit_edge_merging(&mut state, ab, None, Some(label.into())).unwrap();
+        }
+        // Picking a relation type is a step of its own, even mid-edit
+        history.edit_edge_merging(&mut state, ab, Some(rules), Some("since".into())).unwrap();
+        history.undo(&mut state).unwrap();
+        let edge = state.graph.get_edge(ab).unwrap();
+        assert_eq!((edge.relation, edge.label.as_deref()), (None, Some("since")));
+        history.undo(&mut state).unwrap();
+        assert_eq!(state.graph.get_edge(ab).unwrap().label, None);
+
+        // Nodes aren't edges
+        assert_eq!(
+            history.edit_edge_merging(&mut state, a, None, Some("x".into())),
+            Err(GraphError::MissingEdge(a))
+        );
+    }
+
+    #[test]
+    fn test_undo_move_and_edit() {
+        let mut state = GraphState::new();
+        let mut history = History::new();
+        let a = history.add_node(&mut state, Pos2::new(0.0, 0.0), NodeData::default());
+        let b = history.add_node(&mut state, Pos2::new(10.0, 0.0), NodeData::default());
+        let ab = history.add_edge(&mut state, a, b, None).unwrap();
+
+        history.move_elements(&mut state, vec![(b, Pos2::new(10.0, 10.0))]);
+        assert_eq!(state.positions.get(ab), Some(&Pos2::new(5.0, 5.0)));
+        history.undo(&mut state).unwrap();
+        assert_eq!(state.positions.get(b), Some(&Pos2::new(10.0, 0.0)));
+        assert_eq!(state.positions.get(ab), Some(&Pos2::new(5.0, 0.0)));
+
+        history.edit_node(&mut state, a, NodeData::named("renamed")).unwrap();
+        history.edit_edge(&mut state, ab, None, Some("label".into())).unwrap();
+        history.undo(&mut state).unwrap();
+        history.undo(&mut state).unwrap();
+        assert_eq!(state.graph.node_data(a).unwrap().name, "");
+        assert_eq!(state.graph.get_edge(ab).unwrap().label, None);
+
+        // Typing into a field is one step until the edit ends
+        for name in ["r", "re", "ren"] {
+            history.edit_node_merging(&mut state, a, NodeData::named(name)).unwrap();
+        }
+        history.end_merge();
+        history.edit_node_merging(&mut state, a, NodeData::named("ren2")).unwrap();
+        history.undo(&mut state).unwrap();
+        assert_eq!(state.graph.node_data(a).unwrap().name, "ren");
+        history.undo(&mut state).unwrap();
+        assert_eq!(state.graph.node_data(a).unwrap().name, "");
+
+        // Group edits undo in one step
+        history
+            .edit_nodes(&mut state, vec![(a, NodeData::named("x")), (b, NodeData::named("y"))])
+            .unwrap();
+        history.undo(&mut state).unwrap();
+        assert_eq!(state.graph.node_data(a).unwrap().name, "");
+        assert_eq!(state.graph.node_data(b).unwrap().name, "");
+        assert!(history.edit_nodes(&mut state, vec![(a, NodeData::named("x")), (ID::default(), NodeData::default())]).is_err());
+        assert_eq!(state.graph.node_data(a).unwrap().name, "");
+
+        // A new edit drops the redo stack
+        assert!(history.can_redo());
+        history.edit_node(&mut state, b, NodeData::named("b")).unwrap();
+        assert!(!history.can_redo());
+    }
+}
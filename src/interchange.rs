@@ -0,0 +1,278 @@
+// interchange.rs
+// Reading and writing worlds in other tools' formats
+use eframe::egui::{Color32, Pos2};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::io;
+
+use crate::graph::{Graph, GraphError, NodeKindId, RelationTypeId, ID};
+use crate::schema::{NodeKind, Schema};
+use crate::state::GraphState;
+
+pub(crate) mod records;
+
+pub use records::ValueType;
+
+pub mod csv;
+pub mod dot;
+pub mod gexf;
+pub mod graphml;
+pub mod markdown;
+
+#[derive(Debug)]
+pub enum ImportError {
+    Io(io::Error),
+    // The input isn't valid for its format; `line` is 1-based, 0 when unknown
+    Parse { line: usize, message: String },
+    Graph(GraphError),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Io(error) => write!(f, "{error}"),
+            ImportError::Parse { line: 0, message } => write!(f, "{message}"),
+            ImportError::Parse { line, message } => write!(f, "line {line}: {message}"),
+            ImportError::Graph(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+impl From<io::Error> for ImportError {
+    fn from(error: io::Error) -> Self {
+        ImportError::Io(error)
+    }
+}
+
+impl From<GraphError> for ImportError {
+    fn from(error: GraphError) -> Self {
+        ImportError::Graph(error)
+    }
+}
+
+impl ImportError {
+    pub(crate) fn parse(line: usize, message: impl Into<String>) -> Self {
+        ImportError::Parse {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+// What an import added to a document, and what it had to skip
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    // Every new element, nodes before the edges between them
+    pub added: Vec<ID>,
+    pub warnings: Vec<String>,
+}
+
+impl ImportReport {
+    pub fn nodes<'a>(&'a self, graph: &'a Graph) -> impl Iterator<Item = ID> + 'a {
+        self.added.iter().copied().filter(|&id| graph.get_edge(id).is_none())
+    }
+}
+
+// Colors handed to relation types an import creates
+const RELATION_PALETTE: [Color32; 6] = [
+    Color32::LIGHT_BLUE,
+    Color32::GOLD,
+    Color32::LIGHT_RED,
+    Color32::LIGHT_GREEN,
+    Color32::from_rgb(200, 150, 255),
+    Color32::from_rgb(255, 170, 90),
+];
+
+// The relation type with this name, created (with `color` or a palette color) if the document has none
+pub(crate) fn relation_by_name(state: &mut GraphState, name: &str, color: Option<Color32>) -> RelationTypeId {
+    if let Some(id) = state.relations.find_by_name(name) {
+        return id;
+    }
+    let color = color.unwrap_or(RELATION_PALETTE[state.relations.len() % RELATION_PALETTE.len()]);
+    state.relations.add(name, color)
+}
+
+// The node kind with this name, created (along with a schema, if needed) when missing
+pub(crate) fn kind_by_name(state: &mut GraphState, name: &str) -> NodeKindId {
+    let schema = state.schema.get_or_insert_with(Schema::new);
+    schema.find_kind(name).unwrap_or_else(|| schema.add_kind(NodeKind::new(name)))
+}
+
+// The elements to export for a partial export: the given plain nodes, plus every edge whose
+// endpoints both made it in (edges between exported edges included). None means everything
+pub(crate) fn export_set(graph: &Graph, only: Option<&BTreeSet<ID>>) -> BTreeSet<ID> {
+    let Some(only) = only else {
+        return graph.nodes_iter().map(|(id, _)| id).collect();
+    };
+    let mut included: BTreeSet<ID> = only
+        .iter()
+        .copied()
+        .filter(|&id| graph.contains(id) && graph.get_edge(id).is_none())
+        .collect();
+    loop {
+        let before = included.len();
+        for edge in graph.edges_iter() {
+            if included.contains(&edge.source) && included.contains(&edge.target) {
+                included.insert(edge.id);
+            }
+        }
+        if included.len() == before {
+            return included;
+        }
+    }
+}
+
+// Lay out nodes that came without a position on a grid below everything already placed,
+// then put the edges attached to them at their midpoints
+pub fn place_unpositioned(state: &mut GraphState, ids: &[ID]) {
+    const SPACING: f32 = 120.0;
+    let unplaced: Vec<ID> = ids
+        .iter()
+        .copied()
+        .filter(|&id| state.graph.get_edge(id).is_none() && !state.positions.contains_key(id))
+        .collect();
+    if !unplaced.is_empty() {
+        let top = state
+            .positions
+            .values()
+            .map(|pos| pos.y)
+            .fold(f32::NEG_INFINITY, f32::max);
+        let top = if top.is_finite() { top + SPACING } else { 0.0 };
+        let left = state.positions.values().map(|pos| pos.x).fold(f32::INFINITY, f32::min);
+        let left = if left.is_finite() { left } else { 0.0 };
+        let columns = (unplaced.len() as f32).sqrt().ceil() as usize;
+        for (i, &id) in unplaced.iter().enumerate() {
+            let (row, column) = (i / columns, i % columns);
+            let pos = Pos2::new(left + column as f32 * SPACING, top + row as f32 * SPACING);
+            state.positions.insert(id, pos);
+        }
+    }
+    state.update_positions_from(ids.iter().copied());
+}
+
+// "#rrggbb" for a color, dropping alpha
+pub(crate) fn color_to_hex(color: Color32) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r(), color.g(), color.b())
+}
+
+pub(crate) fn color_from_hex(text: &str) -> Option<Color32> {
+    let hex = text.trim().strip_prefix('#')?;
+    if hex.len() != 6 && hex.len() != 8 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    let (r, g, b) = (channel(0)?, channel(2)?, channel(4)?);
+    let a = if hex.len() == 8 { channel(6)? } else { 255 };
+    Some(Color32::from_rgba_unmultiplied(r, g, b, a))
+}
+
+// Text escaped for XML content and attribute values; newlines survive inside attributes
+pub(crate) fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' => escaped.push_str("&#10;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Malformed XML as a parse error on the line where the reader gave up
+pub(crate) fn xml_error(error: roxmltree::Error) -> ImportError {
+    ImportError::parse(error.pos().row as usize, error.to_string())
+}
+
+pub(crate) fn xml_line(doc: &roxmltree::Document, node: roxmltree::Node) -> usize {
+    doc.text_pos_at(node.range().start).row as usize
+}
+
+// An attribute the element can't do without
+pub(crate) fn xml_attribute<'a>(
+    doc: &roxmltree::Document,
+    node: roxmltree::Node<'a, '_>,
+    attribute: &str,
+) -> Result<&'a str, ImportError> {
+    node.attribute(attribute).ok_or_else(|| {
+        ImportError::parse(
+            xml_line(doc, node),
+            format!("<{}> without a {attribute} attribute", node.tag_name().name()),
+        )
+    })
+}
+
+// What the format tests share: a small world with an edge hanging off another edge
+#[cfg(test)]
+pub(crate) mod samples {
+    use super::*;
+
+    pub(crate) struct Sample {
+        pub state: GraphState,
+        pub queen: ID,
+        pub city: ID,
+        pub rival: ID,
+        // The queen rules over the city, "since 1204"
+        pub rule: ID,
+        // From the rival to the rule itself, without a relation type
+        pub challenge: ID,
+    }
+
+    // The queen gets `queen` as her name, so each format can try its own awkward characters
+    pub(crate) fn world(queen: &str) -> Sample {
+        let mut state = GraphState::new();
+        let queen_id = state.add_node_at(Pos2::new(0.0, 10.0));
+        let city = state.add_node_at(Pos2::new(200.0, 50.0));
+        let rival = state.add_node_at(Pos2::new(100.0, -200.0));
+        state.graph.set_name(queen_id, queen).unwrap();
+        state.graph.set_name(city, "Varenholm").unwrap();
+        state.graph.set_name(rival, "Duke Orm").unwrap();
+        let rules = state.relations.add("rules over", Color32::GOLD);
+        let rule = state.add_edge_between(queen_id, city, Some(rules)).unwrap();
+        state.graph.set_edge_label(rule, Some("since 1204".into())).unwrap();
+        let challenge = state.add_edge_between(rival, rule, None).unwrap();
+        Sample { state, queen: queen_id, city, rival, rule, challenge }
+    }
+
+    // The world's people and rule, found again after an import
+    pub(crate) struct Found {
+        pub queen: ID,
+        pub city: ID,
+        pub rival: ID,
+        pub rule: ID,
+    }
+
+    // Checks what every format brings back of `world`, with the queen and city under the names
+    // the format gave them: the three people and the rule, typed and labelled as before
+    pub(crate) fn check_round_trip(imported: &GraphState, queen: &str, city: &str) -> Found {
+        // The people, the rule and one edge from the rival
+        assert_eq!(imported.graph.node_count(), 5);
+        let queen = named(imported, queen);
+        let city = named(imported, city);
+        let rival = named(imported, "Duke Orm");
+
+        let rule = imported.graph.get_outgoing_edges(queen)[0];
+        let edge = imported.graph.get_edge(rule).unwrap();
+        assert_eq!(edge.target, city);
+        assert_eq!(edge.label.as_deref(), Some("since 1204"));
+        assert!(edge.relation.is_some());
+        assert_eq!(edge.relation, imported.relations.find_by_name("rules over"));
+        Found { queen, city, rival, rule }
+    }
+
+    // The node called `name`, which the test expects to be there
+    pub(crate) fn named(state: &GraphState, name: &str) -> ID {
+        state
+            .graph
+            .nodes_iter()
+            .find(|(_, node)| node.data().name == name)
+            .map(|(id, _)| id)
+            .unwrap()
+    }
+}
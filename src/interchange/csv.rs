@@ -0,0 +1,583 @@
+// CSV import and export, for worlds kept in spreadsheets
+//
+// A world is two tables. The nodes table has one row per node: an id column plus any of name,
+// subtitle, description, tags (comma separated), kind, x and y, and one column per property.
+// The edges table has source and target columns holding ids, optionally the edge's own id,
+// its relation type, its label and property columns. Edges may name other edges' ids as their
+// source or target, so edge-to-edge relations need no special encoding.
+//
+// Property headers may carry their type after a colon ("crowned:date", "seat:ref"); without one
+// the type is guessed from the values. References hold the id of the element they point at.
+// Which column means what is decided by the caller (the editor's import wizard), starting
+// from `guess_node_columns` / `guess_edge_columns`
+use ::csv::{ReaderBuilder, StringRecord, Writer};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use super::records::{self, format_value, EdgeRecord, Field, NodeRecord, Value, ValueType};
+use super::{export_set, ImportError, ImportReport};
+use crate::graph::ID;
+use crate::state::GraphState;
+
+// A parsed CSV file: the header row and the data rows with their line numbers
+#[derive(Debug, Clone, Default)]
+pub struct Table {
+    pub headers: Vec<String>,
+    pub rows: Vec<(usize, Vec<String>)>,
+}
+
+impl Table {
+    // Rows shorter than the header are padded, longer ones keep their extra cells out of reach
+    pub fn parse(text: &str) -> Result<Table, ImportError> {
+        let csv_error = |error: ::csv::Error| {
+            let line = error.position().map_or(0, |p| p.line() as usize);
+            ImportError::parse(line, error.to_string())
+        };
+        let mut reader = ReaderBuilder::new()
+            .flexible(true)
+            .trim(::csv::Trim::All)
+            .from_reader(text.as_bytes());
+        let headers: Vec<String> = reader.headers().map_err(csv_error)?.iter().map(str::to_owned).collect();
+        if headers.iter().all(String::is_empty) {
+            return Err(ImportError::parse(1, "the file has no header row"));
+        }
+        let mut rows = Vec::new();
+        for record in reader.records() {
+            let record: StringRecord = record.map_err(csv_error)?;
+            if record.iter().all(str::is_empty) {
+                continue;
+            }
+            let line = record.position().map_or(0, |p| p.line() as usize);
+            let mut cells: Vec<String> = record.iter().map(str::to_owned).collect();
+            cells.resize(headers.len().max(cells.len()), String::new());
+            rows.push((line, cells));
+        }
+        Ok(Table { headers, rows })
+    }
+
+    // The first few non-empty values of a column, for previews
+    pub fn sample(&self, column: usize, count: usize) -> Vec<&str> {
+        self.rows
+            .iter()
+            .map(|(_, cells)| cells[column].as_str())
+            .filter(|cell| !cell.is_empty())
+            .take(count)
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NodeColumn {
+    Skip,
+    Id,
+    Name,
+    Subtitle,
+    Description,
+    Tags,
+    Kind,
+    X,
+    Y,
+    // Property named by the header (type suffix removed)
+    Property(ValueType),
+}
+
+impl NodeColumn {
+    pub const FIELDS: [NodeColumn; 9] = [
+        NodeColumn::Skip,
+        NodeColumn::Id,
+        NodeColumn::Name,
+        NodeColumn::Subtitle,
+        NodeColumn::Description,
+        NodeColumn::Tags,
+        NodeColumn::Kind,
+        NodeColumn::X,
+        NodeColumn::Y,
+    ];
+
+    pub fn name(&self) -> String {
+        let name = match self {
+            NodeColumn::Skip => "(skip)",
+            NodeColumn::Id => "id",
+            NodeColumn::Name => "name",
+            NodeColumn::Subtitle => "subtitle",
+            NodeColumn::Description => "description",
+            NodeColumn::Tags => "tags",
+            NodeColumn::Kind => "kind",
+            NodeColumn::X => "x",
+            NodeColumn::Y => "y",
+            NodeColumn::Property(value_type) => return format!("{} property", value_type.tag()),
+        };
+        name.to_owned()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EdgeColumn {
+    Skip,
+    Id,
+    Source,
+    Target,
+    Relation,
+    Label,
+    Property(ValueType),
+}
+
+impl EdgeColumn {
+    pub const FIELDS: [EdgeColumn; 6] = [
+        EdgeColumn::Skip,
+        EdgeColumn::Id,
+        EdgeColumn::Source,
+        EdgeColumn::Target,
+        EdgeColumn::Relation,
+        EdgeColumn::Label,
+    ];
+
+    pub fn name(&self) -> String {
+        let name = match self {
+            EdgeColumn::Skip => "(skip)",
+            EdgeColumn::Id => "id",
+            EdgeColumn::Source => "source",
+            EdgeColumn::Target => "target",
+            EdgeColumn::Relation => "relation",
+            EdgeColumn::Label => "label",
+            EdgeColumn::Property(value_type) => return format!("{} property", value_type.tag()),
+        };
+        name.to_owned()
+    }
+}
+
+// "crowned:date" -> ("crowned", Some(Date)); unknown suffixes stay part of the name
+pub fn split_header(header: &str) -> (&str, Option<ValueType>) {
+    if let Some((name, tag)) = header.rsplit_once(':') {
+        if let Some(value_type) = ValueType::ALL.into_iter().find(|t| t.tag() == tag.trim()) {
+            return (name.trim(), Some(value_type));
+        }
+    }
+    (header, None)
+}
+
+// The narrowest type every non-empty value of the column parses as
+fn guess_type(table: &Table, column: usize) -> ValueType {
+    let values: Vec<&str> = table
+        .rows
+        .iter()
+        .map(|(_, cells)| cells[column].as_str())
+        .filter(|cell| !cell.is_empty())
+        .collect();
+    let all = |value_type: ValueType| !values.is_empty() && values.iter().all(|v| value_type.parse(v).is_some());
+    [ValueType::Number, ValueType::Bool, ValueType::Date]
+        .into_iter()
+        .find(|&t| all(t))
+        .unwrap_or(ValueType::Text)
+}
+
+fn property_type(table: &Table, column: usize) -> ValueType {
+    split_header(&table.headers[column]).1.unwrap_or_else(|| guess_type(table, column))
+}
+
+pub fn guess_node_columns(table: &Table) -> Vec<NodeColumn> {
+    let mut columns: Vec<NodeColumn> = table
+        .headers
+        .iter()
+        .enumerate()
+        .map(|(i, header)| match header.to_ascii_lowercase().as_str() {
+            "id" | "key" => NodeColumn::Id,
+            "name" | "label" | "title" => NodeColumn::Name,
+            "subtitle" => NodeColumn::Subtitle,
+            "description" | "notes" => NodeColumn::Description,
+            "tags" => NodeColumn::Tags,
+            "kind" | "type" => NodeColumn::Kind,
+            "x" => NodeColumn::X,
+            "y" => NodeColumn::Y,
+            _ => NodeColumn::Property(property_type(table, i)),
+        })
+        .collect();
+    // Without an id column the names serve as ids
+    if !columns.contains(&NodeColumn::Id) {
+        if let Some(name) = columns.iter_mut().find(|c| **c == NodeColumn::Name) {
+            *name = NodeColumn::Id;
+        }
+    }
+    columns
+}
+
+pub fn guess_edge_columns(table: &Table) -> Vec<EdgeColumn> {
+    table
+        .headers
+        .iter()
+        .enumerate()
+        .map(|(i, header)| match header.to_ascii_lowercase().as_str() {
+            "id" | "key" => EdgeColumn::Id,
+            "source" | "from" | "src" => EdgeColumn::Source,
+            "target" | "to" | "tgt" | "dst" => EdgeColumn::Target,
+            "relation" | "relation type" | "type" | "kind" => EdgeColumn::Relation,
+            "label" => EdgeColumn::Label,
+            _ => EdgeColumn::Property(property_type(table, i)),
+        })
+        .collect()
+}
+
+fn column_of<T: PartialEq>(columns: &[T], wanted: &T) -> Option<usize> {
+    columns.iter().position(|c| c == wanted)
+}
+
+// Adds the rows to the document. Duplicate ids and rows, and edges or references naming ids
+// that neither table has, are skipped with a warning. Nodes without x/y are laid out automatically
+pub fn import(
+    state: &mut GraphState,
+    nodes: &Table,
+    node_columns: &[NodeColumn],
+    edges: Option<(&Table, &[EdgeColumn])>,
+) -> Result<ImportReport, ImportError> {
+    let id_column = column_of(node_columns, &NodeColumn::Id)
+        .ok_or_else(|| ImportError::parse(0, "choose which nodes column holds the ids"))?;
+    let mut warnings = Vec::new();
+
+    let mut first_seen: HashMap<String, usize> = HashMap::new();
+    let mut node_records = Vec::new();
+    for (line, cells) in &nodes.rows {
+        let id = &cells[id_column];
+        if id.is_empty() {
+            warnings.push(format!("nodes line {line}: no id, row skipped"));
+            continue;
+        }
+        if let Some(first) = first_seen.get(id) {
+            warnings.push(format!("nodes line {line}: duplicate id '{id}' (first on line {first}), row skipped"));
+            continue;
+        }
+        first_seen.insert(id.clone(), *line);
+        let mut record = NodeRecord {
+            key: id.clone(),
+            ..Default::default()
+        };
+        if column_of(node_columns, &NodeColumn::Name).is_none() {
+            record.name = id.clone();
+        }
+        for (i, column) in node_columns.iter().enumerate() {
+            let cell = &cells[i];
+            let field = match column {
+                NodeColumn::Skip | NodeColumn::Id => continue,
+                NodeColumn::Name => Field::Name,
+                NodeColumn::Subtitle => Field::Subtitle,
+                NodeColumn::Description => Field::Description,
+                NodeColumn::Tags => Field::Tags,
+                NodeColumn::Kind => Field::Kind,
+                NodeColumn::X => Field::X,
+                NodeColumn::Y => Field::Y,
+                NodeColumn::Property(value_type) => {
+                    Field::Property(split_header(&nodes.headers[i]).0.to_owned(), *value_type)
+                }
+            };
+            if cell.is_empty() {
+                continue;
+            }
+            if let Err(message) = record.set(&field, cell) {
+                warnings.push(format!("nodes line {line}: {message}"));
+            }
+        }
+        node_records.push((*line, record));
+    }
+
+    let mut edge_records = Vec::new();
+    if let Some((table, columns)) = edges {
+        let (Some(source), Some(target)) = (
+            column_of(columns, &EdgeColumn::Source),
+            column_of(columns, &EdgeColumn::Target),
+        ) else {
+            return Err(ImportError::parse(0, "choose which edges columns hold the source and target"));
+        };
+        let id_column = column_of(columns, &EdgeColumn::Id);
+        let mut seen_rows: HashMap<Vec<&str>, usize> = HashMap::new();
+        for (line, cells) in &table.rows {
+            let content: Vec<&str> = cells
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| Some(*i) != id_column)
+                .map(|(_, cell)| cell.as_str())
+                .collect();
+            if let Some(first) = seen_rows.get(&content) {
+                warnings.push(format!("edges line {line}: same as line {first}, row skipped"));
+                continue;
+            }
+            seen_rows.insert(content, *line);
+            let key = match id_column.map(|i| &cells[i]).filter(|id| !id.is_empty()) {
+                Some(id) => {
+                    if let Some(first) = first_seen.get(id) {
+                        warnings.push(format!("edges line {line}: id '{id}' already used on line {first}, row skipped"));
+                        continue;
+                    }
+                    first_seen.insert(id.clone(), *line);
+                    id.clone()
+                }
+                // Never collides with a cell value: those are trimmed
+                None => format!(" edge {line}"),
+            };
+            let mut record = EdgeRecord {
+                key,
+                source: cells[source].clone(),
+                target: cells[target].clone(),
+                ..Default::default()
+            };
+            for (i, column) in columns.iter().enumerate() {
+                let cell = &cells[i];
+                let field = match column {
+                    EdgeColumn::Relation => Field::Relation,
+                    EdgeColumn::Label => Field::Label,
+                    EdgeColumn::Property(value_type) => {
+                        Field::Property(split_header(&table.headers[i]).0.to_owned(), *value_type)
+                    }
+                    _ => continue,
+                };
+                if cell.is_empty() {
+                    continue;
+                }
+                if let Err(message) = record.set(&field, cell) {
+                    warnings.push(format!("edges line {line}: {message}"));
+                }
+            }
+            edge_records.push((*line, record));
+        }
+    }
+
+    // Every id is known by now, so unknown references can be reported with their line
+    let known = |key: &str| first_seen.contains_key(key);
+    for (line, record) in &mut node_records {
+        drop_unknown_references(&mut record.properties, known, &mut warnings, &format!("nodes line {line}"));
+    }
+    let mut kept_edges = Vec::new();
+    for (line, mut record) in edge_records {
+        drop_unknown_references(&mut record.properties, known, &mut warnings, &format!("edges line {line}"));
+        let unknown = [&record.source, &record.target].into_iter().find(|end| !known(end)).cloned();
+        match unknown {
+            Some(end) if end.is_empty() => warnings.push(format!("edges line {line}: missing source or target, row skipped")),
+            Some(end) => warnings.push(format!("edges line {line}: unknown id '{end}', row skipped")),
+            None => kept_edges.push(record),
+        }
+    }
+
+    let node_records = node_records.into_iter().map(|(_, record)| record).collect();
+    let mut report = records::build(state, node_records, kept_edges);
+    warnings.append(&mut report.warnings);
+    report.warnings = warnings;
+    Ok(report)
+}
+
+fn drop_unknown_references(
+    properties: &mut BTreeMap<String, Value>,
+    known: impl Fn(&str) -> bool,
+    warnings: &mut Vec<String>,
+    place: &str,
+) {
+    properties.retain(|name, value| match value {
+        Value::Reference(key) if !known(key) => {
+            warnings.push(format!("{place}: '{name}' refers to unknown id '{key}'"));
+            false
+        }
+        _ => true,
+    });
+}
+
+// Both tables for the world, or for `only` and the relations among it, as (nodes, edges).
+// Ids are the same "n<i>" / "e<i>" keys the other formats use
+pub fn export(state: &GraphState, only: Option<&BTreeSet<ID>>) -> (String, String) {
+    let graph = &state.graph;
+    let included = export_set(graph, only);
+    let keys = records::element_keys(graph, &included);
+    let property_headers = |edges: bool| -> Vec<(String, ValueType)> {
+        let mut headers = BTreeSet::new();
+        for &id in &included {
+            if graph.get_edge(id).is_some() == edges {
+                for (name, value) in records::properties_with_keys(graph, id, &keys) {
+                    headers.insert((name, ValueType::of(&value)));
+                }
+            }
+        }
+        headers.into_iter().collect()
+    };
+    let property_cells = |id: ID, headers: &[(String, ValueType)]| -> Vec<String> {
+        let properties = records::properties_with_keys(graph, id, &keys);
+        headers
+            .iter()
+            .map(|(name, value_type)| {
+                properties
+                    .get(name)
+                    .filter(|value| ValueType::of(value) == *value_type)
+                    .map(format_value)
+                    .unwrap_or_default()
+            })
+            .collect()
+    };
+    let typed = |headers: &[(String, ValueType)]| -> Vec<String> {
+        headers.iter().map(|(name, value_type)| format!("{name}:{}", value_type.tag())).collect()
+    };
+    // Writing to a Vec can't fail
+    let finish = |writer: Writer<Vec<u8>>| String::from_utf8(writer.into_inner().unwrap_or_default()).unwrap_or_default();
+
+    let node_properties = property_headers(false);
+    let mut nodes = Writer::from_writer(Vec::new());
+    let mut header: Vec<String> = ["id", "name", "subtitle", "description", "tags", "kind", "x", "y"]
+        .map(str::to_owned)
+        .to_vec();
+    header.extend(typed(&node_properties));
+    let _ = nodes.write_record(&header);
+    for (id, node) in graph.nodes_iter() {
+        if !included.contains(&id) || graph.get_edge(id).is_some() {
+            continue;
+        }
+        let data = node.data();
+        let kind = state
+            .schema
+            .as_ref()
+            .zip(data.kind)
+            .and_then(|(schema, kind)| schema.kinds.get(kind))
+            .map(|kind| kind.name.clone());
+        let pos = state.positions.get(id);
+        let mut row = vec![
+            keys[&id].clone(),
+            data.name.clone(),
+            data.subtitle.clone(),
+            data.description.clone(),
+            data.tags.iter().cloned().collect::<Vec<_>>().join(", "),
+            kind.unwrap_or_default(),
+            pos.map(|p| p.x.to_string()).unwrap_or_default(),
+            pos.map(|p| p.y.to_string()).unwrap_or_default(),
+        ];
+        row.extend(property_cells(id, &node_properties));
+        let _ = nodes.write_record(&row);
+    }
+
+    let edge_properties = property_headers(true);
+    let mut edges = Writer::from_writer(Vec::new());
+    let mut header: Vec<String> = ["id", "source", "target", "relation", "label"].map(str::to_owned).to_vec();
+    header.extend(typed(&edge_properties));
+    let _ = edges.write_record(&header);
+    for edge in graph.edges_iter().filter(|e| included.contains(&e.id)) {
+        let relation = edge.relation.and_then(|r| state.relations.get(r));
+        let mut row = vec![
+            keys[&edge.id].clone(),
+            keys[&edge.source].clone(),
+            keys[&edge.target].clone(),
+            relation.map(|r| r.name.clone()).unwrap_or_default(),
+            edge.label.clone().unwrap_or_default(),
+        ];
+        row.extend(property_cells(edge.id, &edge_properties));
+        let _ = edges.write_record(&row);
+    }
+
+    (finish(nodes), finish(edges))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{Date, PropertyValue};
+    use crate::interchange::samples::{self, named};
+    use eframe::egui::Pos2;
+
+    fn import_text(state: &mut GraphState, nodes: &str, edges: &str) -> ImportReport {
+        let nodes = Table::parse(nodes).unwrap();
+        let edges = Table::parse(edges).unwrap();
+        let (node_columns, edge_columns) = (guess_node_columns(&nodes), guess_edge_columns(&edges));
+        import(state, &nodes, &node_columns, Some((&edges, &edge_columns))).unwrap()
+    }
+
+    #[test]
+    fn test_import_spreadsheet() {
+        let nodes = "\
+Id,Name,Kind,age:number,crowned,seat:ref,notes:text
+q,Queen Ilsa,Character,42,1204-05-06,v,\"Rules, wisely\"
+v,Varenholm,Place,,,,
+q,Queen again,Character,1,,,
+o,Duke Orm,Character,old,,nowhere,
+";
+        let edges = "\
+source,target,relation,label,weight
+q,v,rules over,since 1204,2
+o,q,resents,,1
+o,q,resents,,1
+v,atlantis,trades with,,
+";
+        let mut state = GraphState::new();
+        let report = import_text(&mut state, nodes, edges);
+        let warnings = report.warnings.join("\n");
+        assert_eq!(report.warnings.len(), 5, "{warnings}");
+        assert!(warnings.contains("nodes line 4: duplicate id 'q' (first on line 2)"));
+        assert!(warnings.contains("nodes line 5: 'seat' refers to unknown id 'nowhere'"));
+        assert!(warnings.contains("edges line 4: same as line 3"));
+        assert!(warnings.contains("edges line 5: unknown id 'atlantis'"));
+        // "old" doesn't fit the number column
+        assert!(warnings.contains("nodes line 5: 'old' is not a valid number for 'age'"));
+        assert_eq!(state.graph.node_count(), 5);
+
+        let queen = named(&state, "Queen Ilsa");
+        let city = named(&state, "Varenholm");
+        let data = state.graph.node_data(queen).unwrap();
+        assert_eq!(data.properties["age"], PropertyValue::Number(42.0));
+        assert_eq!(data.properties["crowned"], PropertyValue::Date(Date::new(1204, 5, 6)));
+        assert_eq!(data.properties["seat"], PropertyValue::Reference(city));
+        assert_eq!(data.properties["notes"], PropertyValue::Text("Rules, wisely".into()));
+        assert_eq!(data.kind, state.schema.as_ref().unwrap().find_kind("Character"));
+
+        let rule = state.graph.get_outgoing_edges(queen)[0];
+        assert_eq!(state.graph.get_edge(rule).unwrap().label.as_deref(), Some("since 1204"));
+        assert_eq!(state.graph.get_property(rule, "weight"), Some(&PropertyValue::Number(2.0)));
+
+        // Laid out rather than stacked
+        let positions: BTreeSet<_> = report
+            .nodes(&state.graph)
+            .map(|id| {
+                let pos = state.positions[id];
+                (pos.x as i32, pos.y as i32)
+            })
+            .collect();
+        assert_eq!(positions.len(), 3);
+    }
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let samples::Sample { mut state, queen, city, rule, .. } = samples::world("Queen \"Ilsa\", the First");
+        state.graph.add_tag(queen, "royalty").unwrap();
+        state.graph.set_property(queen, "seat", PropertyValue::Reference(city)).unwrap();
+        state.graph.set_property(queen, "alive", PropertyValue::Bool(true)).unwrap();
+        state.graph.set_property(city, "alive", PropertyValue::Text("sort of".into())).unwrap();
+        state.graph.set_property(rule, "since", PropertyValue::Date(Date::new(1204, 1, 1))).unwrap();
+
+        let (nodes, edges) = export(&state, None);
+        assert!(nodes.starts_with("id,name,subtitle,description,tags,kind,x,y,alive:text,alive:bool,seat:ref\n"));
+
+        let mut imported = GraphState::new();
+        let report = import_text(&mut imported, &nodes, &edges);
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+        let found = samples::check_round_trip(&imported, "Queen \"Ilsa\", the First", "Varenholm");
+        let (queen, city) = (found.queen, found.city);
+        assert_eq!(imported.positions[city], Pos2::new(200.0, 50.0));
+        assert!(imported.graph.node_data(queen).unwrap().tags.contains("royalty"));
+        assert_eq!(imported.graph.get_property(queen, "seat"), Some(&PropertyValue::Reference(city)));
+        assert_eq!(imported.graph.get_property(queen, "alive"), Some(&PropertyValue::Bool(true)));
+        assert_eq!(imported.graph.get_property(city, "alive"), Some(&PropertyValue::Text("sort of".into())));
+        assert_eq!(
+            imported.graph.get_property(found.rule, "since"),
+            Some(&PropertyValue::Date(Date::new(1204, 1, 1)))
+        );
+        // An edge row can name another edge's id as its end
+        let challenge = imported.graph.get_outgoing_edges(found.rival)[0];
+        assert_eq!(imported.graph.get_edge(challenge).unwrap().target, found.rule);
+    }
+
+    #[test]
+    fn test_mapping_errors() {
+        let table = Table::parse("name,colour\nIlsa,red\n").unwrap();
+        // The names become ids when there is no id column
+        assert_eq!(guess_node_columns(&table), vec![NodeColumn::Id, NodeColumn::Property(ValueType::Text)]);
+
+        let mut state = GraphState::new();
+        let columns = vec![NodeColumn::Name, NodeColumn::Skip];
+        assert!(import(&mut state, &table, &columns, None).is_err());
+        let edges = Table::parse("from,to\n").unwrap();
+        let edge_columns = vec![EdgeColumn::Source, EdgeColumn::Skip];
+        let node_columns = guess_node_columns(&table);
+        assert!(import(&mut state, &table, &node_columns, Some((&edges, &edge_columns))).is_err());
+        assert_eq!(state.graph.node_count(), 0);
+
+        assert!(Table::parse("").is_err());
+    }
+}
@@ -0,0 +1,737 @@
+// Graphviz DOT export and import
+//
+// Edges are elements of their own here, so an edge that another edge connects to is written as
+// a point node in the middle of two half edges, the way the editor draws it:
+//
+//   e3 [shape=point];
+//   n1 -> e3 [part=tail, arrowhead=none, label="rules over"];
+//   e3 -> n2 [part=head, label="rules over"];
+//   n4 -> e3 [label="resents"];
+//
+// On import, point nodes with `part` halves become edges again. Point nodes from other tools
+// that have exactly one edge in and one edge out are treated the same way.
+// Edge labels are relation types, `xlabel` is the edge's own label. `pos` is read in points with
+// y pointing up. Default attribute statements (`node [...]`) only affect rendering and are ignored
+use eframe::egui::Pos2;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+
+use super::records::{self, EdgeRecord, NodeRecord, Part};
+use super::{color_from_hex, color_to_hex, export_set, ImportError, ImportReport};
+use crate::graph::ID;
+use crate::relations::LineStyle;
+use crate::state::GraphState;
+use crate::style::{NodeShape, NodeStyle};
+
+type Attributes = BTreeMap<String, String>;
+
+fn quote(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn write_attributes(out: &mut String, attributes: &[(&str, String)]) {
+    if attributes.is_empty() {
+        return;
+    }
+    let list: Vec<String> = attributes.iter().map(|(key, value)| format!("{key}={value}")).collect();
+    let _ = write!(out, " [{}]", list.join(", "));
+}
+
+fn shape_name(shape: NodeShape) -> &'static str {
+    match shape {
+        NodeShape::Rectangle => "box",
+        NodeShape::Circle => "ellipse",
+        NodeShape::Diamond => "diamond",
+        NodeShape::Hexagon => "hexagon",
+    }
+}
+
+fn shape_from_name(name: &str) -> Option<NodeShape> {
+    match name.to_ascii_lowercase().as_str() {
+        "box" | "rect" | "rectangle" | "square" => Some(NodeShape::Rectangle),
+        "ellipse" | "oval" | "circle" | "doublecircle" => Some(NodeShape::Circle),
+        "diamond" => Some(NodeShape::Diamond),
+        "hexagon" => Some(NodeShape::Hexagon),
+        _ => None,
+    }
+}
+
+// Writes the world, or only `only` and the relations among it, as a directed DOT graph
+pub fn export(state: &GraphState, only: Option<&BTreeSet<ID>>) -> String {
+    let graph = &state.graph;
+    let included = export_set(graph, only);
+
+    // Edges that other exported edges attach to need a node of their own
+    let mut points = BTreeSet::new();
+    for edge in graph.edges_iter().filter(|e| included.contains(&e.id)) {
+        for end in [edge.source, edge.target] {
+            if graph.get_edge(end).is_some() {
+                points.insert(end);
+            }
+        }
+    }
+
+    let mut names: HashMap<ID, String> = HashMap::new();
+    let mut out = String::from("digraph world {\n");
+    let theme = &state.theme;
+    let _ = writeln!(
+        out,
+        "  node [shape={}, style=filled, fillcolor={}];",
+        shape_name(theme.node_shape),
+        quote(&color_to_hex(theme.node_fill))
+    );
+
+    for (id, node) in graph.nodes_iter() {
+        if !included.contains(&id) || graph.get_edge(id).is_some() {
+            continue;
+        }
+        let name = format!("n{}", names.len());
+        let data = node.data();
+        let mut attributes = vec![("label", quote(&data.name))];
+        if !data.description.is_empty() {
+            attributes.push(("tooltip", quote(&data.description)));
+        }
+        if let Some(shape) = data.style.shape {
+            attributes.push(("shape", shape_name(shape).to_owned()));
+        }
+        if let Some(fill) = data.style.fill {
+            attributes.push(("fillcolor", quote(&color_to_hex(fill))));
+        }
+        if let Some(pos) = state.positions.get(id) {
+            attributes.push(("pos", quote(&format!("{},{}!", pos.x, -pos.y))));
+        }
+        let _ = write!(out, "  {name}");
+        write_attributes(&mut out, &attributes);
+        out.push_str(";\n");
+        names.insert(id, name);
+    }
+
+    for &id in &points {
+        let name = format!("e{}", names.len());
+        let mut attributes = vec![("shape", "point".to_owned())];
+        if let Some(pos) = state.positions.get(id) {
+            attributes.push(("pos", quote(&format!("{},{}!", pos.x, -pos.y))));
+        }
+        let _ = write!(out, "  {name}");
+        write_attributes(&mut out, &attributes);
+        out.push_str(";\n");
+        names.insert(id, name);
+    }
+
+    for edge in graph.edges_iter() {
+        if !included.contains(&edge.id) {
+            continue;
+        }
+        let (Some(source), Some(target)) = (names.get(&edge.source), names.get(&edge.target)) else {
+            continue;
+        };
+        let relation = edge.relation.and_then(|r| state.relations.get(r));
+        let mut attributes = Vec::new();
+        if let Some(relation) = relation {
+            attributes.push(("label", quote(&relation.name)));
+            attributes.push(("color", quote(&color_to_hex(relation.color))));
+            match relation.line {
+                LineStyle::Solid => {}
+                LineStyle::Dashed => attributes.push(("style", "dashed".to_owned())),
+                LineStyle::Dotted => attributes.push(("style", "dotted".to_owned())),
+            }
+        }
+        if let Some(label) = &edge.label {
+            attributes.push(("xlabel", quote(label)));
+        }
+
+        match names.get(&edge.id).filter(|_| points.contains(&edge.id)) {
+            Some(point) => {
+                let mut tail = vec![("part", "tail".to_owned()), ("arrowhead", "none".to_owned())];
+                tail.extend(attributes.iter().cloned());
+                let mut head = vec![("part", "head".to_owned())];
+                head.extend(attributes);
+                let _ = write!(out, "  {source} -> {point}");
+                write_attributes(&mut out, &tail);
+                let _ = write!(out, ";\n  {point} -> {target}");
+                write_attributes(&mut out, &head);
+                out.push_str(";\n");
+            }
+            None => {
+                let _ = write!(out, "  {source} -> {target}");
+                write_attributes(&mut out, &attributes);
+                out.push_str(";\n");
+            }
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    // Identifier, number, quoted or HTML string
+    Id(String),
+    Open,
+    Close,
+    OpenBracket,
+    CloseBracket,
+    Semicolon,
+    Comma,
+    Equals,
+    Colon,
+    EdgeOp,
+}
+
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, ImportError> {
+    let mut tokens: Vec<(Token, usize)> = Vec::new();
+    let chars: Vec<char> = text.chars().collect();
+    let mut line = 1;
+    let mut i = 0;
+    // Quoted strings joined with '+' are one ID
+    let mut concatenate = false;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\n' => {
+                line += 1;
+                i += 1;
+            }
+            c if c.is_whitespace() => i += 1,
+            // Preprocessor-style lines are comments
+            '#' if tokens.last().is_none_or(|(_, l)| *l < line) => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            '/' if chars.get(i + 1) == Some(&'/') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            '/' if chars.get(i + 1) == Some(&'*') => {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    if chars[i] == '\n' {
+                        line += 1;
+                    }
+                    i += 1;
+                }
+                i += 2;
+            }
+            '{' | '}' | '[' | ']' | ';' | ',' | '=' | ':' => {
+                let token = match c {
+                    '{' => Token::Open,
+                    '}' => Token::Close,
+                    '[' => Token::OpenBracket,
+                    ']' => Token::CloseBracket,
+                    ';' => Token::Semicolon,
+                    ',' => Token::Comma,
+                    '=' => Token::Equals,
+                    _ => Token::Colon,
+                };
+                tokens.push((token, line));
+                i += 1;
+            }
+            '-' if matches!(chars.get(i + 1), Some('>') | Some('-')) => {
+                tokens.push((Token::EdgeOp, line));
+                i += 2;
+            }
+            '+' => {
+                concatenate = true;
+                i += 1;
+            }
+            '"' => {
+                let start_line = line;
+                let mut value = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err(ImportError::parse(start_line, "unterminated string")),
+                        Some('"') => break,
+                        Some('\\') => {
+                            match chars.get(i + 1) {
+                                Some('"') => value.push('"'),
+                                Some('\\') => value.push('\\'),
+                                Some('n') | Some('l') | Some('r') => value.push('\n'),
+                                // Escaped line break continues the string
+                                Some('\n') => line += 1,
+                                Some(other) => {
+                                    value.push('\\');
+                                    value.push(*other);
+                                }
+                                None => {}
+                            }
+                            i += 2;
+                        }
+                        Some(c) => {
+                            if *c == '\n' {
+                                line += 1;
+                            }
+                            value.push(*c);
+                            i += 1;
+                        }
+                    }
+                }
+                i += 1;
+                match tokens.last_mut() {
+                    Some((Token::Id(previous), _)) if concatenate => previous.push_str(&value),
+                    _ => tokens.push((Token::Id(value), start_line)),
+                }
+                concatenate = false;
+            }
+            '<' => {
+                let start_line = line;
+                let mut depth = 0;
+                let mut value = String::new();
+                loop {
+                    let Some(&c) = chars.get(i) else {
+                        return Err(ImportError::parse(start_line, "unterminated HTML label"));
+                    };
+                    i += 1;
+                    match c {
+                        '<' => depth += 1,
+                        '>' => depth -= 1,
+                        '\n' => line += 1,
+                        _ => {}
+                    }
+                    if depth == 0 {
+                        break;
+                    }
+                    if !(c == '<' && depth == 1) {
+                        value.push(c);
+                    }
+                }
+                tokens.push((Token::Id(value), start_line));
+            }
+            c if c.is_alphanumeric() || c == '_' || c == '.' || c == '-' || !c.is_ascii() => {
+                let start = i;
+                while i < chars.len() {
+                    let c = chars[i];
+                    // A '-' is part of a number only at its start
+                    let continues = c.is_alphanumeric() || c == '_' || c == '.' || !c.is_ascii() || (c == '-' && i == start);
+                    if !continues {
+                        break;
+                    }
+                    i += 1;
+                }
+                tokens.push((Token::Id(chars[start..i].iter().collect()), line));
+            }
+            other => return Err(ImportError::parse(line, format!("unexpected character '{other}'"))),
+        }
+    }
+    Ok(tokens)
+}
+
+// The parts of a DOT graph the import cares about, in file order
+#[derive(Default)]
+struct DotGraph {
+    nodes: Vec<(String, Attributes)>,
+    // Where each name is in `nodes`
+    index: HashMap<String, usize>,
+    edges: Vec<(String, String, Attributes)>,
+}
+
+impl DotGraph {
+    fn node(&mut self, name: &str) -> &mut Attributes {
+        let index = match self.index.get(name) {
+            Some(&index) => index,
+            None => {
+                self.index.insert(name.to_owned(), self.nodes.len());
+                self.nodes.push((name.to_owned(), Attributes::new()));
+                self.nodes.len() - 1
+            }
+        };
+        &mut self.nodes[index].1
+    }
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    graph: DotGraph,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.position)
+            .or(self.tokens.last())
+            .map_or(0, |(_, line)| *line)
+    }
+
+    fn error(&self, message: impl Into<String>) -> ImportError {
+        ImportError::parse(self.line(), message)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).map(|(token, _)| token.clone());
+        self.position += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: Token, what: &str) -> Result<(), ImportError> {
+        if self.eat(&token) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected {what}")))
+        }
+    }
+
+    fn id(&mut self) -> Result<String, ImportError> {
+        match self.next() {
+            Some(Token::Id(id)) => Ok(id),
+            _ => {
+                self.position -= 1;
+                Err(self.error("expected a name"))
+            }
+        }
+    }
+
+    fn keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Id(id)) if id.eq_ignore_ascii_case(keyword))
+    }
+
+    fn graph(&mut self) -> Result<(), ImportError> {
+        if self.keyword("strict") {
+            self.position += 1;
+        }
+        if !self.keyword("graph") && !self.keyword("digraph") {
+            return Err(self.error("expected 'graph' or 'digraph'"));
+        }
+        self.position += 1;
+        if matches!(self.peek(), Some(Token::Id(_))) {
+            self.position += 1;
+        }
+        self.expect(Token::Open, "'{'")?;
+        self.statements()?;
+        if self.position < self.tokens.len() {
+            return Err(self.error("unexpected text after the graph"));
+        }
+        Ok(())
+    }
+
+    // Statements up to and including the closing brace; returns the nodes they mention
+    fn statements(&mut self) -> Result<Vec<String>, ImportError> {
+        let mut mentioned = Vec::new();
+        loop {
+            match self.peek() {
+                None => return Err(self.error("missing '}'")),
+                Some(Token::Close) => {
+                    self.position += 1;
+                    return Ok(mentioned);
+                }
+                Some(Token::Semicolon) => self.position += 1,
+                Some(_) => mentioned.extend(self.statement()?),
+            }
+        }
+    }
+
+    fn statement(&mut self) -> Result<Vec<String>, ImportError> {
+        if self.keyword("graph") || self.keyword("node") || self.keyword("edge") {
+            self.position += 1;
+            self.attributes()?;
+            return Ok(Vec::new());
+        }
+        let first = self.endpoint()?;
+        if self.eat(&Token::Equals) {
+            // Graph attribute `name = value`
+            self.id()?;
+            return Ok(Vec::new());
+        }
+
+        let mut chain = vec![first];
+        while self.eat(&Token::EdgeOp) {
+            chain.push(self.endpoint()?);
+        }
+        let attributes = self.attributes()?;
+        let mentioned: Vec<String> = chain.iter().flatten().cloned().collect();
+        for name in &mentioned {
+            self.graph.node(name);
+        }
+
+        if chain.len() == 1 {
+            // Node statement, or a bare subgraph
+            if let [name] = chain[0].as_slice() {
+                self.graph.node(name).extend(attributes);
+            }
+            return Ok(mentioned);
+        }
+        for pair in chain.windows(2) {
+            for source in &pair[0] {
+                for target in &pair[1] {
+                    self.graph.edges.push((source.clone(), target.clone(), attributes.clone()));
+                }
+            }
+        }
+        Ok(mentioned)
+    }
+
+    // A node ID (port stripped) or a subgraph, as the list of nodes it stands for. Names are
+    // registered by the statement, once it is known not to be a `name = value` attribute
+    fn endpoint(&mut self) -> Result<Vec<String>, ImportError> {
+        if self.keyword("subgraph") {
+            self.position += 1;
+            if matches!(self.peek(), Some(Token::Id(_))) {
+                self.position += 1;
+            }
+        }
+        if self.eat(&Token::Open) {
+            return self.statements();
+        }
+        let name = self.id()?;
+        // Ports don't mean anything here
+        while self.eat(&Token::Colon) {
+            self.id()?;
+        }
+        Ok(vec![name])
+    }
+
+    fn attributes(&mut self) -> Result<Attributes, ImportError> {
+        let mut attributes = Attributes::new();
+        while self.eat(&Token::OpenBracket) {
+            while !self.eat(&Token::CloseBracket) {
+                let key = self.id()?;
+                self.expect(Token::Equals, "'='")?;
+                let value = self.id()?;
+                attributes.insert(key, value);
+                if !self.eat(&Token::Comma) {
+                    self.eat(&Token::Semicolon);
+                }
+            }
+        }
+        Ok(attributes)
+    }
+}
+
+fn parse(text: &str) -> Result<DotGraph, ImportError> {
+    let mut parser = Parser {
+        tokens: tokenize(text)?,
+        position: 0,
+        graph: DotGraph::default(),
+    };
+    parser.graph()?;
+    Ok(parser.graph)
+}
+
+fn parse_pos(text: &str) -> Option<Pos2> {
+    let mut parts = text.trim().trim_end_matches('!').split(',');
+    let x = parts.next()?.trim().parse::<f32>().ok()?;
+    let y = parts.next()?.trim().parse::<f32>().ok()?;
+    Some(Pos2::new(x, -y))
+}
+
+// Adds the graph in `text` to `state`, placing nodes without `pos` automatically
+pub fn import(state: &mut GraphState, text: &str) -> Result<ImportReport, ImportError> {
+    let dot = parse(text)?;
+
+    let is_point = |attributes: &Attributes| attributes.get("shape").is_some_and(|s| s == "point");
+    // Edges in and out of each node, and the nodes with `part` halves
+    let mut into: HashMap<&str, usize> = HashMap::new();
+    let mut out_of: HashMap<&str, usize> = HashMap::new();
+    let mut marked: BTreeSet<&str> = BTreeSet::new();
+    for (source, target, attributes) in &dot.edges {
+        *out_of.entry(source).or_default() += 1;
+        *into.entry(target).or_default() += 1;
+        if attributes.contains_key("part") {
+            marked.extend([source.as_str(), target.as_str()]);
+        }
+    }
+    let once = |counts: &HashMap<&str, usize>, name: &str| counts.get(name) == Some(&1);
+    // Points without `part` halves have them guessed from their one edge in and one edge out
+    let unmarked: BTreeSet<&str> = dot
+        .nodes
+        .iter()
+        .map(|(name, attributes)| (name.as_str(), attributes))
+        .filter(|&(name, attributes)| is_point(attributes) && !marked.contains(name))
+        .filter(|&(name, _)| once(&into, name) && once(&out_of, name))
+        .map(|(name, _)| name)
+        .collect();
+
+    let nodes = dot
+        .nodes
+        .iter()
+        .map(|(name, attributes)| {
+            let relay = is_point(attributes) && (marked.contains(name.as_str()) || unmarked.contains(name.as_str()));
+            if relay {
+                return NodeRecord {
+                    key: name.clone(),
+                    relay,
+                    ..Default::default()
+                };
+            }
+            NodeRecord {
+                key: name.clone(),
+                name: attributes.get("label").unwrap_or(name).clone(),
+                description: attributes.get("tooltip").cloned().unwrap_or_default(),
+                position: attributes.get("pos").and_then(|p| parse_pos(p)),
+                style: NodeStyle {
+                    shape: attributes.get("shape").and_then(|s| shape_from_name(s)),
+                    fill: attributes.get("fillcolor").and_then(|c| color_from_hex(c)),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }
+        })
+        .collect();
+
+    let edges = dot
+        .edges
+        .into_iter()
+        .enumerate()
+        .map(|(index, (source, target, attributes))| {
+            let part = match attributes.get("part") {
+                Some(part) => Part::parse(part),
+                None if unmarked.contains(target.as_str()) => Some(Part::Tail),
+                None if unmarked.contains(source.as_str()) => Some(Part::Head),
+                None => None,
+            };
+            let text = |name: &str| attributes.get(name).filter(|text| !text.is_empty()).cloned();
+            EdgeRecord {
+                key: format!("{source} -> {target} #{index}"),
+                relation: text("label"),
+                color: attributes.get("color").and_then(|c| color_from_hex(c)),
+                label: text("xlabel"),
+                part,
+                source,
+                target,
+                ..Default::default()
+            }
+        })
+        .collect();
+
+    Ok(records::build(state, nodes, edges))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interchange::samples::{self, named};
+    use eframe::egui::Color32;
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let samples::Sample { mut state, queen, city, challenge, .. } = samples::world("Queen \"Ilsa\"");
+        state.graph.node_data_mut(queen).unwrap().style.shape = Some(NodeShape::Diamond);
+        state.graph.set_edge_label(challenge, Some("dares".into())).unwrap();
+        let text = export(&state, None);
+        // Only the rule has an edge attached, so only it needs a point node
+        assert_eq!(text.matches("shape=point").count(), 1);
+        assert!(text.contains(r#"label="Queen \"Ilsa\"""#));
+
+        let mut imported = GraphState::new();
+        let report = import(&mut imported, &text).unwrap();
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+        let found = samples::check_round_trip(&imported, "Queen \"Ilsa\"", "Varenholm");
+        assert_eq!(imported.positions[found.city], state.positions[city]);
+        assert_eq!(imported.graph.node_data(found.queen).unwrap().style.shape, Some(NodeShape::Diamond));
+        let rules = imported.relations.find_by_name("rules over").unwrap();
+        assert_eq!(imported.relations.get(rules).unwrap().color, Color32::GOLD);
+
+        // The challenge is back on the rule, with its own label as `xlabel`
+        let challenge = imported.graph.get_outgoing_edges(found.rival)[0];
+        let challenge = imported.graph.get_edge(challenge).unwrap();
+        assert_eq!((challenge.target, challenge.relation), (found.rule, None));
+        assert_eq!(challenge.label.as_deref(), Some("dares"));
+    }
+
+    #[test]
+    fn test_export_subset() {
+        let mut state = GraphState::new();
+        let a = state.add_node_at(Pos2::ZERO);
+        let b = state.add_node_at(Pos2::ZERO);
+        let c = state.add_node_at(Pos2::ZERO);
+        state.add_edge_between(a, b, None).unwrap();
+        state.add_edge_between(b, c, None).unwrap();
+
+        let text = export(&state, Some(&BTreeSet::from([a, b])));
+        assert_eq!(text.matches("->").count(), 1);
+        assert_eq!(text.matches("label=").count(), 2);
+    }
+
+    #[test]
+    fn test_import_foreign_dot() {
+        let text = r#"
+            // A hand-written file
+            strict digraph "court" {
+                graph [rankdir=LR]
+                rankdir=LR
+                node [shape=box]
+                king [label="King" + " Aldric", pos="10,20"];
+                /* chains and subgraphs */
+                king -> { knight1 knight2 } [label="commands"];
+                subgraph cluster_court { label="The court"; color=blue; knight1; knight2 }
+                knight1 -> squire:n -> page
+                # a comment line
+                mid [shape=point];
+                squire -> mid -> page;
+                knight2 -> mid [label=watches]
+                via [shape=point];
+                page -> via -> king [label="serves"];
+            }
+        "#;
+        let mut state = GraphState::new();
+        let report = import(&mut state, text).unwrap();
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+        // `rankdir=LR` and the cluster's `label` and `color` set attributes, they are not nodes
+        let names: BTreeSet<&str> = state
+            .graph
+            .nodes_iter()
+            .filter(|(id, _)| state.graph.get_edge(*id).is_none())
+            .map(|(_, node)| node.data().name.as_str())
+            .collect();
+        assert_eq!(names, BTreeSet::from(["King Aldric", "knight1", "knight2", "squire", "page", "mid"]));
+
+        let king = named(&state, "King Aldric");
+        assert_eq!(state.positions[king], Pos2::new(10.0, -20.0));
+        assert_eq!(state.graph.get_outgoing_edges(king).len(), 2);
+        assert!(state.positions.contains_key(named(&state, "page")));
+
+        // `mid` has two edges in, so it stays a node of its own
+        let mid = named(&state, "mid");
+        assert_eq!(state.graph.get_incoming_edges(mid).len(), 2);
+        let commands = state.relations.find_by_name("commands").unwrap();
+        assert_eq!(state.graph.edges_of_type(commands).len(), 2);
+
+        // `via` has one edge in and one out, so it is the middle of one edge
+        let serves = state.relations.find_by_name("serves").unwrap();
+        let service = state.graph.edges_of_type(serves);
+        assert_eq!(service.len(), 1);
+        assert_eq!(state.graph.get_edge(service[0]).unwrap().target, king);
+    }
+
+    #[test]
+    fn test_import_errors() {
+        let mut state = GraphState::new();
+        let error = import(&mut state, "digraph {\n a -> b [label=\"x]\n}").unwrap_err();
+        assert!(matches!(error, ImportError::Parse { line: 2, .. }), "{error}");
+        assert!(import(&mut state, "digraph { a -> }").is_err());
+        assert!(import(&mut state, "not a graph").is_err());
+        assert_eq!(state.graph.node_count(), 0);
+
+        // Self-loops can't exist in a world, the rest still comes in
+        let report = import(&mut state, "digraph { a -> a; a -> b }").unwrap();
+        assert_eq!(report.warnings.len(), 1);
+        assert_eq!(state.graph.node_count(), 3);
+    }
+}
@@ -0,0 +1,335 @@
+// GEXF 1.3 export and import, for Gephi and friends
+//
+// Node names are node labels and positions are viz:position with y pointing up, the way Gephi
+// shows them. Subtitle, description, tags, kind and properties are node attribute columns; as in
+// GraphML, property columns have ids starting with their type (text, number, bool, date, ref)
+// so dates and references come back typed. Edge `kind` is the relation type, edge `label` the
+// edge's own label, viz:color the relation's color and edge attribute columns its properties.
+// Gephi's edge `weight` is read as a number property.
+//
+// An edge that other edges connect to becomes a node with element="edge" and two half edges
+// with part="tail" / part="head", exactly as in GraphML:
+//
+//   <node id="e3"><attvalues><attvalue for="element" value="edge"/></attvalues></node>
+//   <edge id="e3.tail" source="n1" target="e3">... part=tail ...</edge>
+//   <edge id="e3.head" source="e3" target="n2">... part=head ...</edge>
+//
+// Older GEXF versions (attvalue `id` instead of `for`) read the same way
+use eframe::egui::{Color32, Pos2};
+use roxmltree::{Document, Node};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
+
+use super::records::{self, format_value, EdgeRecord, Field, NodeRecord, Value};
+use super::{xml_attribute, xml_error, xml_escape, xml_line, ImportError, ImportReport};
+use crate::graph::{PropertyValue, ID};
+use crate::state::GraphState;
+
+// Node columns every export declares, all strings; the title is the id
+const NODE_COLUMNS: [&str; 5] = ["subtitle", "description", "tags", "kind", "element"];
+
+fn write_attvalues(out: &mut String, values: &[(&str, String)]) {
+    if values.is_empty() {
+        return;
+    }
+    out.push_str("        <attvalues>\n");
+    for (column, value) in values {
+        let _ = writeln!(out, "          <attvalue for=\"{column}\" value=\"{}\"/>", xml_escape(value));
+    }
+    out.push_str("        </attvalues>\n");
+}
+
+pub fn export(state: &GraphState, only: Option<&BTreeSet<ID>>) -> String {
+    let records = records::collect(state, only);
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str("<gexf xmlns=\"http://gexf.net/1.3\" xmlns:viz=\"http://gexf.net/1.3/viz\" version=\"1.3\">\n");
+    let _ = writeln!(out, "  <meta>\n    <creator>node_simulator {}</creator>\n  </meta>", env!("CARGO_PKG_VERSION"));
+    out.push_str("  <graph defaultedgetype=\"directed\" mode=\"static\">\n");
+
+    out.push_str("    <attributes class=\"node\">\n");
+    for id in NODE_COLUMNS {
+        let _ = writeln!(out, "      <attribute id=\"{id}\" title=\"{id}\" type=\"string\"/>");
+    }
+    for column in records.columns.iter().filter(|c| !c.for_edges) {
+        let _ = writeln!(
+            out,
+            "      <attribute id=\"{}\" title=\"{}\" type=\"{}\"/>",
+            column.id,
+            xml_escape(&column.name),
+            column.value_type.xml_type()
+        );
+    }
+    out.push_str("    </attributes>\n");
+    out.push_str("    <attributes class=\"edge\">\n");
+    out.push_str("      <attribute id=\"part\" title=\"part\" type=\"string\"/>\n");
+    for column in records.columns.iter().filter(|c| c.for_edges) {
+        let _ = writeln!(
+            out,
+            "      <attribute id=\"{}\" title=\"{}\" type=\"{}\"/>",
+            column.id,
+            xml_escape(&column.name),
+            column.value_type.xml_type()
+        );
+    }
+    out.push_str("    </attributes>\n");
+
+    out.push_str("    <nodes>\n");
+    for node in &records.nodes {
+        let _ = writeln!(out, "      <node id=\"{}\" label=\"{}\">", node.key, xml_escape(&node.name));
+        let mut values = Vec::new();
+        if node.relay {
+            values.push(("element", "edge".to_owned()));
+        }
+        if !node.subtitle.is_empty() {
+            values.push(("subtitle", node.subtitle.clone()));
+        }
+        if !node.description.is_empty() {
+            values.push(("description", node.description.clone()));
+        }
+        if !node.tags.is_empty() {
+            let tags: Vec<&str> = node.tags.iter().map(String::as_str).collect();
+            values.push(("tags", tags.join(", ")));
+        }
+        if let Some(kind) = &node.kind {
+            values.push(("kind", kind.clone()));
+        }
+        for (name, value) in &node.properties {
+            if let Some(column) = records.column(name, value, false) {
+                values.push((column.id.as_str(), format_value(value)));
+            }
+        }
+        write_attvalues(&mut out, &values);
+        if let Some(pos) = node.position.filter(|_| !node.relay) {
+            let _ = writeln!(out, "        <viz:position x=\"{}\" y=\"{}\" z=\"0\"/>", pos.x, -pos.y);
+        }
+        out.push_str("      </node>\n");
+    }
+    out.push_str("    </nodes>\n");
+
+    out.push_str("    <edges>\n");
+    for edge in &records.edges {
+        let _ = write!(out, "      <edge id=\"{}\" source=\"{}\" target=\"{}\"", edge.key, edge.source, edge.target);
+        if let Some(relation) = &edge.relation {
+            let _ = write!(out, " kind=\"{}\"", xml_escape(relation));
+        }
+        if let Some(label) = &edge.label {
+            let _ = write!(out, " label=\"{}\"", xml_escape(label));
+        }
+        out.push_str(">\n");
+        let mut values: Vec<(&str, String)> = edge.part.map(|part| ("part", part.name().to_owned())).into_iter().collect();
+        for (name, value) in &edge.properties {
+            if let Some(column) = records.column(name, value, true) {
+                values.push((column.id.as_str(), format_value(value)));
+            }
+        }
+        write_attvalues(&mut out, &values);
+        if let Some(color) = edge.color {
+            let _ = writeln!(out, "        <viz:color r=\"{}\" g=\"{}\" b=\"{}\"/>", color.r(), color.g(), color.b());
+        }
+        out.push_str("      </edge>\n");
+    }
+    out.push_str("    </edges>\n  </graph>\n</gexf>\n");
+    out
+}
+
+struct Column {
+    field: Field,
+    default: Option<String>,
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.tag_name().name() == name)
+}
+
+// Applies an element's attvalues, then the column defaults for whatever it left out
+fn fill(
+    doc: &Document,
+    element: Node,
+    columns: &HashMap<String, Column>,
+    warnings: &mut Vec<String>,
+    mut set: impl FnMut(&Field, &str) -> Result<(), String>,
+) {
+    let mut given = BTreeSet::new();
+    let values = child(element, "attvalues").into_iter().flat_map(|list| list.children());
+    for value in values.filter(|n| n.tag_name().name() == "attvalue") {
+        let Some(column) = value.attribute("for").or_else(|| value.attribute("id")) else { continue };
+        given.insert(column);
+        let Some(Column { field, .. }) = columns.get(column) else { continue };
+        if let Err(message) = set(field, value.attribute("value").unwrap_or_default()) {
+            warnings.push(format!("line {}: {message}", xml_line(doc, value)));
+        }
+    }
+    for (id, column) in columns {
+        if let (false, Some(default)) = (given.contains(id.as_str()), &column.default) {
+            let _ = set(&column.field, default);
+        }
+    }
+}
+
+pub fn import(state: &mut GraphState, text: &str) -> Result<ImportReport, ImportError> {
+    let doc = Document::parse(text).map_err(xml_error)?;
+    let root = doc.root_element();
+    if root.tag_name().name() != "gexf" {
+        return Err(ImportError::parse(xml_line(&doc, root), "not a GEXF file"));
+    }
+    let graph = child(root, "graph").ok_or_else(|| ImportError::parse(xml_line(&doc, root), "the file has no <graph>"))?;
+
+    let mut node_columns = HashMap::new();
+    let mut edge_columns = HashMap::new();
+    for list in graph.children().filter(|n| n.tag_name().name() == "attributes") {
+        let for_edges = list.attribute("class") == Some("edge");
+        for attribute in list.children().filter(|n| n.tag_name().name() == "attribute") {
+            let id = xml_attribute(&doc, attribute, "id")?;
+            let title = attribute.attribute("title").unwrap_or_default();
+            let xml_type = attribute.attribute("type").unwrap_or("string");
+            let column = Column {
+                field: Field::classify(id, title, xml_type, for_edges),
+                default: child(attribute, "default").map(|d| d.text().unwrap_or_default().to_owned()),
+            };
+            let columns = if for_edges { &mut edge_columns } else { &mut node_columns };
+            columns.insert(id.to_owned(), column);
+        }
+    }
+
+    let mut warnings = Vec::new();
+    let mut nodes = Vec::new();
+    // Hierarchical graphs nest <nodes> inside nodes; they are flattened
+    for element in graph.descendants().filter(|n| n.tag_name().name() == "node") {
+        let mut record = NodeRecord {
+            key: xml_attribute(&doc, element, "id")?.to_owned(),
+            name: element.attribute("label").unwrap_or_default().to_owned(),
+            ..Default::default()
+        };
+        fill(&doc, element, &node_columns, &mut warnings, |field, text| record.set(field, text));
+        if let Some(position) = child(element, "position") {
+            let number = |name| position.attribute(name).and_then(|v: &str| v.parse::<f32>().ok());
+            if let (Some(x), Some(y)) = (number("x"), number("y")) {
+                record.position = Some(Pos2::new(x, -y));
+            }
+        }
+        nodes.push(record);
+    }
+
+    let mut edges = Vec::new();
+    for element in graph.descendants().filter(|n| n.tag_name().name() == "edge") {
+        let mut record = EdgeRecord {
+            key: element.attribute("id").map_or_else(|| format!("#edge{}", edges.len()), str::to_owned),
+            source: xml_attribute(&doc, element, "source")?.to_owned(),
+            target: xml_attribute(&doc, element, "target")?.to_owned(),
+            relation: element.attribute("kind").map(str::to_owned),
+            label: element.attribute("label").map(str::to_owned),
+            ..Default::default()
+        };
+        fill(&doc, element, &edge_columns, &mut warnings, |field, text| record.set(field, text));
+        if let Some(weight) = element.attribute("weight").and_then(|w| w.trim().parse().ok()) {
+            record.properties.insert("weight".into(), Value::Plain(PropertyValue::Number(weight)));
+        }
+        if let Some(color) = child(element, "color") {
+            let channel = |name| color.attribute(name).and_then(|v: &str| v.trim().parse::<u8>().ok());
+            if let (Some(r), Some(g), Some(b)) = (channel("r"), channel("g"), channel("b")) {
+                record.color = Some(Color32::from_rgb(r, g, b));
+            }
+        }
+        edges.push(record);
+    }
+
+    let mut report = records::build(state, nodes, edges);
+    warnings.append(&mut report.warnings);
+    report.warnings = warnings;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::Date;
+    use crate::interchange::samples::{self, named};
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let samples::Sample { mut state, queen, rival, .. } = samples::world("Queen \"Ilsa\"");
+        state.graph.node_data_mut(queen).unwrap().subtitle = "of House Varen".into();
+        state.graph.set_property(queen, "crowned", PropertyValue::Date(Date::new(1204, 5, 6))).unwrap();
+        state.graph.set_property(rival, "enemy", PropertyValue::Reference(queen)).unwrap();
+
+        let text = export(&state, None);
+        assert!(text.contains("<viz:position x=\"0\" y=\"-10\""));
+
+        let mut imported = GraphState::new();
+        let report = import(&mut imported, &text).unwrap();
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+        let found = samples::check_round_trip(&imported, "Queen \"Ilsa\"", "Varenholm");
+        assert_eq!(imported.graph.node_data(found.queen).unwrap().subtitle, "of House Varen");
+        assert_eq!(
+            imported.graph.get_property(found.queen, "crowned"),
+            Some(&PropertyValue::Date(Date::new(1204, 5, 6)))
+        );
+        let enemy = imported.graph.get_property(found.rival, "enemy");
+        assert_eq!(enemy, Some(&PropertyValue::Reference(found.queen)));
+        assert_eq!(imported.positions[found.queen], Pos2::new(0.0, 10.0));
+
+        let rules = imported.relations.find_by_name("rules over").unwrap();
+        assert_eq!(imported.relations.get(rules).unwrap().color, Color32::GOLD);
+        let challenge = imported.graph.get_outgoing_edges(found.rival)[0];
+        let challenge = imported.graph.get_edge(challenge).unwrap();
+        assert_eq!((challenge.target, challenge.relation), (found.rule, None));
+    }
+
+    #[test]
+    fn test_import_gephi_file() {
+        let text = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <gexf xmlns="http://www.gexf.net/1.2draft" xmlns:viz="http://www.gexf.net/1.2draft/viz" version="1.2">
+              <graph defaultedgetype="directed">
+                <attributes class="node">
+                  <attribute id="0" title="modularity_class" type="integer"/>
+                  <attribute id="1" title="founded" type="date"><default>0001-01-01</default></attribute>
+                </attributes>
+                <nodes>
+                  <node id="1" label="Varenholm">
+                    <attvalues><attvalue id="0" value="3"/><attvalue id="1" value="1066-10-14"/></attvalues>
+                    <viz:position x="15.5" y="20.0" z="0.0"/>
+                  </node>
+                  <node id="2" label="Oskfjord">
+                    <attvalues><attvalue id="0" value="many"/></attvalues>
+                  </node>
+                </nodes>
+                <edges>
+                  <edge id="0" source="1" target="2" weight="2.0"/>
+                </edges>
+              </graph>
+            </gexf>"#;
+        let mut state = GraphState::new();
+        let report = import(&mut state, text).unwrap();
+        assert_eq!(report.warnings.len(), 1, "{:?}", report.warnings);
+        assert!(report.warnings[0].starts_with("line 14"));
+        assert_eq!(state.graph.node_count(), 3);
+
+        let city = named(&state, "Varenholm");
+        let harbor = named(&state, "Oskfjord");
+        assert_eq!(state.positions[city], Pos2::new(15.5, -20.0));
+        assert_eq!(state.graph.get_property(city, "modularity_class"), Some(&PropertyValue::Number(3.0)));
+        assert_eq!(
+            state.graph.get_property(city, "founded"),
+            Some(&PropertyValue::Date(Date::new(1066, 10, 14)))
+        );
+        assert_eq!(state.graph.get_property(harbor, "founded"), Some(&PropertyValue::Date(Date::new(1, 1, 1))));
+        let road = state.graph.get_outgoing_edges(city)[0];
+        assert_eq!(state.graph.get_property(road, "weight"), Some(&PropertyValue::Number(2.0)));
+    }
+
+    #[test]
+    fn test_import_errors() {
+        let mut state = GraphState::new();
+        let Err(ImportError::Parse { line, .. }) = import(&mut state, "<gexf>\n<graph>\n<nodes>\n<node id=\"a\">\n</nodes>") else {
+            panic!("malformed XML accepted");
+        };
+        assert_eq!(line, 5);
+        let Err(ImportError::Parse { line, .. }) = import(&mut state, "<gexf>\n<graph><nodes>\n<node label='x'/></nodes></graph></gexf>") else {
+            panic!("node without id accepted");
+        };
+        assert_eq!(line, 3);
+        assert!(import(&mut state, "<graphml/>").is_err());
+        assert_eq!(state.graph.node_count(), 0);
+    }
+}
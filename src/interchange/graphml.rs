@@ -0,0 +1,355 @@
+// GraphML export and import
+//
+// Nodes carry their fields as <data> under fixed keys (label, subtitle, description, tags, kind,
+// x, y) and every property gets a key whose id starts with its type: text, number, bool, date or
+// ref. GraphML only has strings, numbers and booleans, so dates ("YYYY-MM-DD") and references
+// (the id of the node pointed at) are strings whose type is recovered from the key id.
+// Positions are editor coordinates, y pointing down.
+//
+// Edges carry their relation type, their own label, the relation's color and their properties
+// (keys with for="edge"). An edge that other
+// edges connect to becomes a node with element="edge" and two half edges marked part="tail"
+// and part="head":
+//
+//   <node id="e3"><data key="element">edge</data></node>
+//   <edge source="n1" target="e3"><data key="part">tail</data>...</edge>
+//   <edge source="e3" target="n2"><data key="part">head</data>...</edge>
+//   <edge source="n4" target="e3">...</edge>
+//
+// Files from other tools are read by key name ("name", "label", "x", ...), with <default> values
+// honored; other keys become properties of their declared type. yEd node geometry and
+// labels are used when the file has no x/y or label keys
+use roxmltree::{Document, Node};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
+
+use super::records::{self, format_value, EdgeRecord, Field, NodeRecord};
+use super::{color_to_hex, xml_attribute, xml_error, xml_escape, xml_line, ImportError, ImportReport};
+use crate::graph::ID;
+use crate::state::GraphState;
+
+// (id, for, attr.name, attr.type) of the keys every export declares
+const KEYS: [(&str, &str, &str, &str); 12] = [
+    ("label", "node", "label", "string"),
+    ("subtitle", "node", "subtitle", "string"),
+    ("description", "node", "description", "string"),
+    ("tags", "node", "tags", "string"),
+    ("kind", "node", "kind", "string"),
+    ("x", "node", "x", "double"),
+    ("y", "node", "y", "double"),
+    ("element", "node", "element", "string"),
+    ("relation", "edge", "relation", "string"),
+    ("edge_label", "edge", "label", "string"),
+    ("color", "edge", "color", "string"),
+    ("part", "edge", "part", "string"),
+];
+
+fn write_data(out: &mut String, key: &str, value: &str) {
+    let _ = writeln!(out, "      <data key=\"{key}\">{}</data>", xml_escape(value));
+}
+
+pub fn export(state: &GraphState, only: Option<&BTreeSet<ID>>) -> String {
+    let records = records::collect(state, only);
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+    for (id, domain, name, xml_type) in KEYS {
+        let _ = writeln!(out, "  <key id=\"{id}\" for=\"{domain}\" attr.name=\"{name}\" attr.type=\"{xml_type}\"/>");
+    }
+    for column in &records.columns {
+        let _ = writeln!(
+            out,
+            "  <key id=\"{}\" for=\"{}\" attr.name=\"{}\" attr.type=\"{}\"/>",
+            column.id,
+            if column.for_edges { "edge" } else { "node" },
+            xml_escape(&column.name),
+            column.value_type.xml_type()
+        );
+    }
+    out.push_str("  <graph id=\"world\" edgedefault=\"directed\">\n");
+
+    for node in &records.nodes {
+        let _ = writeln!(out, "    <node id=\"{}\">", node.key);
+        if node.relay {
+            write_data(&mut out, "element", "edge");
+        }
+        if !node.name.is_empty() {
+            write_data(&mut out, "label", &node.name);
+        }
+        if !node.subtitle.is_empty() {
+            write_data(&mut out, "subtitle", &node.subtitle);
+        }
+        if !node.description.is_empty() {
+            write_data(&mut out, "description", &node.description);
+        }
+        if !node.tags.is_empty() {
+            let tags: Vec<&str> = node.tags.iter().map(String::as_str).collect();
+            write_data(&mut out, "tags", &tags.join(", "));
+        }
+        if let Some(kind) = &node.kind {
+            write_data(&mut out, "kind", kind);
+        }
+        if let Some(pos) = node.position.filter(|_| !node.relay) {
+            write_data(&mut out, "x", &pos.x.to_string());
+            write_data(&mut out, "y", &pos.y.to_string());
+        }
+        for (name, value) in &node.properties {
+            if let Some(column) = records.column(name, value, false) {
+                write_data(&mut out, &column.id, &format_value(value));
+            }
+        }
+        out.push_str("    </node>\n");
+    }
+
+    for edge in &records.edges {
+        let _ = writeln!(
+            out,
+            "    <edge id=\"{}\" source=\"{}\" target=\"{}\">",
+            edge.key, edge.source, edge.target
+        );
+        if let Some(part) = edge.part {
+            write_data(&mut out, "part", part.name());
+        }
+        if let Some(relation) = &edge.relation {
+            write_data(&mut out, "relation", relation);
+        }
+        if let Some(color) = edge.color {
+            write_data(&mut out, "color", &color_to_hex(color));
+        }
+        if let Some(label) = &edge.label {
+            write_data(&mut out, "edge_label", label);
+        }
+        for (name, value) in &edge.properties {
+            if let Some(column) = records.column(name, value, true) {
+                write_data(&mut out, &column.id, &format_value(value));
+            }
+        }
+        out.push_str("    </edge>\n");
+    }
+    out.push_str("  </graph>\n</graphml>\n");
+    out
+}
+
+struct Key {
+    field: Field,
+    default: Option<String>,
+}
+
+fn children<'a, 'input>(node: Node<'a, 'input>, name: &'static str) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.children().filter(move |child| child.tag_name().name() == name)
+}
+
+// yEd keeps geometry and the visible label in a nodegraphics blob instead of keys
+fn read_yed_graphics(node: Node, record: &mut NodeRecord) {
+    let Some(shape) = node.descendants().find(|n| n.tag_name().name().ends_with("Node") && n.has_children()) else {
+        return;
+    };
+    if record.position.is_none() {
+        if let Some(geometry) = shape.children().find(|n| n.tag_name().name() == "Geometry") {
+            let number = |name| geometry.attribute(name).and_then(|v: &str| v.parse::<f32>().ok());
+            if let (Some(x), Some(y)) = (number("x"), number("y")) {
+                let (width, height) = (number("width").unwrap_or(0.0), number("height").unwrap_or(0.0));
+                record.position = Some(eframe::egui::Pos2::new(x + width / 2.0, y + height / 2.0));
+            }
+        }
+    }
+    if record.name.is_empty() {
+        if let Some(label) = shape.children().find(|n| n.tag_name().name() == "NodeLabel") {
+            record.name = label.text().unwrap_or_default().trim().to_owned();
+        }
+    }
+}
+
+// Applies an element's <data>, then the key defaults for whatever it left out
+fn fill(
+    doc: &Document,
+    element: Node,
+    keys: &HashMap<String, Key>,
+    warnings: &mut Vec<String>,
+    mut set: impl FnMut(&Field, &str) -> Result<(), String>,
+) {
+    let mut given = BTreeSet::new();
+    for data in children(element, "data") {
+        let Some(key) = data.attribute("key") else { continue };
+        given.insert(key);
+        let Some(Key { field, .. }) = keys.get(key) else { continue };
+        let text: String = data.descendants().filter(|n| n.is_text()).filter_map(|n| n.text()).collect();
+        if let Err(message) = set(field, &text) {
+            warnings.push(format!("line {}: {message}", xml_line(doc, data)));
+        }
+    }
+    for (id, key) in keys {
+        if let (false, Some(default)) = (given.contains(id.as_str()), &key.default) {
+            let _ = set(&key.field, default);
+        }
+    }
+}
+
+pub fn import(state: &mut GraphState, text: &str) -> Result<ImportReport, ImportError> {
+    let doc = Document::parse(text).map_err(xml_error)?;
+    let root = doc.root_element();
+    if root.tag_name().name() != "graphml" {
+        return Err(ImportError::parse(xml_line(&doc, root), "not a GraphML file"));
+    }
+
+    let mut node_keys = HashMap::new();
+    let mut edge_keys = HashMap::new();
+    for key in children(root, "key") {
+        let id = xml_attribute(&doc, key, "id")?;
+        let name = key.attribute("attr.name").unwrap_or_default();
+        let xml_type = key.attribute("attr.type").unwrap_or("string");
+        let default = children(key, "default").next().map(|d| d.text().unwrap_or_default().to_owned());
+        let domain = key.attribute("for").unwrap_or("all");
+        if matches!(domain, "node" | "all") {
+            let field = Field::classify(id, name, xml_type, false);
+            node_keys.insert(id.to_owned(), Key { field, default: default.clone() });
+        }
+        if matches!(domain, "edge" | "all") {
+            let field = Field::classify(id, name, xml_type, true);
+            edge_keys.insert(id.to_owned(), Key { field, default });
+        }
+    }
+
+    let graph = children(root, "graph")
+        .next()
+        .ok_or_else(|| ImportError::parse(xml_line(&doc, root), "the file has no <graph>"))?;
+
+    let mut warnings = Vec::new();
+    let mut nodes = Vec::new();
+    let mut edges = Vec::new();
+    // Nested graphs are flattened; their nodes and edges join the top level
+    for element in graph.descendants() {
+        match element.tag_name().name() {
+            "node" => {
+                let mut record = NodeRecord {
+                    key: xml_attribute(&doc, element, "id")?.to_owned(),
+                    ..Default::default()
+                };
+                fill(&doc, element, &node_keys, &mut warnings, |field, text| record.set(field, text));
+                read_yed_graphics(element, &mut record);
+                nodes.push(record);
+            }
+            "edge" => {
+                let mut record = EdgeRecord {
+                    key: element.attribute("id").map_or_else(|| format!("#edge{}", edges.len()), str::to_owned),
+                    source: xml_attribute(&doc, element, "source")?.to_owned(),
+                    target: xml_attribute(&doc, element, "target")?.to_owned(),
+                    ..Default::default()
+                };
+                fill(&doc, element, &edge_keys, &mut warnings, |field, text| record.set(field, text));
+                edges.push(record);
+            }
+            _ => {}
+        }
+    }
+
+    let mut report = records::build(state, nodes, edges);
+    warnings.append(&mut report.warnings);
+    report.warnings = warnings;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{Date, PropertyValue};
+    use crate::interchange::samples::{self, named};
+    use eframe::egui::{Color32, Pos2};
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let samples::Sample { mut state, queen, city, rival, rule, challenge } = samples::world("Queen <Ilsa> & co");
+        state.graph.set_description(queen, "Rules\nwisely").unwrap();
+        state.graph.add_tag(queen, "royalty").unwrap();
+        state.graph.add_tag(queen, "house varen").unwrap();
+        state.graph.set_property(queen, "age", PropertyValue::Number(42.5)).unwrap();
+        state.graph.set_property(queen, "crowned", PropertyValue::Date(Date::new(-12, 3, 1))).unwrap();
+        state.graph.set_property(queen, "alive", PropertyValue::Bool(true)).unwrap();
+        state.graph.set_property(queen, "seat", PropertyValue::Reference(city)).unwrap();
+        // Same name, different type on another node
+        state.graph.set_property(city, "age", PropertyValue::Text("old".into())).unwrap();
+        let character = super::super::kind_by_name(&mut state, "Character");
+        state.graph.set_kind(queen, Some(character)).unwrap();
+        state.graph.set_property(rule, "since", PropertyValue::Date(Date::new(1204, 1, 1))).unwrap();
+        state.graph.set_property(challenge, "intensity", PropertyValue::Number(3.0)).unwrap();
+
+        let text = export(&state, None);
+        assert!(text.contains("<data key=\"element\">edge</data>"));
+
+        let mut imported = GraphState::new();
+        let report = import(&mut imported, &text).unwrap();
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+        let found = samples::check_round_trip(&imported, "Queen <Ilsa> & co", "Varenholm");
+        let data = imported.graph.node_data(found.queen).unwrap();
+        assert_eq!(data.description, "Rules\nwisely");
+        assert_eq!(data.tags, state.graph.node_data(queen).unwrap().tags);
+        assert_eq!(data.properties["age"], PropertyValue::Number(42.5));
+        assert_eq!(data.properties["crowned"], PropertyValue::Date(Date::new(-12, 3, 1)));
+        assert_eq!(data.properties["alive"], PropertyValue::Bool(true));
+        assert_eq!(data.properties["seat"], PropertyValue::Reference(found.city));
+        assert_eq!(imported.graph.get_property(found.city, "age"), Some(&PropertyValue::Text("old".into())));
+        assert_eq!(data.kind, imported.schema.as_ref().unwrap().find_kind("Character"));
+        assert_eq!(imported.positions[found.rival], state.positions[rival]);
+
+        let rules = imported.relations.find_by_name("rules over").unwrap();
+        assert_eq!(imported.relations.get(rules).unwrap().color, Color32::GOLD);
+        let since = imported.graph.get_property(found.rule, "since");
+        assert_eq!(since, Some(&PropertyValue::Date(Date::new(1204, 1, 1))));
+        // Edges are elements with data of their own, even the one on the rule
+        let challenge = imported.graph.get_outgoing_edges(found.rival)[0];
+        assert_eq!(imported.graph.get_edge(challenge).unwrap().target, found.rule);
+        assert_eq!(imported.graph.get_property(challenge, "intensity"), Some(&PropertyValue::Number(3.0)));
+    }
+
+    #[test]
+    fn test_import_foreign_graphml() {
+        let text = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <graphml xmlns="http://graphml.graphdrawing.org/xmlns" xmlns:y="http://www.yworks.com/xml/graphml">
+              <key id="d0" for="node" attr.name="name" attr.type="string"/>
+              <key id="d1" for="node" attr.name="population" attr.type="int">
+                <default>0</default>
+              </key>
+              <key id="d2" for="edge" attr.name="weight" attr.type="double"/>
+              <key id="d3" for="node" yfiles.type="nodegraphics"/>
+              <graph id="G" edgedefault="undirected">
+                <node id="a"><data key="d0">Varenholm</data><data key="d1">12000</data></node>
+                <node id="b"><data key="d0">Oskfjord</data></node>
+                <node id="c">
+                  <data key="d3"><y:ShapeNode><y:Geometry x="10" y="20" width="30" height="40"/>
+                    <y:NodeLabel>Harbor</y:NodeLabel></y:ShapeNode></data>
+                </node>
+                <edge source="a" target="b"><data key="d2">2.5</data></edge>
+                <edge source="b" target="nowhere"/>
+              </graph>
+            </graphml>"#;
+        let mut state = GraphState::new();
+        let report = import(&mut state, text).unwrap();
+        assert_eq!(state.graph.node_count(), 4);
+        assert_eq!(report.warnings.len(), 1, "{:?}", report.warnings);
+        assert!(report.warnings[0].contains("nowhere"));
+
+        let a = named(&state, "Varenholm");
+        let b = named(&state, "Oskfjord");
+        assert_eq!(state.graph.get_property(a, "population"), Some(&PropertyValue::Number(12000.0)));
+        assert_eq!(state.graph.get_property(b, "population"), Some(&PropertyValue::Number(0.0)));
+        assert_eq!(state.positions[named(&state, "Harbor")], Pos2::new(25.0, 40.0));
+        let edge = state.graph.get_outgoing_edges(a)[0];
+        assert_eq!(state.graph.get_edge(edge).unwrap().target, b);
+        assert_eq!(state.graph.get_property(edge, "weight"), Some(&PropertyValue::Number(2.5)));
+    }
+
+    #[test]
+    fn test_import_errors() {
+        let mut state = GraphState::new();
+        let Err(ImportError::Parse { line, .. }) = import(&mut state, "<graphml>\n<graph>\n<node id='a'>\n</graph>") else {
+            panic!("malformed XML accepted");
+        };
+        assert_eq!(line, 4);
+        let Err(ImportError::Parse { line, .. }) = import(&mut state, "<graphml>\n<graph>\n<edge source='a'/>\n</graph></graphml>") else {
+            panic!("edge without target accepted");
+        };
+        assert_eq!(line, 3);
+        assert!(import(&mut state, "<gexf/>").is_err());
+        assert_eq!(state.graph.node_count(), 0);
+    }
+}
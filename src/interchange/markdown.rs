@@ -0,0 +1,662 @@
+// Markdown wiki export and Obsidian-style vault import
+//
+// Every plain node becomes a note named after it. Kind, tags, subtitle and properties go in the
+// YAML front matter (references as "[[Note]]"), the description is the body, and two generated
+// sections list the relations:
+//
+//   ## Relations
+//   - rules over:: [[Varenholm]] — since 1204
+//   - [[Oskfjord]]
+//
+//   ## Referenced by
+//   - [[Duke Orm]] (resents)
+//
+// "Relations" holds the outgoing edges to other nodes as Dataview-style inline fields, so the
+// relation type survives a round trip; untyped edges are bare links. "Referenced by" lists the
+// incoming edges and any edge-to-edge relations for readers; importing skips it, since every
+// edge is read from its source's note. Edge-to-edge relations are not reconstructed.
+//
+// Notes from other vaults import the same way: `relation:: [[Note]]` anywhere is a typed edge,
+// any other [[link]] an untyped one, #tags in the text become tags. Links resolve by note name
+// or front matter alias, case-insensitively
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::io;
+use std::path::Path;
+
+use super::records::{self, EdgeRecord, NodeRecord, Value};
+use super::{export_set, ImportReport};
+use crate::graph::{PropertyValue, ID};
+use crate::state::GraphState;
+
+const RELATIONS_HEADING: &str = "## Relations";
+const REFERENCED_HEADING: &str = "## Referenced by";
+
+// One markdown file: its name without ".md" and its contents
+#[derive(Debug, Clone, PartialEq)]
+pub struct Note {
+    pub name: String,
+    pub text: String,
+}
+
+// A name usable as a file name and inside [[links]]
+fn note_name(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| if "\\/:*?\"<>|#^[]".contains(c) || c.is_control() { '-' } else { c })
+        .collect();
+    let cleaned = cleaned.trim().trim_start_matches('.').trim();
+    if cleaned.is_empty() {
+        "Untitled".to_owned()
+    } else {
+        cleaned.to_owned()
+    }
+}
+
+// JSON strings are valid YAML, so quoting is just JSON escaping
+fn yaml_quote(text: &str) -> String {
+    serde_json::to_string(text).unwrap_or_default()
+}
+
+fn yaml_key(key: &str) -> String {
+    let plain = !key.is_empty() && key.chars().all(|c| c.is_alphanumeric() || " _-".contains(c)) && key.trim() == key;
+    if plain {
+        key.to_owned()
+    } else {
+        yaml_quote(key)
+    }
+}
+
+pub fn export(state: &GraphState, only: Option<&BTreeSet<ID>>) -> Vec<Note> {
+    let graph = &state.graph;
+    let included = export_set(graph, only);
+
+    // File names first, so every link can use them
+    let mut taken = HashSet::new();
+    let mut names: HashMap<ID, String> = HashMap::new();
+    for (id, node) in graph.nodes_iter() {
+        if !included.contains(&id) || graph.get_edge(id).is_some() {
+            continue;
+        }
+        let base = note_name(&node.data().name);
+        let mut name = base.clone();
+        let mut n = 2;
+        while !taken.insert(name.to_lowercase()) {
+            name = format!("{base} {n}");
+            n += 1;
+        }
+        names.insert(id, name);
+    }
+    let link = |id: ID| names.get(&id).map(|name| format!("[[{name}]]"));
+    let relation_name = |id: ID| {
+        graph
+            .get_edge(id)
+            .and_then(|e| e.relation)
+            .and_then(|r| state.relations.get(r))
+            .map(|r| r.name.clone())
+    };
+    // "[[Queen]] rules over [[Varenholm]]" for an edge some other edge points at
+    let describe_edge = |id: ID| {
+        let edge = graph.get_edge(id)?;
+        let relation = relation_name(id).unwrap_or_else(|| "→".to_owned());
+        Some(format!("{} {relation} {}", link(edge.source)?, link(edge.target)?))
+    };
+
+    let mut notes = Vec::new();
+    for (id, node) in graph.nodes_iter() {
+        let Some(name) = names.get(&id) else {
+            continue;
+        };
+        let data = node.data();
+        let mut text = String::new();
+
+        let mut front = Vec::new();
+        if let Some(kind) = data
+            .kind
+            .zip(state.schema.as_ref())
+            .and_then(|(kind, schema)| schema.kinds.get(kind))
+        {
+            front.push(format!("kind: {}", yaml_quote(&kind.name)));
+        }
+        if !data.tags.is_empty() {
+            let tags: Vec<String> = data.tags.iter().map(|t| yaml_quote(t)).collect();
+            front.push(format!("tags: [{}]", tags.join(", ")));
+        }
+        if !data.subtitle.is_empty() {
+            front.push(format!("subtitle: {}", yaml_quote(&data.subtitle)));
+        }
+        for (key, value) in &data.properties {
+            let value = match value {
+                PropertyValue::Text(text) => yaml_quote(text),
+                PropertyValue::Number(number) => number.to_string(),
+                PropertyValue::Bool(flag) => flag.to_string(),
+                PropertyValue::Date(date) => date.to_string(),
+                PropertyValue::Reference(target) => match link(*target) {
+                    Some(link) => yaml_quote(&link),
+                    None => continue,
+                },
+            };
+            front.push(format!("{}: {value}", yaml_key(key)));
+        }
+        if !front.is_empty() {
+            text.push_str("---\n");
+            for line in front {
+                text.push_str(&line);
+                text.push('\n');
+            }
+            text.push_str("---\n");
+        }
+
+        text.push_str(&format!("# {}\n", data.name));
+        if !data.description.trim().is_empty() {
+            text.push('\n');
+            text.push_str(data.description.trim_end());
+            text.push('\n');
+        }
+
+        let mut relations = Vec::new();
+        for edge_id in graph.get_outgoing_edges(id) {
+            let Some(edge) = graph.get_edge(edge_id).filter(|e| included.contains(&e.id)) else {
+                continue;
+            };
+            let target = match link(edge.target) {
+                Some(target) => target,
+                None => match describe_edge(edge.target) {
+                    // Two links on one line: there for readers, skipped by the import
+                    Some(target) => {
+                        let relation = relation_name(edge_id).unwrap_or_else(|| "→".to_owned());
+                        relations.push(format!("- {relation}: “{target}”"));
+                        continue;
+                    }
+                    None => continue,
+                },
+            };
+            let mut line = match relation_name(edge_id) {
+                Some(relation) => format!("- {relation}:: {target}"),
+                None => format!("- {target}"),
+            };
+            if let Some(label) = edge.label.as_deref().filter(|l| !l.is_empty()) {
+                line.push_str(&format!(" — {label}"));
+            }
+            relations.push(line);
+        }
+        if !relations.is_empty() {
+            text.push_str(&format!("\n{RELATIONS_HEADING}\n"));
+            for line in relations {
+                text.push_str(&line);
+                text.push('\n');
+            }
+        }
+
+        let mut referenced = Vec::new();
+        let mut incoming: Vec<ID> = graph.get_incoming_edges(id);
+        // Relations attached to this node's own edges are mentioned here too
+        for edge_id in graph.get_outgoing_edges(id) {
+            incoming.extend(graph.get_incoming_edges(edge_id));
+        }
+        for edge_id in incoming {
+            let Some(edge) = graph.get_edge(edge_id).filter(|e| included.contains(&e.id)) else {
+                continue;
+            };
+            let Some(source) = link(edge.source).or_else(|| describe_edge(edge.source)) else {
+                continue;
+            };
+            let mut line = format!("- {source}");
+            match (relation_name(edge_id), edge.target == id) {
+                (Some(relation), true) => line.push_str(&format!(" ({relation})")),
+                (relation, false) => {
+                    let relation = relation.unwrap_or_else(|| "→".to_owned());
+                    line.push_str(&format!(" ({relation} “{}”)", describe_edge(edge.target).unwrap_or_default()));
+                }
+                (None, true) => {}
+            }
+            referenced.push(line);
+        }
+        if !referenced.is_empty() {
+            text.push_str(&format!("\n{REFERENCED_HEADING}\n"));
+            for line in referenced {
+                text.push_str(&line);
+                text.push('\n');
+            }
+        }
+
+        notes.push(Note {
+            name: name.clone(),
+            text,
+        });
+    }
+    notes
+}
+
+pub fn write_vault(dir: &Path, notes: &[Note]) -> io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    for note in notes {
+        std::fs::write(dir.join(format!("{}.md", note.name)), &note.text)?;
+    }
+    Ok(())
+}
+
+// Every .md file under `dir`, skipping hidden folders such as .obsidian
+pub fn read_vault(dir: &Path) -> io::Result<Vec<Note>> {
+    let mut notes = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let mut entries: Vec<_> = std::fs::read_dir(&dir)?.collect::<io::Result<_>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            let path = entry.path();
+            let hidden = entry.file_name().to_string_lossy().starts_with('.');
+            if entry.file_type()?.is_dir() {
+                if !hidden {
+                    pending.push(path);
+                }
+            } else if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("md")) {
+                let name = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+                notes.push(Note {
+                    name,
+                    text: std::fs::read_to_string(&path)?,
+                });
+            }
+        }
+    }
+    Ok(notes)
+}
+
+// A YAML scalar: (text, whether it was quoted)
+fn yaml_scalar(raw: &str) -> (String, bool) {
+    let raw = raw.trim();
+    if raw.starts_with('"') {
+        if let Ok(text) = serde_json::from_str::<String>(raw) {
+            return (text, true);
+        }
+    }
+    if let Some(inner) = raw.strip_prefix('\'').and_then(|r| r.strip_suffix('\'')) {
+        return (inner.replace("''", "'"), true);
+    }
+    (raw.to_owned(), false)
+}
+
+// Splits "[a, "b, c"]" into its items, minding quotes
+fn yaml_flow_list(inner: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut quote = None;
+    for c in inner.chars() {
+        match (quote, c) {
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                current.push(c);
+            }
+            (Some(q), c) if c == q => {
+                quote = None;
+                current.push(c);
+            }
+            (None, ',') => items.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    items.push(current);
+    items
+        .iter()
+        .map(|item| yaml_scalar(item).0)
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+enum YamlValue {
+    Scalar(String, bool),
+    List(Vec<String>),
+}
+
+// The simple key: value front matter notes use; nested structures are kept as text
+fn parse_front_matter(lines: &[&str]) -> Vec<(String, YamlValue)> {
+    let mut entries: Vec<(String, YamlValue)> = Vec::new();
+    for line in lines {
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        if let Some(item) = line.trim_start().strip_prefix("- ") {
+            if let Some((_, YamlValue::List(items))) = entries.last_mut() {
+                items.push(yaml_scalar(item).0);
+            }
+            continue;
+        }
+        let (key, value) = if let Some(rest) = line.strip_prefix('"') {
+            match rest.find('"') {
+                Some(end) => (yaml_scalar(&line[..end + 2]).0, rest[end + 1..].trim_start().strip_prefix(':')),
+                None => continue,
+            }
+        } else {
+            match line.split_once(':') {
+                Some((key, value)) => (key.trim().to_owned(), Some(value)),
+                None => continue,
+            }
+        };
+        let Some(value) = value else { continue };
+        let value = value.trim();
+        let value = if value.is_empty() {
+            YamlValue::List(Vec::new())
+        } else if let Some(inner) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+            YamlValue::List(yaml_flow_list(inner))
+        } else {
+            let (text, quoted) = yaml_scalar(value);
+            YamlValue::Scalar(text, quoted)
+        };
+        entries.push((key, value));
+    }
+    entries
+}
+
+// Target note of a link body like "Folder/Note#Heading|shown text"
+fn link_target(inner: &str) -> &str {
+    let inner = inner.split('|').next().unwrap_or_default();
+    let inner = inner.split(['#', '^']).next().unwrap_or_default();
+    inner.rsplit('/').next().unwrap_or_default().trim()
+}
+
+// Link targets in a line, leaving out embeds (![[image.png]])
+fn links(line: &str) -> Vec<(usize, String)> {
+    let mut found = Vec::new();
+    let mut rest = 0;
+    while let Some(start) = line[rest..].find("[[").map(|i| i + rest) {
+        let Some(end) = line[start..].find("]]").map(|i| i + start) else {
+            break;
+        };
+        if !line[..start].ends_with('!') {
+            let target = link_target(&line[start + 2..end]);
+            if !target.is_empty() {
+                found.push((start, target.to_owned()));
+            }
+        }
+        rest = end + 2;
+    }
+    found
+}
+
+fn inline_tags(line: &str) -> Vec<String> {
+    let mut tags = Vec::new();
+    for (i, _) in line.match_indices('#') {
+        if i > 0 && !line[..i].ends_with(char::is_whitespace) {
+            continue;
+        }
+        let tag: String = line[i + 1..]
+            .chars()
+            .take_while(|c| c.is_alphanumeric() || "_-/".contains(*c))
+            .collect();
+        // "#1" is not a tag in Obsidian
+        if tag.chars().any(|c| !c.is_numeric()) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+struct Link {
+    target: String,
+    relation: Option<String>,
+    label: Option<String>,
+}
+
+// A line's links: the one after "relation::" is typed, and in the relations section the text
+// after " — " is the edge's label
+fn line_links(line: &str, in_relations: bool) -> Vec<Link> {
+    let body = line.trim_start().trim_start_matches(['-', '*', '+']).trim_start();
+    let field = body
+        .split_once("::")
+        .map(|(key, value)| (key.trim(), value))
+        .filter(|(key, _)| !key.is_empty() && !key.contains("[["));
+    let (relation, value) = match field {
+        Some((key, value)) => (Some(key.to_owned()), value),
+        None => (None, body),
+    };
+    let mut found: Vec<Link> = links(value)
+        .into_iter()
+        .map(|(_, target)| Link {
+            target,
+            relation: relation.clone(),
+            label: None,
+        })
+        .collect();
+    // Generated lines have exactly one link; the rest (edge-to-edge relations) are for reading
+    if in_relations && found.len() != 1 {
+        return Vec::new();
+    }
+    if in_relations {
+        found[0].label = value
+            .split_once("]] — ")
+            .map(|(_, label)| label.trim().to_owned())
+            .filter(|label| !label.is_empty());
+    }
+    found
+}
+
+pub fn import(state: &mut GraphState, notes: &[Note]) -> ImportReport {
+    let mut warnings = Vec::new();
+    let mut node_records = Vec::new();
+    // lowercase note name or alias -> key of the note's node
+    let mut lookup: HashMap<String, String> = HashMap::new();
+    let mut note_links = Vec::new();
+
+    for note in notes {
+        let key = note.name.to_lowercase();
+        if lookup.get(&key) == Some(&key) {
+            warnings.push(format!("a second note named '{}' was skipped", note.name));
+            continue;
+        }
+        lookup.insert(key.clone(), key.clone());
+
+        let mut lines: Vec<&str> = note.text.lines().collect();
+        let mut record = NodeRecord {
+            key: key.clone(),
+            name: note.name.clone(),
+            ..Default::default()
+        };
+        let mut references = Vec::new();
+        if lines.first().map(|l| l.trim()) == Some("---") {
+            if let Some(end) = lines.iter().skip(1).position(|l| l.trim() == "---").map(|i| i + 1) {
+                for (field, value) in parse_front_matter(&lines[1..end]) {
+                    match (field.as_str(), value) {
+                        ("tags" | "tag", YamlValue::List(tags)) => record.tags.extend(tags),
+                        ("tags" | "tag", YamlValue::Scalar(tags, _)) => {
+                            record.tags.extend(tags.split([',', ' ']).filter(|t| !t.is_empty()).map(str::to_owned))
+                        }
+                        ("aliases" | "alias", YamlValue::List(aliases)) => {
+                            for alias in aliases {
+                                lookup.entry(alias.to_lowercase()).or_insert_with(|| key.clone());
+                            }
+                        }
+                        ("kind", YamlValue::Scalar(kind, _)) => record.kind = Some(kind).filter(|k| !k.is_empty()),
+                        ("subtitle", YamlValue::Scalar(subtitle, _)) => record.subtitle = subtitle,
+                        (_, YamlValue::List(items)) => {
+                            if !items.is_empty() {
+                                let text = PropertyValue::Text(items.join(", "));
+                                record.properties.insert(field, Value::Plain(text));
+                            }
+                        }
+                        (_, YamlValue::Scalar(text, quoted)) => {
+                            let link = links(&text).into_iter().next().filter(|(start, _)| *start == 0);
+                            if let Some((_, target)) = link.filter(|_| text.trim_end().ends_with("]]")) {
+                                references.push((field, target));
+                                continue;
+                            }
+                            let value = if quoted {
+                                PropertyValue::Text(text)
+                            } else if let Ok(number) = text.parse() {
+                                PropertyValue::Number(number)
+                            } else if let Some(date) = crate::graph::Date::parse(&text).filter(|_| text.len() >= 8) {
+                                PropertyValue::Date(date)
+                            } else {
+                                match text.as_str() {
+                                    "true" => PropertyValue::Bool(true),
+                                    "false" => PropertyValue::Bool(false),
+                                    _ => PropertyValue::Text(text),
+                                }
+                            };
+                            record.properties.insert(field, Value::Plain(value));
+                        }
+                    }
+                }
+                lines.drain(..=end);
+            }
+        }
+
+        // A leading "# Title" is the node's name
+        let first = lines.iter().position(|l| !l.trim().is_empty());
+        if let Some(title) = first.and_then(|i| lines[i].strip_prefix("# ").map(|t| (i, t))) {
+            record.name = title.1.trim().to_owned();
+            lines.drain(..=title.0);
+        }
+
+        let mut description = Vec::new();
+        let mut section = None;
+        for line in lines {
+            let trimmed = line.trim_end();
+            if trimmed == RELATIONS_HEADING || trimmed == REFERENCED_HEADING {
+                section = Some(trimmed);
+                continue;
+            }
+            if section.is_some() && line.starts_with('#') && line.trim_start_matches('#').starts_with(' ') {
+                section = None;
+            }
+            match section {
+                Some(REFERENCED_HEADING) => {}
+                Some(_) => note_links.extend(line_links(line, true).into_iter().map(|l| (key.clone(), l))),
+                None => {
+                    note_links.extend(line_links(line, false).into_iter().map(|l| (key.clone(), l)));
+                    record.tags.extend(inline_tags(line));
+                    description.push(line);
+                }
+            }
+        }
+        record.description = description.join("\n").trim().to_owned();
+        node_records.push((record, references));
+    }
+
+    let resolve = |target: &str| lookup.get(&target.to_lowercase()).cloned();
+    let mut nodes = Vec::new();
+    for (mut record, references) in node_records {
+        for (field, target) in references {
+            match resolve(&target) {
+                Some(key) => {
+                    record.properties.insert(field, Value::Reference(key));
+                }
+                None => warnings.push(format!("'{}': '{field}' links to unknown note '{target}'", record.name)),
+            }
+        }
+        nodes.push(record);
+    }
+
+    let mut seen = HashSet::new();
+    let mut edges = Vec::new();
+    for (source, link) in note_links {
+        let Some(target) = resolve(&link.target) else {
+            warnings.push(format!("'{source}' links to unknown note '{}'", link.target));
+            continue;
+        };
+        if target == source || !seen.insert((source.clone(), target.clone(), link.relation.clone())) {
+            continue;
+        }
+        edges.push(EdgeRecord {
+            key: format!("{source} -> {target} {}", edges.len()),
+            source: source.clone(),
+            target,
+            relation: link.relation,
+            label: link.label,
+            ..Default::default()
+        });
+    }
+
+    let mut report = records::build(state, nodes, edges);
+    warnings.append(&mut report.warnings);
+    report.warnings = warnings;
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::Date;
+    use crate::interchange::samples::{self, named};
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let samples::Sample { mut state, queen, city, rival, .. } = samples::world("Queen Ilsa");
+        state.graph.set_name(city, "Varen/holm").unwrap();
+        state.graph.set_description(queen, "Rules from the *high seat*.\n\nFeared by many.").unwrap();
+        state.graph.node_data_mut(queen).unwrap().subtitle = "of House Varen".into();
+        state.graph.add_tag(queen, "royalty").unwrap();
+        state.graph.set_property(queen, "age", PropertyValue::Number(42.0)).unwrap();
+        state.graph.set_property(queen, "motto: first", PropertyValue::Text("12".into())).unwrap();
+        state.graph.set_property(queen, "crowned", PropertyValue::Date(Date::new(1204, 5, 6))).unwrap();
+        state.graph.set_property(queen, "seat", PropertyValue::Reference(city)).unwrap();
+        let character = super::super::kind_by_name(&mut state, "Character");
+        state.graph.set_kind(queen, Some(character)).unwrap();
+        state.add_edge_between(rival, queen, None).unwrap();
+        // The rival's challenge of the rule is listed for readers on both pages, but not imported
+
+        let notes = export(&state, None);
+        assert_eq!(notes.len(), 3);
+        let queen_note = notes.iter().find(|n| n.name == "Queen Ilsa").unwrap();
+        assert!(queen_note.text.contains("- rules over:: [[Varen-holm]] — since 1204"));
+        assert!(queen_note.text.contains("## Referenced by\n- [[Duke Orm]]\n"));
+        assert!(notes.iter().any(|n| n.name == "Varen-holm"));
+
+        let mut imported = GraphState::new();
+        let report = import(&mut imported, &notes);
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+        let found = samples::check_round_trip(&imported, "Queen Ilsa", "Varen/holm");
+        let data = imported.graph.node_data(found.queen).unwrap();
+        assert_eq!(data.description, "Rules from the *high seat*.\n\nFeared by many.");
+        assert_eq!(data.subtitle, "of House Varen");
+        assert!(data.tags.contains("royalty"));
+        assert_eq!(data.properties["age"], PropertyValue::Number(42.0));
+        assert_eq!(data.properties["motto: first"], PropertyValue::Text("12".into()));
+        assert_eq!(data.properties["crowned"], PropertyValue::Date(Date::new(1204, 5, 6)));
+        assert_eq!(data.properties["seat"], PropertyValue::Reference(found.city));
+        assert_eq!(data.kind, imported.schema.as_ref().unwrap().find_kind("Character"));
+
+        // Only the plain link to the queen comes back
+        let links = imported.graph.get_outgoing_edges(found.rival);
+        assert_eq!(links.len(), 1);
+        let link = imported.graph.get_edge(links[0]).unwrap();
+        assert_eq!((link.target, link.relation), (found.queen, None));
+    }
+
+    #[test]
+    fn test_import_obsidian_vault() {
+        let notes = [
+            Note {
+                name: "Varenholm".into(),
+                text: "---\naliases:\n  - The Capital\ntags: [city, coast]\npopulation: 12000\n---\n\
+                       A port ruled by [[Queen Ilsa|the queen]]. #trade ![[map.png]]\n\
+                       Trades with:: [[folder/Oskfjord#Harbor]]\n"
+                    .into(),
+            },
+            Note {
+                name: "Queen Ilsa".into(),
+                text: "Lives in [[the capital]], visits [[Varenholm]] and [[Atlantis]].\n".into(),
+            },
+            Note {
+                name: "Oskfjord".into(),
+                text: String::new(),
+            },
+        ];
+        let mut state = GraphState::new();
+        let report = import(&mut state, &notes);
+        assert_eq!(report.warnings.len(), 1, "{:?}", report.warnings);
+        assert!(report.warnings[0].contains("Atlantis"));
+
+        let city = named(&state, "Varenholm");
+        let queen = named(&state, "Queen Ilsa");
+        let harbor = named(&state, "Oskfjord");
+        let data = state.graph.node_data(city).unwrap();
+        assert_eq!(data.tags, BTreeSet::from(["city".into(), "coast".into(), "trade".into()]));
+        assert_eq!(data.properties["population"], PropertyValue::Number(12000.0));
+
+        // Alias and plain link to the same note make one edge
+        assert_eq!(state.graph.get_outgoing_edges(queen).len(), 1);
+        let trades = state.relations.find_by_name("Trades with").unwrap();
+        let outgoing = state.graph.get_outgoing_edges_of_type(city, trades);
+        assert_eq!(state.graph.get_edge(outgoing[0]).unwrap().target, harbor);
+        assert_eq!(state.graph.get_outgoing_edges(city).len(), 2);
+    }
+}
@@ -0,0 +1,573 @@
+// Format-neutral elements that the file formats read into and write from.
+//
+// Every format here only connects nodes, so an edge that other edges attach to travels as a
+// "relay" node with the edge's key, plus two half edges marked with their `part`:
+// the tail half runs from the edge's source to the relay, the head half from the relay to its target.
+// Other edges attach to the relay like to any node. Importing joins the halves back into one edge
+use eframe::egui::{Color32, Pos2};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use super::{color_from_hex, export_set, kind_by_name, place_unpositioned, relation_by_name, ImportReport};
+use crate::graph::{Date, Graph, NodeData, PropertyValue, ID};
+use crate::state::GraphState;
+use crate::style::NodeStyle;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Part {
+    Tail,
+    Head,
+}
+
+impl Part {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Part::Tail => "tail",
+            Part::Head => "head",
+        }
+    }
+
+    pub(crate) fn parse(text: &str) -> Option<Self> {
+        match text.trim() {
+            "tail" => Some(Part::Tail),
+            "head" => Some(Part::Head),
+            _ => None,
+        }
+    }
+}
+
+// A property value with references spelled as the key of the element they point at
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Value {
+    Plain(PropertyValue),
+    Reference(String),
+}
+
+// Property types as the formats store them. Tags are also the prefixes of attribute ids,
+// which is how dates and references survive formats that only know strings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ValueType {
+    Text,
+    Number,
+    Bool,
+    Date,
+    Reference,
+}
+
+impl ValueType {
+    pub(crate) const ALL: [ValueType; 5] = [
+        ValueType::Text,
+        ValueType::Number,
+        ValueType::Bool,
+        ValueType::Date,
+        ValueType::Reference,
+    ];
+
+    pub(crate) fn tag(self) -> &'static str {
+        match self {
+            ValueType::Text => "text",
+            ValueType::Number => "number",
+            ValueType::Bool => "bool",
+            ValueType::Date => "date",
+            ValueType::Reference => "ref",
+        }
+    }
+
+    // Our own type from an attribute id like "date3"
+    pub(crate) fn from_id(id: &str) -> Option<Self> {
+        let tag = id.trim_end_matches(|c: char| c.is_ascii_digit());
+        if tag.len() == id.len() {
+            return None;
+        }
+        ValueType::ALL.into_iter().find(|t| t.tag() == tag)
+    }
+
+    // Type name shared by GraphML and GEXF; dates and references are written as strings
+    pub(crate) fn xml_type(self) -> &'static str {
+        match self {
+            ValueType::Number => "double",
+            ValueType::Bool => "boolean",
+            ValueType::Text | ValueType::Date | ValueType::Reference => "string",
+        }
+    }
+
+    pub(crate) fn from_xml_type(name: &str) -> Self {
+        match name.to_ascii_lowercase().as_str() {
+            "double" | "float" | "int" | "integer" | "long" => ValueType::Number,
+            "boolean" => ValueType::Bool,
+            "date" => ValueType::Date,
+            _ => ValueType::Text,
+        }
+    }
+
+    pub(crate) fn of(value: &Value) -> Self {
+        match value {
+            Value::Plain(PropertyValue::Text(_)) => ValueType::Text,
+            Value::Plain(PropertyValue::Number(_)) => ValueType::Number,
+            Value::Plain(PropertyValue::Bool(_)) => ValueType::Bool,
+            Value::Plain(PropertyValue::Date(_)) => ValueType::Date,
+            Value::Plain(PropertyValue::Reference(_)) | Value::Reference(_) => ValueType::Reference,
+        }
+    }
+
+    pub(crate) fn parse(self, text: &str) -> Option<Value> {
+        let text = text.trim();
+        let value = match self {
+            ValueType::Text => return Some(Value::Plain(PropertyValue::Text(text.to_owned()))),
+            ValueType::Number => PropertyValue::Number(text.parse().ok()?),
+            ValueType::Bool => match text.to_ascii_lowercase().as_str() {
+                "true" | "yes" | "1" => PropertyValue::Bool(true),
+                "false" | "no" | "0" => PropertyValue::Bool(false),
+                _ => return None,
+            },
+            ValueType::Date => PropertyValue::Date(Date::parse(text)?),
+            ValueType::Reference => return Some(Value::Reference(text.to_owned())),
+        };
+        Some(Value::Plain(value))
+    }
+}
+
+pub(crate) fn format_value(value: &Value) -> String {
+    match value {
+        Value::Plain(PropertyValue::Text(text)) => text.clone(),
+        Value::Plain(PropertyValue::Number(number)) => number.to_string(),
+        Value::Plain(PropertyValue::Bool(flag)) => flag.to_string(),
+        Value::Plain(PropertyValue::Date(date)) => date.to_string(),
+        // Only exported values whose target was written, see `collect`
+        Value::Plain(PropertyValue::Reference(_)) => String::new(),
+        Value::Reference(key) => key.clone(),
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct NodeRecord {
+    pub key: String,
+    pub name: String,
+    pub subtitle: String,
+    pub description: String,
+    pub tags: BTreeSet<String>,
+    pub kind: Option<String>,
+    pub properties: BTreeMap<String, Value>,
+    pub position: Option<Pos2>,
+    // Only DOT carries styles
+    pub style: NodeStyle,
+    // Stands for the edge with the same key, see the module comment
+    pub relay: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct EdgeRecord {
+    pub key: String,
+    pub source: String,
+    pub target: String,
+    pub relation: Option<String>,
+    // Used when the import has to create the relation type
+    pub color: Option<Color32>,
+    pub label: Option<String>,
+    pub part: Option<Part>,
+    // Halves leave these to their relay node
+    pub properties: BTreeMap<String, Value>,
+}
+
+// A property column: one per name and type found among the exported nodes, and among the edges
+#[derive(Debug, Clone)]
+pub(crate) struct Column {
+    pub id: String,
+    pub name: String,
+    pub value_type: ValueType,
+    pub for_edges: bool,
+}
+
+pub(crate) struct Records {
+    pub nodes: Vec<NodeRecord>,
+    pub edges: Vec<EdgeRecord>,
+    pub columns: Vec<Column>,
+}
+
+impl Records {
+    pub(crate) fn column(&self, name: &str, value: &Value, for_edges: bool) -> Option<&Column> {
+        let value_type = ValueType::of(value);
+        self.columns
+            .iter()
+            .find(|c| c.name == name && c.value_type == value_type && c.for_edges == for_edges)
+    }
+}
+
+// The world (or `only` and the relations among it) as records. Keys are "n<i>" for nodes and
+// "e<i>" for edges; references to elements that aren't exported are left out
+pub(crate) fn collect(state: &GraphState, only: Option<&BTreeSet<ID>>) -> Records {
+    let graph = &state.graph;
+    let included = export_set(graph, only);
+    let keys = element_keys(graph, &included);
+    let properties_of = |id: ID| properties_with_keys(graph, id, &keys);
+
+    let mut relays = BTreeSet::new();
+    for edge in graph.edges_iter().filter(|e| included.contains(&e.id)) {
+        for end in [edge.source, edge.target] {
+            if graph.get_edge(end).is_some() {
+                relays.insert(end);
+            }
+        }
+    }
+
+    let mut nodes = Vec::new();
+    for (id, node) in graph.nodes_iter() {
+        let is_edge = graph.get_edge(id).is_some();
+        if !included.contains(&id) || (is_edge && !relays.contains(&id)) {
+            continue;
+        }
+        let data = node.data();
+        let kind = state
+            .schema
+            .as_ref()
+            .zip(data.kind)
+            .and_then(|(schema, kind)| schema.kinds.get(kind))
+            .map(|kind| kind.name.clone());
+        nodes.push(NodeRecord {
+            key: keys[&id].clone(),
+            name: data.name.clone(),
+            subtitle: data.subtitle.clone(),
+            description: data.description.clone(),
+            tags: data.tags.clone(),
+            kind,
+            properties: properties_of(id),
+            position: state.positions.get(id).copied(),
+            style: data.style.clone(),
+            relay: is_edge,
+        });
+    }
+
+    let mut edges = Vec::new();
+    for edge in graph.edges_iter().filter(|e| included.contains(&e.id)) {
+        let relation = edge.relation.and_then(|r| state.relations.get(r));
+        let record = EdgeRecord {
+            key: keys[&edge.id].clone(),
+            source: keys[&edge.source].clone(),
+            target: keys[&edge.target].clone(),
+            relation: relation.map(|r| r.name.clone()),
+            color: relation.map(|r| r.color),
+            label: edge.label.clone(),
+            part: None,
+            properties: BTreeMap::new(),
+        };
+        if relays.contains(&edge.id) {
+            let relay = record.key.clone();
+            edges.push(EdgeRecord {
+                key: format!("{relay}.tail"),
+                target: relay.clone(),
+                part: Some(Part::Tail),
+                ..record.clone()
+            });
+            edges.push(EdgeRecord {
+                key: format!("{relay}.head"),
+                source: relay,
+                part: Some(Part::Head),
+                ..record
+            });
+        } else {
+            edges.push(EdgeRecord {
+                properties: properties_of(edge.id),
+                ..record
+            });
+        }
+    }
+
+    let mut column_types: BTreeSet<(bool, String, ValueType)> = BTreeSet::new();
+    let node_properties = nodes.iter().map(|n| (false, &n.properties));
+    for (for_edges, properties) in node_properties.chain(edges.iter().map(|e| (true, &e.properties))) {
+        for (name, value) in properties {
+            column_types.insert((for_edges, name.clone(), ValueType::of(value)));
+        }
+    }
+    let columns = column_types
+        .into_iter()
+        .enumerate()
+        .map(|(i, (for_edges, name, value_type))| Column {
+            id: format!("{}{i}", value_type.tag()),
+            name,
+            value_type,
+            for_edges,
+        })
+        .collect();
+
+    Records { nodes, edges, columns }
+}
+
+// "n<i>" for nodes and "e<i>" for edges, numbered in graph order
+pub(crate) fn element_keys(graph: &Graph, included: &BTreeSet<ID>) -> HashMap<ID, String> {
+    graph
+        .nodes_iter()
+        .filter(|(id, _)| included.contains(id))
+        .enumerate()
+        .map(|(i, (id, _))| {
+            let prefix = if graph.get_edge(id).is_some() { "e" } else { "n" };
+            (id, format!("{prefix}{i}"))
+        })
+        .collect()
+}
+
+// An element's properties with references spelled as keys; references to elements without
+// a key are left out
+pub(crate) fn properties_with_keys(graph: &Graph, id: ID, keys: &HashMap<ID, String>) -> BTreeMap<String, Value> {
+    let Some(data) = graph.node_data(id) else {
+        return BTreeMap::new();
+    };
+    data.properties
+        .iter()
+        .filter_map(|(name, value)| {
+            let value = match value {
+                PropertyValue::Reference(target) => Value::Reference(keys.get(target)?.clone()),
+                other => Value::Plain(other.clone()),
+            };
+            Some((name.clone(), value))
+        })
+        .collect()
+}
+
+// An edge of the world about to be created: a plain record, or the two halves around a relay
+struct PendingEdge {
+    source: String,
+    target: String,
+    relation: Option<String>,
+    color: Option<Color32>,
+    label: Option<String>,
+    properties: BTreeMap<String, Value>,
+    // Key other edges use to attach to this one
+    key: String,
+}
+
+// Adds the records to the document: nodes, then edges as their endpoints appear, then references.
+// Nodes without a position are placed automatically
+pub(crate) fn build(state: &mut GraphState, nodes: Vec<NodeRecord>, edges: Vec<EdgeRecord>) -> ImportReport {
+    let mut report = ImportReport::default();
+    let relays: BTreeMap<String, &NodeRecord> = nodes.iter().filter(|n| n.relay).map(|n| (n.key.clone(), n)).collect();
+
+    let mut pending = Vec::new();
+    let mut halves: HashMap<String, (Option<EdgeRecord>, Option<EdgeRecord>)> = HashMap::new();
+    for edge in edges {
+        let relay = match edge.part {
+            Some(Part::Tail) if relays.contains_key(&edge.target) => edge.target.clone(),
+            Some(Part::Head) if relays.contains_key(&edge.source) => edge.source.clone(),
+            _ => {
+                pending.push(PendingEdge {
+                    key: edge.key,
+                    source: edge.source,
+                    target: edge.target,
+                    relation: edge.relation,
+                    color: edge.color,
+                    label: edge.label,
+                    properties: edge.properties,
+                });
+                continue;
+            }
+        };
+        let entry = halves.entry(relay).or_default();
+        match edge.part {
+            Some(Part::Tail) => entry.0 = Some(edge),
+            _ => entry.1 = Some(edge),
+        }
+    }
+    for (relay, node) in &relays {
+        let Some((Some(tail), Some(head))) = halves.remove(relay) else {
+            report.warnings.push(format!("edge '{relay}' is missing one of its halves"));
+            continue;
+        };
+        pending.push(PendingEdge {
+            key: relay.clone(),
+            source: tail.source,
+            target: head.target,
+            relation: head.relation.or(tail.relation),
+            color: head.color.or(tail.color),
+            label: head.label.or(tail.label),
+            properties: node.properties.clone(),
+        });
+    }
+
+    let mut ids: HashMap<String, ID> = HashMap::new();
+    // (element key, property, key of the element it refers to), set once everything exists
+    let mut references = Vec::new();
+    let mut split = |key: &str, properties: &BTreeMap<String, Value>| -> BTreeMap<String, PropertyValue> {
+        let mut plain = BTreeMap::new();
+        for (name, value) in properties {
+            match value {
+                Value::Plain(value) => {
+                    plain.insert(name.clone(), value.clone());
+                }
+                Value::Reference(target) => references.push((key.to_owned(), name.clone(), target.clone())),
+            }
+        }
+        plain
+    };
+    for node in nodes.iter().filter(|n| !n.relay) {
+        if ids.contains_key(&node.key) {
+            report.warnings.push(format!("duplicate node '{}' skipped", node.key));
+            continue;
+        }
+        let mut data = NodeData::named(node.name.as_str());
+        data.subtitle = node.subtitle.clone();
+        data.description = node.description.clone();
+        data.tags = node.tags.clone();
+        data.kind = node.kind.as_deref().map(|kind| kind_by_name(state, kind));
+        data.properties = split(&node.key, &node.properties);
+        data.style = node.style.clone();
+        let id = state.graph.add_node(data);
+        if let Some(pos) = node.position {
+            state.positions.insert(id, pos);
+        }
+        ids.insert(node.key.clone(), id);
+        report.added.push(id);
+    }
+
+    // Edges can hang off edges, so keep adding whichever have both ends in place
+    loop {
+        let before = pending.len();
+        pending.retain(|edge| {
+            let (Some(&source), Some(&target)) = (ids.get(&edge.source), ids.get(&edge.target)) else {
+                return true;
+            };
+            let relation = edge
+                .relation
+                .as_deref()
+                .filter(|name| !name.is_empty())
+                .map(|name| relation_by_name(state, name, edge.color));
+            match state.add_edge_between(source, target, relation) {
+                Ok(id) => {
+                    // Freshly created, so it exists
+                    let _ = state.graph.set_edge_label(id, edge.label.clone().filter(|l| !l.is_empty()));
+                    if let Some(data) = state.graph.node_data_mut(id) {
+                        data.properties = split(&edge.key, &edge.properties);
+                    }
+                    ids.insert(edge.key.clone(), id);
+                    report.added.push(id);
+                }
+                Err(error) => report
+                    .warnings
+                    .push(format!("skipped {} -> {}: {error}", edge.source, edge.target)),
+            }
+            false
+        });
+        if pending.is_empty() || pending.len() == before {
+            break;
+        }
+    }
+    for edge in pending {
+        let missing = if ids.contains_key(&edge.source) { &edge.target } else { &edge.source };
+        report
+            .warnings
+            .push(format!("skipped {} -> {}: unknown element '{missing}'", edge.source, edge.target));
+    }
+
+    for (node, name, key) in references {
+        match ids.get(&key) {
+            Some(&target) => {
+                let _ = state.graph.set_property(ids[&node], name, PropertyValue::Reference(target));
+            }
+            None => report
+                .warnings
+                .push(format!("'{node}': property '{name}' refers to unknown element '{key}'")),
+        }
+    }
+
+    place_unpositioned(state, &report.added);
+    report
+}
+
+// What a GraphML key or a GEXF attribute column holds
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Field {
+    Name,
+    Subtitle,
+    Description,
+    Tags,
+    Kind,
+    X,
+    Y,
+    Element,
+    Relation,
+    Label,
+    Color,
+    Part,
+    Property(String, ValueType),
+}
+
+impl Field {
+    // Property columns we wrote are recognized by their id. Everything else by its name, then
+    // its id, so files from other tools map "name" or "weight" sensibly
+    pub(crate) fn classify(id: &str, name: &str, xml_type: &str, for_edges: bool) -> Field {
+        if let Some(value_type) = ValueType::from_id(id) {
+            return Field::Property(name.to_owned(), value_type);
+        }
+        let reserved = |text: &str| {
+            let field = match (for_edges, text.to_ascii_lowercase().as_str()) {
+                (false, "label" | "name") => Field::Name,
+                (false, "subtitle") => Field::Subtitle,
+                (false, "description") => Field::Description,
+                (false, "tags") => Field::Tags,
+                (false, "kind" | "type") => Field::Kind,
+                (false, "x") => Field::X,
+                (false, "y") => Field::Y,
+                (false, "element") => Field::Element,
+                (true, "relation" | "kind" | "type") => Field::Relation,
+                (true, "label" | "edge_label") => Field::Label,
+                (true, "color") => Field::Color,
+                (true, "part") => Field::Part,
+                _ => return None,
+            };
+            Some(field)
+        };
+        if let Some(field) = reserved(name).or_else(|| reserved(id)) {
+            return field;
+        }
+        let name = if name.is_empty() { id } else { name };
+        Field::Property(name.to_owned(), ValueType::from_xml_type(xml_type))
+    }
+}
+
+impl NodeRecord {
+    pub(crate) fn set(&mut self, field: &Field, text: &str) -> Result<(), String> {
+        let coordinate = || text.trim().parse::<f32>().map_err(|_| format!("'{text}' is not a coordinate"));
+        match field {
+            Field::Name => self.name = text.to_owned(),
+            Field::Subtitle => self.subtitle = text.to_owned(),
+            Field::Description => self.description = text.to_owned(),
+            Field::Tags => {
+                self.tags = text
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|tag| !tag.is_empty())
+                    .map(str::to_owned)
+                    .collect();
+            }
+            Field::Kind => self.kind = Some(text.trim().to_owned()).filter(|kind| !kind.is_empty()),
+            Field::X => self.position.get_or_insert(Pos2::ZERO).x = coordinate()?,
+            Field::Y => self.position.get_or_insert(Pos2::ZERO).y = coordinate()?,
+            Field::Element => self.relay = text.trim() == "edge",
+            Field::Property(name, value_type) => {
+                let value = value_type
+                    .parse(text)
+                    .ok_or_else(|| format!("'{text}' is not a valid {} for '{name}'", value_type.tag()))?;
+                self.properties.insert(name.clone(), value);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl EdgeRecord {
+    pub(crate) fn set(&mut self, field: &Field, text: &str) -> Result<(), String> {
+        match field {
+            Field::Relation => self.relation = Some(text.to_owned()),
+            Field::Label => self.label = Some(text.to_owned()),
+            Field::Color => self.color = Some(color_from_hex(text).ok_or_else(|| format!("'{text}' is not a color"))?),
+            Field::Part => self.part = Some(Part::parse(text).ok_or_else(|| format!("'{text}' is not an edge part"))?),
+            Field::Property(name, value_type) => {
+                let value = value_type
+                    .parse(text)
+                    .ok_or_else(|| format!("'{text}' is not a valid {} for '{name}'", value_type.tag()))?;
+                self.properties.insert(name.clone(), value);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
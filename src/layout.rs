@@ -0,0 +1,59 @@
+// layout.rs
+// Automatic placement of nodes. Layouts only ever move plain nodes: edge-nodes keep their
+// place relative to their endpoints and follow them through `update_positions_from`
+use eframe::egui::{Pos2, Rect};
+use std::collections::BTreeSet;
+
+use crate::graph::ID;
+use crate::state::GraphState;
+
+pub mod force;
+pub mod layered;
+pub mod radial;
+
+// Plain nodes a layout arranges: placed and inside `only` (everything when None), pinned or not
+pub fn plain_nodes(state: &GraphState, only: Option<&BTreeSet<ID>>) -> Vec<ID> {
+    state
+        .graph
+        .nodes_iter()
+        .map(|(id, _)| id)
+        .filter(|&id| state.graph.get_edge(id).is_none() && state.positions.contains_key(id))
+        .filter(|id| only.is_none_or(|only| only.contains(id)))
+        .collect()
+}
+
+// The plain nodes a layout may actually move: the unpinned ones
+pub fn movable_nodes(state: &GraphState, only: Option<&BTreeSet<ID>>) -> Vec<ID> {
+    plain_nodes(state, only)
+        .into_iter()
+        .filter(|&id| !state.is_pinned(id))
+        .collect()
+}
+
+// Put nodes at the given positions, edge-nodes following
+pub fn apply(state: &mut GraphState, positions: Vec<(ID, Pos2)>) {
+    for &(id, pos) in &positions {
+        state.positions.insert(id, pos);
+    }
+    state.update_positions_from(positions.into_iter().map(|(id, _)| id));
+}
+
+// Move computed positions (in the layout's own coordinates) to where the nodes already are:
+// onto the first pinned node if there is one, otherwise centered on the nodes' current bounds.
+// Pinned nodes are then dropped, since they stay put
+pub(crate) fn anchor(state: &GraphState, computed: Vec<(ID, Pos2)>) -> Vec<(ID, Pos2)> {
+    let pinned = computed
+        .iter()
+        .filter(|&&(id, _)| state.is_pinned(id))
+        .find_map(|&(id, pos)| Some(*state.positions.get(id)? - pos));
+    let shift = pinned.unwrap_or_else(|| {
+        let current: Vec<Pos2> = computed.iter().filter_map(|&(id, _)| state.positions.get(id).copied()).collect();
+        let new: Vec<Pos2> = computed.iter().map(|&(_, pos)| pos).collect();
+        Rect::from_points(&current).center() - Rect::from_points(&new).center()
+    });
+    computed
+        .into_iter()
+        .filter(|&(id, _)| !state.is_pinned(id))
+        .map(|(id, pos)| (id, pos + shift))
+        .collect()
+}
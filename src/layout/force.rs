@@ -0,0 +1,304 @@
+// Force-directed layout: edges pull their endpoints together like springs, nodes close to each
+// other push apart, and a weak pull toward the middle keeps separate components from drifting off.
+// Each step moves a node at most `temperature`, which cools until the layout settles.
+// Nodes only push within REPULSION_RANGE spring lengths, found through a grid of that size, so a
+// step costs about O(n) rather than O(n²)
+use eframe::egui::{Pos2, Vec2};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use super::{movable_nodes, plain_nodes};
+use crate::graph::ID;
+use crate::state::GraphState;
+
+// Farthest two nodes push each other, in spring lengths
+const REPULSION_RANGE: f32 = 3.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ForceLayout {
+    // Length an edge settles at with nothing else around
+    pub spring_length: f32,
+    pub spring_strength: f32,
+    pub repulsion: f32,
+    pub gravity: f32,
+    // Largest distance a node moves in the first step
+    pub initial_temperature: f32,
+    // Factor the temperature is multiplied by after each step
+    pub cooling: f32,
+    // The simulation is settled once the temperature or the largest move drops below this
+    pub min_temperature: f32,
+}
+
+impl Default for ForceLayout {
+    fn default() -> Self {
+        Self {
+            spring_length: 120.0,
+            spring_strength: 1.0,
+            repulsion: 1.0,
+            gravity: 0.02,
+            initial_temperature: 60.0,
+            cooling: 0.95,
+            min_temperature: 0.5,
+        }
+    }
+}
+
+// A force layout in progress, advanced one step at a time (e.g. once per frame)
+pub struct ForceSimulation {
+    layout: ForceLayout,
+    movable: Vec<ID>,
+    // Nodes that take part: the movable ones, pinned ones in scope and the neighbours of both
+    bodies: Vec<ID>,
+    // Edges that pull on a body
+    springs: Vec<ID>,
+    temperature: f32,
+    settled: bool,
+}
+
+impl ForceSimulation {
+    // Lay out `only` (everything when None); their neighbours still push and pull but stay put
+    pub fn new(state: &GraphState, layout: ForceLayout, only: Option<&BTreeSet<ID>>) -> Self {
+        let movable = movable_nodes(state, only);
+        let mut bodies = plain_nodes(state, only);
+        let springs: Vec<ID> = match only {
+            None => state.graph.edges_iter().map(|edge| edge.id).collect(),
+            Some(_) => {
+                let mut springs = BTreeSet::new();
+                let mut stack: Vec<ID> = bodies.clone();
+                // Edges attached to edges pull on those edges' ends too
+                while let Some(id) = stack.pop() {
+                    for edge in state.graph.get_outgoing_edges(id).into_iter().chain(state.graph.get_incoming_edges(id)) {
+                        if springs.insert(edge) {
+                            stack.push(edge);
+                        }
+                    }
+                }
+                let mut neighbours = HashSet::new();
+                for &edge in &springs {
+                    plain_ends(state, edge, &mut neighbours);
+                }
+                let in_scope: HashSet<ID> = bodies.iter().copied().collect();
+                bodies.extend(neighbours.into_iter().filter(|id| !in_scope.contains(id)));
+                springs.into_iter().collect()
+            }
+        };
+        Self {
+            layout,
+            settled: movable.is_empty(),
+            movable,
+            bodies,
+            springs,
+            temperature: layout.initial_temperature,
+        }
+    }
+
+    // The nodes this simulation moves
+    pub fn movable(&self) -> &[ID] {
+        &self.movable
+    }
+
+    pub fn is_settled(&self) -> bool {
+        self.settled
+    }
+
+    // Advance one step. Returns the largest distance a node moved
+    pub fn step(&mut self, state: &mut GraphState) -> f32 {
+        if self.settled {
+            return 0.0;
+        }
+        let forces = self.forces(state);
+        let mut largest: f32 = 0.0;
+        for &id in &self.movable {
+            let (Some(&force), Some(&pos)) = (forces.get(&id), state.positions.get(id)) else {
+                continue;
+            };
+            let length = force.length();
+            if length < f32::EPSILON {
+                continue;
+            }
+            let distance = length.min(self.temperature);
+            state.positions.insert(id, pos + force / length * distance);
+            largest = largest.max(distance);
+        }
+        state.update_positions_from(self.movable.iter().copied());
+
+        self.temperature *= self.layout.cooling;
+        let min = self.layout.min_temperature;
+        self.settled = self.temperature < min || largest < min;
+        largest
+    }
+
+    // Step until settled or `max_steps` is reached. Returns how many steps were taken
+    pub fn run(&mut self, state: &mut GraphState, max_steps: usize) -> usize {
+        let mut steps = 0;
+        while !self.settled && steps < max_steps {
+            self.step(state);
+            steps += 1;
+        }
+        steps
+    }
+
+    // Net force on every body
+    fn forces(&self, state: &GraphState) -> HashMap<ID, Vec2> {
+        let layout = &self.layout;
+        let k = layout.spring_length;
+        let bodies: Vec<(ID, Pos2)> = self
+            .bodies
+            .iter()
+            .filter_map(|&id| Some((id, *state.positions.get(id)?)))
+            .collect();
+        let mut forces: HashMap<ID, Vec2> = bodies.iter().map(|&(id, _)| (id, Vec2::ZERO)).collect();
+
+        // Each node only meets the others in its own and the neighbouring cells
+        let range = k * REPULSION_RANGE;
+        let cell_of = |p: Pos2| ((p.x / range).floor() as i32, (p.y / range).floor() as i32);
+        let mut grid: HashMap<(i32, i32), Vec<usize>> = HashMap::new();
+        for (i, &(_, pos)) in bodies.iter().enumerate() {
+            grid.entry(cell_of(pos)).or_default().push(i);
+        }
+        for (i, &(a, pa)) in bodies.iter().enumerate() {
+            let (cx, cy) = cell_of(pa);
+            for cell in (cx - 1..=cx + 1).flat_map(|x| (cy - 1..=cy + 1).map(move |y| (x, y))) {
+                for &j in grid.get(&cell).into_iter().flatten().filter(|&&j| j > i) {
+                    let (b, pb) = bodies[j];
+                    let (direction, distance) = separation(pa, pb, i + j);
+                    if distance > range {
+                        continue;
+                    }
+                    let push = direction * (layout.repulsion * k * k / distance.max(1.0));
+                    *forces.entry(a).or_default() += push;
+                    *forces.entry(b).or_default() -= push;
+                }
+            }
+        }
+
+        for edge in self.springs.iter().filter_map(|&id| state.graph.get_edge(id)) {
+            let (Some(&ps), Some(&pt)) = (state.positions.get(edge.source), state.positions.get(edge.target)) else {
+                continue;
+            };
+            let delta = pt - ps;
+            let pull = delta * (layout.spring_strength * delta.length() / k);
+            distribute(state, &mut forces, edge.source, pull);
+            distribute(state, &mut forces, edge.target, -pull);
+        }
+
+        if !bodies.is_empty() {
+            let center = bodies.iter().fold(Vec2::ZERO, |sum, (_, p)| sum + p.to_vec2()) / bodies.len() as f32;
+            for &(id, pos) in &bodies {
+                *forces.entry(id).or_default() += (center - pos.to_vec2()) * layout.gravity;
+            }
+        }
+        forces
+    }
+}
+
+// Lay out `only` (everything when None) in one go, as if the simulation ran to the end
+pub fn run(state: &mut GraphState, layout: ForceLayout, only: Option<&BTreeSet<ID>>) {
+    // Cooling alone bounds the step count; the cap only guards against odd parameters
+    ForceSimulation::new(state, layout, only).run(state, 10_000);
+}
+
+// Unit vector from b to a and the distance between them. Nodes on top of each other
+// are pushed apart in a direction picked from `seed`, so they don't stay stuck together
+fn separation(a: Pos2, b: Pos2, seed: usize) -> (Vec2, f32) {
+    let delta = a - b;
+    let distance = delta.length();
+    if distance > 0.01 {
+        return (delta / distance, distance);
+    }
+    let angle = seed as f32 * 2.399_963; // golden angle
+    (Vec2::angled(angle), 0.01)
+}
+
+// The plain nodes an edge hangs from, following edges attached to edges down to their ends
+fn plain_ends(state: &GraphState, id: ID, into: &mut HashSet<ID>) {
+    match state.graph.get_edge(id) {
+        Some(edge) => {
+            plain_ends(state, edge.source, into);
+            plain_ends(state, edge.target, into);
+        }
+        None => {
+            if state.positions.contains_key(id) {
+                into.insert(id);
+            }
+        }
+    }
+}
+
+// An edge-node can't move by itself, so a force on it is shared between its endpoints
+fn distribute(state: &GraphState, forces: &mut HashMap<ID, Vec2>, id: ID, force: Vec2) {
+    match state.graph.get_edge(id) {
+        Some(edge) => {
+            distribute(state, forces, edge.source, force * 0.5);
+            distribute(state, forces, edge.target, force * 0.5);
+        }
+        None => {
+            if let Some(total) = forces.get_mut(&id) {
+                *total += force;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spring_settles_near_rest_length() {
+        let mut state = GraphState::new();
+        let a = state.add_node_at(Pos2::new(0.0, 0.0));
+        let b = state.add_node_at(Pos2::new(1000.0, 0.0));
+        let ab = state.add_edge_between(a, b, None).unwrap();
+        let layout = ForceLayout { gravity: 0.0, ..ForceLayout::default() };
+
+        run(&mut state, layout, None);
+        let distance = state.positions[a].distance(state.positions[b]);
+        assert!((distance - layout.spring_length).abs() < 10.0, "{distance}");
+        // The edge-node followed its endpoints
+        let mid = ((state.positions[a].to_vec2() + state.positions[b].to_vec2()) * 0.5).to_pos2();
+        assert!(state.positions[ab].distance(mid) < 1e-3);
+    }
+
+    #[test]
+    fn test_pinned_and_excluded_nodes_stay() {
+        let mut state = GraphState::new();
+        let a = state.add_node_at(Pos2::new(0.0, 0.0));
+        let b = state.add_node_at(Pos2::new(0.0, 0.0));
+        let c = state.add_node_at(Pos2::new(5.0, 0.0));
+        let far = state.add_node_at(Pos2::new(900.0, 0.0));
+        let b_far = state.add_edge_between(b, far, None).unwrap();
+        state.add_edge_between(c, b_far, None).unwrap();
+        state.set_pinned(a, true).unwrap();
+
+        let mut simulation = ForceSimulation::new(&state, ForceLayout::default(), Some(&BTreeSet::from([a, b, c])));
+        assert_eq!(simulation.movable(), &[b, c]);
+        let steps = simulation.run(&mut state, 1000);
+        assert!(simulation.is_settled() && steps < 1000);
+
+        assert_eq!(state.positions[a], Pos2::new(0.0, 0.0));
+        assert_eq!(state.positions[far], Pos2::new(900.0, 0.0));
+        // b started on top of the pinned node and got pushed off it
+        assert!(state.positions[b].distance(state.positions[a]) > 50.0);
+        assert!(state.positions[c].x > 5.0);
+    }
+
+    #[test]
+    fn test_only_nearby_and_connected_nodes_take_part() {
+        let mut state = GraphState::new();
+        let a = state.add_node_at(Pos2::new(0.0, 0.0));
+        let b = state.add_node_at(Pos2::new(100.0, 0.0));
+        let elsewhere = state.add_node_at(Pos2::new(5000.0, 0.0));
+        let ab = state.add_edge_between(a, b, None).unwrap();
+        let layout = ForceLayout { gravity: 0.0, ..ForceLayout::default() };
+
+        // Laying out `a` still feels `b` through their edge, but not unrelated nodes
+        let simulation = ForceSimulation::new(&state, layout, Some(&BTreeSet::from([a])));
+        assert_eq!(simulation.bodies, vec![a, b]);
+        assert_eq!(simulation.springs, vec![ab]);
+
+        // Nodes farther apart than the repulsion range leave each other alone
+        let forces = ForceSimulation::new(&state, layout, None).forces(&state);
+        assert_eq!(forces[&elsewhere], Vec2::ZERO);
+        assert!(forces[&a].x < 0.0);
+    }
+}
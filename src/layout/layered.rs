@@ -0,0 +1,427 @@
+// Layered (Sugiyama-style) layout for hierarchies: every edge points from one layer to a later one.
+//   1. Cycles are broken by treating the edges that close them as reversed
+//   2. Nodes get ranks (layers) from the longest path reaching them
+//   3. Edges spanning several layers get a chain of dummy vertices, one per layer crossed
+//   4. Barycenter sweeps reorder each layer to cut down edge crossings
+//   5. Nodes slide toward their neighbours without overlapping to get their final offsets
+// Only edges between two plain nodes in the laid out set count; relations on edges are ignored
+use eframe::egui::Pos2;
+use std::collections::{BTreeSet, HashMap};
+
+use super::{anchor, plain_nodes};
+use crate::graph::ID;
+use crate::state::GraphState;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayerDirection {
+    // Sources at the top, later layers below
+    Down,
+    // Sources on the left, later layers to the right
+    Right,
+}
+
+impl LayerDirection {
+    pub const ALL: [LayerDirection; 2] = [LayerDirection::Down, LayerDirection::Right];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LayerDirection::Down => "Top to bottom",
+            LayerDirection::Right => "Left to right",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LayeredLayout {
+    pub direction: LayerDirection,
+    // Distance between consecutive layers
+    pub layer_gap: f32,
+    // Distance between neighbouring nodes in a layer
+    pub node_gap: f32,
+    // Down-and-up barycenter sweeps spent on reducing crossings
+    pub sweeps: usize,
+}
+
+impl Default for LayeredLayout {
+    fn default() -> Self {
+        Self {
+            direction: LayerDirection::Down,
+            layer_gap: 120.0,
+            node_gap: 110.0,
+            sweeps: 8,
+        }
+    }
+}
+
+// New positions for the unpinned plain nodes in `only` (everything when None).
+// The layout sits where the nodes were, or around the first pinned node among them
+pub fn layout(state: &GraphState, settings: &LayeredLayout, only: Option<&BTreeSet<ID>>) -> Vec<(ID, Pos2)> {
+    let nodes = plain_nodes(state, only);
+    if nodes.is_empty() {
+        return Vec::new();
+    }
+    let index: HashMap<ID, usize> = nodes.iter().enumerate().map(|(i, &id)| (id, i)).collect();
+    let edges: BTreeSet<(usize, usize)> = state
+        .graph
+        .edges_iter()
+        .filter_map(|edge| Some((*index.get(&edge.source)?, *index.get(&edge.target)?)))
+        .filter(|(s, t)| s != t)
+        .collect();
+
+    let edges = break_cycles(nodes.len(), &edges);
+    let ranks = assign_ranks(nodes.len(), &edges);
+    let mut layered = Layered::build(&ranks, &edges);
+    layered.reduce_crossings(settings.sweeps);
+    let offsets = layered.offsets(settings.node_gap);
+
+    let computed = nodes
+        .iter()
+        .enumerate()
+        .map(|(v, &id)| {
+            let along = ranks[v] as f32 * settings.layer_gap;
+            let across = offsets[v];
+            let pos = match settings.direction {
+                LayerDirection::Down => Pos2::new(across, along),
+                LayerDirection::Right => Pos2::new(along, across),
+            };
+            (id, pos)
+        })
+        .collect();
+    anchor(state, computed)
+}
+
+// Lay out `only` (everything when None) in place
+pub fn run(state: &mut GraphState, settings: &LayeredLayout, only: Option<&BTreeSet<ID>>) {
+    let moves = layout(state, settings, only);
+    super::apply(state, moves);
+}
+
+// Reverse every edge that a depth-first search finds closing a cycle, leaving a DAG
+fn break_cycles(n: usize, edges: &BTreeSet<(usize, usize)>) -> BTreeSet<(usize, usize)> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Mark {
+        New,
+        Open,
+        Done,
+    }
+    let mut successors = vec![Vec::new(); n];
+    for &(s, t) in edges {
+        successors[s].push(t);
+    }
+    let mut marks = vec![Mark::New; n];
+    let mut back_edges = BTreeSet::new();
+    for root in 0..n {
+        if marks[root] != Mark::New {
+            continue;
+        }
+        // Explicit stack of (vertex, next successor to look at) so deep chains can't overflow
+        let mut stack = vec![(root, 0)];
+        marks[root] = Mark::Open;
+        while let Some((v, next)) = stack.last_mut() {
+            let v = *v;
+            let Some(&w) = successors[v].get(*next) else {
+                marks[v] = Mark::Done;
+                stack.pop();
+                continue;
+            };
+            *next += 1;
+            match marks[w] {
+                Mark::New => {
+                    marks[w] = Mark::Open;
+                    stack.push((w, 0));
+                }
+                Mark::Open => {
+                    back_edges.insert((v, w));
+                }
+                Mark::Done => {}
+            }
+        }
+    }
+    edges
+        .iter()
+        .map(|&(s, t)| if back_edges.contains(&(s, t)) { (t, s) } else { (s, t) })
+        .collect()
+}
+
+// Longest-path ranks over a DAG. Sources that only feed later layers are then pulled down
+// next to their earliest successor, so e.g. someone marrying into a family sits beside them
+fn assign_ranks(n: usize, edges: &BTreeSet<(usize, usize)>) -> Vec<usize> {
+    let mut successors = vec![Vec::new(); n];
+    let mut in_degree = vec![0; n];
+    for &(s, t) in edges {
+        successors[s].push(t);
+        in_degree[t] += 1;
+    }
+    let mut order: Vec<usize> = (0..n).filter(|&v| in_degree[v] == 0).collect();
+    let mut remaining = in_degree.clone();
+    let mut i = 0;
+    while let Some(&v) = order.get(i) {
+        i += 1;
+        for &w in &successors[v] {
+            remaining[w] -= 1;
+            if remaining[w] == 0 {
+                order.push(w);
+            }
+        }
+    }
+
+    let mut ranks = vec![0; n];
+    for &v in &order {
+        for &w in &successors[v] {
+            ranks[w] = ranks[w].max(ranks[v] + 1);
+        }
+    }
+    for &v in order.iter().rev() {
+        if in_degree[v] == 0 {
+            if let Some(earliest) = successors[v].iter().map(|&w| ranks[w]).min() {
+                ranks[v] = earliest - 1;
+            }
+        }
+    }
+    ranks
+}
+
+// Vertices per layer, real nodes first (0..n) and dummies after them
+struct Layered {
+    real: usize,
+    layers: Vec<Vec<usize>>,
+    // Neighbours in the layer above and below
+    up: Vec<Vec<usize>>,
+    down: Vec<Vec<usize>>,
+}
+
+impl Layered {
+    fn build(ranks: &[usize], edges: &BTreeSet<(usize, usize)>) -> Self {
+        let real = ranks.len();
+        let depth = ranks.iter().max().map_or(0, |r| r + 1);
+        let mut layers = vec![Vec::new(); depth];
+        for (v, &rank) in ranks.iter().enumerate() {
+            layers[rank].push(v);
+        }
+        let mut up = vec![Vec::new(); real];
+        let mut down = vec![Vec::new(); real];
+        for &(s, t) in edges {
+            let mut previous = s;
+            for layer in &mut layers[ranks[s] + 1..ranks[t]] {
+                let dummy = up.len();
+                up.push(Vec::new());
+                down.push(Vec::new());
+                layer.push(dummy);
+                down[previous].push(dummy);
+                up[dummy].push(previous);
+                previous = dummy;
+            }
+            down[previous].push(t);
+            up[t].push(previous);
+        }
+        Self { real, layers, up, down }
+    }
+
+    fn is_dummy(&self, v: usize) -> bool {
+        v >= self.real
+    }
+
+    // Where each vertex sits within its layer
+    fn positions(&self) -> Vec<usize> {
+        let mut positions = vec![0; self.up.len()];
+        for layer in &self.layers {
+            for (i, &v) in layer.iter().enumerate() {
+                positions[v] = i;
+            }
+        }
+        positions
+    }
+
+    fn crossings(&self) -> usize {
+        let positions = self.positions();
+        let mut total = 0;
+        for layer in &self.layers {
+            let lines: Vec<(usize, usize)> = layer
+                .iter()
+                .flat_map(|&v| self.down[v].iter().map(move |&w| (v, w)))
+                .map(|(v, w)| (positions[v], positions[w]))
+                .collect();
+            for (i, &(a1, b1)) in lines.iter().enumerate() {
+                for &(a2, b2) in &lines[i + 1..] {
+                    if (a1 < a2 && b1 > b2) || (a1 > a2 && b1 < b2) {
+                        total += 1;
+                    }
+                }
+            }
+        }
+        total
+    }
+
+    // Alternate downward and upward barycenter sweeps, keeping the best ordering seen
+    fn reduce_crossings(&mut self, sweeps: usize) {
+        let mut best = (self.crossings(), self.layers.clone());
+        for _ in 0..sweeps {
+            if best.0 == 0 {
+                break;
+            }
+            for i in 1..self.layers.len() {
+                self.order_by_neighbours(i, true);
+            }
+            for i in (0..self.layers.len().saturating_sub(1)).rev() {
+                self.order_by_neighbours(i, false);
+            }
+            let crossings = self.crossings();
+            if crossings < best.0 {
+                best = (crossings, self.layers.clone());
+            }
+        }
+        self.layers = best.1;
+    }
+
+    // Sort a layer by the mean position of each vertex's neighbours in the layer above
+    // (or below). Vertices without such neighbours keep their place
+    fn order_by_neighbours(&mut self, layer: usize, from_above: bool) {
+        let positions = self.positions();
+        let neighbours = if from_above { &self.up } else { &self.down };
+        let mut keyed: Vec<(f32, usize)> = self.layers[layer]
+            .iter()
+            .enumerate()
+            .map(|(i, &v)| {
+                let around = &neighbours[v];
+                let key = if around.is_empty() {
+                    i as f32
+                } else {
+                    around.iter().map(|&w| positions[w] as f32).sum::<f32>() / around.len() as f32
+                };
+                (key, v)
+            })
+            .collect();
+        keyed.sort_by(|a, b| a.0.total_cmp(&b.0));
+        self.layers[layer] = keyed.into_iter().map(|(_, v)| v).collect();
+    }
+
+    // Offset of every vertex across its layer. Vertices are pulled toward the mean of their
+    // neighbours, then packed left-to-right and right-to-left keeping the gaps; the mean of
+    // the two packings keeps the gaps too and doesn't lean to either side
+    fn offsets(&self, node_gap: f32) -> Vec<f32> {
+        let gap = |a: usize, b: usize| {
+            if self.is_dummy(a) || self.is_dummy(b) {
+                node_gap * 0.5
+            } else {
+                node_gap
+            }
+        };
+        let mut x = vec![0.0; self.up.len()];
+        for layer in &self.layers {
+            let mut next = 0.0;
+            for (i, &v) in layer.iter().enumerate() {
+                if i > 0 {
+                    next += gap(layer[i - 1], v);
+                }
+                x[v] = next;
+            }
+        }
+
+        for pass in 0..4 {
+            let downward = pass % 2 == 0;
+            let neighbours = if downward { &self.up } else { &self.down };
+            let order: Vec<usize> = if downward {
+                (0..self.layers.len()).collect()
+            } else {
+                (0..self.layers.len()).rev().collect()
+            };
+            for i in order {
+                let layer = &self.layers[i];
+                if layer.is_empty() {
+                    continue;
+                }
+                let desired: Vec<f32> = layer
+                    .iter()
+                    .map(|&v| {
+                        let around = &neighbours[v];
+                        if around.is_empty() {
+                            x[v]
+                        } else {
+                            around.iter().map(|&w| x[w]).sum::<f32>() / around.len() as f32
+                        }
+                    })
+                    .collect();
+                let mut left = desired.clone();
+                for j in 1..layer.len() {
+                    left[j] = left[j].max(left[j - 1] + gap(layer[j - 1], layer[j]));
+                }
+                let mut right = desired;
+                for j in (0..layer.len() - 1).rev() {
+                    right[j] = right[j].min(right[j + 1] - gap(layer[j], layer[j + 1]));
+                }
+                for (j, &v) in layer.iter().enumerate() {
+                    x[v] = (left[j] + right[j]) * 0.5;
+                }
+            }
+        }
+        x
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(state: &GraphState, id: ID) -> Pos2 {
+        state.positions[id]
+    }
+
+    #[test]
+    fn test_tree_is_layered() {
+        let mut state = GraphState::new();
+        let root = state.add_node_at(Pos2::new(0.0, 500.0));
+        let left = state.add_node_at(Pos2::new(0.0, 0.0));
+        let right = state.add_node_at(Pos2::new(0.0, 0.0));
+        let grandchild = state.add_node_at(Pos2::new(0.0, -200.0));
+        let spouse = state.add_node_at(Pos2::new(300.0, 300.0));
+        state.add_edge_between(root, left, None).unwrap();
+        state.add_edge_between(root, right, None).unwrap();
+        state.add_edge_between(left, grandchild, None).unwrap();
+        // A source that only joins the second generation sits beside it, not at the top
+        state.add_edge_between(spouse, grandchild, None).unwrap();
+
+        run(&mut state, &LayeredLayout::default(), None);
+        assert!(at(&state, root).y < at(&state, left).y);
+        assert_eq!(at(&state, left).y, at(&state, right).y);
+        assert_eq!(at(&state, spouse).y, at(&state, left).y);
+        assert!(at(&state, left).y < at(&state, grandchild).y);
+        assert!((at(&state, left).x - at(&state, right).x).abs() >= LayeredLayout::default().node_gap);
+    }
+
+    #[test]
+    fn test_cycles_and_crossings() {
+        let mut state = GraphState::new();
+        let a = state.add_node_at(Pos2::ZERO);
+        let b = state.add_node_at(Pos2::ZERO);
+        let c = state.add_node_at(Pos2::ZERO);
+        let d = state.add_node_at(Pos2::ZERO);
+        // Crossed on insertion order: a over c, b over d, but a -> d and b -> c
+        state.add_edge_between(a, d, None).unwrap();
+        state.add_edge_between(b, c, None).unwrap();
+        // And a cycle on top of it
+        let e = state.add_node_at(Pos2::ZERO);
+        state.add_edge_between(d, e, None).unwrap();
+        state.add_edge_between(e, a, None).unwrap();
+
+        let settings = LayeredLayout { direction: LayerDirection::Right, ..LayeredLayout::default() };
+        run(&mut state, &settings, None);
+        let ranks: BTreeSet<_> = [a, d, e].iter().map(|&id| at(&state, id).x as i32).collect();
+        assert_eq!(ranks.len(), 3);
+        let (a_y, b_y, c_y, d_y) = (at(&state, a).y, at(&state, b).y, at(&state, c).y, at(&state, d).y);
+        assert_eq!(a_y < b_y, d_y < c_y);
+    }
+
+    #[test]
+    fn test_selection_anchored_on_pinned_node() {
+        let mut state = GraphState::new();
+        let boss = state.add_node_at(Pos2::new(40.0, 40.0));
+        let aide = state.add_node_at(Pos2::new(-300.0, 900.0));
+        let outsider = state.add_node_at(Pos2::new(7.0, 7.0));
+        state.add_edge_between(boss, aide, None).unwrap();
+        state.add_edge_between(outsider, boss, None).unwrap();
+        state.set_pinned(boss, true).unwrap();
+
+        let moves = layout(&state, &LayeredLayout::default(), Some(&BTreeSet::from([boss, aide])));
+        assert_eq!(moves, vec![(aide, Pos2::new(40.0, 40.0 + LayeredLayout::default().layer_gap))]);
+    }
+}
@@ -0,0 +1,217 @@
+// Radial layout: a root node in the middle and everything else in rings by hop distance,
+// each subtree of the breadth-first tree getting a wedge sized by how many leaves it has.
+// Circular layout: every node evenly spaced on a single circle, neighbours kept together.
+// Edges count in both directions; relations on edges are ignored
+use eframe::egui::{Pos2, Vec2};
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::f32::consts::TAU;
+
+use super::{anchor, plain_nodes};
+use crate::graph::ID;
+use crate::state::GraphState;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RadialLayout {
+    // Distance between consecutive rings
+    pub ring_gap: f32,
+    // Rings grow past `ring_gap` steps when needed to keep nodes at least this far apart
+    pub node_gap: f32,
+}
+
+impl Default for RadialLayout {
+    fn default() -> Self {
+        Self {
+            ring_gap: 140.0,
+            node_gap: 90.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CircularLayout {
+    // Distance between neighbours along the circle
+    pub node_gap: f32,
+    pub min_radius: f32,
+}
+
+impl Default for CircularLayout {
+    fn default() -> Self {
+        Self {
+            node_gap: 90.0,
+            min_radius: 100.0,
+        }
+    }
+}
+
+// Plain nodes in `nodes` connected to `id` by an edge, either way round
+fn neighbours(state: &GraphState, id: ID, nodes: &BTreeSet<ID>) -> Vec<ID> {
+    let outgoing = state.graph.get_outgoing_edges(id).into_iter().filter_map(|e| state.graph.get_edge(e).map(|e| e.target));
+    let incoming = state.graph.get_incoming_edges(id).into_iter().filter_map(|e| state.graph.get_edge(e).map(|e| e.source));
+    let mut found: Vec<ID> = outgoing.chain(incoming).filter(|other| nodes.contains(other)).collect();
+    found.dedup();
+    found
+}
+
+// New positions for the unpinned plain nodes in `only` (everything when None), with `root`
+// staying where it is. Nodes `root` can't reach go on one more ring outside the rest
+pub fn radial(state: &GraphState, root: ID, settings: &RadialLayout, only: Option<&BTreeSet<ID>>) -> Vec<(ID, Pos2)> {
+    let nodes: BTreeSet<ID> = plain_nodes(state, only).into_iter().collect();
+    let Some(&center) = state.positions.get(root).filter(|_| nodes.contains(&root)) else {
+        return Vec::new();
+    };
+
+    // Breadth-first tree: each node's parent and hop distance
+    let mut depth = HashMap::from([(root, 0)]);
+    let mut children: HashMap<ID, Vec<ID>> = HashMap::new();
+    let mut order = vec![root];
+    let mut queue = VecDeque::from([root]);
+    while let Some(id) = queue.pop_front() {
+        for next in neighbours(state, id, &nodes) {
+            if depth.contains_key(&next) {
+                continue;
+            }
+            depth.insert(next, depth[&id] + 1);
+            children.entry(id).or_default().push(next);
+            order.push(next);
+            queue.push_back(next);
+        }
+    }
+
+    // Leaves below each node, children before parents
+    let mut leaves: HashMap<ID, usize> = HashMap::new();
+    for &id in order.iter().rev() {
+        let below = children.get(&id).map_or(1, |c| c.iter().map(|c| leaves[c]).sum());
+        leaves.insert(id, below);
+    }
+
+    // Rings step out by ring_gap, or further if a ring needs the room
+    let unreached: Vec<ID> = nodes.iter().copied().filter(|id| !depth.contains_key(id)).collect();
+    let rings = depth.values().max().copied().unwrap_or(0) + usize::from(!unreached.is_empty());
+    let mut per_ring = vec![0; rings + 1];
+    for &d in depth.values() {
+        per_ring[d] += 1;
+    }
+    per_ring[rings] += unreached.len();
+    let mut radii = vec![0.0; rings + 1];
+    for ring in 1..=rings {
+        let needed = per_ring[ring] as f32 * settings.node_gap / TAU;
+        radii[ring] = (radii[ring - 1] + settings.ring_gap).max(needed);
+    }
+
+    // Each node sits in the middle of its wedge; children split it by leaf count
+    let mut computed = vec![(root, center)];
+    let mut wedges = vec![(root, 0.0, TAU)];
+    while let Some((id, start, width)) = wedges.pop() {
+        let Some(kids) = children.get(&id) else {
+            continue;
+        };
+        let total = leaves[&id] as f32;
+        let mut at = start;
+        for &kid in kids {
+            let share = width * leaves[&kid] as f32 / total;
+            let angle = at + share * 0.5;
+            computed.push((kid, center + Vec2::angled(angle) * radii[depth[&kid]]));
+            wedges.push((kid, at, share));
+            at += share;
+        }
+    }
+    for (i, &id) in unreached.iter().enumerate() {
+        let angle = TAU * i as f32 / unreached.len() as f32;
+        computed.push((id, center + Vec2::angled(angle) * radii[rings]));
+    }
+
+    computed.retain(|&(id, _)| !state.is_pinned(id));
+    computed
+}
+
+// New positions for the unpinned plain nodes in `only` (everything when None) on one circle,
+// centered where they were. Nodes follow a depth-first walk so connected ones end up close
+pub fn circular(state: &GraphState, settings: &CircularLayout, only: Option<&BTreeSet<ID>>) -> Vec<(ID, Pos2)> {
+    let nodes: BTreeSet<ID> = plain_nodes(state, only).into_iter().collect();
+    if nodes.is_empty() {
+        return Vec::new();
+    }
+    let mut visited = BTreeSet::new();
+    let mut order = Vec::with_capacity(nodes.len());
+    for &start in &nodes {
+        let mut stack = vec![start];
+        while let Some(id) = stack.pop() {
+            if !visited.insert(id) {
+                continue;
+            }
+            order.push(id);
+            // Reversed so the first neighbour is walked first
+            stack.extend(neighbours(state, id, &nodes).into_iter().rev());
+        }
+    }
+
+    let radius = (order.len() as f32 * settings.node_gap / TAU).max(settings.min_radius);
+    let computed = order
+        .iter()
+        .enumerate()
+        .map(|(i, &id)| {
+            let angle = TAU * i as f32 / order.len() as f32;
+            (id, Pos2::ZERO + Vec2::angled(angle) * radius)
+        })
+        .collect();
+    anchor(state, computed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_radial_rings_by_hops() {
+        let mut state = GraphState::new();
+        let hero = state.add_node_at(Pos2::new(50.0, 50.0));
+        let friend = state.add_node_at(Pos2::ZERO);
+        let rival = state.add_node_at(Pos2::ZERO);
+        let friend_of_friend = state.add_node_at(Pos2::ZERO);
+        let stranger = state.add_node_at(Pos2::ZERO);
+        state.add_edge_between(hero, friend, None).unwrap();
+        // Incoming edges count as well
+        state.add_edge_between(rival, hero, None).unwrap();
+        let link = state.add_edge_between(friend, friend_of_friend, None).unwrap();
+        state.add_edge_between(stranger, link, None).unwrap();
+
+        let settings = RadialLayout::default();
+        let moves: HashMap<ID, Pos2> = radial(&state, hero, &settings, None).into_iter().collect();
+        let center = Pos2::new(50.0, 50.0);
+        let ring = |id: ID| moves[&id].distance(center);
+        assert_eq!(moves[&hero], center);
+        assert!((ring(friend) - settings.ring_gap).abs() < 1e-3);
+        assert!((ring(rival) - settings.ring_gap).abs() < 1e-3);
+        assert!((ring(friend_of_friend) - 2.0 * settings.ring_gap).abs() < 1e-3);
+        // Only related to an edge, so out on the ring past everyone reachable
+        assert!((ring(stranger) - 3.0 * settings.ring_gap).abs() < 1e-3);
+        assert!(moves[&friend].distance(moves[&rival]) > settings.node_gap);
+    }
+
+    #[test]
+    fn test_circular_keeps_spacing_and_pins() {
+        let mut state = GraphState::new();
+        let ids: Vec<ID> = (0..12).map(|i| state.add_node_at(Pos2::new(i as f32 * 10.0, 0.0))).collect();
+        let settings = CircularLayout::default();
+        let radius = (12.0 * settings.node_gap / TAU).max(settings.min_radius);
+
+        // Centered on where the nodes were, neighbours one gap apart
+        let moves = circular(&state, &settings, None);
+        assert_eq!(moves.len(), 12);
+        for (_, pos) in &moves {
+            assert!((pos.distance(Pos2::new(55.0, 0.0)) - radius).abs() < 1e-2);
+        }
+        for pair in moves.windows(2) {
+            assert!((pair[0].1.distance(pair[1].1) - settings.node_gap).abs() < 2.0);
+        }
+
+        // A pinned node stays and the circle runs through it
+        state.set_pinned(ids[3], true).unwrap();
+        let moves = circular(&state, &settings, None);
+        assert_eq!(moves.len(), 11);
+        assert!(moves.iter().all(|&(id, _)| id != ids[3]));
+        let pinned_at = state.positions[ids[3]];
+        let nearest = moves.iter().map(|(_, p)| p.distance(pinned_at)).fold(f32::MAX, f32::min);
+        assert!((nearest - settings.node_gap).abs() < 2.0);
+    }
+}
@@ -1,3 +1,14 @@
 pub mod state;
 pub mod editor;
-pub mod graph;
\ No newline at end of file
+pub mod graph;
+pub mod relations;
+pub mod schema;
+pub mod history;
+pub mod file_format;
+pub mod interchange;
+pub mod style;
+pub mod render;
+pub mod autosave;
+pub mod layout;
+pub mod spatial;
+pub mod tombstone;
@@ -6,6 +6,6 @@ fn main() -> eframe::Result<()> {
     eframe::run_native(
         "Graph Editor with Camera Controls",
         options,
-        Box::new(|_cc| Ok(Box::new(GraphEditor::default()))),
+        Box::new(|_cc| Ok(Box::new(GraphEditor::new()))),
     )
 }
\ No newline at end of file
@@ -0,0 +1,112 @@
+// relations.rs
+use eframe::egui::Color32;
+use serde::{Deserialize, Serialize};
+use crate::graph::RelationTypeId;
+use crate::tombstone::TombstoneMap;
+
+// How the line of an edge is stroked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum LineStyle {
+    #[default]
+    Solid,
+    Dashed,
+    Dotted,
+}
+
+impl LineStyle {
+    pub const ALL: [LineStyle; 3] = [LineStyle::Solid, LineStyle::Dashed, LineStyle::Dotted];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LineStyle::Solid => "Solid",
+            LineStyle::Dashed => "Dashed",
+            LineStyle::Dotted => "Dotted",
+        }
+    }
+}
+
+// A kind of relation edges can carry ("is the father of", "rules over", ...)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RelationType {
+    pub name: String,
+    pub color: Color32,
+    // Stroke width in screen pixels
+    pub width: f32,
+    pub line: LineStyle,
+}
+
+impl RelationType {
+    pub const DEFAULT_WIDTH: f32 = 1.5;
+
+    pub fn new(name: impl Into<String>, color: Color32) -> Self {
+        Self {
+            name: name.into(),
+            color,
+            width: Self::DEFAULT_WIDTH,
+            line: LineStyle::Solid,
+        }
+    }
+}
+
+// Per-document registry of relation kinds
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RelationRegistry {
+    // Removed types stay buried under their ID so undo can bring them back
+    types: TombstoneMap<RelationTypeId, RelationType>,
+}
+
+impl RelationRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, name: impl Into<String>, color: Color32) -> RelationTypeId {
+        self.types.insert(RelationType::new(name, color))
+    }
+
+    // Callers should also clear the type from edges, see GraphState::remove_relation_type
+    pub fn remove(&mut self, id: RelationTypeId) -> Option<RelationType> {
+        self.types.remove(id)
+    }
+
+    // Bring back a removed type under its old ID
+    pub fn restore(&mut self, id: RelationTypeId) -> bool {
+        self.types.restore(id)
+    }
+
+    // Free removed types for good, except those `keep` holds on to for a later restore
+    pub fn purge_removed(&mut self, keep: impl Fn(RelationTypeId) -> bool) {
+        self.types.purge(keep);
+    }
+
+    pub fn get(&self, id: RelationTypeId) -> Option<&RelationType> {
+        self.types.get(id)
+    }
+
+    pub fn get_mut(&mut self, id: RelationTypeId) -> Option<&mut RelationType> {
+        self.types.get_mut(id)
+    }
+
+    pub fn contains(&self, id: RelationTypeId) -> bool {
+        self.types.contains_key(id)
+    }
+
+    pub fn find_by_name(&self, name: &str) -> Option<RelationTypeId> {
+        self.types
+            .iter()
+            .find(|(_, relation)| relation.name == name)
+            .map(|(id, _)| id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (RelationTypeId, &RelationType)> {
+        self.types.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.types.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty()
+    }
+}
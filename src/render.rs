@@ -0,0 +1,599 @@
+// Drawing a document outside the editor. The graph is laid out into a scene of plain shapes and
+// text the same way the canvas draws it, which can then be written as SVG or rasterised to PNG
+use ab_glyph::{Font, FontArc, GlyphId, ScaleFont};
+use eframe::egui::{self, Color32, Pos2, Rect, Stroke, Vec2};
+use std::collections::HashMap;
+
+use crate::graph::ID;
+use crate::relations::{LineStyle, RelationType};
+use crate::state::{Camera, GraphState};
+use crate::style::{NodeShape, ResolvedStyle};
+
+pub mod png;
+pub mod svg;
+
+// Empty space around the graph when the whole world is exported
+const MARGIN: f32 = 20.0;
+// Straight pieces each half of a bent edge is drawn with
+const CURVE_SEGMENTS: usize = 16;
+// How far past the edge of a view elements are still drawn, in pixels, so node shapes and
+// labels reaching into the view from outside aren't cut off
+pub const CULL_MARGIN: f32 = 300.0;
+
+// What part of the world ends up in the picture
+#[derive(Debug, Clone, Copy)]
+pub enum Area {
+    // Every element at zoom 1.0, cropped to their bounds
+    World,
+    // Exactly what a canvas of `size` shows through `camera`
+    View { camera: Camera, size: Vec2 },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RenderOptions {
+    pub area: Area,
+    // Font size of node names at zoom 1.0, as in the editor's label settings
+    pub font_size: f32,
+    // Leave the background out instead of filling it with the theme's (or white)
+    pub transparent: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Item {
+    // An open polyline, straight or following a curve
+    Line { points: Vec<Pos2>, stroke: Stroke, line: LineStyle, dash: Option<[f32; 2]> },
+    Shape { shape: NodeShape, rect: Rect, corner_radius: f32, fill: Color32, stroke: Stroke },
+    Arrow { points: [Pos2; 3], color: Color32 },
+    Ring { center: Pos2, radius: f32, stroke: Stroke },
+    // One line of text centered on `center`, turned by `angle` radians around it.
+    // `baseline` is how far below the center the baseline sits before turning
+    Text { text: String, center: Pos2, size: f32, color: Color32, angle: f32, width: f32, baseline: f32 },
+}
+
+impl Item {
+    // Area covered by the item, generous for turned text
+    fn bounds(&self) -> Rect {
+        match self {
+            Item::Line { points, stroke, .. } => Rect::from_points(points).expand(stroke.width),
+            Item::Shape { rect, stroke, .. } => rect.expand(stroke.width),
+            Item::Arrow { points, .. } => Rect::from_points(points),
+            Item::Ring { center, radius, stroke } => Rect::from_center_size(*center, Vec2::splat((radius + stroke.width) * 2.0)),
+            Item::Text { center, size, width, angle, .. } => {
+                let extent = if *angle == 0.0 { egui::vec2(*width, size * 1.5) } else { Vec2::splat(width.max(size * 1.5)) };
+                Rect::from_center_size(*center, extent)
+            }
+        }
+    }
+
+    fn translate(&mut self, delta: Vec2) {
+        match self {
+            Item::Line { points, .. } => points.iter_mut().for_each(|p| *p += delta),
+            Item::Shape { rect, .. } => *rect = rect.translate(delta),
+            Item::Arrow { points, .. } => points.iter_mut().for_each(|p| *p += delta),
+            Item::Ring { center, .. } | Item::Text { center, .. } => *center += delta,
+        }
+    }
+}
+
+// Everything to draw, back to front, in a picture of `size` with its origin at the top left
+#[derive(Debug, Clone)]
+pub struct Scene {
+    pub size: Vec2,
+    pub background: Option<Color32>,
+    pub items: Vec<Item>,
+}
+
+// egui's built-in proportional fonts, so exported text measures the same as on the canvas.
+// Characters missing from the first face fall back to the next one, like egui does
+pub struct Fonts {
+    faces: Vec<(FontArc, f32)>,
+}
+
+impl Default for Fonts {
+    fn default() -> Self {
+        let definitions = egui::FontDefinitions::default();
+        let faces = definitions
+            .families
+            .get(&egui::FontFamily::Proportional)
+            .into_iter()
+            .flatten()
+            .filter_map(|name| definitions.font_data.get(name))
+            .filter_map(|data| {
+                let font = FontArc::try_from_vec(data.font.to_vec()).ok()?;
+                Some((font, data.tweak.scale))
+            })
+            .collect();
+        Self { faces }
+    }
+}
+
+// A glyph placed on a line of text, `x` from where the line starts
+pub(crate) struct PlacedGlyph<'a> {
+    pub(crate) font: &'a FontArc,
+    pub(crate) id: GlyphId,
+    pub(crate) x: f32,
+    pub(crate) size: f32,
+}
+
+impl Fonts {
+    fn face(&self, c: char) -> Option<&(FontArc, f32)> {
+        self.faces
+            .iter()
+            .find(|(font, _)| font.glyph_id(c).0 != 0)
+            .or(self.faces.first())
+    }
+
+    pub(crate) fn glyphs(&self, text: &str, size: f32) -> Vec<PlacedGlyph<'_>> {
+        let mut placed = Vec::new();
+        let mut x = 0.0;
+        let mut previous: Option<(&FontArc, GlyphId)> = None;
+        for c in text.chars() {
+            let Some((font, tweak)) = self.face(c) else {
+                break;
+            };
+            let scaled = font.as_scaled(size * tweak);
+            let id = font.glyph_id(c);
+            if let Some((previous_font, previous_id)) = previous {
+                if std::ptr::eq(previous_font, font) {
+                    x += scaled.kern(previous_id, id);
+                }
+            }
+            placed.push(PlacedGlyph { font, id, x, size: size * tweak });
+            x += scaled.h_advance(id);
+            previous = Some((font, id));
+        }
+        placed
+    }
+
+    pub fn width(&self, text: &str, size: f32) -> f32 {
+        self.glyphs(text, size)
+            .last()
+            .map_or(0.0, |g| g.x + g.font.as_scaled(g.size).h_advance(g.id))
+    }
+
+    // Height of a line of text and how far its baseline is below the top
+    fn line_metrics(&self, size: f32) -> (f32, f32) {
+        let Some((font, tweak)) = self.faces.first() else {
+            return (size, size * 0.8);
+        };
+        let scaled = font.as_scaled(size * tweak);
+        (scaled.ascent() - scaled.descent() + scaled.line_gap(), scaled.ascent())
+    }
+
+    fn text(&self, text: String, center: Pos2, size: f32, color: Color32, angle: f32) -> Item {
+        let width = self.width(&text, size);
+        let (height, ascent) = self.line_metrics(size);
+        Item::Text { text, center, size, color, angle, width, baseline: ascent - height * 0.5 }
+    }
+
+    fn size_of(&self, text: &str, size: f32) -> Vec2 {
+        egui::vec2(self.width(text, size), self.line_metrics(size).0)
+    }
+}
+
+// Nodes are at least their style's size and otherwise grow so their shape fits the label
+pub fn node_rect(center: Pos2, zoom: f32, style: &ResolvedStyle, name: Option<Vec2>, secondary: Option<Vec2>) -> Rect {
+    let mut size = Vec2::splat(style.size * zoom);
+    if let Some(name) = name {
+        let padding = egui::vec2(10.0, 6.0) * zoom;
+        let text_height = name.y + secondary.map_or(0.0, |s| s.y);
+        let text_width = secondary.map_or(0.0, |s| s.x).max(name.x);
+        size = size.max(style.shape.fit(egui::vec2(text_width, text_height) + padding));
+    }
+    if style.shape == NodeShape::Circle {
+        size = Vec2::splat(size.max_elem());
+    }
+    Rect::from_center_size(center, size)
+}
+
+// Corners of a filled triangle with its tip at `tip`, pointing along from -> tip,
+// or None when the line is too short to carry it
+pub fn arrowhead(from: Pos2, tip: Pos2, size: f32) -> Option<[Pos2; 3]> {
+    let dir = tip - from;
+    if dir.length() < size {
+        return None;
+    }
+    let dir = dir.normalized();
+    let normal = dir.rot90();
+    let back = tip - dir * size;
+    Some([tip, back + normal * size * 0.45, back - normal * size * 0.45])
+}
+
+// Arrowhead at the end of a polyline, pointing along its last `size` or so
+pub fn arrowhead_on(points: &[Pos2], size: f32) -> Option<[Pos2; 3]> {
+    let (&tip, rest) = points.split_last()?;
+    let from = rest.iter().rev().find(|p| p.distance(tip) >= size).or(rest.first())?;
+    arrowhead(*from, tip, size)
+}
+
+// Arrowhead size for an edge line `width` wide; it shrinks with the zoom down to half size
+pub fn arrow_size(width: f32, zoom: f32) -> f32 {
+    (8.0 + width * 2.0) * zoom.max(0.5)
+}
+
+// Dash and gap lengths of a line style at `zoom`, or None for solid lines. Dots are empty dashes
+pub fn dash_pattern(line: LineStyle, zoom: f32) -> Option<[f32; 2]> {
+    let scale = zoom.max(0.3);
+    match line {
+        LineStyle::Solid => None,
+        LineStyle::Dashed => Some([8.0 * scale, 5.0 * scale]),
+        LineStyle::Dotted => Some([0.0, 5.0 * scale]),
+    }
+}
+
+// Angle for text running along `dir`, turned half around when it would read upside down
+pub fn upright_angle(dir: Vec2) -> f32 {
+    let angle = dir.angle();
+    if angle.abs() > std::f32::consts::FRAC_PI_2 {
+        angle - std::f32::consts::PI.copysign(angle)
+    } else {
+        angle
+    }
+}
+
+// An edge drawn from `from` to `to` bending through its control point `through`: a quadratic
+// curve that passes `through` halfway, split there into two polylines. Edges whose control point
+// is on the midpoint stay two straight segments
+pub fn edge_curve(from: Pos2, through: Pos2, to: Pos2) -> [Vec<Pos2>; 2] {
+    let control = through + (through - from.lerp(to, 0.5));
+    if control.distance(through) < 0.5 {
+        return [vec![from, through], vec![through, to]];
+    }
+    let at = |t: f32| {
+        let u = 1.0 - t;
+        (from.to_vec2() * (u * u) + control.to_vec2() * (2.0 * u * t) + to.to_vec2() * (t * t)).to_pos2()
+    };
+    let half = |start: f32| -> Vec<Pos2> {
+        (0..=CURVE_SEGMENTS)
+            .map(|i| at(start + 0.5 * i as f32 / CURVE_SEGMENTS as f32))
+            .collect()
+    };
+    [half(0.0), half(0.5)]
+}
+
+// Cut the end of a polyline off where it enters `shape` drawn in `rect`, so an arrow on it stays visible
+pub fn clip_to_border(points: &mut Vec<Pos2>, shape: NodeShape, rect: Rect) {
+    // Points inside the outline are their own border point, since the border is capped at them
+    while points.len() > 1 && shape.border_towards(rect, points[points.len() - 1]) == points[points.len() - 1] {
+        points.pop();
+    }
+    if let Some(&last) = points.last() {
+        points.push(shape.border_towards(rect, last));
+    }
+}
+
+// The text a node shows: its name after its icon, and the subtitle or else the kind's name.
+// Nodes with neither name nor icon stay plain shapes
+pub fn node_label(state: &GraphState, id: ID, style: &ResolvedStyle) -> Option<(String, Option<String>)> {
+    let data = state.graph.node_data(id)?;
+    let name = match (style.icon.as_deref(), data.name.is_empty()) {
+        (None, true) => return None,
+        (None, false) => data.name.clone(),
+        (Some(icon), true) => icon.to_owned(),
+        (Some(icon), false) => format!("{icon} {}", data.name),
+    };
+    let kind = state
+        .schema
+        .as_ref()
+        .zip(data.kind)
+        .and_then(|(schema, kind)| schema.kinds.get(kind))
+        .map(|kind| kind.name.as_str());
+    let secondary = Some(data.subtitle.as_str()).filter(|s| !s.is_empty()).or(kind);
+    Some((name, secondary.map(str::to_owned)))
+}
+
+// Relation name and the edge's own label, as drawn along the edge
+pub fn edge_label(relation: Option<&RelationType>, label: Option<&str>) -> Option<String> {
+    match (relation, label) {
+        (Some(r), Some(label)) => Some(format!("{} · {}", r.name, label)),
+        (Some(r), None) => Some(r.name.clone()),
+        (None, Some(label)) => Some(label.to_owned()),
+        (None, None) => None,
+    }
+}
+
+struct Outline {
+    rect: Rect,
+    shape: NodeShape,
+    fill: Color32,
+    name: Option<(String, Vec2)>,
+    secondary: Option<(String, Vec2)>,
+}
+
+pub fn build_scene(state: &GraphState, options: &RenderOptions, fonts: &Fonts) -> Scene {
+    let (zoom, to_picture): (f32, Box<dyn Fn(Pos2) -> Pos2>) = match options.area {
+        Area::World => (1.0, Box::new(|p| p)),
+        Area::View { camera, .. } => (camera.zoom, Box::new(move |p| camera.world_to_screen(p, Pos2::ZERO))),
+    };
+    let name_size = options.font_size * zoom;
+    let secondary_size = options.font_size * 0.8 * zoom;
+
+    // A view only needs what can show up in it
+    let ids: Vec<ID> = match options.area {
+        Area::World => state.positions.keys().collect(),
+        Area::View { camera, size } => {
+            let view = Rect::from_min_max(
+                camera.screen_to_world(Pos2::ZERO, Pos2::ZERO),
+                camera.screen_to_world(size.to_pos2(), Pos2::ZERO),
+            );
+            state.elements_touching(view.expand(CULL_MARGIN / camera.zoom))
+        }
+    };
+
+    // Every element's outline first, since edges stop at the border of what they point at
+    let mut outlines: HashMap<ID, Outline> = HashMap::new();
+    for &id in &ids {
+        let Some(pos) = state.positions.get(id) else {
+            continue;
+        };
+        let style = state.style_of(id);
+        let (name, secondary) = match node_label(state, id, &style) {
+            Some((name, secondary)) => {
+                let name_extent = fonts.size_of(&name, name_size);
+                let secondary = secondary.map(|s| {
+                    let extent = fonts.size_of(&s, secondary_size);
+                    (s, extent)
+                });
+                (Some((name, name_extent)), secondary)
+            }
+            None => (None, None),
+        };
+        let rect = node_rect(
+            to_picture(*pos),
+            zoom,
+            &style,
+            name.as_ref().map(|n| n.1),
+            secondary.as_ref().map(|s| s.1),
+        );
+        outlines.insert(id, Outline { rect, shape: style.shape, fill: style.fill, name, secondary });
+    }
+
+    let mut items = Vec::new();
+
+    // 1) Edges, as two halves meeting at the edge's own node
+    for edge in ids.iter().filter_map(|&id| state.graph.get_edge(id)) {
+        let (Some(src), Some(mid), Some(tgt)) = (
+            outlines.get(&edge.source),
+            outlines.get(&edge.id),
+            outlines.get(&edge.target),
+        ) else {
+            continue;
+        };
+        let relation = edge.relation.and_then(|r| state.relations.get(r));
+        let (width, line) = relation.map_or((RelationType::DEFAULT_WIDTH, LineStyle::Solid), |r| (r.width, r.line));
+        let color = relation.map_or(Color32::LIGHT_BLUE, |r| r.color);
+        let stroke = Stroke::new(width, color);
+        let (start, middle) = (src.rect.center(), mid.rect.center());
+
+        let [first, mut second] = edge_curve(start, middle, tgt.rect.center());
+        clip_to_border(&mut second, tgt.shape, tgt.rect);
+        let arrow = arrowhead_on(&second, arrow_size(width, zoom));
+        let dash = dash_pattern(line, zoom);
+        items.push(Item::Line { points: first, stroke, line, dash });
+        items.push(Item::Line { points: second, stroke, line, dash });
+        if let Some(points) = arrow {
+            items.push(Item::Arrow { points, color });
+        }
+
+        // Rings and the label sit where the canvas puts them, see GraphEditor::draw_edge_segment
+        for (endpoint, anchor) in [(edge.source, src), (edge.target, tgt)] {
+            if state.graph.get_edge(endpoint).is_some() {
+                let radius = anchor.rect.size().max_elem() * 0.5 + 3.0 * zoom;
+                items.push(Item::Ring { center: anchor.rect.center(), radius, stroke });
+            }
+        }
+
+        if let Some(label) = edge_label(relation, edge.label.as_deref()) {
+            let dir = middle - start;
+            if fonts.width(&label, secondary_size) <= dir.length() && dir.length_sq() >= 1.0 {
+                let angle = upright_angle(dir);
+                let height = fonts.line_metrics(secondary_size).0;
+                let rot = egui::emath::Rot2::from_angle(angle);
+                let center = start + dir * 0.5 + rot * egui::vec2(0.0, -height * 0.5 - 2.0);
+                items.push(fonts.text(label, center, secondary_size, color, angle));
+            }
+        }
+    }
+
+    // 2) Nodes, including the nodes in the middle of edges, with their labels
+    for id in ids {
+        let Some(outline) = outlines.remove(&id) else {
+            continue;
+        };
+        let rect = outline.rect;
+        items.push(Item::Shape {
+            shape: outline.shape,
+            rect,
+            corner_radius: 5.0 * zoom,
+            fill: outline.fill,
+            stroke: Stroke::new(1.0, Color32::BLACK),
+        });
+        if let Some((name, name_extent)) = outline.name {
+            let secondary_height = outline.secondary.as_ref().map_or(0.0, |s| s.1.y);
+            let top = rect.center().y - (name_extent.y + secondary_height) * 0.5;
+            let center = egui::pos2(rect.center().x, top + name_extent.y * 0.5);
+            items.push(fonts.text(name, center, name_size, Color32::BLACK, 0.0));
+            if let Some((secondary, extent)) = outline.secondary {
+                let center = egui::pos2(rect.center().x, top + name_extent.y + extent.y * 0.5);
+                items.push(fonts.text(secondary, center, secondary_size, Color32::DARK_GRAY, 0.0));
+            }
+        }
+    }
+
+    let size = match options.area {
+        Area::World => {
+            let bounds = items.iter().fold(Rect::NOTHING, |bounds, item| bounds.union(item.bounds()));
+            if bounds.is_positive() {
+                let delta = Vec2::splat(MARGIN) - bounds.min.to_vec2();
+                items.iter_mut().for_each(|item| item.translate(delta));
+                bounds.size() + Vec2::splat(MARGIN * 2.0)
+            } else {
+                Vec2::splat(MARGIN * 2.0)
+            }
+        }
+        Area::View { size, .. } => {
+            let visible = Rect::from_min_size(Pos2::ZERO, size);
+            items.retain(|item| item.bounds().intersects(visible));
+            size
+        }
+    };
+    let background = (!options.transparent).then(|| state.theme.background.unwrap_or(Color32::WHITE));
+    Scene { size, background, items }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::NodeData;
+
+    fn sample() -> (GraphState, ID, ID) {
+        let mut state = GraphState::default();
+        let a = state.graph.add_node(NodeData::named("Queen Aldra"));
+        let b = state.graph.add_node(NodeData::named("Varenholm"));
+        state.positions.insert(a, egui::pos2(0.0, 0.0));
+        state.positions.insert(b, egui::pos2(300.0, 100.0));
+        let rules = state.relations.add("rules over", Color32::GOLD);
+        state.add_edge_between(a, b, Some(rules)).unwrap();
+        (state, a, b)
+    }
+
+    fn options(area: Area) -> RenderOptions {
+        RenderOptions { area, font_size: 14.0, transparent: false }
+    }
+
+    #[test]
+    fn test_world_scene_frames_every_element() {
+        let (state, _, _) = sample();
+        let fonts = Fonts::default();
+        let scene = build_scene(&state, &options(Area::World), &fonts);
+
+        let texts: Vec<&str> = scene
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Text { text, .. } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert!(texts.contains(&"Queen Aldra"));
+        assert!(texts.contains(&"Varenholm"));
+        assert!(texts.contains(&"rules over"));
+        assert_eq!(scene.items.iter().filter(|i| matches!(i, Item::Arrow { .. })).count(), 1);
+        assert_eq!(scene.items.iter().filter(|i| matches!(i, Item::Shape { .. })).count(), 3);
+
+        // Everything fits inside the picture with the margin left over
+        let frame = Rect::from_min_size(Pos2::ZERO, scene.size).shrink(MARGIN - 0.01);
+        for item in &scene.items {
+            assert!(frame.contains_rect(item.bounds()), "{item:?} outside {frame:?}");
+        }
+        assert!(scene.size.x > 300.0 && scene.size.y > 100.0);
+        assert_eq!(scene.background, Some(Color32::WHITE));
+    }
+
+    #[test]
+    fn test_view_scene_follows_the_camera() {
+        let (state, a, _) = sample();
+        let fonts = Fonts::default();
+        let camera = Camera { offset: egui::vec2(50.0, 50.0), zoom: 2.0 };
+        let area = Area::View { camera, size: egui::vec2(200.0, 200.0) };
+        let scene = build_scene(&state, &options(area), &fonts);
+
+        assert_eq!(scene.size, egui::vec2(200.0, 200.0));
+        // Only the queen is in view, drawn where the canvas would put her
+        let shapes: Vec<Rect> = scene
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Shape { rect, .. } => Some(*rect),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(shapes.len(), 1);
+        assert_eq!(shapes[0].center(), camera.world_to_screen(state.positions[a], Pos2::ZERO));
+        assert!(!scene.items.iter().any(|i| matches!(i, Item::Text { text, .. } if text == "Varenholm")));
+    }
+
+    #[test]
+    fn test_bent_edges_curve_through_their_control_point() {
+        let (mut state, a, b) = sample();
+        let edge = state.graph.get_outgoing_edges(a)[0];
+        let through = state.midpoint(edge).unwrap() + egui::vec2(0.0, 60.0);
+        state.set_control_point(edge, through).unwrap();
+        let camera = Camera::default();
+        let area = Area::View { camera, size: egui::vec2(400.0, 300.0) };
+        let scene = build_scene(&state, &options(area), &Fonts::default());
+
+        let lines: Vec<&Vec<Pos2>> = scene
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Line { points, .. } => Some(points),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(lines.len(), 2);
+        // The halves meet at the control point and bulge towards it
+        assert_eq!(lines[0].first(), Some(&state.positions[a]));
+        assert!(lines[0].last().unwrap().distance(through) < 0.01);
+        assert!(lines[1][0].distance(through) < 0.01);
+        assert!(lines[0].len() > 2 && lines[1].len() > 2);
+        assert!(lines[0][lines[0].len() / 2].y > state.positions[a].lerp(state.positions[b], 0.25).y);
+    }
+
+    #[test]
+    fn test_edges_stop_at_the_target_border() {
+        let (state, _, _) = sample();
+        let fonts = Fonts::default();
+        let scene = build_scene(&state, &options(Area::World), &fonts);
+        // Varenholm is the rightmost shape
+        let target = scene
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Shape { rect, .. } => Some(*rect),
+                _ => None,
+            })
+            .max_by(|a, b| a.center().x.total_cmp(&b.center().x))
+            .unwrap();
+        let Some(Item::Arrow { points, .. }) = scene.items.iter().find(|i| matches!(i, Item::Arrow { .. })) else {
+            panic!("no arrow");
+        };
+        let tip = points[0];
+        assert!(target.expand(0.01).contains(tip) && !target.shrink(0.01).contains(tip));
+    }
+
+    #[test]
+    fn test_arrowheads_sit_on_the_end_of_the_line() {
+        let line = [Pos2::ZERO, egui::pos2(50.0, 0.0), egui::pos2(100.0, 0.0)];
+        let [tip, left, right] = arrowhead_on(&line, 10.0).unwrap();
+        assert_eq!(tip, egui::pos2(100.0, 0.0));
+        assert_eq!((left, right), (egui::pos2(90.0, -4.5), egui::pos2(90.0, 4.5)));
+        // Too short to carry one
+        assert_eq!(arrowhead_on(&[Pos2::ZERO, egui::pos2(5.0, 0.0)], 10.0), None);
+
+        assert_eq!(arrow_size(1.0, 2.0), 20.0);
+        // Zooming out shrinks arrows only down to half size
+        assert_eq!(arrow_size(1.0, 0.1), arrow_size(1.0, 0.5));
+    }
+
+    #[test]
+    fn test_dashes_scale_with_the_zoom() {
+        assert_eq!(dash_pattern(LineStyle::Solid, 1.0), None);
+        assert_eq!(dash_pattern(LineStyle::Dashed, 1.0), Some([8.0, 5.0]));
+        assert_eq!(dash_pattern(LineStyle::Dashed, 2.0), Some([16.0, 10.0]));
+        assert_eq!(dash_pattern(LineStyle::Dotted, 1.0), Some([0.0, 5.0]));
+        // Far out the pattern stops shrinking so lines don't turn solid
+        assert_eq!(dash_pattern(LineStyle::Dashed, 0.1), dash_pattern(LineStyle::Dashed, 0.3));
+    }
+
+    #[test]
+    fn test_text_along_edges_stays_upright() {
+        let close = |dir: Vec2, angle: f32| (upright_angle(dir) - angle).abs() < 1e-5;
+        assert!(close(egui::vec2(1.0, 0.0), 0.0));
+        assert!(close(egui::vec2(-1.0, 0.0), 0.0));
+        assert!(close(egui::vec2(0.0, 1.0), std::f32::consts::FRAC_PI_2));
+        // Running up and to the left reads like running down and to the right
+        assert!(close(egui::vec2(-1.0, -1.0), std::f32::consts::FRAC_PI_4));
+        assert!(close(egui::vec2(-1.0, 1.0), -std::f32::consts::FRAC_PI_4));
+    }
+}
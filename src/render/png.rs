@@ -0,0 +1,242 @@
+// PNG output through a software rasteriser, so pictures can be made without a window or GPU.
+// Text is drawn from the glyph outlines of the editor's own fonts
+use ab_glyph::{Font, OutlineCurve};
+use eframe::egui::{Color32, Pos2, Rect, Stroke, Vec2};
+use tiny_skia::{FillRule, LineCap, Paint, Path, PathBuilder, Pixmap, StrokeDash, Transform};
+
+use super::{Fonts, Item, Scene};
+use crate::relations::LineStyle;
+use crate::style::NodeShape;
+
+// Largest picture side we are willing to allocate, in pixels
+pub const MAX_SIDE: u32 = 16384;
+
+// Pixel size of a picture `size` scene units big at `scale` pixels per unit
+pub fn pixel_size(size: Vec2, scale: f32) -> (u32, u32) {
+    let size = size * scale;
+    (size.x.ceil().max(1.0) as u32, size.y.ceil().max(1.0) as u32)
+}
+
+fn paint(color: Color32) -> Paint<'static> {
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(r, g, b, a);
+    paint.anti_alias = true;
+    paint
+}
+
+fn polygon(points: &[Pos2]) -> Option<Path> {
+    let (first, rest) = points.split_first()?;
+    let mut path = PathBuilder::new();
+    path.move_to(first.x, first.y);
+    for p in rest {
+        path.line_to(p.x, p.y);
+    }
+    path.close();
+    path.finish()
+}
+
+// Rectangle with its corners rounded by quarter circles
+fn rounded_rect(rect: Rect, radius: f32) -> Option<Path> {
+    let r = radius.min(rect.width() * 0.5).min(rect.height() * 0.5).max(0.0);
+    // Control point distance that makes a cubic look like a quarter circle
+    let k = r * 0.552_284_8;
+    let (l, t, rt, b) = (rect.left(), rect.top(), rect.right(), rect.bottom());
+    let mut path = PathBuilder::new();
+    path.move_to(l + r, t);
+    path.line_to(rt - r, t);
+    path.cubic_to(rt - r + k, t, rt, t + r - k, rt, t + r);
+    path.line_to(rt, b - r);
+    path.cubic_to(rt, b - r + k, rt - r + k, b, rt - r, b);
+    path.line_to(l + r, b);
+    path.cubic_to(l + r - k, b, l, b - r + k, l, b - r);
+    path.line_to(l, t + r);
+    path.cubic_to(l, t + r - k, l + r - k, t, l + r, t);
+    path.close();
+    path.finish()
+}
+
+fn stroke_path(pixmap: &mut Pixmap, path: &Path, stroke: Stroke, transform: Transform) {
+    if stroke.width <= 0.0 {
+        return;
+    }
+    let style = tiny_skia::Stroke { width: stroke.width, ..Default::default() };
+    pixmap.stroke_path(path, &paint(stroke.color), &style, transform, None);
+}
+
+// Outlines of a line of text laid out with its left end on the baseline at the origin
+fn text_path(fonts: &Fonts, text: &str, size: f32) -> Option<Path> {
+    let mut path = PathBuilder::new();
+    for glyph in fonts.glyphs(text, size) {
+        let Some(outline) = glyph.font.outline(glyph.id) else {
+            continue;
+        };
+        // Outlines are in font units with y pointing up
+        let scale = glyph.size / glyph.font.height_unscaled();
+        let to_px = |p: ab_glyph::Point| (glyph.x + p.x * scale, -p.y * scale);
+        let mut last: Option<ab_glyph::Point> = None;
+        for curve in &outline.curves {
+            let start = match curve {
+                OutlineCurve::Line(a, _) | OutlineCurve::Quad(a, _, _) | OutlineCurve::Cubic(a, _, _, _) => *a,
+            };
+            // A new contour starts wherever a curve doesn't continue the previous one
+            if last != Some(start) {
+                if last.is_some() {
+                    path.close();
+                }
+                let (x, y) = to_px(start);
+                path.move_to(x, y);
+            }
+            last = Some(match curve {
+                OutlineCurve::Line(_, b) => {
+                    let (x, y) = to_px(*b);
+                    path.line_to(x, y);
+                    *b
+                }
+                OutlineCurve::Quad(_, c, b) => {
+                    let ((cx, cy), (x, y)) = (to_px(*c), to_px(*b));
+                    path.quad_to(cx, cy, x, y);
+                    *b
+                }
+                OutlineCurve::Cubic(_, c1, c2, b) => {
+                    let ((c1x, c1y), (c2x, c2y), (x, y)) = (to_px(*c1), to_px(*c2), to_px(*b));
+                    path.cubic_to(c1x, c1y, c2x, c2y, x, y);
+                    *b
+                }
+            });
+        }
+        if last.is_some() {
+            path.close();
+        }
+    }
+    path.finish()
+}
+
+// Draws the scene at `scale` pixels per unit and encodes it as PNG
+pub fn render(scene: &Scene, fonts: &Fonts, scale: f32) -> Result<Vec<u8>, String> {
+    let (width, height) = pixel_size(scene.size, scale);
+    if width > MAX_SIDE || height > MAX_SIDE {
+        return Err(format!("{width}×{height} pixels is too large (at most {MAX_SIDE} per side)"));
+    }
+    let mut pixmap = Pixmap::new(width, height).ok_or("could not allocate the picture")?;
+    if let Some(background) = scene.background {
+        let [r, g, b, a] = background.to_srgba_unmultiplied();
+        pixmap.fill(tiny_skia::Color::from_rgba8(r, g, b, a));
+    }
+    let base = Transform::from_scale(scale, scale);
+
+    for item in &scene.items {
+        match item {
+            Item::Line { points, stroke, line, dash } => {
+                let mut path = PathBuilder::new();
+                for (i, p) in points.iter().enumerate() {
+                    if i == 0 {
+                        path.move_to(p.x, p.y);
+                    } else {
+                        path.line_to(p.x, p.y);
+                    }
+                }
+                let Some(path) = path.finish() else {
+                    continue;
+                };
+                // Round caps on nearly empty dashes make dots as wide as the canvas draws them
+                let (width, line_cap) = match line {
+                    LineStyle::Dotted => (stroke.width * 1.5, LineCap::Round),
+                    _ => (stroke.width, LineCap::default()),
+                };
+                let style = tiny_skia::Stroke {
+                    width,
+                    line_cap,
+                    dash: dash.and_then(|[dash, gap]| StrokeDash::new(vec![dash.max(0.01), gap], 0.0)),
+                    ..Default::default()
+                };
+                pixmap.stroke_path(&path, &paint(stroke.color), &style, base, None);
+            }
+            Item::Shape { shape, rect, corner_radius, fill, stroke } => {
+                let path = match shape {
+                    NodeShape::Rectangle => rounded_rect(*rect, *corner_radius),
+                    NodeShape::Circle => PathBuilder::from_circle(rect.center().x, rect.center().y, rect.width() * 0.5),
+                    NodeShape::Diamond | NodeShape::Hexagon => polygon(&shape.polygon(*rect).unwrap_or_default()),
+                };
+                if let Some(path) = path {
+                    pixmap.fill_path(&path, &paint(*fill), FillRule::Winding, base, None);
+                    stroke_path(&mut pixmap, &path, *stroke, base);
+                }
+            }
+            Item::Arrow { points, color } => {
+                if let Some(path) = polygon(points) {
+                    pixmap.fill_path(&path, &paint(*color), FillRule::Winding, base, None);
+                }
+            }
+            Item::Ring { center, radius, stroke } => {
+                if let Some(path) = PathBuilder::from_circle(center.x, center.y, *radius) {
+                    stroke_path(&mut pixmap, &path, *stroke, base);
+                }
+            }
+            Item::Text { text, center, size, color, angle, width, baseline } => {
+                let Some(path) = text_path(fonts, text, *size) else {
+                    continue;
+                };
+                let place = base
+                    .pre_translate(center.x, center.y)
+                    .pre_concat(Transform::from_rotate(angle.to_degrees()))
+                    .pre_translate(-width * 0.5, *baseline);
+                pixmap.fill_path(&path, &paint(*color), FillRule::Winding, place, None);
+            }
+        }
+    }
+    pixmap.encode_png().map_err(|error| error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::NodeData;
+    use crate::render::{build_scene, Area, RenderOptions};
+    use crate::state::{Camera, GraphState};
+    use eframe::egui;
+
+    // Width and height from the IHDR chunk
+    fn png_size(png: &[u8]) -> (u32, u32) {
+        let word = |at: usize| u32::from_be_bytes(png[at..at + 4].try_into().unwrap());
+        (word(16), word(20))
+    }
+
+    #[test]
+    fn test_renders_at_the_chosen_resolution() {
+        let mut state = GraphState::default();
+        let a = state.graph.add_node(NodeData::named("Ferrow"));
+        let b = state.graph.add_node(NodeData::named("Ilse"));
+        state.positions.insert(a, egui::pos2(0.0, 0.0));
+        state.positions.insert(b, egui::pos2(150.0, 80.0));
+        state.add_edge_between(a, b, None).unwrap();
+        let fonts = Fonts::default();
+
+        let camera = Camera { offset: egui::vec2(100.0, 50.0), zoom: 1.5 };
+        let area = Area::View { camera, size: egui::vec2(320.0, 240.0) };
+        let options = RenderOptions { area, font_size: 14.0, transparent: false };
+        let scene = build_scene(&state, &options, &fonts);
+        let png = render(&scene, &fonts, 2.0).unwrap();
+        assert_eq!(&png[1..4], b"PNG");
+        assert_eq!(png_size(&png), (640, 480));
+
+        let pixmap = Pixmap::decode_png(&png).unwrap();
+        // The white background, with the dark name somewhere over the first node
+        assert_eq!(pixmap.pixel(0, 0).unwrap().red(), 255);
+        let center = camera.world_to_screen(state.positions[a], egui::Pos2::ZERO) * 2.0;
+        let dark = (-40..40)
+            .flat_map(|dx| (-15..15).map(move |dy| (dx, dy)))
+            .filter_map(|(dx, dy)| pixmap.pixel((center.x as i32 + dx) as u32, (center.y as i32 + dy) as u32))
+            .any(|p| p.red() < 80 && p.green() < 80);
+        assert!(dark);
+    }
+
+    #[test]
+    fn test_refuses_huge_pictures() {
+        let state = GraphState::default();
+        let fonts = Fonts::default();
+        let options = RenderOptions { area: Area::World, font_size: 14.0, transparent: true };
+        let scene = build_scene(&state, &options, &fonts);
+        assert!(render(&scene, &fonts, 1000.0).is_err());
+    }
+}
@@ -0,0 +1,158 @@
+// SVG output. Text stays text, in the font the editor uses with a generic fallback,
+// so it can be selected and searched in the finished document
+use eframe::egui::{Color32, Pos2, Stroke};
+use std::fmt::Write;
+
+use super::{Item, Scene};
+use crate::interchange::{color_to_hex, xml_escape};
+use crate::relations::LineStyle;
+use crate::style::NodeShape;
+
+// `name="#rrggbb"` plus an opacity attribute when the color is translucent
+fn paint(name: &str, color: Color32) -> String {
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    let hex = color_to_hex(Color32::from_rgb(r, g, b));
+    if a == 255 {
+        format!("{name}=\"{hex}\"")
+    } else {
+        format!("{name}=\"{hex}\" {name}-opacity=\"{:.3}\"", a as f32 / 255.0)
+    }
+}
+
+fn stroke(stroke: Stroke) -> String {
+    if stroke.width <= 0.0 || stroke.color == Color32::TRANSPARENT {
+        return "stroke=\"none\"".to_owned();
+    }
+    format!("{} stroke-width=\"{}\"", paint("stroke", stroke.color), stroke.width)
+}
+
+fn points(points: &[Pos2]) -> String {
+    let pairs: Vec<String> = points.iter().map(|p| format!("{},{}", p.x, p.y)).collect();
+    pairs.join(" ")
+}
+
+pub fn render(scene: &Scene) -> String {
+    let mut svg = String::new();
+    let (width, height) = (scene.size.x.ceil(), scene.size.y.ceil());
+    svg.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\" font-family=\"Ubuntu, 'Ubuntu Light', sans-serif\">"
+    );
+    if let Some(background) = scene.background {
+        let _ = writeln!(svg, "  <rect width=\"100%\" height=\"100%\" {}/>", paint("fill", background));
+    }
+
+    for item in &scene.items {
+        let _ = match item {
+            Item::Line { points: line_points, stroke: line_stroke, line, dash } => {
+                let mut dash = dash.map_or(String::new(), |[dash, gap]| format!(" stroke-dasharray=\"{dash} {gap}\""));
+                if *line == LineStyle::Dotted {
+                    dash.push_str(" stroke-linecap=\"round\"");
+                }
+                // Dots are as wide as the ones the canvas draws
+                let line_stroke = match line {
+                    LineStyle::Dotted => Stroke::new(line_stroke.width * 1.5, line_stroke.color),
+                    _ => *line_stroke,
+                };
+                writeln!(
+                    svg,
+                    "  <polyline points=\"{}\" fill=\"none\" {}{dash}/>",
+                    points(line_points),
+                    stroke(line_stroke)
+                )
+            }
+            Item::Shape { shape, rect, corner_radius, fill, stroke: outline } => {
+                let fill = paint("fill", *fill);
+                match shape {
+                    NodeShape::Rectangle => writeln!(
+                        svg,
+                        "  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"{corner_radius}\" {fill} {}/>",
+                        rect.left(),
+                        rect.top(),
+                        rect.width(),
+                        rect.height(),
+                        stroke(*outline)
+                    ),
+                    NodeShape::Circle => writeln!(
+                        svg,
+                        "  <circle cx=\"{}\" cy=\"{}\" r=\"{}\" {fill} {}/>",
+                        rect.center().x,
+                        rect.center().y,
+                        rect.width() * 0.5,
+                        stroke(*outline)
+                    ),
+                    NodeShape::Diamond | NodeShape::Hexagon => writeln!(
+                        svg,
+                        "  <polygon points=\"{}\" {fill} {}/>",
+                        points(&shape.polygon(*rect).unwrap_or_default()),
+                        stroke(*outline)
+                    ),
+                }
+            }
+            Item::Arrow { points: corners, color } => {
+                writeln!(svg, "  <polygon points=\"{}\" {}/>", points(corners), paint("fill", *color))
+            }
+            Item::Ring { center, radius, stroke: ring } => writeln!(
+                svg,
+                "  <circle cx=\"{}\" cy=\"{}\" r=\"{radius}\" fill=\"none\" {}/>",
+                center.x,
+                center.y,
+                stroke(*ring)
+            ),
+            Item::Text { text, center, size, color, angle, baseline, .. } => {
+                let turn = if *angle == 0.0 {
+                    String::new()
+                } else {
+                    format!(" transform=\"rotate({} {} {})\"", angle.to_degrees(), center.x, center.y)
+                };
+                writeln!(
+                    svg,
+                    "  <text x=\"{}\" y=\"{}\" font-size=\"{size}\" text-anchor=\"middle\" {}{turn}>{}</text>",
+                    center.x,
+                    center.y + baseline,
+                    paint("fill", *color),
+                    xml_escape(text)
+                )
+            }
+        };
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::{build_scene, Area, Fonts, RenderOptions};
+    use crate::state::GraphState;
+    use eframe::egui;
+
+    #[test]
+    fn test_writes_shapes_arrows_and_real_text() {
+        let mut state = GraphState::default();
+        let a = state.add_node_at(egui::pos2(0.0, 0.0));
+        let b = state.add_node_at(egui::pos2(200.0, 0.0));
+        state.graph.set_name(a, "Tom & Jerry").unwrap();
+        state.graph.set_name(b, "<Cheese>").unwrap();
+        state.graph.node_data_mut(b).unwrap().style.shape = Some(NodeShape::Diamond);
+        let likes = state.relations.add("likes", Color32::from_rgb(200, 30, 30));
+        state.relations.get_mut(likes).unwrap().line = LineStyle::Dashed;
+        state.add_edge_between(a, b, Some(likes)).unwrap();
+
+        let options = RenderOptions { area: Area::World, font_size: 14.0, transparent: true };
+        let svg = render(&build_scene(&state, &options, &Fonts::default()));
+
+        assert!(svg.starts_with("<?xml"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert!(svg.contains(">Tom &amp; Jerry</text>"));
+        assert!(svg.contains(">&lt;Cheese&gt;</text>"));
+        assert!(svg.contains(">likes</text>"));
+        assert!(svg.contains("<rect x="));
+        // The diamond and the arrowhead
+        assert_eq!(svg.matches("<polygon").count(), 2);
+        assert!(svg.contains("stroke=\"#c81e1e\" stroke-width=\"1.5\" stroke-dasharray=\"8 5\""));
+        // Transparent: no background rectangle
+        assert!(!svg.contains("width=\"100%\""));
+    }
+}
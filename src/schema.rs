@@ -0,0 +1,674 @@
+// schema.rs
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::graph::{Graph, NodeKindId, RelationTypeId, ID};
+use crate::relations::RelationRegistry;
+use crate::style::NodeStyle;
+use crate::tombstone::TombstoneMap;
+
+// A kind of world element (Character, Place, Faction, Event...)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeKind {
+    pub name: String,
+    // Properties every element of this kind must carry
+    pub required_properties: BTreeSet<String>,
+    // Default look of elements of this kind
+    pub style: NodeStyle,
+}
+
+impl NodeKind {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            required_properties: BTreeSet::new(),
+            style: NodeStyle::default(),
+        }
+    }
+}
+
+// What may sit at one end of a relation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Endpoint {
+    // Any plain node, whatever its kind
+    AnyNode,
+    // A node of this kind
+    Kind(NodeKindId),
+    // Any edge (edge-to-edge relation)
+    AnyEdge,
+    // An edge carrying this relation type
+    EdgeOfType(RelationTypeId),
+}
+
+impl Endpoint {
+    fn matches(&self, graph: &Graph, id: ID) -> bool {
+        let edge = graph.get_edge(id);
+        match *self {
+            Endpoint::AnyNode => edge.is_none(),
+            Endpoint::Kind(kind) => graph.node_data(id).is_some_and(|d| d.kind == Some(kind)),
+            Endpoint::AnyEdge => edge.is_some(),
+            Endpoint::EdgeOfType(relation) => edge.is_some_and(|e| e.relation == Some(relation)),
+        }
+    }
+}
+
+// Which elements a relation type may connect, and how often
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RelationRule {
+    // None is the rule for untyped edges
+    pub relation: Option<RelationTypeId>,
+    // An empty list allows anything on that side
+    pub sources: Vec<Endpoint>,
+    pub targets: Vec<Endpoint>,
+    // Most edges of this relation a single element may have as source / target
+    pub max_outgoing: Option<u32>,
+    pub max_incoming: Option<u32>,
+}
+
+impl RelationRule {
+    pub fn new(relation: Option<RelationTypeId>) -> Self {
+        Self {
+            relation,
+            sources: Vec::new(),
+            targets: Vec::new(),
+            max_outgoing: None,
+            max_incoming: None,
+        }
+    }
+
+    pub fn from(mut self, endpoint: Endpoint) -> Self {
+        self.sources.push(endpoint);
+        self
+    }
+
+    pub fn to(mut self, endpoint: Endpoint) -> Self {
+        self.targets.push(endpoint);
+        self
+    }
+
+    pub fn max_outgoing(mut self, max: u32) -> Self {
+        self.max_outgoing = Some(max);
+        self
+    }
+
+    pub fn max_incoming(mut self, max: u32) -> Self {
+        self.max_incoming = Some(max);
+        self
+    }
+}
+
+// An endpoint a removal took off one side of a rule
+#[derive(Debug, Clone, PartialEq)]
+pub struct DroppedEndpoint {
+    pub relation: Option<RelationTypeId>,
+    // Off the sources, else off the targets
+    pub source: bool,
+    // Where it was in that list
+    pub index: usize,
+    pub endpoint: Endpoint,
+}
+
+// What removing a node kind or relation type took out of the rules
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DroppedRules {
+    // The removed relation type's own rule, with its place in the list
+    pub rule: Option<(usize, RelationRule)>,
+    pub endpoints: Vec<DroppedEndpoint>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SchemaViolation {
+    // Strict schema and no rule covers this relation type
+    RelationNotAllowed { relation: Option<RelationTypeId> },
+    SourceNotAllowed { relation: Option<RelationTypeId>, source: ID },
+    TargetNotAllowed { relation: Option<RelationTypeId>, target: ID },
+    TooManyOutgoing { relation: Option<RelationTypeId>, source: ID, max: u32 },
+    TooManyIncoming { relation: Option<RelationTypeId>, target: ID, max: u32 },
+    UnknownKind { element: ID, kind: NodeKindId },
+    MissingProperty { element: ID, property: String },
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaViolation::RelationNotAllowed { .. } => {
+                write!(f, "the schema has no rule for this relation type")
+            }
+            SchemaViolation::SourceNotAllowed { .. } => {
+                write!(f, "this relation can't start at an element of that kind")
+            }
+            SchemaViolation::TargetNotAllowed { .. } => {
+                write!(f, "this relation can't end at an element of that kind")
+            }
+            SchemaViolation::TooManyOutgoing { max, .. } => {
+                write!(f, "an element may only be the source of {max} such relations")
+            }
+            SchemaViolation::TooManyIncoming { max, .. } => {
+                write!(f, "an element may only be the target of {max} such relations")
+            }
+            SchemaViolation::UnknownKind { .. } => {
+                write!(f, "element has a kind the schema doesn't define")
+            }
+            SchemaViolation::MissingProperty { property, .. } => {
+                write!(f, "required property \"{property}\" is missing")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SchemaViolation {}
+
+// Result of checking a whole document; each violation is paired with the offending element
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValidationReport {
+    pub violations: Vec<(ID, SchemaViolation)>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+// Optional ontology for a document: node kinds plus rules on what relations may connect
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Schema {
+    // Removed kinds stay buried under their ID so undo can bring them back
+    pub kinds: TombstoneMap<NodeKindId, NodeKind>,
+    pub rules: Vec<RelationRule>,
+    // When set, relation types without a rule are rejected instead of allowed
+    pub strict: bool,
+}
+
+impl Schema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_kind(&mut self, kind: NodeKind) -> NodeKindId {
+        self.kinds.insert(kind)
+    }
+
+    // Drops the kind, and its endpoints from rules that allow something else as well.
+    // Returns the kind and what came out of the rules
+    pub fn remove_kind(&mut self, kind: NodeKindId) -> Option<(NodeKind, DroppedRules)> {
+        let removed = self.kinds.remove(kind)?;
+        let endpoints = self.drop_endpoint(Endpoint::Kind(kind));
+        Some((removed, DroppedRules { rule: None, endpoints }))
+    }
+
+    // Bring back a removed kind under its old ID. Rules that mentioned it are up to the caller
+    pub fn restore_kind(&mut self, kind: NodeKindId) -> bool {
+        self.kinds.restore(kind)
+    }
+
+    // Free removed kinds for good, except those `keep` holds on to for a later restore
+    pub fn purge_removed(&mut self, keep: impl Fn(NodeKindId) -> bool) {
+        self.kinds.purge(keep);
+    }
+
+    pub fn find_kind(&self, name: &str) -> Option<NodeKindId> {
+        self.kinds
+            .iter()
+            .find(|(_, kind)| kind.name == name)
+            .map(|(id, _)| id)
+    }
+
+    // Adds a rule, replacing any existing rule for the same relation type
+    pub fn set_rule(&mut self, rule: RelationRule) {
+        self.remove_rule(rule.relation);
+        self.rules.push(rule);
+    }
+
+    pub fn remove_rule(&mut self, relation: Option<RelationTypeId>) -> Option<RelationRule> {
+        let index = self.rules.iter().position(|r| r.relation == relation)?;
+        Some(self.rules.remove(index))
+    }
+
+    pub fn rule_for(&self, relation: Option<RelationTypeId>) -> Option<&RelationRule> {
+        self.rules.iter().find(|r| r.relation == relation)
+    }
+
+    // Forget a relation type that was removed from the registry: its own rule goes, and so do
+    // its endpoints in rules that allow something else as well
+    pub fn forget_relation(&mut self, relation: RelationTypeId) -> DroppedRules {
+        let rule = self
+            .rules
+            .iter()
+            .position(|r| r.relation == Some(relation))
+            .map(|index| (index, self.rules.remove(index)));
+        let endpoints = self.drop_endpoint(Endpoint::EdgeOfType(relation));
+        DroppedRules { rule, endpoints }
+    }
+
+    // Takes `endpoint` off every rule side that has other endpoints. Where it is the only one it
+    // stays, so the side matches nothing instead of turning into "anything"
+    fn drop_endpoint(&mut self, endpoint: Endpoint) -> Vec<DroppedEndpoint> {
+        let mut dropped = Vec::new();
+        for rule in &mut self.rules {
+            let relation = rule.relation;
+            for (source, side) in [(true, &mut rule.sources), (false, &mut rule.targets)] {
+                if side.iter().all(|e| *e == endpoint) {
+                    continue;
+                }
+                let mut index = 0;
+                side.retain(|e| {
+                    index += 1;
+                    if *e != endpoint {
+                        return true;
+                    }
+                    dropped.push(DroppedEndpoint { relation, source, index: index - 1, endpoint });
+                    false
+                });
+            }
+        }
+        dropped
+    }
+
+    // Undo what remove_kind or forget_relation took out, leaving rule edits made since alone.
+    // A rule that was removed since keeps its endpoints gone, and one already back is not doubled
+    pub fn restore_rules(&mut self, dropped: DroppedRules) {
+        if let Some((index, rule)) = dropped.rule {
+            if self.rule_for(rule.relation).is_none() {
+                self.rules.insert(index.min(self.rules.len()), rule);
+            }
+        }
+        // Ascending indices, so each one lands where it was once those before it are back
+        for DroppedEndpoint { relation, source, index, endpoint } in dropped.endpoints {
+            let Some(rule) = self.rules.iter_mut().find(|r| r.relation == relation) else {
+                continue;
+            };
+            let side = if source { &mut rule.sources } else { &mut rule.targets };
+            if !side.contains(&endpoint) {
+                side.insert(index.min(side.len()), endpoint);
+            }
+        }
+    }
+
+    // Check whether a new edge source -> target of the given relation would be allowed
+    pub fn check_edge(
+        &self,
+        graph: &Graph,
+        source: ID,
+        target: ID,
+        relation: Option<RelationTypeId>,
+    ) -> Result<(), SchemaViolation> {
+        let Some(rule) = self.rule_for(relation) else {
+            return self.check_unruled(relation);
+        };
+        Self::check_endpoints(rule, graph, source, target)?;
+
+        if let Some(max) = rule.max_outgoing {
+            if count_of_type(graph.get_outgoing_edges(source), graph, relation) >= max as usize {
+                return Err(SchemaViolation::TooManyOutgoing { relation, source, max });
+            }
+        }
+        if let Some(max) = rule.max_incoming {
+            if count_of_type(graph.get_incoming_edges(target), graph, relation) >= max as usize {
+                return Err(SchemaViolation::TooManyIncoming { relation, target, max });
+            }
+        }
+        Ok(())
+    }
+
+    // Check the whole document and list every violation
+    pub fn validate(&self, graph: &Graph) -> ValidationReport {
+        let mut report = ValidationReport::default();
+
+        for (id, _) in graph.nodes_iter() {
+            for violation in self.kind_violations(graph, id) {
+                report.violations.push((id, violation));
+            }
+        }
+
+        // Ordered so the report lists cardinalities the same way every time
+        let mut outgoing: BTreeMap<(ID, Option<RelationTypeId>), u32> = BTreeMap::new();
+        let mut incoming: BTreeMap<(ID, Option<RelationTypeId>), u32> = BTreeMap::new();
+        for edge in graph.edges_iter() {
+            let Some(rule) = self.rule_for(edge.relation) else {
+                if let Err(violation) = self.check_unruled(edge.relation) {
+                    report.violations.push((edge.id, violation));
+                }
+                continue;
+            };
+            if let Err(violation) = Self::check_endpoints(rule, graph, edge.source, edge.target) {
+                report.violations.push((edge.id, violation));
+            }
+            *outgoing.entry((edge.source, edge.relation)).or_default() += 1;
+            *incoming.entry((edge.target, edge.relation)).or_default() += 1;
+        }
+
+        // Cardinalities are reported once per over-connected element
+        for ((source, relation), count) in outgoing {
+            if let Some(max) = self.rule_for(relation).and_then(|r| r.max_outgoing) {
+                if count > max {
+                    report.violations.push((source, SchemaViolation::TooManyOutgoing { relation, source, max }));
+                }
+            }
+        }
+        for ((target, relation), count) in incoming {
+            if let Some(max) = self.rule_for(relation).and_then(|r| r.max_incoming) {
+                if count > max {
+                    report.violations.push((target, SchemaViolation::TooManyIncoming { relation, target, max }));
+                }
+            }
+        }
+
+        report
+    }
+
+    // Violations involving a single element: its kind and required properties and,
+    // for an edge, its endpoints and the limits at either end
+    pub fn validate_element(&self, graph: &Graph, id: ID) -> Vec<SchemaViolation> {
+        let mut violations = self.kind_violations(graph, id);
+        let Some(edge) = graph.get_edge(id) else {
+            return violations;
+        };
+        let relation = edge.relation;
+        let Some(rule) = self.rule_for(relation) else {
+            violations.extend(self.check_unruled(relation).err());
+            return violations;
+        };
+        violations.extend(Self::check_endpoints(rule, graph, edge.source, edge.target).err());
+
+        if let Some(max) = rule.max_outgoing {
+            if count_of_type(graph.get_outgoing_edges(edge.source), graph, relation) > max as usize {
+                violations.push(SchemaViolation::TooManyOutgoing { relation, source: edge.source, max });
+            }
+        }
+        if let Some(max) = rule.max_incoming {
+            if count_of_type(graph.get_incoming_edges(edge.target), graph, relation) > max as usize {
+                violations.push(SchemaViolation::TooManyIncoming { relation, target: edge.target, max });
+            }
+        }
+        violations
+    }
+
+    fn kind_violations(&self, graph: &Graph, id: ID) -> Vec<SchemaViolation> {
+        let Some(data) = graph.node_data(id) else {
+            return Vec::new();
+        };
+        let Some(kind_id) = data.kind else {
+            return Vec::new();
+        };
+        let Some(kind) = self.kinds.get(kind_id) else {
+            return vec![SchemaViolation::UnknownKind { element: id, kind: kind_id }];
+        };
+        kind.required_properties
+            .iter()
+            .filter(|property| !data.properties.contains_key(*property))
+            .map(|property| SchemaViolation::MissingProperty { element: id, property: property.clone() })
+            .collect()
+    }
+
+    // Human readable description using element, kind and relation names
+    pub fn describe(
+        &self,
+        violation: &SchemaViolation,
+        graph: &Graph,
+        relations: &RelationRegistry,
+    ) -> String {
+        let element = |id: ID| {
+            graph
+                .node_data(id)
+                .map(|d| d.name.as_str())
+                .filter(|name| !name.is_empty())
+                .map_or_else(|| "unnamed element".to_owned(), |name| format!("\"{name}\""))
+        };
+        let relation_name = |relation: &Option<RelationTypeId>| {
+            relation
+                .and_then(|r| relations.get(r))
+                .map_or_else(|| "untyped".to_owned(), |r| format!("\"{}\"", r.name))
+        };
+        match violation {
+            SchemaViolation::RelationNotAllowed { relation } => {
+                format!("{} relations are not allowed by the schema", relation_name(relation))
+            }
+            SchemaViolation::SourceNotAllowed { relation, source } => format!(
+                "{} can't be the source of a {} relation",
+                element(*source),
+                relation_name(relation)
+            ),
+            SchemaViolation::TargetNotAllowed { relation, target } => format!(
+                "{} can't be the target of a {} relation",
+                element(*target),
+                relation_name(relation)
+            ),
+            SchemaViolation::TooManyOutgoing { relation, source, max } => format!(
+                "{} may be the source of at most {max} {} relations",
+                element(*source),
+                relation_name(relation)
+            ),
+            SchemaViolation::TooManyIncoming { relation, target, max } => format!(
+                "{} may be the target of at most {max} {} relations",
+                element(*target),
+                relation_name(relation)
+            ),
+            SchemaViolation::UnknownKind { element: id, .. } => {
+                format!("{} has a kind the schema doesn't define", element(*id))
+            }
+            SchemaViolation::MissingProperty { element: id, property } => {
+                let kind = graph
+                    .node_data(*id)
+                    .and_then(|d| d.kind)
+                    .and_then(|k| self.kinds.get(k))
+                    .map_or("", |k| k.name.as_str());
+                format!("{} ({kind}) is missing required property \"{property}\"", element(*id))
+            }
+        }
+    }
+
+    fn check_unruled(&self, relation: Option<RelationTypeId>) -> Result<(), SchemaViolation> {
+        if self.strict {
+            Err(SchemaViolation::RelationNotAllowed { relation })
+        } else {
+            Ok(())
+        }
+    }
+
+    fn check_endpoints(
+        rule: &RelationRule,
+        graph: &Graph,
+        source: ID,
+        target: ID,
+    ) -> Result<(), SchemaViolation> {
+        let relation = rule.relation;
+        if !rule.sources.is_empty() && !rule.sources.iter().any(|e| e.matches(graph, source)) {
+            return Err(SchemaViolation::SourceNotAllowed { relation, source });
+        }
+        if !rule.targets.is_empty() && !rule.targets.iter().any(|e| e.matches(graph, target)) {
+            return Err(SchemaViolation::TargetNotAllowed { relation, target });
+        }
+        Ok(())
+    }
+}
+
+fn count_of_type(edges: Vec<ID>, graph: &Graph, relation: Option<RelationTypeId>) -> usize {
+    edges
+        .into_iter()
+        .filter(|&e| graph.get_edge(e).is_some_and(|edge| edge.relation == relation))
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{NodeData, PropertyValue};
+    use eframe::egui::Color32;
+
+    struct World {
+        graph: Graph,
+        relations: RelationRegistry,
+        schema: Schema,
+        character: NodeKindId,
+        place: NodeKindId,
+        father_of: RelationTypeId,
+        born_in: RelationTypeId,
+        witnessed: RelationTypeId,
+    }
+
+    fn world() -> World {
+        let mut relations = RelationRegistry::new();
+        let father_of = relations.add("father of", Color32::RED);
+        let born_in = relations.add("born in", Color32::GREEN);
+        let witnessed = relations.add("witnessed", Color32::BLUE);
+
+        let mut schema = Schema::new();
+        let mut character_kind = NodeKind::new("Character");
+        character_kind.required_properties.insert("age".into());
+        let character = schema.add_kind(character_kind);
+        let place = schema.add_kind(NodeKind::new("Place"));
+        schema.set_rule(
+            RelationRule::new(Some(father_of))
+                .from(Endpoint::Kind(character))
+                .to(Endpoint::Kind(character))
+                .max_incoming(1),
+        );
+        schema.set_rule(
+            RelationRule::new(Some(born_in))
+                .from(Endpoint::Kind(character))
+                .to(Endpoint::Kind(place))
+                .max_outgoing(1),
+        );
+        // Someone can witness a birth: character -> "born in" edge
+        schema.set_rule(
+            RelationRule::new(Some(witnessed))
+                .from(Endpoint::Kind(character))
+                .to(Endpoint::EdgeOfType(born_in)),
+        );
+        schema.strict = true;
+
+        World { graph: Graph::new(), relations, schema, character, place, father_of, born_in, witnessed }
+    }
+
+    fn add(graph: &mut Graph, name: &str, kind: NodeKindId) -> ID {
+        let id = graph.add_node(NodeData::named(name));
+        graph.node_data_mut(id).unwrap().kind = Some(kind);
+        id
+    }
+
+    #[test]
+    fn test_check_edge() {
+        let mut w = world();
+        let father = add(&mut w.graph, "Aldric", w.character);
+        let son = add(&mut w.graph, "Corwin", w.character);
+        let midwife = add(&mut w.graph, "Ilse", w.character);
+        let city = add(&mut w.graph, "Varenholm", w.place);
+        let g = &w.graph;
+
+        assert!(w.schema.check_edge(g, father, son, Some(w.father_of)).is_ok());
+        assert_eq!(
+            w.schema.check_edge(g, father, city, Some(w.father_of)),
+            Err(SchemaViolation::TargetNotAllowed { relation: Some(w.father_of), target: city })
+        );
+        assert_eq!(
+            w.schema.check_edge(g, city, son, Some(w.born_in)),
+            Err(SchemaViolation::SourceNotAllowed { relation: Some(w.born_in), source: city })
+        );
+        assert_eq!(
+            w.schema.check_edge(g, father, son, None),
+            Err(SchemaViolation::RelationNotAllowed { relation: None })
+        );
+
+        // Edge-to-edge rule
+        let birth = w.graph.add_typed_edge(son, city, Some(w.born_in)).unwrap();
+        let fatherhood = w.graph.add_typed_edge(father, son, Some(w.father_of)).unwrap();
+        let g = &w.graph;
+        assert!(w.schema.check_edge(g, midwife, birth, Some(w.witnessed)).is_ok());
+        assert!(w.schema.check_edge(g, midwife, fatherhood, Some(w.witnessed)).is_err());
+
+        // Cardinalities: one birthplace, one father
+        assert_eq!(
+            w.schema.check_edge(g, son, city, Some(w.born_in)),
+            Err(SchemaViolation::TooManyOutgoing { relation: Some(w.born_in), source: son, max: 1 })
+        );
+        assert_eq!(
+            w.schema.check_edge(g, midwife, son, Some(w.father_of)),
+            Err(SchemaViolation::TooManyIncoming { relation: Some(w.father_of), target: son, max: 1 })
+        );
+
+        // Non-strict schemas allow relations they don't mention
+        w.schema.strict = false;
+        assert!(w.schema.check_edge(&w.graph, father, city, None).is_ok());
+    }
+
+    #[test]
+    fn test_validate_document() {
+        let mut w = world();
+        let father = add(&mut w.graph, "Aldric", w.character);
+        let son = add(&mut w.graph, "Corwin", w.character);
+        let city = add(&mut w.graph, "Varenholm", w.place);
+        w.graph.set_property(father, "age", PropertyValue::Number(54.0)).unwrap();
+
+        // Built without going through the schema
+        w.graph.add_typed_edge(father, son, Some(w.father_of)).unwrap();
+        w.graph.add_typed_edge(city, son, Some(w.father_of)).unwrap();
+        let untyped = w.graph.add_edge(son, city).unwrap();
+
+        let report = w.schema.validate(&w.graph);
+        assert!(!report.is_valid());
+        let violations: Vec<_> = report.violations.iter().map(|(_, v)| v.clone()).collect();
+
+        assert!(violations.contains(&SchemaViolation::MissingProperty { element: son, property: "age".into() }));
+        assert!(violations.contains(&SchemaViolation::SourceNotAllowed { relation: Some(w.father_of), source: city }));
+        assert!(violations.contains(&SchemaViolation::TooManyIncoming { relation: Some(w.father_of), target: son, max: 1 }));
+        assert!(report.violations.contains(&(untyped, SchemaViolation::RelationNotAllowed { relation: None })));
+        assert_eq!(violations.len(), 4);
+
+        // Per-element checks agree with the whole-document report
+        let bad_edge = w.graph.get_incoming_edges_of_type(son, w.father_of)
+            .into_iter()
+            .find(|&e| w.graph.get_edge(e).unwrap().source == city)
+            .unwrap();
+        let element = w.schema.validate_element(&w.graph, bad_edge);
+        assert!(element.contains(&SchemaViolation::SourceNotAllowed { relation: Some(w.father_of), source: city }));
+        assert!(element.contains(&SchemaViolation::TooManyIncoming { relation: Some(w.father_of), target: son, max: 1 }));
+        assert_eq!(w.schema.validate_element(&w.graph, father), vec![]);
+
+        let text = w.schema.describe(&violations[0], &w.graph, &w.relations);
+        assert!(text.contains("Corwin"));
+
+        // Several over-connected elements are reported in the same order on every run
+        let daughter = add(&mut w.graph, "Maren", w.character);
+        w.graph.add_typed_edge(father, daughter, Some(w.father_of)).unwrap();
+        w.graph.add_typed_edge(son, daughter, Some(w.father_of)).unwrap();
+        let first = w.schema.validate(&w.graph).violations;
+        let limits: Vec<_> = first
+            .iter()
+            .filter(|(_, v)| matches!(v, SchemaViolation::TooManyIncoming { .. }))
+            .map(|&(id, _)| id)
+            .collect();
+        assert_eq!(limits, vec![son, daughter]);
+        for _ in 0..5 {
+            assert_eq!(w.schema.validate(&w.graph).violations, first);
+        }
+    }
+
+    #[test]
+    fn test_removal_never_loosens_rules() {
+        let mut w = world();
+        let father = add(&mut w.graph, "Aldric", w.character);
+        let hut = w.graph.add_node(NodeData::named("Hut"));
+        w.schema.strict = false;
+        w.schema.set_rule(RelationRule::new(None).to(Endpoint::Kind(w.place)).to(Endpoint::AnyEdge));
+
+        // "born in" only allowed places, so with places gone it allows nothing at all
+        let rejected = Err(SchemaViolation::TargetNotAllowed { relation: Some(w.born_in), target: hut });
+        assert_eq!(w.schema.check_edge(&w.graph, father, hut, Some(w.born_in)), rejected);
+        let (_, dropped) = w.schema.remove_kind(w.place).unwrap();
+        assert_eq!(w.schema.check_edge(&w.graph, father, hut, Some(w.born_in)), rejected);
+        assert_eq!(w.schema.rule_for(Some(w.born_in)).unwrap().targets, vec![Endpoint::Kind(w.place)]);
+
+        // A side with other choices only loses the kind
+        assert_eq!(w.schema.rule_for(None).unwrap().targets, vec![Endpoint::AnyEdge]);
+        assert_eq!(dropped.endpoints.len(), 1);
+        assert!(w.schema.check_edge(&w.graph, father, hut, None).is_err());
+
+        // Same for relation types: "witnessed" may still only point at births, of which there are none
+        let birth = w.graph.add_edge(father, hut).unwrap();
+        let dropped = w.schema.forget_relation(w.born_in);
+        assert_eq!(dropped.rule.map(|(_, rule)| rule.relation), Some(Some(w.born_in)));
+        assert!(w.schema.check_edge(&w.graph, father, birth, Some(w.witnessed)).is_err());
+    }
+}
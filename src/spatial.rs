@@ -0,0 +1,347 @@
+// spatial.rs
+// Where elements sit on the world plane. Positions are filed in a uniform grid as well, so hit
+// testing, box selection and culling only look at the cells they cover instead of every element
+use eframe::egui::{Pos2, Rect, Vec2};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use slotmap::SecondaryMap;
+use std::collections::{HashMap, HashSet};
+use std::ops::Index;
+
+use crate::graph::ID;
+
+// Side of a grid cell in world units, around a few nodes across
+const CELL_SIZE: f32 = 200.0;
+// Elements spanning more cells than this (long curved edges) go in one list checked by every
+// query rather than in each cell they cover
+const MAX_SPAN_CELLS: i64 = 64;
+
+type Cell = (i32, i32);
+
+fn cell_of(pos: Pos2) -> Cell {
+    ((pos.x / CELL_SIZE).floor() as i32, (pos.y / CELL_SIZE).floor() as i32)
+}
+
+// First and last cell a rect covers on each axis
+fn cells_of(rect: Rect) -> (Cell, Cell) {
+    (cell_of(rect.min), cell_of(rect.max))
+}
+
+fn cell_count((min, max): (Cell, Cell)) -> i64 {
+    (max.0 as i64 - min.0 as i64 + 1).saturating_mul(max.1 as i64 - min.1 as i64 + 1)
+}
+
+#[derive(Clone, Default)]
+struct Grid {
+    cells: HashMap<Cell, Vec<ID>>,
+    large: HashSet<ID>,
+    // The area each element is filed under
+    spans: SecondaryMap<ID, Rect>,
+}
+
+impl Grid {
+    fn insert(&mut self, id: ID, span: Rect) {
+        if let Some(old) = self.spans.get(id).copied() {
+            // Small moves mostly stay in the same cells
+            if !self.large.contains(&id) && cells_of(old) == cells_of(span) {
+                self.spans.insert(id, span);
+                return;
+            }
+            self.remove(id);
+        }
+        self.spans.insert(id, span);
+        let cells = cells_of(span);
+        if cell_count(cells) > MAX_SPAN_CELLS {
+            self.large.insert(id);
+            return;
+        }
+        let ((x0, y0), (x1, y1)) = cells;
+        for x in x0..=x1 {
+            for y in y0..=y1 {
+                self.cells.entry((x, y)).or_default().push(id);
+            }
+        }
+    }
+
+    fn remove(&mut self, id: ID) {
+        let Some(span) = self.spans.remove(id) else {
+            return;
+        };
+        if self.large.remove(&id) {
+            return;
+        }
+        let ((x0, y0), (x1, y1)) = cells_of(span);
+        for x in x0..=x1 {
+            for y in y0..=y1 {
+                if let Some(cell) = self.cells.get_mut(&(x, y)) {
+                    if let Some(i) = cell.iter().position(|&other| other == id) {
+                        cell.swap_remove(i);
+                    }
+                    if cell.is_empty() {
+                        self.cells.remove(&(x, y));
+                    }
+                }
+            }
+        }
+    }
+
+    // Every element whose span meets `rect`, each once
+    fn query(&self, rect: Rect, mut found: impl FnMut(ID)) {
+        let ((x0, y0), (x1, y1)) = cells_of(rect);
+        let outer = cell_count(((x0, y0), (x1, y1)));
+        if outer > self.cells.len() as i64 {
+            // Zoomed far out: cheaper to walk the filled cells than the empty ones
+            for (&(x, y), ids) in &self.cells {
+                if (x0..=x1).contains(&x) && (y0..=y1).contains(&y) {
+                    self.visit_cell((x, y), (x0, y0), ids, rect, &mut found);
+                }
+            }
+        } else {
+            for x in x0..=x1 {
+                for y in y0..=y1 {
+                    if let Some(ids) = self.cells.get(&(x, y)) {
+                        self.visit_cell((x, y), (x0, y0), ids, rect, &mut found);
+                    }
+                }
+            }
+        }
+        for &id in &self.large {
+            let span = self.spans[id];
+            if span.intersects(rect) {
+                found(id);
+            }
+        }
+    }
+
+    // An element covering several cells is reported only from the first one the query shares with it
+    fn visit_cell(&self, cell: Cell, first: Cell, ids: &[ID], rect: Rect, found: &mut impl FnMut(ID)) {
+        for &id in ids {
+            let span = self.spans[id];
+            let (own, _) = cells_of(span);
+            if cell == (own.0.max(first.0), own.1.max(first.1)) && span.intersects(rect) {
+                found(id);
+            }
+        }
+    }
+}
+
+// Element positions, kept in step with a grid over them.
+// Saved exactly like the plain map it wraps
+#[derive(Clone, Default)]
+pub struct Positions {
+    map: SecondaryMap<ID, Pos2>,
+    grid: Grid,
+}
+
+impl Positions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn get(&self, id: ID) -> Option<&Pos2> {
+        self.map.get(id)
+    }
+
+    pub fn contains_key(&self, id: ID) -> bool {
+        self.map.contains_key(id)
+    }
+
+    pub fn iter(&self) -> slotmap::secondary::Iter<'_, ID, Pos2> {
+        self.map.iter()
+    }
+
+    pub fn keys(&self) -> slotmap::secondary::Keys<'_, ID, Pos2> {
+        self.map.keys()
+    }
+
+    pub fn values(&self) -> slotmap::secondary::Values<'_, ID, Pos2> {
+        self.map.values()
+    }
+
+    // Returns the previous position
+    pub fn insert(&mut self, id: ID, pos: Pos2) -> Option<Pos2> {
+        self.insert_spanning(id, pos, Rect::from_min_size(pos, Vec2::ZERO))
+    }
+
+    // Place an element that is drawn well beyond its position, like a curved edge, so queries
+    // touching any of `span` find it
+    pub fn insert_spanning(&mut self, id: ID, pos: Pos2, span: Rect) -> Option<Pos2> {
+        let previous = self.map.insert(id, pos);
+        // Keys of removed elements are refused by the map
+        if self.map.contains_key(id) {
+            self.grid.insert(id, span.union(Rect::from_min_size(pos, Vec2::ZERO)));
+        }
+        previous
+    }
+
+    pub fn remove(&mut self, id: ID) -> Option<Pos2> {
+        self.grid.remove(id);
+        self.map.remove(id)
+    }
+
+    pub fn retain(&mut self, mut keep: impl FnMut(ID, &Pos2) -> bool) {
+        let grid = &mut self.grid;
+        self.map.retain(|id, pos| {
+            let kept = keep(id, pos);
+            if !kept {
+                grid.remove(id);
+            }
+            kept
+        });
+    }
+
+    // Every element whose position lies inside `rect`
+    pub fn in_rect(&self, rect: Rect) -> Vec<ID> {
+        let mut ids = Vec::new();
+        self.grid.query(rect, |id| {
+            if rect.contains(self.map[id]) {
+                ids.push(id);
+            }
+        });
+        ids
+    }
+
+    // Every element drawn anywhere inside `rect`, edges included when their curve may cross it
+    pub fn touching(&self, rect: Rect) -> Vec<ID> {
+        let mut ids = Vec::new();
+        self.grid.query(rect, |id| ids.push(id));
+        ids
+    }
+
+    // The element closest to `pos`, if any is nearer than `radius`
+    pub fn nearest(&self, pos: Pos2, radius: f32) -> Option<ID> {
+        let mut best: Option<(ID, f32)> = None;
+        self.grid.query(Rect::from_center_size(pos, Vec2::splat(radius * 2.0)), |id| {
+            let distance = self.map[id].distance(pos);
+            if distance < radius && best.is_none_or(|(_, d)| distance < d) {
+                best = Some((id, distance));
+            }
+        });
+        best.map(|(id, _)| id)
+    }
+}
+
+impl Index<ID> for Positions {
+    type Output = Pos2;
+
+    fn index(&self, id: ID) -> &Pos2 {
+        &self.map[id]
+    }
+}
+
+impl<'a> IntoIterator for &'a Positions {
+    type Item = (ID, &'a Pos2);
+    type IntoIter = slotmap::secondary::Iter<'a, ID, Pos2>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.map.iter()
+    }
+}
+
+impl From<SecondaryMap<ID, Pos2>> for Positions {
+    fn from(map: SecondaryMap<ID, Pos2>) -> Self {
+        let mut positions = Positions::new();
+        for (id, &pos) in &map {
+            positions.insert(id, pos);
+        }
+        positions
+    }
+}
+
+impl From<Positions> for SecondaryMap<ID, Pos2> {
+    fn from(positions: Positions) -> Self {
+        positions.map
+    }
+}
+
+impl Serialize for Positions {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.map.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Positions {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        SecondaryMap::deserialize(deserializer).map(Positions::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use slotmap::SlotMap;
+
+    fn scattered(count: usize) -> (SlotMap<ID, ()>, Positions) {
+        let mut ids = SlotMap::with_key();
+        let mut positions = Positions::new();
+        for i in 0..count {
+            // Spread over a few thousand units, including negative cells
+            let pos = Pos2::new((i * 7919 % 3001) as f32 - 1500.0, (i * 104729 % 2003) as f32 - 1000.0);
+            positions.insert(ids.insert(()), pos);
+        }
+        (ids, positions)
+    }
+
+    fn sorted(mut ids: Vec<ID>) -> Vec<ID> {
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn test_queries_match_a_linear_scan() {
+        let (_, mut positions) = scattered(2000);
+        let moved: Vec<ID> = positions.keys().step_by(3).collect();
+        for (i, &id) in moved.iter().enumerate() {
+            positions.insert(id, positions[id] + Vec2::new(i as f32 * 0.7, -(i as f32)));
+        }
+        positions.retain(|id, _| !moved[..100].contains(&id));
+
+        for rect in [
+            Rect::from_min_max(Pos2::new(-300.0, -250.0), Pos2::new(420.0, 90.0)),
+            Rect::from_min_max(Pos2::new(-5000.0, -5000.0), Pos2::new(5000.0, 5000.0)),
+            Rect::from_min_max(Pos2::new(1.0, 1.0), Pos2::new(2.0, 2.0)),
+        ] {
+            let scan: Vec<ID> = positions.iter().filter(|(_, &p)| rect.contains(p)).map(|(id, _)| id).collect();
+            assert_eq!(sorted(positions.in_rect(rect)), sorted(scan.clone()));
+            assert_eq!(sorted(positions.touching(rect)), sorted(scan));
+        }
+
+        let target = Pos2::new(10.0, -20.0);
+        let closest = positions
+            .iter()
+            .min_by(|(_, a), (_, b)| a.distance(target).total_cmp(&b.distance(target)))
+            .map(|(id, _)| id);
+        assert_eq!(positions.nearest(target, 1000.0), closest);
+        assert_eq!(positions.nearest(Pos2::new(9000.0, 0.0), 10.0), None);
+    }
+
+    #[test]
+    fn test_spans_are_found_from_any_cell_they_cover() {
+        let mut ids: SlotMap<ID, ()> = SlotMap::with_key();
+        let mut positions = Positions::new();
+        let (short, long) = (ids.insert(()), ids.insert(()));
+        positions.insert_spanning(short, Pos2::new(0.0, 0.0), Rect::from_min_max(Pos2::new(-500.0, 0.0), Pos2::new(500.0, 0.0)));
+        // Far too long to file in every cell
+        positions.insert_spanning(long, Pos2::new(0.0, 0.0), Rect::from_min_max(Pos2::ZERO, Pos2::new(20000.0, 20000.0)));
+
+        let far_left = Rect::from_center_size(Pos2::new(-450.0, 0.0), Vec2::splat(20.0));
+        assert_eq!(positions.touching(far_left), vec![short]);
+        assert!(positions.in_rect(far_left).is_empty());
+        let far_out = Rect::from_center_size(Pos2::new(15000.0, 15000.0), Vec2::splat(20.0));
+        assert_eq!(positions.touching(far_out), vec![long]);
+        // Each once, however many cells the query shares with them
+        assert_eq!(sorted(positions.touching(Rect::everything_right_of(-1000.0))), sorted(vec![short, long]));
+
+        positions.remove(long);
+        positions.insert(short, Pos2::new(3000.0, 0.0));
+        assert!(positions.touching(far_left).is_empty() && positions.touching(far_out).is_empty());
+        assert_eq!(positions.in_rect(Rect::from_center_size(Pos2::new(3000.0, 0.0), Vec2::splat(1.0))), vec![short]);
+    }
+}
@@ -1,15 +1,25 @@
 // graph_state.rs
-use eframe::egui::{Pos2, Vec2};
-use crate::graph::{Graph, ID, NodeData};
+use eframe::egui::{Pos2, Rect, Vec2};
+use crate::graph::{Graph, GraphError, ID, NodeData, NodeKindId, RelationTypeId, Subgraph};
+use crate::relations::RelationRegistry;
+use crate::schema::{DroppedRules, Schema};
+use crate::style::{ResolvedStyle, Theme};
+use crate::file_format::{self, Format};
+use crate::spatial::Positions;
 use slotmap::SecondaryMap;
 use serde::{Serialize, Deserialize};
 use std::io::{Read, Write};
 use std::fs::File;
-use std::collections::{HashSet, VecDeque};
+use std::collections::{BTreeSet, HashSet, VecDeque};
 use std::path::Path;
 
+// Distance between the curves of edges that run between the same two elements
+const PARALLEL_EDGE_GAP: f32 = 30.0;
+// A control point dropped this close to where the edge would rest re-attaches it
+const REATTACH_DISTANCE: f32 = 6.0;
+
 // Camera state to manage pan and zoom
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
 pub struct Camera {
     pub offset: Vec2,     // Translation/pan offset
     pub zoom: f32,        // Zoom factor
@@ -46,16 +56,41 @@ impl Camera {
 #[derive(Serialize, Deserialize, Clone)]
 pub struct GraphState {
     pub graph: Graph,
-    pub positions: SecondaryMap<ID, Pos2>,
+    pub positions: Positions,
     pub camera: Camera,
+    pub relations: RelationRegistry,
+    pub schema: Option<Schema>,
+    pub theme: Theme,
+    // Nodes automatic layouts leave where they are
+    #[serde(default)]
+    pub pinned: BTreeSet<ID>,
+    // Edges whose control point was dragged off, as an offset from the midpoint of their ends.
+    // The others rest on the midpoint, spread sideways when several run between the same ends
+    #[serde(default)]
+    pub edge_offsets: SecondaryMap<ID, Vec2>,
+}
+
+
+// Elements taken out of a document together with where they were drawn
+#[derive(Debug, Clone, Default)]
+pub struct Detached {
+    pub subgraph: Subgraph,
+    pub positions: Vec<(ID, Pos2)>,
+    pub pinned: Vec<ID>,
+    pub edge_offsets: Vec<(ID, Vec2)>,
 }
 
 impl Default for GraphState {
     fn default() -> Self {
         Self {
             graph: Graph::new(),
-            positions: SecondaryMap::new(),
+            positions: Positions::new(),
             camera: Camera::default(),
+            relations: RelationRegistry::new(),
+            schema: None,
+            theme: Theme::default(),
+            pinned: BTreeSet::new(),
+            edge_offsets: SecondaryMap::new(),
         }
     }
 }
@@ -66,24 +101,39 @@ impl GraphState {
         Self::default()
     }
     
-    // Recursively update midpoints for edge-nodes connected to `start_id`
+    // The style an element is drawn with after applying its kind and the theme
+    pub fn style_of(&self, id: ID) -> ResolvedStyle {
+        let is_edge = self.graph.get_edge(id).is_some();
+        let Some(data) = self.graph.node_data(id) else {
+            return self.theme.resolve(is_edge, None, &Default::default());
+        };
+        let kind = self
+            .schema
+            .as_ref()
+            .zip(data.kind)
+            .and_then(|(schema, kind)| schema.kinds.get(kind))
+            .map(|kind| &kind.style);
+        self.theme.resolve(is_edge, kind, &data.style)
+    }
+
+    // Recursively update control points for edge-nodes connected to `start_id`
     pub fn update_positions_recursive(&mut self, start_id: ID) {
+        self.update_positions_from([start_id]);
+    }
+
+    // Update control points for every edge-node connected to any of `start_ids`.
+    // Moving a group should call this once rather than once per element
+    pub fn update_positions_from(&mut self, start_ids: impl IntoIterator<Item = ID>) {
         let mut visited = HashSet::new();
-        let mut queue = VecDeque::new();
-        queue.push_back(start_id);
+        let mut queue: VecDeque<ID> = start_ids.into_iter().collect();
+        let mut affected = Vec::new();
 
         while let Some(curr) = queue.pop_front() {
             if !visited.insert(curr) {
                 continue;
             }
-            if let Some(edge) = self.graph.get_edge(curr) {
-                if let (Some(&src), Some(&tgt)) = (
-                    self.positions.get(edge.source),
-                    self.positions.get(edge.target),
-                ) {
-                    let mid = ((src.to_vec2() + tgt.to_vec2()) * 0.5).to_pos2();
-                    self.positions.insert(curr, mid);
-                }
+            if self.graph.get_edge(curr).is_some() {
+                affected.push(curr);
             }
             let outgoing = self.graph.get_outgoing_edges(curr);
             let incoming = self.graph.get_incoming_edges(curr);
@@ -91,13 +141,157 @@ impl GraphState {
                 queue.push_back(neighbor);
             }
         }
+
+        // An edge attached to another edge must wait for that edge's control point
+        let mut settled = HashSet::new();
+        for edge_id in affected {
+            self.settle_edge(edge_id, &visited, &mut settled);
+        }
     }
-    
-    // Clean up positions that don't have corresponding graph elements
+
+    // Re-place every edge, e.g. after loading a file from before parallel edges were spread out
+    pub fn settle_all_edges(&mut self) {
+        let ids: Vec<ID> = self.graph.nodes_iter().map(|(id, _)| id).collect();
+        self.update_positions_from(ids);
+    }
+
+    fn settle_edge(&mut self, edge_id: ID, affected: &HashSet<ID>, settled: &mut HashSet<ID>) {
+        if !settled.insert(edge_id) {
+            return;
+        }
+        let Some(edge) = self.graph.get_edge(edge_id) else {
+            return;
+        };
+        let (source, target) = (edge.source, edge.target);
+        for end in [source, target] {
+            if affected.contains(&end) && self.graph.get_edge(end).is_some() {
+                self.settle_edge(end, affected, settled);
+            }
+        }
+        let point = match (self.edge_offsets.get(edge_id), self.midpoint(edge_id)) {
+            (Some(&offset), Some(mid)) => Some(mid + offset),
+            _ => self.resting_point(edge_id),
+        };
+        let (Some(point), Some(mid)) = (point, self.midpoint(edge_id)) else {
+            return;
+        };
+        // The curve through the control point stays inside the triangle of its ends and
+        // the curve's own control point
+        let span = Rect::from_points(&[self.positions[source], self.positions[target], point + (point - mid)]);
+        self.positions.insert_spanning(edge_id, point, span);
+    }
+
+    // Halfway between an edge's ends, if both are placed
+    pub fn midpoint(&self, edge_id: ID) -> Option<Pos2> {
+        let edge = self.graph.get_edge(edge_id)?;
+        let (src, tgt) = (self.positions.get(edge.source)?, self.positions.get(edge.target)?);
+        Some(src.lerp(*tgt, 0.5))
+    }
+
+    // Where an edge's control point sits while attached: the midpoint, pushed sideways so
+    // attached edges between the same two elements (in either direction) don't overlap
+    pub fn resting_point(&self, edge_id: ID) -> Option<Pos2> {
+        let edge = self.graph.get_edge(edge_id)?;
+        let (source, target) = (edge.source, edge.target);
+        let mid = self.midpoint(edge_id)?;
+        let mut siblings: Vec<ID> = self
+            .graph
+            .get_outgoing_edges(source)
+            .into_iter()
+            .chain(self.graph.get_incoming_edges(source))
+            .filter(|&e| e == edge_id || !self.edge_offsets.contains_key(e))
+            .filter(|&e| self.graph.get_edge(e).is_some_and(|e| e.source == target || e.target == target))
+            .collect();
+        siblings.sort();
+        siblings.dedup();
+        if siblings.len() < 2 {
+            return Some(mid);
+        }
+        // Measured from the lower ID's side so both directions agree on which way is which
+        let (low, high) = if source < target { (source, target) } else { (target, source) };
+        let normal = (self.positions[high] - self.positions[low]).normalized().rot90();
+        let index = siblings.iter().position(|&e| e == edge_id)? as f32;
+        let shift = index - (siblings.len() - 1) as f32 * 0.5;
+        Some(mid + normal * shift * PARALLEL_EDGE_GAP)
+    }
+
+    // Whether an edge's control point was dragged off its resting point
+    pub fn is_detached(&self, edge_id: ID) -> bool {
+        self.edge_offsets.contains_key(edge_id)
+    }
+
+    // Drag an edge's control point to `pos`. It keeps its offset from the midpoint as the ends
+    // move, until it is dropped back near its resting point or re-attached
+    pub fn set_control_point(&mut self, edge_id: ID, pos: Pos2) -> Result<(), GraphError> {
+        if self.graph.get_edge(edge_id).is_none() {
+            return Err(GraphError::MissingEdge(edge_id));
+        }
+        let (Some(rest), Some(mid)) = (self.resting_point(edge_id), self.midpoint(edge_id)) else {
+            return Ok(());
+        };
+        let offset = (pos.distance(rest) >= REATTACH_DISTANCE).then_some(pos - mid);
+        self.set_edge_offset(edge_id, offset)?;
+        Ok(())
+    }
+
+    // Hold an edge's control point at `offset` from the midpoint, or re-attach it with None.
+    // Returns the previous offset
+    pub fn set_edge_offset(&mut self, edge_id: ID, offset: Option<Vec2>) -> Result<Option<Vec2>, GraphError> {
+        let edge = self.graph.get_edge(edge_id).ok_or(GraphError::MissingEdge(edge_id))?;
+        let (source, target) = (edge.source, edge.target);
+        let previous = match offset {
+            Some(offset) => self.edge_offsets.insert(edge_id, offset),
+            None => self.edge_offsets.remove(edge_id),
+        };
+        // Its attached siblings spread out again without it, or make room for it
+        self.update_positions_from([source, target, edge_id]);
+        Ok(previous)
+    }
+
+    // Put elements at the given positions: nodes first, then edge control points, so a
+    // control point moved along with its ends keeps its place relative to them.
+    // Returns where the elements were
+    pub fn move_elements(&mut self, moves: &[(ID, Pos2)]) -> Vec<(ID, Pos2)> {
+        let previous = moves
+            .iter()
+            .filter_map(|&(id, _)| self.positions.get(id).map(|&pos| (id, pos)))
+            .collect();
+        let (edges, nodes): (Vec<_>, Vec<_>) = moves
+            .iter()
+            .filter(|&&(id, _)| self.graph.contains(id))
+            .partition(|&&(id, _)| self.graph.get_edge(id).is_some());
+        // Removed elements are skipped, so a layout still running can't place them again
+        for &(id, pos) in &nodes {
+            self.positions.insert(id, pos);
+        }
+        self.update_positions_from(nodes.into_iter().map(|(id, _)| id));
+        for (id, pos) in edges {
+            // Only edges get here, and those always exist
+            let _ = self.set_control_point(id, pos);
+        }
+        previous
+    }
+
+    // Clean up positions (and pins and control point offsets) that don't have corresponding graph elements.
+    // Removing an element already takes its own along, so this is only needed for loaded files
     pub fn cleanup_positions(&mut self) {
         self.positions.retain(|id, _| {
             self.graph.get_node(id).is_some() || self.graph.get_edge(id).is_some()
         });
+        self.pinned.retain(|&id| self.graph.contains(id));
+        self.edge_offsets.retain(|id, _| self.graph.get_edge(id).is_some());
+    }
+
+    pub fn is_pinned(&self, id: ID) -> bool {
+        self.pinned.contains(&id)
+    }
+
+    // Pin or unpin an element, returning whether it was pinned before
+    pub fn set_pinned(&mut self, id: ID, pinned: bool) -> Result<bool, GraphError> {
+        if !self.graph.contains(id) {
+            return Err(GraphError::MissingNode(id));
+        }
+        Ok(if pinned { !self.pinned.insert(id) } else { self.pinned.remove(&id) })
     }
     
     // Add a new node at the given position
@@ -107,47 +301,168 @@ impl GraphState {
         node_id
     }
     
-    // Remove a node or edge by ID
-    pub fn remove_element(&mut self, id: ID) {
-        if self.graph.get_node(id).is_some() {
-            self.graph.remove_node(id);
-        } else if self.graph.get_edge(id).is_some() {
-            self.graph.remove_edge(id);
+    // Remove a node or edge by ID (edges are nodes too, so one call covers both).
+    // Returns everything that was removed so it can be restored later
+    pub fn remove_element(&mut self, id: ID) -> Result<Detached, GraphError> {
+        let subgraph = self.graph.detach(id)?;
+        let positions = subgraph
+            .ids()
+            .filter_map(|id| self.positions.remove(id).map(|pos| (id, pos)))
+            .collect();
+        let pinned = subgraph.ids().filter(|id| self.pinned.remove(id)).collect();
+        let edge_offsets = subgraph
+            .ids()
+            .filter_map(|id| self.edge_offsets.remove(id).map(|offset| (id, offset)))
+            .collect();
+        // Parallel edges that are left close the gap
+        let ends: Vec<ID> = subgraph.edges.iter().flat_map(|e| [e.source, e.target]).collect();
+        self.update_positions_from(ends);
+        Ok(Detached { subgraph, positions, pinned, edge_offsets })
+    }
+
+    // Put removed elements back under their original IDs
+    pub fn restore(&mut self, mut detached: Detached) -> Result<(), GraphError> {
+        // The relation type may have been deleted while the edge was gone
+        for edge in &mut detached.subgraph.edges {
+            edge.relation = edge.relation.filter(|&r| self.relations.contains(r));
+        }
+        let ends: Vec<ID> = detached.subgraph.edges.iter().flat_map(|e| [e.source, e.target]).collect();
+        self.graph.restore(detached.subgraph)?;
+        for (id, pos) in detached.positions {
+            self.positions.insert(id, pos);
+        }
+        self.pinned.extend(detached.pinned);
+        for (id, offset) in detached.edge_offsets {
+            self.edge_offsets.insert(id, offset);
         }
-        self.positions.remove(id);
+        self.update_positions_from(ends);
+        Ok(())
     }
     
     // Add an edge between two nodes
-    pub fn add_edge_between(&mut self, source: ID, target: ID) -> Option<ID> {
-        self.graph.add_edge(source, target).inspect(|&edge_id| {
-            // Calculate midpoint position for the edge
-            if let (Some(&src_pos), Some(&tgt_pos)) = (
-                self.positions.get(source),
-                self.positions.get(target),
-            ) {
-                let mid = ((src_pos.to_vec2() + tgt_pos.to_vec2()) * 0.5).to_pos2();
-                self.positions.insert(edge_id, mid);
-            }
+    pub fn add_edge_between(
+        &mut self,
+        source: ID,
+        target: ID,
+        relation: Option<RelationTypeId>,
+    ) -> Result<ID, GraphError> {
+        // Don't let edges point at a relation type this document doesn't know about
+        if let Some(r) = relation.filter(|&r| !self.relations.contains(r)) {
+            return Err(GraphError::UnknownRelation(r));
+        }
+        if let Some(schema) = &self.schema {
+            schema.check_edge(&self.graph, source, target, relation)?;
+        }
+        self.graph.add_typed_edge(source, target, relation).inspect(|_| {
+            // Place the new edge and spread it out from any parallel ones
+            self.update_positions_from([source, target]);
         })
     }
     
+    // Remove a relation type from the registry, leaving its edges untyped.
+    // Returns the edges that had it and what came out of the schema rules
+    pub fn remove_relation_type(&mut self, relation: RelationTypeId) -> Result<(Vec<ID>, DroppedRules), GraphError> {
+        self.relations.remove(relation).ok_or(GraphError::UnknownRelation(relation))?;
+        let edges = self.graph.clear_relation_type(relation);
+        let dropped = self
+            .schema
+            .as_mut()
+            .map(|schema| schema.forget_relation(relation))
+            .unwrap_or_default();
+        Ok((edges, dropped))
+    }
+
+    // Undo remove_relation_type: bring the type back, give it to `edges` again and put back
+    // the rule entries it dropped
+    pub fn restore_relation_type(
+        &mut self,
+        relation: RelationTypeId,
+        edges: &[ID],
+        dropped: DroppedRules,
+    ) -> Result<(), GraphError> {
+        if let Some(&missing) = edges.iter().find(|&&id| self.graph.get_edge(id).is_none()) {
+            return Err(GraphError::MissingEdge(missing));
+        }
+        if !self.relations.restore(relation) {
+            return Err(GraphError::UnknownRelation(relation));
+        }
+        for &id in edges {
+            self.graph.set_edge_relation(id, Some(relation))?;
+        }
+        if let Some(schema) = &mut self.schema {
+            schema.restore_rules(dropped);
+        }
+        Ok(())
+    }
+
+    // Remove a node kind from the schema, leaving its nodes without a kind.
+    // Returns the nodes that had it and what came out of the schema rules
+    pub fn remove_node_kind(&mut self, kind: NodeKindId) -> Result<(Vec<ID>, DroppedRules), GraphError> {
+        let schema = self.schema.as_mut().ok_or(GraphError::NoSchema)?;
+        let (_, dropped) = schema.remove_kind(kind).ok_or(GraphError::UnknownKind(kind))?;
+        let nodes = self.graph.nodes_of_kind(kind);
+        for &id in &nodes {
+            self.graph.set_kind(id, None)?;
+        }
+        Ok((nodes, dropped))
+    }
+
+    // Undo remove_node_kind: bring the kind back, give it to `nodes` again and put back the
+    // rule entries it dropped
+    pub fn restore_node_kind(&mut self, kind: NodeKindId, nodes: &[ID], dropped: DroppedRules) -> Result<(), GraphError> {
+        let schema = self.schema.as_mut().ok_or(GraphError::NoSchema)?;
+        if let Some(&missing) = nodes.iter().find(|&&id| !self.graph.contains(id)) {
+            return Err(GraphError::MissingNode(missing));
+        }
+        if !schema.restore_kind(kind) {
+            return Err(GraphError::UnknownKind(kind));
+        }
+        for &id in nodes {
+            self.graph.set_kind(id, Some(kind))?;
+        }
+        schema.restore_rules(dropped);
+        Ok(())
+    }
+
+    // Assign a kind from the schema to an element
+    pub fn set_node_kind(&mut self, id: ID, kind: Option<NodeKindId>) -> Result<Option<NodeKindId>, GraphError> {
+        if let Some(kind) = kind {
+            let schema = self.schema.as_ref().ok_or(GraphError::NoSchema)?;
+            if !schema.kinds.contains_key(kind) {
+                return Err(GraphError::UnknownKind(kind));
+            }
+        }
+        self.graph.set_kind(id, kind)
+    }
+
+    // Every element whose position lies inside `rect` (world coordinates)
+    pub fn elements_in_rect(&self, rect: Rect) -> Vec<ID> {
+        self.positions.in_rect(rect)
+    }
+
+    // Every element drawn inside `rect` (world coordinates), in a stable back-to-front order.
+    // Edges whose curve may cross it count, and so do the ends of those edges
+    pub fn elements_touching(&self, rect: Rect) -> Vec<ID> {
+        let mut ids = self.positions.touching(rect);
+        let ends: Vec<ID> = ids
+            .iter()
+            .filter_map(|&id| self.graph.get_edge(id))
+            .flat_map(|edge| [edge.source, edge.target])
+            .collect();
+        ids.extend(ends);
+        ids.sort();
+        ids.dedup();
+        ids
+    }
+
     // Find the closest element to the given position
     pub fn find_element_at(&self, position: Pos2, hit_radius: f32) -> Option<ID> {
-        self.positions
-            .iter()
-            .filter(|(_, &pos)| pos.distance(position) < hit_radius)
-            .min_by(|(_, &pos_a), (_, &pos_b)| {
-                let dist_a = pos_a.distance(position);
-                let dist_b = pos_b.distance(position);
-                dist_a.partial_cmp(&dist_b).unwrap_or(std::cmp::Ordering::Equal)
-            })
-            .map(|(id, _)| id)
+        self.positions.nearest(position, hit_radius)
     }
         
     // Save the graph state to a file
     pub fn save_to_file(&self, path: &Path) -> std::io::Result<()> {
-        let encoded = bincode::serialize(self)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+        let encoded = file_format::encode(self, Format::from_path(path))?;
         let mut file = File::create(path)?;
         file.write_all(&encoded)?;
         Ok(())
@@ -158,8 +473,159 @@ impl GraphState {
         let mut file = File::open(path)?;
         let mut buffer = Vec::new();
         file.read_to_end(&mut buffer)?;
-        let state: GraphState = bincode::deserialize(&buffer)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-        Ok(state)
+        file_format::decode(&buffer, Format::from_path(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::PropertyValue;
+    use crate::schema::NodeKind;
+
+    #[test]
+    fn test_save_load_round_trip() {
+        let mut state = GraphState::new();
+        let a = state.add_node_at(Pos2::new(10.0, 20.0));
+        let b = state.add_node_at(Pos2::new(-5.0, 3.0));
+        let rules = state.relations.add("rules over", eframe::egui::Color32::GOLD);
+        let edge = state.add_edge_between(a, b, Some(rules)).unwrap();
+        state.graph.set_edge_label(edge, Some("by treaty".into())).unwrap();
+
+        state.graph.set_name(a, "Harbor").unwrap();
+        state.graph.set_description(a, "Where the fleet winters").unwrap();
+        state.graph.add_tag(a, "place").unwrap();
+        state.graph.set_property(a, "population", PropertyValue::Number(1200.0)).unwrap();
+        state.graph.set_property(a, "neighbor", PropertyValue::Reference(b)).unwrap();
+
+        let path = std::env::temp_dir().join(format!("node_sim_round_trip_{}.bin", std::process::id()));
+        state.save_to_file(&path).unwrap();
+        let loaded = GraphState::load_from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.graph.node_data(a), state.graph.node_data(a));
+        assert_eq!(loaded.graph.node_data(b), state.graph.node_data(b));
+        assert_eq!(loaded.positions.get(a), state.positions.get(a));
+        assert_eq!(loaded.graph.edges_iter().count(), 1);
+        assert_eq!(loaded.graph.edges_of_type(rules), vec![edge]);
+        assert_eq!(loaded.graph.get_edge(edge).unwrap().label.as_deref(), Some("by treaty"));
+        assert_eq!(loaded.relations.get(rules).unwrap().name, "rules over");
+    }
+
+    #[test]
+    fn test_group_position_update() {
+        let mut state = GraphState::new();
+        let a = state.add_node_at(Pos2::new(0.0, 0.0));
+        let b = state.add_node_at(Pos2::new(10.0, 0.0));
+        let c = state.add_node_at(Pos2::new(0.0, 10.0));
+        let ab = state.add_edge_between(a, b, None).unwrap();
+        let on_edge = state.add_edge_between(c, ab, None).unwrap();
+
+        // Move c and b together; the edge-on-edge must see ab's new midpoint
+        state.positions.insert(b, Pos2::new(20.0, 0.0));
+        state.positions.insert(c, Pos2::new(0.0, 20.0));
+        state.update_positions_from([c, b]);
+        assert_eq!(state.positions.get(ab), Some(&Pos2::new(10.0, 0.0)));
+        assert_eq!(state.positions.get(on_edge), Some(&Pos2::new(5.0, 10.0)));
+
+        let boxed = state.elements_in_rect(Rect::from_min_max(Pos2::new(-1.0, -1.0), Pos2::new(11.0, 1.0)));
+        assert_eq!(boxed.len(), 2);
+        assert!(boxed.contains(&a) && boxed.contains(&ab));
+    }
+
+    #[test]
+    fn test_removal_leaves_no_positions_behind() {
+        let mut state = GraphState::new();
+        let a = state.add_node_at(Pos2::new(0.0, 0.0));
+        let b = state.add_node_at(Pos2::new(100.0, 0.0));
+        let ab = state.add_edge_between(a, b, None).unwrap();
+        state.set_control_point(ab, Pos2::new(50.0, 40.0)).unwrap();
+        state.set_pinned(a, true).unwrap();
+
+        state.remove_element(a).unwrap();
+        // A layout that was still moving the removed elements doesn't place them again
+        state.move_elements(&[(a, Pos2::new(5.0, 5.0)), (ab, Pos2::new(50.0, 5.0))]);
+        assert_eq!(state.positions.len(), 1);
+        assert!(state.pinned.is_empty() && state.edge_offsets.is_empty());
+        assert_eq!(state.elements_touching(Rect::EVERYTHING), vec![b]);
+    }
+
+    #[test]
+    fn test_parallel_edges_spread_out() {
+        let mut state = GraphState::new();
+        let a = state.add_node_at(Pos2::new(0.0, 0.0));
+        let b = state.add_node_at(Pos2::new(100.0, 0.0));
+        let first = state.add_edge_between(a, b, None).unwrap();
+        assert_eq!(state.positions[first], Pos2::new(50.0, 0.0));
+
+        // Both directions share the gap, centered on the midpoint
+        let second = state.add_edge_between(b, a, None).unwrap();
+        let (p1, p2) = (state.positions[first], state.positions[second]);
+        assert_eq!(p1.x, 50.0);
+        assert_eq!(p2.x, 50.0);
+        assert_eq!((p1.y - p2.y).abs(), PARALLEL_EDGE_GAP);
+        assert_eq!(p1.y + p2.y, 0.0);
+
+        // A detached edge no longer takes up a slot, and removing one closes the gap
+        state.set_control_point(first, Pos2::new(50.0, 80.0)).unwrap();
+        assert_eq!(state.positions[second], Pos2::new(50.0, 0.0));
+        state.set_edge_offset(first, None).unwrap();
+        let detached = state.remove_element(second).unwrap();
+        assert_eq!(state.positions[first], Pos2::new(50.0, 0.0));
+        state.restore(detached).unwrap();
+        assert_eq!((state.positions[first].y - state.positions[second].y).abs(), PARALLEL_EDGE_GAP);
+    }
+
+    #[test]
+    fn test_touching_finds_edges_crossing_the_view() {
+        let mut state = GraphState::new();
+        let a = state.add_node_at(Pos2::new(-5000.0, 0.0));
+        let b = state.add_node_at(Pos2::new(5000.0, 0.0));
+        let c = state.add_node_at(Pos2::new(0.0, 3000.0));
+        let ab = state.add_edge_between(a, b, None).unwrap();
+        let ac = state.add_edge_between(a, c, None).unwrap();
+        // Bent so its curve sweeps through the origin while its control point stays away
+        state.set_control_point(ac, Pos2::new(-1200.0, 700.0)).unwrap();
+
+        // Edges cross these views with nothing placed inside; their ends come along to draw them
+        let on_ab = Rect::from_center_size(Pos2::new(2000.0, 0.0), Vec2::splat(100.0));
+        assert!(state.elements_in_rect(on_ab).is_empty());
+        assert_eq!(state.elements_touching(on_ab), vec![a, b, ab]);
+        let on_ac = Rect::from_center_size(Pos2::new(-275.0, 1650.0), Vec2::splat(100.0));
+        assert!(state.elements_in_rect(on_ac).is_empty());
+        assert_eq!(state.elements_touching(on_ac), vec![a, c, ac]);
+        assert!(state.elements_touching(on_ab.translate(Vec2::new(0.0, -500.0))).is_empty());
+        assert_eq!(state.find_element_at(Pos2::new(4990.0, 5.0), 20.0), Some(b));
+    }
+
+    #[test]
+    fn test_schema_rejects_edge() {
+        use crate::schema::{Endpoint, RelationRule, SchemaViolation};
+
+        let mut state = GraphState::new();
+        let rules = state.relations.add("rules over", eframe::egui::Color32::GOLD);
+        let mut schema = Schema::new();
+        let person = schema.add_kind(NodeKind::new("Character"));
+        schema.set_rule(RelationRule::new(Some(rules)).from(Endpoint::Kind(person)));
+        state.schema = Some(schema);
+
+        let king = state.add_node_at(Pos2::ZERO);
+        let city = state.add_node_at(Pos2::new(50.0, 0.0));
+        state.set_node_kind(king, Some(person)).unwrap();
+
+        assert_eq!(
+            state.add_edge_between(city, king, Some(rules)),
+            Err(GraphError::SchemaViolation(SchemaViolation::SourceNotAllowed { relation: Some(rules), source: city }))
+        );
+        assert_eq!(state.add_edge_between(king, king, Some(rules)), Err(GraphError::SelfLoop(king)));
+        let edge = state.add_edge_between(king, city, Some(rules)).unwrap();
+        assert_eq!(state.positions.get(edge), Some(&Pos2::new(25.0, 0.0)));
+
+        state.remove_node_kind(person).unwrap();
+        assert_eq!(state.remove_node_kind(person), Err(GraphError::UnknownKind(person)));
+        assert_eq!(state.graph.node_data(king).unwrap().kind, None);
+        // The rule doesn't open up to everyone once its only source kind is gone
+        let town = state.add_node_at(Pos2::new(0.0, 50.0));
+        assert!(state.add_edge_between(king, town, Some(rules)).is_err());
     }
 }
\ No newline at end of file
@@ -0,0 +1,238 @@
+// style.rs
+use eframe::egui::{Color32, Pos2, Rect, Vec2};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum NodeShape {
+    #[default]
+    Rectangle,
+    Circle,
+    Diamond,
+    Hexagon,
+}
+
+impl NodeShape {
+    pub const ALL: [NodeShape; 4] = [
+        NodeShape::Rectangle,
+        NodeShape::Circle,
+        NodeShape::Diamond,
+        NodeShape::Hexagon,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            NodeShape::Rectangle => "Rectangle",
+            NodeShape::Circle => "Circle",
+            NodeShape::Diamond => "Diamond",
+            NodeShape::Hexagon => "Hexagon",
+        }
+    }
+
+    // Size of the shape's bounding box so that `content` fits inside the shape
+    pub fn fit(self, content: Vec2) -> Vec2 {
+        match self {
+            NodeShape::Rectangle => content,
+            NodeShape::Circle => Vec2::splat(content.length()),
+            // A rectangle fits in a diamond twice its size
+            NodeShape::Diamond => content * 2.0,
+            NodeShape::Hexagon => Vec2::new(content.x * 1.5, content.y),
+        }
+    }
+
+    // Corners of the outline, None for the shapes drawn natively (rectangle, circle)
+    pub fn polygon(self, rect: Rect) -> Option<Vec<Pos2>> {
+        let c = rect.center();
+        match self {
+            NodeShape::Rectangle | NodeShape::Circle => None,
+            NodeShape::Diamond => Some(vec![
+                Pos2::new(c.x, rect.top()),
+                Pos2::new(rect.right(), c.y),
+                Pos2::new(c.x, rect.bottom()),
+                Pos2::new(rect.left(), c.y),
+            ]),
+            NodeShape::Hexagon => {
+                let inset = rect.width() / 4.0;
+                Some(vec![
+                    Pos2::new(rect.left(), c.y),
+                    Pos2::new(rect.left() + inset, rect.top()),
+                    Pos2::new(rect.right() - inset, rect.top()),
+                    Pos2::new(rect.right(), c.y),
+                    Pos2::new(rect.right() - inset, rect.bottom()),
+                    Pos2::new(rect.left() + inset, rect.bottom()),
+                ])
+            }
+        }
+    }
+
+    // Point where the line from the center towards `towards` leaves the outline
+    pub fn border_towards(self, rect: Rect, towards: Pos2) -> Pos2 {
+        let center = rect.center();
+        let dir = towards - center;
+        if dir.length_sq() < f32::EPSILON {
+            return center;
+        }
+        let half = rect.size() * 0.5;
+        // How far along `dir` the border is, capped so we never overshoot `towards`
+        let scale = match self {
+            NodeShape::Rectangle => {
+                let x = if dir.x.abs() > f32::EPSILON { half.x / dir.x.abs() } else { f32::INFINITY };
+                let y = if dir.y.abs() > f32::EPSILON { half.y / dir.y.abs() } else { f32::INFINITY };
+                x.min(y)
+            }
+            NodeShape::Circle => half.x.min(half.y) / dir.length(),
+            NodeShape::Diamond | NodeShape::Hexagon => {
+                let points = self.polygon(rect).unwrap_or_default();
+                ray_polygon_exit(center, dir, &points).unwrap_or(1.0)
+            }
+        };
+        center + dir * scale.min(1.0)
+    }
+}
+
+// Smallest t > 0 where center + dir * t crosses an edge of the polygon
+fn ray_polygon_exit(center: Pos2, dir: Vec2, points: &[Pos2]) -> Option<f32> {
+    let mut best: Option<f32> = None;
+    for (i, &a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        let side = b - a;
+        let denom = dir.x * side.y - dir.y * side.x;
+        if denom.abs() < f32::EPSILON {
+            continue;
+        }
+        let offset = a - center;
+        let t = (offset.x * side.y - offset.y * side.x) / denom;
+        let u = (offset.x * dir.y - offset.y * dir.x) / denom;
+        if t > 0.0 && (0.0..=1.0).contains(&u) {
+            best = Some(best.map_or(t, |best| best.min(t)));
+        }
+    }
+    best
+}
+
+// Visual overrides for a node or a node kind; unset fields fall through to the next level
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct NodeStyle {
+    pub fill: Option<Color32>,
+    pub shape: Option<NodeShape>,
+    // Minimum width/height in world units
+    pub size: Option<f32>,
+    // Emoji or other glyph drawn in front of the name
+    pub icon: Option<String>,
+}
+
+impl NodeStyle {
+    pub fn is_empty(&self) -> bool {
+        *self == NodeStyle::default()
+    }
+}
+
+// Document-wide defaults that node and kind styles override
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Theme {
+    pub node_fill: Color32,
+    pub edge_fill: Color32,
+    pub node_shape: NodeShape,
+    pub node_size: f32,
+    // Edge nodes are kept small so they read as handles on the line
+    pub edge_size: f32,
+    pub background: Option<Color32>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            node_fill: Color32::LIGHT_GREEN,
+            edge_fill: Color32::LIGHT_BLUE,
+            node_shape: NodeShape::Rectangle,
+            node_size: 20.0,
+            edge_size: 20.0,
+            background: None,
+        }
+    }
+}
+
+// The style a node is actually drawn with
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedStyle {
+    pub fill: Color32,
+    pub shape: NodeShape,
+    pub size: f32,
+    pub icon: Option<String>,
+}
+
+impl Theme {
+    // Node style wins over kind style, which wins over the theme.
+    // Edge nodes keep the edge defaults unless styled themselves
+    pub fn resolve(&self, is_edge: bool, kind: Option<&NodeStyle>, node: &NodeStyle) -> ResolvedStyle {
+        let (fill, shape, size) = if is_edge {
+            (self.edge_fill, NodeShape::Rectangle, self.edge_size)
+        } else {
+            (self.node_fill, self.node_shape, self.node_size)
+        };
+        let kind = kind.filter(|_| !is_edge);
+        ResolvedStyle {
+            fill: node.fill.or(kind.and_then(|k| k.fill)).unwrap_or(fill),
+            shape: node.shape.or(kind.and_then(|k| k.shape)).unwrap_or(shape),
+            size: node.size.or(kind.and_then(|k| k.size)).unwrap_or(size),
+            icon: node.icon.clone().or_else(|| kind.and_then(|k| k.icon.clone())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_style_resolution() {
+        let theme = Theme::default();
+        let kind = NodeStyle {
+            fill: Some(Color32::RED),
+            shape: Some(NodeShape::Circle),
+            icon: Some("👑".into()),
+            ..Default::default()
+        };
+        let node = NodeStyle {
+            fill: Some(Color32::GOLD),
+            ..Default::default()
+        };
+
+        let plain = theme.resolve(false, None, &NodeStyle::default());
+        assert_eq!(plain.fill, Color32::LIGHT_GREEN);
+        assert_eq!(plain.shape, NodeShape::Rectangle);
+
+        let styled = theme.resolve(false, Some(&kind), &node);
+        assert_eq!(styled.fill, Color32::GOLD);
+        assert_eq!(styled.shape, NodeShape::Circle);
+        assert_eq!(styled.icon.as_deref(), Some("👑"));
+        assert_eq!(styled.size, theme.node_size);
+
+        // Edge nodes ignore kind styles
+        let edge = theme.resolve(true, Some(&kind), &NodeStyle::default());
+        assert_eq!(edge.fill, Color32::LIGHT_BLUE);
+        assert_eq!(edge.icon, None);
+    }
+
+    #[test]
+    fn test_border_towards() {
+        let rect = Rect::from_center_size(Pos2::ZERO, Vec2::new(40.0, 20.0));
+        let right = Pos2::new(100.0, 0.0);
+        let down = Pos2::new(0.0, 100.0);
+
+        for shape in NodeShape::ALL {
+            let border = shape.border_towards(rect, right);
+            assert!((border.x - 20.0).abs() < 1e-3 || shape == NodeShape::Circle, "{shape:?}");
+            let border = shape.border_towards(rect, down);
+            assert!((border.y - 10.0).abs() < 1e-3, "{shape:?}");
+        }
+        assert!((NodeShape::Circle.border_towards(rect, right).x - 10.0).abs() < 1e-3);
+
+        // Diagonal on a diamond lands on the sloped side
+        let border = NodeShape::Diamond.border_towards(rect, Pos2::new(100.0, 50.0));
+        assert!((border.x / 20.0 + border.y / 10.0 - 1.0).abs() < 1e-3);
+
+        // Never past the point we aim at
+        let near = Pos2::new(5.0, 0.0);
+        assert_eq!(NodeShape::Rectangle.border_towards(rect, near), near);
+    }
+}
@@ -0,0 +1,179 @@
+// tombstone.rs
+// A SlotMap whose removed entries stay behind, hidden, under their key until purged, so undo
+// can bring them back with every reference to the key still valid. Only live entries are saved
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use slotmap::{Key, SlotMap};
+use std::collections::BTreeSet;
+use std::ops::{Index, IndexMut};
+
+#[derive(Debug, Clone)]
+pub struct TombstoneMap<K: Key, V> {
+    map: SlotMap<K, V>,
+    removed: BTreeSet<K>,
+}
+
+impl<K: Key, V> Default for TombstoneMap<K, V> {
+    fn default() -> Self {
+        Self { map: SlotMap::with_key(), removed: BTreeSet::new() }
+    }
+}
+
+impl<K: Key + Ord, V> TombstoneMap<K, V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, value: V) -> K {
+        self.map.insert(value)
+    }
+
+    // Hide an entry, keeping it for `restore`. Returns a copy of what was removed
+    pub fn remove(&mut self, key: K) -> Option<V>
+    where
+        V: Clone,
+    {
+        let value = self.get(key)?.clone();
+        self.removed.insert(key);
+        Some(value)
+    }
+
+    // Bring back a removed entry under its old key
+    pub fn restore(&mut self, key: K) -> bool {
+        self.removed.remove(&key)
+    }
+
+    // Free removed entries for good, except those `keep` holds on to for a later restore
+    pub fn purge(&mut self, keep: impl Fn(K) -> bool) {
+        let (kept, freed) = std::mem::take(&mut self.removed).into_iter().partition(|&key| keep(key));
+        for key in freed {
+            self.map.remove(key);
+        }
+        self.removed = kept;
+    }
+
+    pub fn get(&self, key: K) -> Option<&V> {
+        self.map.get(key).filter(|_| !self.removed.contains(&key))
+    }
+
+    pub fn get_mut(&mut self, key: K) -> Option<&mut V> {
+        self.map.get_mut(key).filter(|_| !self.removed.contains(&key))
+    }
+
+    pub fn contains_key(&self, key: K) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (K, &V)> {
+        self.map.iter().filter(|(key, _)| !self.removed.contains(key))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = K> + '_ {
+        self.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, value)| value)
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len() - self.removed.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K: Key + Ord, V> Index<K> for TombstoneMap<K, V> {
+    type Output = V;
+
+    fn index(&self, key: K) -> &V {
+        self.get(key).expect("no live entry for key")
+    }
+}
+
+impl<K: Key + Ord, V> IndexMut<K> for TombstoneMap<K, V> {
+    fn index_mut(&mut self, key: K) -> &mut V {
+        self.get_mut(key).expect("no live entry for key")
+    }
+}
+
+// Equal when the live entries are
+impl<K: Key + Ord, V: PartialEq> PartialEq for TombstoneMap<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl<K: Key, V> From<SlotMap<K, V>> for TombstoneMap<K, V> {
+    fn from(map: SlotMap<K, V>) -> Self {
+        Self { map, removed: BTreeSet::new() }
+    }
+}
+
+// Saved as a plain SlotMap of the live entries
+impl<K: Key + Ord + Serialize, V: Clone + Serialize> Serialize for TombstoneMap<K, V> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.removed.is_empty() {
+            return self.map.serialize(serializer);
+        }
+        let mut live = self.map.clone();
+        for &key in &self.removed {
+            live.remove(key);
+        }
+        live.serialize(serializer)
+    }
+}
+
+impl<'de, K: Key + Deserialize<'de>, V: Deserialize<'de>> Deserialize<'de> for TombstoneMap<K, V> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        SlotMap::deserialize(deserializer).map(TombstoneMap::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use slotmap::DefaultKey;
+
+    #[test]
+    fn test_removed_entries_come_back_under_their_key() {
+        let mut map: TombstoneMap<DefaultKey, &str> = TombstoneMap::new();
+        let a = map.insert("a");
+        let b = map.insert("b");
+
+        assert_eq!(map.remove(a), Some("a"));
+        assert_eq!(map.remove(a), None);
+        assert!(!map.contains_key(a));
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.keys().collect::<Vec<_>>(), vec![b]);
+        // New entries never take a buried key
+        let c = map.insert("c");
+        assert_ne!(c, a);
+
+        assert!(map.restore(a));
+        assert_eq!(map[a], "a");
+        assert_eq!(map.len(), 3);
+
+        map.remove(a);
+        map.remove(b);
+        map.purge(|key| key == a);
+        assert!(!map.restore(b));
+        assert!(map.restore(a));
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn test_removed_entries_are_not_saved() {
+        let mut map: TombstoneMap<DefaultKey, String> = TombstoneMap::new();
+        let a = map.insert("a".to_owned());
+        let b = map.insert("b".to_owned());
+        map.remove(a);
+
+        let bytes = bincode::serialize(&map).unwrap();
+        let loaded: TombstoneMap<DefaultKey, String> = bincode::deserialize(&bytes).unwrap();
+        assert!(!loaded.contains_key(a));
+        assert_eq!(loaded[b], "b");
+        assert_eq!(loaded.len(), 1);
+    }
+}